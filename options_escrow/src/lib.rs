@@ -1,8 +1,368 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
+use anchor_lang::{Discriminator, InstructionData};
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::sysvar::instructions::{self as sysvar_instructions, load_current_index_checked, load_instruction_at_checked};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
+use anchor_spl::token::{self, Burn, CloseAccount, MintTo, Token, TokenAccount, Transfer, Mint};
+use pyth_sdk_solana::state::load_price_account;
+use pyth_sdk_solana::{load_price_feed_from_account_info, Price};
 
 declare_id!("9aYFqSL95jbn72YAcdoTXjAiZfwopsV7JhkSsqKLS4cf");
 
+/// Maximum number of weighted fee collectors the governance account can hold.
+pub const MAX_FEE_COLLECTORS: usize = 5;
+
+/// Sum that all fee collector weights must add up to (100.00% in basis points).
+pub const TOTAL_FEE_WEIGHT_BPS: u16 = 10000;
+
+/// Maximum fee rate governance can set (100.00% in basis points).
+pub const MAX_FEE_BPS: u64 = 10000;
+
+/// Maximum number of pending fee claims a fee vault can track.
+///
+/// This is larger than `MAX_FEE_COLLECTORS` so that balances owed to collectors removed by
+/// governance stay claimable after the collector list changes.
+pub const MAX_FEE_CLAIMS: usize = 2 * MAX_FEE_COLLECTORS;
+
+/// Seed for the per-mint fee vault state account.
+pub const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+
+/// Seed for the per-mint fee vault token account.
+pub const FEE_VAULT_TOKENS_SEED: &[u8] = b"fee_vault_tokens";
+
+/// Seed for the per-collateral-mint market account.
+pub const MARKET_SEED: &[u8] = b"market";
+
+/// Seed for an escrow's vault, the token account holding its collateral.
+///
+/// The vault is its own token authority, so the program moves collateral out of it by
+/// signing with these seeds and no other account can.
+pub const ESCROW_VAULT_SEED: &[u8] = b"escrow_vault";
+
+/// Default maximum age in seconds of an oracle price used by the program.
+pub const MAX_ORACLE_PRICE_AGE: u64 = 60;
+
+/// Furthest an oracle settlement price's publish time may be from the escrow's expiry (in
+/// seconds). Escrows not settled in time settle through a manual or attested price.
+pub const SETTLEMENT_PRICE_TOLERANCE: i64 = 120;
+
+/// Decimals of strike prices, which are quoted in the oracles' quote currency (e.g. USD).
+pub const STRIKE_PRICE_DECIMALS: i32 = 6;
+
+/// Seed for the per-mint treasury state account.
+pub const TREASURY_SEED: &[u8] = b"treasury";
+
+/// Shortest treasury timelock governance can set (one day, in seconds).
+pub const MIN_TREASURY_TIMELOCK: i64 = 86_400;
+
+/// Seed for the per-mint treasury token account.
+pub const TREASURY_TOKENS_SEED: &[u8] = b"treasury_tokens";
+
+/// Seed for the governance token staking pool.
+pub const STAKING_POOL_SEED: &[u8] = b"staking_pool";
+
+/// Seed for the staking pool's token account holding staked governance tokens.
+pub const STAKE_VAULT_SEED: &[u8] = b"stake_vault";
+
+/// Seed for a staker's position in the staking pool.
+pub const STAKE_POSITION_SEED: &[u8] = b"stake_position";
+
+/// Fixed-point scale of the staking pool's reward-per-share accumulator.
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+/// How long newly staked tokens must be held before they count toward fee discounts (about one
+/// Solana epoch, in seconds).
+pub const STAKE_DISCOUNT_MIN_AGE: i64 = 2 * 24 * 60 * 60;
+
+/// Seed for the per-input-mint buyback configuration.
+pub const BUYBACK_SEED: &[u8] = b"buyback";
+
+/// Seed for the token account receiving bought-back governance tokens before they are burned.
+pub const BUYBACK_TOKENS_SEED: &[u8] = b"buyback_tokens";
+
+/// Maximum number of staking fee discount tiers.
+pub const MAX_FEE_DISCOUNT_TIERS: usize = 4;
+
+/// Seed for the per-market liquidity-mining emissions schedule.
+pub const EMISSIONS_SEED: &[u8] = b"emissions";
+
+/// Seed for the emissions schedule's reward token account.
+pub const EMISSIONS_VAULT_SEED: &[u8] = b"emissions_vault";
+
+/// Seed for a writer's liquidity-mining position in a market.
+pub const WRITER_EMISSIONS_SEED: &[u8] = b"writer_emissions";
+
+/// Seed for a user's points and activity stats.
+pub const USER_STATS_SEED: &[u8] = b"user_stats";
+
+/// Seed the SPL Governance (Realms) program uses for a governance's native treasury.
+pub const REALMS_NATIVE_TREASURY_SEED: &[u8] = b"native-treasury";
+
+/// Seed for governance proposals.
+pub const PROPOSAL_SEED: &[u8] = b"proposal";
+/// Seed for a voter's record on a proposal.
+pub const VOTE_RECORD_SEED: &[u8] = b"vote_record";
+
+/// Market pause flag blocking new escrows.
+pub const PAUSE_NEW_ESCROWS: u8 = 1 << 0;
+/// Market pause flag blocking exercise and settlement.
+pub const PAUSE_EXERCISE: u8 = 1 << 1;
+/// Market pause flag blocking collateral deposits.
+pub const PAUSE_DEPOSITS: u8 = 1 << 2;
+
+/// Maximum number of markets a user can have open notional in at once.
+pub const MAX_USER_OPEN_MARKETS: usize = 8;
+
+/// Seed for the protocol-wide stats account.
+pub const PROTOCOL_STATS_SEED: &[u8] = b"protocol_stats";
+/// Seed for a market's stats account.
+pub const MARKET_STATS_SEED: &[u8] = b"market_stats";
+
+/// Seed for a user's position account.
+pub const USER_POSITION_SEED: &[u8] = b"user_position";
+/// Maximum number of open escrows a user position can list.
+pub const MAX_OPEN_ESCROWS_PER_USER: usize = 32;
+
+/// Seed for a market's risk configuration.
+pub const RISK_CONFIG_SEED: &[u8] = b"risk_config";
+/// Maximum number of shock scenarios in a risk configuration.
+pub const MAX_RISK_SCENARIOS: usize = 16;
+
+/// Seed for a market's oracle registry.
+pub const ORACLE_REGISTRY_SEED: &[u8] = b"oracle_registry";
+/// Seed for a market's grid of standard expiry times.
+pub const EXPIRY_GRID_SEED: &[u8] = b"expiry_grid";
+/// Maximum number of extra price feeds in an oracle registry, besides the market's own.
+pub const MAX_EXTRA_ORACLE_FEEDS: usize = 2;
+
+/// Seed for a market's price history.
+pub const PRICE_HISTORY_SEED: &[u8] = b"price_history";
+/// Number of observations kept in a price history ring buffer.
+pub const MAX_PRICE_OBSERVATIONS: usize = 64;
+
+/// Maximum number of discrete exercise windows of a Bermudan option.
+pub const MAX_EXERCISE_WINDOWS: usize = 12;
+
+/// Seed for the PDA holding a market's basket of underlyings.
+pub const BASKET_SEED: &[u8] = b"basket";
+
+/// Maximum number of underlyings in a basket.
+pub const MAX_BASKET_COMPONENTS: usize = 4;
+
+/// Seed for the PDA streaming a renter's premium to an escrow's writer.
+pub const RENTAL_SEED: &[u8] = b"rental";
+
+/// Seed for the token account holding a rental's prepaid premium.
+pub const RENTAL_TOKENS_SEED: &[u8] = b"rental_tokens";
+
+/// Seed for the PDA lending an escrow's option from its holder to a borrower.
+pub const OPTION_LOAN_SEED: &[u8] = b"option_loan";
+
+/// Seed for a reusable set of escrow parameters.
+pub const ESCROW_TEMPLATE_SEED: &[u8] = b"escrow_template";
+
+/// Seed for a fungible option series.
+pub const OPTION_SERIES_SEED: &[u8] = b"option_series";
+/// Seed for the mint of a series' option tokens.
+pub const SERIES_OPTION_MINT_SEED: &[u8] = b"series_option_mint";
+/// Seed for the token account holding a series' collateral.
+pub const SERIES_VAULT_SEED: &[u8] = b"series_vault";
+/// Seed for a writer's position in a series.
+pub const SERIES_WRITER_SEED: &[u8] = b"series_writer";
+/// Seed for the mint of a series' writer tokens.
+pub const SERIES_WRITER_MINT_SEED: &[u8] = b"series_writer_mint";
+/// Seed for the fractional ownership of an escrow's option.
+pub const ESCROW_FRACTION_SEED: &[u8] = b"escrow_fraction";
+/// Seed for the mint of an escrow's fraction shares.
+pub const FRACTION_SHARE_MINT_SEED: &[u8] = b"fraction_share_mint";
+/// Seed for a series' registry of non-tokenized holders.
+pub const HOLDER_REGISTRY_SEED: &[u8] = b"holder_registry";
+/// Maximum number of holders in a series' holder registry.
+pub const MAX_REGISTRY_HOLDERS: usize = 128;
+
+/// Share of a reaped escrow's rent paid to the caller of `reap_unfunded`, in basis points.
+pub const REAP_REWARD_BPS: u64 = 1000;
+
+/// Largest residual balance, in base units of the collateral mint, that `sweep_dust` moves to
+/// the treasury.
+pub const DUST_THRESHOLD: u64 = 10_000;
+
+/// Seconds in the year the initialization fee rate is quoted over.
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+/// Seed for a governance-approved fee override between a writer and an OTC counterparty.
+pub const FEE_APPROVAL_SEED: &[u8] = b"fee_approval";
+
+/// Maximum number of named counterparties on a private OTC escrow.
+pub const MAX_ALLOWED_COUNTERPARTIES: usize = 4;
+
+/// Seed for a participant's whitelist entry in a permissioned market.
+pub const WHITELIST_SEED: &[u8] = b"whitelist";
+
+/// Seed for a holder's session key.
+pub const SESSION_SEED: &[u8] = b"session";
+
+/// Session scope flag allowing early exercise.
+pub const SESSION_SCOPE_EXERCISE: u8 = 1 << 0;
+/// Session scope flag allowing settlement claims.
+pub const SESSION_SCOPE_CLAIM: u8 = 1 << 1;
+
+/// Longest a session key can stay valid, in seconds.
+pub const MAX_SESSION_DURATION: i64 = 7 * 24 * 60 * 60;
+
+/// Seed for a depositor's authorization letting keepers pull delegated collateral into an escrow.
+pub const DEPOSIT_AUTHORIZATION_SEED: &[u8] = b"deposit_authorization";
+
+/// Number of remaining accounts `settle_many` takes per escrow.
+pub const SETTLE_MANY_GROUP_SIZE: usize = 4;
+
+/// Maximum number of escrows `initialize_escrows_batch` opens at once.
+pub const MAX_BATCH_ESCROWS: usize = 8;
+
+/// Maximum number of escrows `settle_many` settles at once.
+///
+/// Each escrow costs three account loads and at most one token CPI: the crank fee paid to the
+/// settler out of the escrow's vault, as in `settle_escrow`. Protocol fees and the holder's and
+/// writer's proceeds are accrued on the escrow, not transferred. The compute cost per escrow
+/// hasn't been measured on-chain, so no compute budget is enforced; the size is capped by the
+/// 64-account transaction lock limit instead: 11 groups of `SETTLE_MANY_GROUP_SIZE` accounts
+/// plus the shared accounts. Cranks should request a compute-unit limit for full batches.
+pub const SETTLE_MANY_MAX_ESCROWS: usize = 11;
+
+/// Layout version of newly created escrow accounts.
+///
+/// The version sits at the fixed `ESCROW_VERSION_OFFSET`. `migrate_escrow` grows escrows of
+/// an older version to the current `EscrowAccount::SPACE` and stamps them with this version;
+/// version 3 moved fields to fixed offsets, so older escrows can't be migrated in place.
+/// Version 4 added `price_disputable_until`, growing the account past its reserved space.
+pub const ESCROW_ACCOUNT_VERSION: u8 = 4;
+/// Oldest escrow layout version `migrate_escrow` can grow in place.
+pub const MIN_MIGRATABLE_ESCROW_VERSION: u8 = 3;
+
+/// Byte offset of `EscrowAccount::initializer_key`, including the discriminator.
+pub const ESCROW_INITIALIZER_KEY_OFFSET: usize = 8;
+/// Byte offset of `EscrowAccount::holder`.
+pub const ESCROW_HOLDER_OFFSET: usize = ESCROW_INITIALIZER_KEY_OFFSET + 32;
+/// Byte offset of `EscrowAccount::collateral_mint`.
+pub const ESCROW_COLLATERAL_MINT_OFFSET: usize = ESCROW_HOLDER_OFFSET + 32;
+/// Byte offset of `EscrowAccount::expiration` (little-endian `i64`).
+pub const ESCROW_EXPIRATION_OFFSET: usize = ESCROW_COLLATERAL_MINT_OFFSET + 32;
+/// Byte offset of `EscrowAccount::is_exercised`, the escrow's state flag (0 while open).
+pub const ESCROW_STATE_OFFSET: usize = ESCROW_EXPIRATION_OFFSET + 8;
+/// Byte offset of `EscrowAccount::version`.
+pub const ESCROW_VERSION_OFFSET: usize = ESCROW_STATE_OFFSET + 1;
+
+/// Zeroed bytes reserved at the end of the escrow, market, and governance accounts.
+///
+/// New fields are carved out of this space, shrinking it by their size, so they can be added
+/// without growing the account: the reserved zeros decode as the new fields' defaults.
+pub const ACCOUNT_RESERVED_BYTES: usize = 64;
+
+/// Seed of the event authority PDA, fixed by Anchor's event-cpi instruction handler.
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+/// Seed for a market's audit log.
+pub const AUDIT_LOG_SEED: &[u8] = b"audit_log";
+/// Number of actions kept in an audit log ring buffer.
+pub const AUDIT_LOG_ENTRIES: usize = 32;
+
+/// Seed for a market's implied volatility configuration.
+pub const VOL_CONFIG_SEED: &[u8] = b"vol_config";
+/// Maximum number of tenor points on a volatility curve.
+pub const MAX_VOL_POINTS: usize = 16;
+/// Largest implied volatility a curve may quote, in basis points (500%).
+pub const MAX_VOL_BPS: u64 = 50_000;
+/// Fixed-point unit of delta and gamma in `Greeks` (millionths).
+pub const GREEKS_UNIT: i64 = 1_000_000;
+
+/// Seed for the keeper registry.
+pub const KEEPER_REGISTRY_SEED: &[u8] = b"keeper_registry";
+/// Seed for the token account holding keeper stake.
+pub const KEEPER_STAKE_VAULT_SEED: &[u8] = b"keeper_stake_vault";
+/// Seed for a keeper's stake account.
+pub const KEEPER_SEED: &[u8] = b"keeper";
+/// Time governance has to override a keeper-submitted settlement price before escrows settle at it (in seconds).
+pub const KEEPER_PRICE_DISPUTE_WINDOW: i64 = 3600;
+
+/// Seed for the settlers allowed to settle one series (market and expiry).
+pub const SERIES_SETTLERS_SEED: &[u8] = b"series_settlers";
+/// Maximum number of settlers of one series.
+pub const MAX_SERIES_SETTLERS: usize = 8;
+
+/// Domain prefix of price attestation messages, so signatures can't be replayed from other uses.
+pub const PRICE_ATTESTATION_DOMAIN: &[u8] = b"options_escrow:settlement_price";
+
+/// Seed for a market's lending configuration.
+pub const LENDING_CONFIG_SEED: &[u8] = b"lending_config";
+
+/// Seed for a perp DEX whitelisted for hedging.
+pub const HEDGE_VENUE_SEED: &[u8] = b"hedge_venue";
+/// Seed for a writer's hedging vault in one market.
+pub const HEDGE_VAULT_SEED: &[u8] = b"hedge_vault";
+
+/// Seed for a market's protocol-owned writer vault.
+pub const POL_VAULT_SEED: &[u8] = b"pol_vault";
+/// Seed for the system-owned PDA writing a protocol-owned vault's escrows.
+pub const POL_WRITER_SEED: &[u8] = b"pol_writer";
+/// Seed for a protocol-owned vault's issuance auction of one epoch.
+pub const ISSUANCE_AUCTION_SEED: &[u8] = b"issuance_auction";
+/// Seed for a bidder's sealed bid in an issuance auction.
+pub const AUCTION_BID_SEED: &[u8] = b"auction_bid";
+/// Maximum number of escrows sold in one issuance auction.
+pub const MAX_AUCTION_LOTS: usize = 16;
+/// Seed for a protocol-owned vault's strategy configuration.
+pub const STRATEGY_CONFIG_SEED: &[u8] = b"strategy_config";
+/// Bisection steps taken to find the strike of a target delta.
+pub const STRIKE_SEARCH_STEPS: usize = 24;
+/// Seed for a share holders' proposal to change a vault strategy.
+pub const STRATEGY_PROPOSAL_SEED: &[u8] = b"strategy_proposal";
+/// Seed for a share holder's vote on a strategy proposal.
+pub const STRATEGY_VOTE_SEED: &[u8] = b"strategy_vote";
+/// Shortest notice between the end of a strategy vote and the new strategy taking effect.
+pub const MIN_STRATEGY_NOTICE_PERIOD: i64 = 24 * 60 * 60;
+
+/// Seed for a market's backstop liquidity pool.
+pub const BACKSTOP_POOL_SEED: &[u8] = b"backstop_pool";
+/// Seed for the system-owned PDA holding the escrows a backstop pool took over.
+pub const BACKSTOP_WRITER_SEED: &[u8] = b"backstop_writer";
+/// Seed for a designated backstop liquidity provider's position.
+pub const BACKSTOP_LP_SEED: &[u8] = b"backstop_lp";
+/// Seed for a backstop LP's queued withdrawal.
+pub const WITHDRAWAL_REQUEST_SEED: &[u8] = b"withdrawal_request";
+/// Maximum number of withdrawals queued in a backstop pool at once.
+pub const MAX_QUEUED_WITHDRAWALS: usize = 16;
+
+/// Fixed-point scale of LST exchange rates (underlying per LST).
+pub const LST_RATE_SCALE: u64 = 1_000_000_000;
+
+/// SPL stake pool program (`SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy`), whose pools back
+/// LST collateral such as jitoSOL.
+pub const SPL_STAKE_POOL_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    6, 129, 78, 212, 202, 246, 138, 23, 70, 114, 253, 172, 134, 3, 26, 99,
+    232, 78, 161, 94, 250, 29, 68, 183, 34, 147, 246, 219, 219, 0, 22, 80,
+]);
+
+/// Fixed-point scale of interest-bearing collateral exchange rates (underlying per share).
+pub const EXCHANGE_RATE_SCALE: u64 = 1_000_000_000_000;
+
+/// SPL token-lending program (`LendZqTs7gn5CTSJU1jWKhKuVpjJGom45nnwPb2AMTi`), whose reserves
+/// price interest-bearing collateral such as cTokens.
+pub const TOKEN_LENDING_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    5, 8, 194, 206, 177, 181, 208, 92, 135, 73, 128, 172, 82, 207, 101, 151,
+    64, 231, 233, 185, 53, 106, 175, 42, 3, 98, 103, 50, 99, 82, 108, 21,
+]);
+
+/// Jupiter aggregator program (`JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4`), which
+/// `exercise_and_swap` routes swaps through.
+pub const JUPITER_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
+    4, 121, 213, 91, 242, 49, 192, 110, 238, 116, 197, 110, 206, 104, 21, 7,
+    253, 177, 178, 222, 163, 244, 142, 81, 2, 177, 205, 162, 86, 188, 19, 143,
+]);
+
 #[program]
 mod options_escrow {
     use super::*;
@@ -10,8 +370,9 @@ mod options_escrow {
     /// Initializes the escrow account with option parameters and charges a fee.
     ///
     /// The escrow account holds details of the option contract, including the strike price,
-    /// expiration date, and the collateral amount. This function also transfers a fee to
-    /// the fee collector based on the governance settings.
+    /// expiration date, and the collateral amount. This function also moves a fee into the
+    /// fee vault for the market's fee mint, where it is accrued to the fee collectors based on
+    /// the governance settings.
     pub fn initialize_escrow(
         ctx: Context<InitializeEscrow>,
         option_type: OptionType,      // Type of option: Call or Put
@@ -19,29 +380,53 @@ mod options_escrow {
         expiration: i64,              // Expiration time as a Unix timestamp
         collateral_amount: u64,       // Amount of collateral to be deposited
         collateral_mint: Pubkey,      // Token mint for the collateral
+        terms: OptionTerms,           // Payoff style, barrier, and exercise windows of the option
     ) -> Result<()> {
-        let escrow_account = &mut ctx.accounts.escrow_account;
-        
+        ctx.accounts.market.require_not_paused(PAUSE_NEW_ESCROWS)?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), ctx.accounts.initializer.key)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let leg = EscrowLeg { option_type, strike_price, collateral_amount };
+        let expiration = ctx.accounts.market.validate_new_escrow(
+            ctx.accounts.collateral_oracle.as_ref(),
+            ctx.accounts.expiry_grid.as_deref(),
+            &leg,
+            expiration,
+            &terms,
+            current_time,
+        )?;
+
         // Initialize escrow account details
-        escrow_account.initializer_key = *ctx.accounts.initializer.key;
-        escrow_account.option_type = option_type;
-        escrow_account.strike_price = strike_price;
-        escrow_account.expiration = expiration;
-        escrow_account.collateral_amount = collateral_amount;
-        escrow_account.collateral_mint = collateral_mint;
-        escrow_account.is_exercised = false;
-
-        // Transfer fee to the fee collector
-        let governance = &ctx.accounts.governance;
-        let fee = collateral_amount * governance.fee_rate / 10000; // Calculate fee based on the fee rate
-        let cpi_accounts_fee = Transfer {
-            from: ctx.accounts.initializer_collateral_account.to_account_info(),
-            to: ctx.accounts.fee_collector.to_account_info(),
-            authority: ctx.accounts.initializer.to_account_info(),
-        };
-        let cpi_ctx_fee = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts_fee);
-        token::transfer(cpi_ctx_fee, fee)?;
+        let mut escrow_account = EscrowAccount::new(ctx.accounts.initializer.key(), &leg, expiration, collateral_mint, terms, current_time);
+        escrow_account.counterparty = ctx.accounts.fee_approval.as_ref().map_or(Pubkey::default(), |approval| approval.counterparty);
+        escrow_account.fee_override_bps = ctx.accounts.fee_approval.as_ref().map(|approval| approval.fee_bps);
+        escrow_account.deposit_exchange_rate = ctx.accounts.market.collateral_exchange_rate(ctx.accounts.collateral_reserve.as_ref())?.unwrap_or(0);
+        ctx.accounts.escrow_account.set_inner(escrow_account);
 
+        // Collect the fee into the fee vault and accrue it to the fee collectors
+        let fee = match &ctx.accounts.fee_approval {
+            Some(approval) => u64::try_from(collateral_amount as u128 * approval.fee_bps as u128 / 10000).map_err(|_| ErrorCode::MathOverflow)?,
+            None => ctx.accounts.governance.init_fee(collateral_amount, expiration.saturating_sub(current_time))?,
+        };
+        ctx.accounts.collect_fee(fee)?;
+        ctx.accounts.initializer_stats.record_notional_written(&ctx.accounts.market, collateral_amount)?;
+        ctx.accounts.initializer_stats.add_open_notional(&ctx.accounts.market, collateral_amount)?;
+        ctx.accounts.market.add_open_interest(collateral_amount)?;
+        let market_stats = &mut ctx.accounts.market_stats;
+        market_stats.notional_volume = market_stats.notional_volume.saturating_add(collateral_amount);
+        market_stats.escrows_opened = market_stats.escrows_opened.saturating_add(1);
+        ctx.accounts.protocol_stats.record_open();
+        let escrow_key = ctx.accounts.escrow_account.key();
+        ctx.accounts.initializer_position.add_written(escrow_key)?;
+        emit_lifecycle_event(&EscrowInitialized {
+            seq: ctx.accounts.market_stats.next_event_seq(),
+            market: ctx.accounts.market_stats.market,
+            escrow: escrow_key,
+            writer: ctx.accounts.initializer.key(),
+            strike_price,
+            expiration,
+            collateral_amount,
+        }, ctx.accounts.event_authority.as_ref(), ctx.bumps.event_authority)?;
         Ok(())
     }
 
@@ -51,7 +436,11 @@ mod options_escrow {
     /// It ensures that the correct token type (SPL token) is deposited and verifies
     /// that the user's token account matches the specified collateral mint.
     pub fn deposit_collateral(ctx: Context<DepositCollateral>, amount: u64) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_DEPOSITS)?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), ctx.accounts.user.key)?;
         let escrow_account = &ctx.accounts.escrow_account;
+        escrow_account.require_allowed(ctx.accounts.user.key)?;
 
         // Ensure the user's collateral account mint matches the escrow's expected mint
         if ctx.accounts.user_collateral_account.mint != escrow_account.collateral_mint {
@@ -68,148 +457,183 @@ mod options_escrow {
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
         token::transfer(cpi_ctx, amount)?;
 
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.record_deposit(amount)?;
+        escrow_account.vault_bump = ctx.bumps.escrow_collateral_account;
+        escrow_account.enroll_emissions(
+            ctx.accounts.emissions_schedule.as_mut(),
+            ctx.accounts.writer_emissions.as_mut(),
+            Clock::get()?.unix_timestamp,
+        )?;
+        let market_stats = &mut ctx.accounts.market_stats;
+        market_stats.collateral_locked = market_stats.collateral_locked.saturating_add(amount);
+        emit_lifecycle_event(&CollateralDeposited {
+            seq: market_stats.next_event_seq(),
+            market: market_stats.market,
+            escrow: escrow_account.key(),
+            depositor: ctx.accounts.user.key(),
+            amount,
+        }, ctx.accounts.event_authority.as_ref(), ctx.bumps.event_authority)?;
         Ok(())
     }
 
     /// Settles the escrow account upon option expiration and deducts the fee.
     ///
-    /// The settlement depends on whether the option expires In-the-Money (ITM) or Out-of-the-Money (OTM).
-    /// If ITM, the collateral is transferred to the option holder, minus the governance fee.
-    /// If OTM, the collateral is returned to the initializer, also minus the fee.
-    pub fn settle_escrow(ctx: Context<SettleEscrow>, is_itm: bool) -> Result<()> {
-        let escrow_account = &mut ctx.accounts.escrow_account;
+    /// The settlement depends on whether the option expires In-the-Money (ITM) or Out-of-the-Money (OTM)
+    /// at the oracle price, or at the price set by governance if the circuit breaker tripped.
+    /// If ITM by at least the market's auto-exercise threshold (and the holder hasn't opted
+    /// out), the collateral (minus the governance fee) is held for the option holder to pull
+    /// with `claim_settlement`: the tracked holder if the escrow has one, or else the settler.
+    /// Otherwise the collateral is returned to the initializer, also minus the fee. The fee is
+    /// moved into the fee vault and accrued to the fee collectors for later claiming.
+    /// Knocked-out options and perpetual options whose funding lapsed can be settled before
+    /// expiry and always return the collateral.
+    pub fn settle_escrow(ctx: Context<SettleEscrow>) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_EXERCISE)?;
+        ctx.accounts.escrow_account.require_not_lent()?;
+        ctx.accounts.escrow_account.require_not_on_loan()?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_settler(ctx.accounts.series_settlers.as_deref(), ctx.accounts.user.key)?;
+        let escrow_account = &ctx.accounts.escrow_account;
         let governance = &ctx.accounts.governance;
+        escrow_account.require_allowed(ctx.accounts.user.key)?;
 
         // Ensure the option has not been exercised yet
         if escrow_account.is_exercised {
             return Err(ErrorCode::OptionAlreadyExercised.into());
         }
 
-        // Ensure the option has expired before settling
+        // Settlement records claims on the full collateral, so the vault must hold all of it
+        if escrow_account.deposited_amount < escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowNotFunded.into());
+        }
+
+        // Ensure the option has expired (or been knocked out, or its funding lapsed) before settling
         let current_time = Clock::get()?.unix_timestamp;
-        if current_time < escrow_account.expiration {
+        if current_time < escrow_account.expiration
+            && !escrow_account.is_knocked_out()
+            && !escrow_account.is_funding_lapsed(current_time)
+        {
             return Err(ErrorCode::OptionNotExpired.into());
         }
+        let is_itm = ctx.accounts.pricing().auto_exercises(ctx.remaining_accounts)?;
+        let greeks = ctx.accounts.greeks(current_time);
 
-        // Calculate the fee and remaining amount after fee deduction
-        let fee = escrow_account.collateral_amount * governance.fee_rate / 10000;
-        let amount_after_fee = escrow_account.collateral_amount - fee;
+        // Calculate the fee, collect it, and compute the remaining collateral
+        let collateral_amount = escrow_account.collateral_amount;
+        let fee = collateral_amount * escrow_account.fee_rate(governance.settlement_fee_bps) / 10000;
+        let collateral_fee = ctx.accounts.collect_fee(fee, is_itm)?;
+        let amount_after_fee = collateral_amount - collateral_fee;
 
-        // Handle the settlement based on whether the option is ITM or OTM
-        if is_itm {
-            // Transfer collateral (minus fee) to the option holder (user) if ITM
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.escrow_collateral_account.to_account_info(),
-                to: ctx.accounts.user_collateral_account.to_account_info(),
-                authority: ctx.accounts.escrow_authority.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, amount_after_fee)?;
-        } else {
-            // Return collateral (minus fee) to the initializer if OTM
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.escrow_collateral_account.to_account_info(),
-                to: ctx.accounts.initializer_collateral_account.to_account_info(),
-                authority: ctx.accounts.escrow_authority.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, amount_after_fee)?;
-        }
+        // Pay the crank incentive to the settler out of what is left
+        let crank_fee = ctx.accounts.crank_fee(amount_after_fee, current_time)?;
+        let user_collateral_account = ctx.accounts.user_collateral_account.to_account_info();
+        ctx.accounts.transfer_collateral(user_collateral_account, crank_fee)?;
+        let amount_after_fee = amount_after_fee - crank_fee;
 
-        // Transfer the collected fee to the fee collector
-        let cpi_accounts_fee = Transfer {
-            from: ctx.accounts.escrow_collateral_account.to_account_info(),
-            to: ctx.accounts.fee_collector.to_account_info(),
-            authority: ctx.accounts.escrow_authority.to_account_info(),
+        // Handle the settlement based on whether the option is ITM or OTM: the holder's proceeds
+        // (minus fee) and the rest, owed to the initializer, stay in the escrow until claimed.
+        // An escrow nobody holds has no one to pay, so its proceeds go back to the writer
+        let holder = ctx.accounts.escrow_account.holder_key();
+        let holder_amount = match (is_itm, holder) {
+            (true, Some(_)) => ctx.accounts.pricing().holder_payout(amount_after_fee)?,
+            _ => 0,
         };
-        let cpi_ctx_fee = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts_fee);
-        token::transfer(cpi_ctx_fee, fee)?;
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.payout_recipient = holder.unwrap_or_default();
+        escrow_account.unclaimed_payout = holder_amount;
+        escrow_account.unclaimed_writer_amount = amount_after_fee - holder_amount;
 
         // Mark the option as exercised
-        escrow_account.is_exercised = true;
+        ctx.accounts.escrow_account.is_exercised = true;
+        ctx.accounts.escrow_account.settled_at = current_time;
+        ctx.accounts.release_emissions_notional()?;
+        ctx.accounts.record_close(false);
+        emit_lifecycle_event(&EscrowSettled {
+            seq: ctx.accounts.market_stats.next_event_seq(),
+            market: ctx.accounts.market_stats.market,
+            escrow: ctx.accounts.escrow_account.key(),
+            is_itm,
+            holder_amount,
+            writer_amount: amount_after_fee - holder_amount,
+            fee: collateral_fee,
+            greeks,
+        }, ctx.accounts.event_authority.as_ref(), ctx.bumps.event_authority)?;
+        record_audit(
+            ctx.accounts.audit_log.as_ref(),
+            ctx.accounts.user.key(),
+            ctx.accounts.escrow_account.key(),
+            instruction::SettleEscrow::DISCRIMINATOR,
+            collateral_amount,
+        )?;
         Ok(())
     }
 
     /// Allows early exercise of the option for American-style options.
     ///
-    /// The option can be exercised early before the expiration if it's an American option.
+    /// The option can be exercised early before the expiration if it's an American option, or
+    /// during one of its exercise windows if it's a Bermudan option.
     /// It follows similar logic as `settle_escrow` to transfer the collateral based on
-    /// whether the option is ITM or OTM, and deducts the governance fee.
-    pub fn exercise_early(ctx: Context<SettleEscrow>, is_itm: bool) -> Result<()> {
-        let escrow_account = &mut ctx.accounts.escrow_account;
-
-        // Ensure the option has not been exercised yet
-        if escrow_account.is_exercised {
-            return Err(ErrorCode::OptionAlreadyExercised.into());
-        }
-
-        // Ensure it's an American option to allow early exercise
-        if escrow_account.option_type != OptionType::Call && escrow_account.option_type != OptionType::Put {
-            return Err(ErrorCode::CannotExerciseEarly.into());
-        }
-
-        // Calculate the fee and remaining amount after fee deduction
-        let governance = &ctx.accounts.governance;
-        let fee = escrow_account.collateral_amount * governance.fee_rate / 10000;
-        let amount_after_fee = escrow_account.collateral_amount - fee;
-
-        // Handle early exercise based on whether the option is ITM or OTM
-        if is_itm {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.escrow_collateral_account.to_account_info(),
-                to: ctx.accounts.user_collateral_account.to_account_info(),
-                authority: ctx.accounts.escrow_authority.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, amount_after_fee)?;
-        } else {
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.escrow_collateral_account.to_account_info(),
-                to: ctx.accounts.initializer_collateral_account.to_account_info(),
-                authority: ctx.accounts.escrow_authority.to_account_info(),
-            };
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-            token::transfer(cpi_ctx, amount_after_fee)?;
-        }
-
-        // Transfer the collected fee to the fee collector
-        let cpi_accounts_fee = Transfer {
-            from: ctx.accounts.escrow_collateral_account.to_account_info(),
-            to: ctx.accounts.fee_collector.to_account_info(),
-            authority: ctx.accounts.escrow_authority.to_account_info(),
-        };
-        let cpi_ctx_fee = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts_fee);
-        token::transfer(cpi_ctx_fee, fee)?;
-
-        // Mark the option as exercised
-        escrow_account.is_exercised = true;
+    /// whether the option is ITM or OTM at the oracle price, and deducts the governance fee.
+    /// If the writer set an early-exercise surcharge, the exerciser also pays it to the writer.
+    /// When the collateral is wSOL, an exerciser receiving its own proceeds can pass `unwrap_sol`
+    /// to close its wSOL account and receive native SOL instead.
+    pub fn exercise_early(ctx: Context<SettleEscrow>, unwrap_sol: bool) -> Result<()> {
+        ctx.accounts.exercise(ctx.remaining_accounts, unwrap_sol, ctx.bumps.event_authority, instruction::ExerciseEarly::DISCRIMINATOR)?;
+        Ok(())
+    }
 
+    /// Updates the fee collectors.
+    ///
+    /// This function allows the governance authority to update the weighted list of accounts
+    /// where protocol fees are collected (e.g. treasury, insurance fund, stakers).
+    pub fn update_governance(ctx: Context<UpdateGovernance>, new_fee_collectors: Vec<FeeCollector>) -> Result<()> {
+        validate_fee_collectors(&new_fee_collectors)?;
+        let governance = &mut ctx.accounts.governance;
+        governance.fee_collectors = new_fee_collectors;
         Ok(())
     }
 
-    /// Updates governance parameters (fee rate and fee collector).
+    /// Updates a single protocol fee rate.
     ///
-    /// This function allows the governance authority to update key parameters, including the
-    /// fee rate (as basis points) and the address where protocol fees are collected.
-    pub fn update_governance(ctx: Context<UpdateGovernance>, new_fee_rate: u64, new_fee_collector: Pubkey) -> Result<()> {
+    /// The initialization, settlement, and early exercise fees are controlled independently,
+    /// so governance can change one without touching the others.
+    pub fn set_fee_rate(ctx: Context<UpdateGovernance>, fee_type: FeeType, new_fee_bps: u64) -> Result<()> {
+        if new_fee_bps > MAX_FEE_BPS {
+            return Err(ErrorCode::FeeRateTooHigh.into());
+        }
         let governance = &mut ctx.accounts.governance;
-        governance.fee_rate = new_fee_rate;
-        governance.fee_collector = new_fee_collector;
+        match fee_type {
+            FeeType::Init => governance.init_fee_bps = new_fee_bps,
+            FeeType::Settlement => governance.settlement_fee_bps = new_fee_bps,
+            FeeType::Exercise => governance.exercise_fee_bps = new_fee_bps,
+            FeeType::Cancellation => governance.cancellation_fee_bps = new_fee_bps,
+            FeeType::InitFloor => governance.min_init_fee_bps = new_fee_bps,
+            FeeType::Crank => governance.crank_fee_bps = new_fee_bps,
+        }
         Ok(())
     }
 
     /// Initializes the governance account.
     ///
-    /// This function sets up the governance account, allowing it to store the initial fee rate,
-    /// fee collector address, and governance authority responsible for future updates.
-    pub fn initialize_governance(ctx: Context<InitializeGovernance>, fee_rate: u64, fee_collector: Pubkey) -> Result<()> {
+    /// This function sets up the governance account, allowing it to store the initial fee rates,
+    /// weighted fee collectors, and governance authority responsible for future updates.
+    pub fn initialize_governance(
+        ctx: Context<InitializeGovernance>,
+        init_fee_bps: u64,            // Fee charged on escrow initialization
+        settlement_fee_bps: u64,      // Fee charged on settlement at expiry
+        exercise_fee_bps: u64,        // Fee charged on early exercise
+        fee_collectors: Vec<FeeCollector>,
+    ) -> Result<()> {
+        if init_fee_bps > MAX_FEE_BPS || settlement_fee_bps > MAX_FEE_BPS || exercise_fee_bps > MAX_FEE_BPS {
+            return Err(ErrorCode::FeeRateTooHigh.into());
+        }
+        validate_fee_collectors(&fee_collectors)?;
         let governance = &mut ctx.accounts.governance;
-        governance.fee_rate = fee_rate;
-        governance.fee_collector = fee_collector;
+        governance.init_fee_bps = init_fee_bps;
+        governance.settlement_fee_bps = settlement_fee_bps;
+        governance.exercise_fee_bps = exercise_fee_bps;
+        governance.fee_collectors = fee_collectors;
         governance.governance_authority = *ctx.accounts.governance_authority.key;
         Ok(())
     }
@@ -223,138 +647,12477 @@ mod options_escrow {
         governance.governance_authority = new_governance_authority;
         Ok(())
     }
-}
 
-#[account]
-/// Structure to hold escrow account data.
-///
-/// This account stores the details of the escrow, such as the initializer (option writer),
-/// the type of option (Call or Put), strike price, expiration, collateral amount, and whether
-/// the option has been exercised.
-pub struct EscrowAccount {
-    pub initializer_key: Pubkey,     // The user who initialized the escrow
-    pub option_type: OptionType,     // Call or Put option
-    pub strike_price: u64,           // Strike price for the option
-    pub expiration: i64,             // Expiration time (Unix timestamp)
-    pub collateral_amount: u64,      // Collateral amount deposited in the escrow
-    pub collateral_mint: Pubkey,     // Token mint for the collateral (SPL token)
-    pub is_exercised: bool,          // Indicates if the option has been exercised
-}
+    /// Transfers the governance authority to a Realms DAO.
+    ///
+    /// The new authority is the native treasury of `realms_governance`, a system-owned PDA the
+    /// SPL Governance program signs for when executing a passed proposal. Unlike the governance
+    /// account itself, the native treasury can also pay rent for the accounts governance creates.
+    pub fn transfer_governance_to_realms(
+        ctx: Context<UpdateGovernance>,
+        governance_program: Pubkey,   // SPL Governance program instance the DAO uses
+        realms_governance: Pubkey,    // Realms governance account that will own the protocol
+    ) -> Result<()> {
+        let governance = &mut ctx.accounts.governance;
+        governance.governance_authority = realms_native_treasury(&governance_program, &realms_governance);
+        Ok(())
+    }
 
-/// Governance account storing key parameters for the protocol.
-///
-/// The governance account stores the fee rate (in basis points) for the protocol and the
-/// address of the fee collector. It also stores the governance authority, which is allowed
-/// to update these parameters.
-#[account]
-pub struct Governance {
-    pub fee_rate: u64,                // Fee rate in basis points (e.g., 500 = 5.00%)
-    pub fee_collector: Pubkey,        // Address where protocol fees are collected
-    pub governance_authority: Pubkey, // Account authorized to update governance settings
-}
+    /// Creates the market for a collateral mint.
+    ///
+    /// Every collateral mint needs a market before escrows using it can be created. The market
+    /// starts out charging fees in the collateral mint itself; governance can later switch it
+    /// to a dedicated fee mint with `set_market_fee_mint`.
+    pub fn initialize_market(ctx: Context<InitializeMarket>, collateral_oracle: Pubkey) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.collateral_mint = ctx.accounts.collateral_mint.key();
+        market.collateral_decimals = ctx.accounts.collateral_mint.decimals;
+        market.fee_mint = ctx.accounts.collateral_mint.key();
+        market.fee_decimals = ctx.accounts.collateral_mint.decimals;
+        market.collateral_oracle = collateral_oracle;
+        market.fee_oracle = collateral_oracle;
+        market.points_weights = PointsWeights { notional_written: 0, premium_paid: 0, fees_paid: 0 };
+        market.paused_flags = 0;
+        market.max_open_notional_per_user = 0;
+        market.open_interest = 0;
+        market.open_interest_cap = 0;
+        market.min_strike_bps = 0;
+        market.max_strike_bps = 0;
+        market.max_price_deviation_bps = 0;
+        market.oracle_config = OracleConfig::DEFAULT;
+        market.uses_oracle_registry = false;
+        market.uses_expiry_grid = false;
+        market.twap_window = 0;
+        market.auto_exercise_threshold_bps = 0;
+        market.exercise_cutoff = 0;
+        market.funding_deadline = 0;
+        market.max_tenor = 0;
+        market.compliance_authority = Pubkey::default();
+        market.gate_mint = Pubkey::default();
+        market.gate_min_amount = 0;
+        market.bump = ctx.bumps.market;
 
-/// Enum to define the option type (Call or Put).
-///
-/// This enum specifies the type of option being created: either a Call option (buy) or a Put option (sell).
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
-pub enum OptionType {
-    Call, // Call option gives the buyer the right to buy
-    Put,  // Put option gives the buyer the right to sell
-}
+        let market_stats = &mut ctx.accounts.market_stats;
+        market_stats.market = market.key();
+        market_stats.notional_volume = 0;
+        market_stats.collateral_locked = 0;
+        market_stats.fees_collected = 0;
+        market_stats.escrows_opened = 0;
+        market_stats.escrows_closed = 0;
+        market_stats.event_seq = 0;
+        market_stats.bump = ctx.bumps.market_stats;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-/// Context for initializing the escrow.
-///
-/// This struct defines the context for the `initialize_escrow` instruction, specifying
-/// the accounts involved, including the escrow account, the initializer, the collateral
-/// accounts, and the governance account.
-pub struct InitializeEscrow<'info> {
-    #[account(init, payer = initializer, space = 8 + 8 + 8 + 8 + 8 + 32 + 1)]
-    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow account to store option details
-    #[account(mut)]
-    pub initializer: Signer<'info>,                      // The initializer (creator of the escrow)
-    #[account(mut)]
-    pub initializer_collateral_account: Account<'info, TokenAccount>,  // Initializer's token account for collateral
-    #[account(mut)]
-    pub fee_collector: Account<'info, TokenAccount>,     // Account where protocol fees are sent
-    #[account(mut)]
-    pub governance: Account<'info, Governance>,          // Governance account storing fee rate and fee collector
-    pub system_program: Program<'info, System>,          // System program for account creation
-    pub token_program: Program<'info, Token>,            // Token program for handling SPL tokens
-    pub rent: Sysvar<'info, Rent>,                       // Rent system for account initialization
-}
+    /// Sets the mint in which a market charges its fees.
+    ///
+    /// When the fee mint differs from the collateral mint, fees computed on the collateral are
+    /// converted into the fee mint using the two oracle prices and paid by the user from a
+    /// fee mint token account. Passing the collateral mint switches back to collateral fees.
+    pub fn set_market_fee_mint(ctx: Context<SetMarketFeeMint>, collateral_oracle: Pubkey, fee_oracle: Pubkey) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.fee_mint = ctx.accounts.fee_mint.key();
+        market.fee_decimals = ctx.accounts.fee_mint.decimals;
+        market.collateral_oracle = collateral_oracle;
+        market.fee_oracle = fee_oracle;
+        ctx.accounts.audit(instruction::SetMarketFeeMint::DISCRIMINATOR, 0)?;
+        Ok(())
+    }
 
-#[derive(Accounts)]
-/// Context for depositing collateral into the escrow.
-///
-/// This struct defines the context for the `deposit_collateral` instruction, specifying
-/// the user's collateral account, the escrow account, and the necessary programs.
-pub struct DepositCollateral<'info> {
-    #[account(mut)]
-    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow account receiving collateral
-    #[account(mut)]
-    pub user: Signer<'info>,                              // User depositing collateral
-    #[account(mut)]
-    pub user_collateral_account: Account<'info, TokenAccount>,  // User's token account for depositing collateral
-    #[account(mut)]
-    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's token account holding collateral
-    pub token_program: Program<'info, Token>,             // Token program for token transfers
-}
+    /// Creates the fee vault for a token mint.
+    ///
+    /// Every collateral mint needs a fee vault before escrows using it can be created. The
+    /// vault's token account is owned by the fee vault PDA, so fees always have a valid
+    /// destination and never depend on a collector's token account existing.
+    pub fn initialize_fee_vault(ctx: Context<InitializeFeeVault>) -> Result<()> {
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        fee_vault.mint = ctx.accounts.mint.key();
+        fee_vault.token_account = ctx.accounts.fee_vault_token_account.key();
+        fee_vault.bump = ctx.bumps.fee_vault;
+        fee_vault.claims = Vec::new();
+        Ok(())
+    }
 
-#[derive(Accounts)]
-/// Context for settling the escrow when the option expires.
-///
-/// This struct defines the context for the `settle_escrow` and `exercise_early` instructions,
-/// specifying the involved accounts, including the escrow, the user, the initializer, and the
-/// governance and fee accounts.
-pub struct SettleEscrow<'info> {
-    #[account(mut)]
-    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow account storing option details
-    #[account(mut)]
-    pub user: Signer<'info>,                              // The user settling the option
-    #[account(mut)]
-    pub user_collateral_account: Account<'info, TokenAccount>,  // User's token account (receiving collateral if ITM)
-    #[account(mut)]
-    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's token account holding collateral
-    #[account(mut)]
-    pub initializer_collateral_account: Account<'info, TokenAccount>, // Initializer's token account (receiving collateral if OTM)
-    #[account(mut)]
-    pub escrow_authority: AccountInfo<'info>,             // The authority controlling the escrow (PDA)
-    #[account(mut)]
-    pub fee_collector: Account<'info, TokenAccount>,      // Account where protocol fees are sent
-    #[account(mut)]
-    pub governance: Account<'info, Governance>,           // Governance account storing fee rate and fee collector
-    pub token_program: Program<'info, Token>,             // Token program for token transfers
-}
+    /// Pays out the fees accrued to a fee collector.
+    ///
+    /// The claimable balance is looked up by the owner of the destination token account, so
+    /// fees can only ever reach the collector itself. Anyone may call this on the collector's
+    /// behalf, which also allows PDA collectors to be paid.
+    pub fn claim_fees(ctx: Context<ClaimFees>) -> Result<()> {
+        let recipient = ctx.accounts.recipient_token_account.owner;
 
-#[derive(Accounts)]
-/// Context for updating governance settings.
-///
-/// This struct defines the context for the `update_governance` instruction, which
-/// allows the governance authority to update the fee rate and fee collector.
-pub struct UpdateGovernance<'info> {
-    #[account(mut, has_one = governance_authority)]
-    pub governance: Account<'info, Governance>,  // Governance account to be updated
-    pub governance_authority: Signer<'info>,     // Governance authority account
-}
+        // The staking pool's share is paid out to stakers through `claim_rewards`
+        let (staking_pool, _) = Pubkey::find_program_address(&[STAKING_POOL_SEED], ctx.program_id);
+        if recipient == staking_pool {
+            return Err(ErrorCode::StakingRewardsNotClaimable.into());
+        }
+        let amount = ctx.accounts.fee_vault.take_claim(&recipient)?;
 
-#[derive(Accounts)]
-/// Context for initializing the governance account.
-///
-/// This struct defines the context for the `initialize_governance` instruction, which
-/// creates the governance account and sets the initial fee rate and fee collector.
-pub struct InitializeGovernance<'info> {
-    #[account(init, payer = governance_authority, space = 8 + 32 + 32 + 8)]
-    pub governance: Account<'info, Governance>,           // Governance account to store protocol parameters
-    #[account(mut)]
-    pub governance_authority: Signer<'info>,              // Initial governance authority (e.g., program deployer)
-    pub system_program: Program<'info, System>,           // System program for account creation
-}
+        let mint = ctx.accounts.fee_vault.mint;
+        let bump = ctx.accounts.fee_vault.bump;
+        let seeds: &[&[u8]] = &[FEE_VAULT_SEED, mint.as_ref(), &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.fee_vault_token_account.to_account_info(),
+            to: ctx.accounts.recipient_token_account.to_account_info(),
+            authority: ctx.accounts.fee_vault.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
 
-#[error_code]
-/// Custom error codes for the program.
-///
-/// This enum defines custom error codes for common failure cases in the protocol,
+        Ok(())
+    }
+
+    /// Moves the staking pool's fees in a mint other than its reward mint to the treasury of
+    /// that mint.
+    ///
+    /// Stakers are only paid in the reward mint, so the pool's share of fees in other mints
+    /// could otherwise never leave the fee vault. The treasury then claims them with
+    /// `claim_fees`. Anyone can call this.
+    pub fn sweep_staking_fees(ctx: Context<SweepStakingFees>) -> Result<()> {
+        let pool_key = ctx.accounts.staking_pool.key();
+        let amount = ctx.accounts.fee_vault.take_claim(&pool_key)?;
+        let treasury = ctx.accounts.treasury.key();
+        ctx.accounts.fee_vault.credit(treasury, amount)
+    }
+
+    /// Creates the protocol treasury for a token mint.
+    ///
+    /// The treasury is a PDA whose token account can only be drained through
+    /// `withdraw_treasury`. Adding the treasury PDA as a fee collector makes `claim_fees`
+    /// pay its share of protocol fees into the treasury token account.
+    pub fn initialize_treasury(ctx: Context<InitializeTreasury>) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.mint = ctx.accounts.mint.key();
+        treasury.token_account = ctx.accounts.treasury_token_account.key();
+        treasury.bump = ctx.bumps.treasury;
+        treasury.pending_withdrawal = None;
+        Ok(())
+    }
+
+    /// Sets the delay between queueing and executing a treasury withdrawal.
+    ///
+    /// The timelock is at least `MIN_TREASURY_TIMELOCK`. Increases apply immediately, while a
+    /// decrease only takes effect once the current timelock has passed, so a compromised
+    /// authority can't shorten it and drain the treasury at once.
+    pub fn set_treasury_timelock(ctx: Context<UpdateGovernance>, treasury_timelock: i64) -> Result<()> {
+        if treasury_timelock < MIN_TREASURY_TIMELOCK {
+            return Err(ErrorCode::TreasuryTimelockTooShort.into());
+        }
+        let current_time = Clock::get()?.unix_timestamp;
+        let governance = &mut ctx.accounts.governance;
+        let current_timelock = governance.current_treasury_timelock(current_time);
+        if treasury_timelock >= current_timelock {
+            governance.treasury_timelock = treasury_timelock;
+            governance.pending_treasury_timelock = 0;
+        } else {
+            governance.treasury_timelock = current_timelock;
+            governance.pending_treasury_timelock = treasury_timelock;
+            governance.treasury_timelock_change_at = current_time.checked_add(current_timelock).ok_or(ErrorCode::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Queues a treasury withdrawal that can be executed once the timelock has passed.
+    ///
+    /// Queueing replaces any withdrawal that is already pending.
+    pub fn queue_treasury_withdrawal(ctx: Context<QueueTreasuryWithdrawal>, amount: u64, destination: Pubkey) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let unlock_time = current_time
+            .checked_add(ctx.accounts.governance.current_treasury_timelock(current_time))
+            .ok_or(ErrorCode::MathOverflow)?;
+        ctx.accounts.treasury.pending_withdrawal = Some(PendingWithdrawal { amount, destination, unlock_time });
+        Ok(())
+    }
+
+    /// Cancels the pending treasury withdrawal.
+    pub fn cancel_treasury_withdrawal(ctx: Context<QueueTreasuryWithdrawal>) -> Result<()> {
+        ctx.accounts.treasury.pending_withdrawal = None;
+        Ok(())
+    }
+
+    /// Withdraws tokens from the treasury to a destination token account.
+    ///
+    /// Only the governance authority can withdraw. The same amount and destination must have
+    /// been queued with `queue_treasury_withdrawal` and the timelock must have passed.
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64, destination: Pubkey) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+
+        // Enforce the timelock by consuming the matching queued withdrawal
+        treasury.consume_withdrawal(amount, destination)?;
+
+        let mint = treasury.mint;
+        let bump = treasury.bump;
+        let seeds: &[&[u8]] = &[TREASURY_SEED, mint.as_ref(), &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.treasury_token_account.to_account_info(),
+            to: ctx.accounts.destination_token_account.to_account_info(),
+            authority: treasury.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+
+    /// Creates the governance token staking pool.
+    ///
+    /// Stakers earn the pool's share of protocol fees paid in `reward_mint`. To route fees to
+    /// stakers, governance adds the staking pool PDA as a fee collector. Locking is disabled
+    /// until governance sets the lock parameters with `set_lock_params`.
+    pub fn initialize_staking_pool(ctx: Context<InitializeStakingPool>) -> Result<()> {
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.stake_mint = ctx.accounts.stake_mint.key();
+        staking_pool.reward_mint = ctx.accounts.reward_mint.key();
+        staking_pool.stake_vault = ctx.accounts.stake_vault.key();
+        staking_pool.total_staked = 0;
+        staking_pool.total_boosted = 0;
+        staking_pool.reward_per_share = 0;
+        staking_pool.penalty_per_share = 0;
+        staking_pool.max_lock_duration = 0;
+        staking_pool.max_boost_bps = 0;
+        staking_pool.early_exit_penalty_bps = 0;
+        staking_pool.bump = ctx.bumps.staking_pool;
+        Ok(())
+    }
+
+    /// Sets the vote-escrow lock parameters of the staking pool.
+    ///
+    /// A lock of `max_lock_duration` earns `max_boost_bps` of extra rewards and doubles the
+    /// staker's voting power; shorter locks scale linearly. Unstaking before the lock ends
+    /// forfeits up to `early_exit_penalty_bps` of the amount to the remaining lockers.
+    pub fn set_lock_params(
+        ctx: Context<SetLockParams>,
+        max_lock_duration: i64,
+        max_boost_bps: u64,
+        early_exit_penalty_bps: u64,
+    ) -> Result<()> {
+        if max_lock_duration < 0 || early_exit_penalty_bps > 10000 {
+            return Err(ErrorCode::InvalidLockParams.into());
+        }
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.max_lock_duration = max_lock_duration;
+        staking_pool.max_boost_bps = max_boost_bps;
+        staking_pool.early_exit_penalty_bps = early_exit_penalty_bps;
+        Ok(())
+    }
+
+    /// Creates an empty stake position for the signer.
+    pub fn create_stake_position(ctx: Context<CreateStakePosition>) -> Result<()> {
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.owner = ctx.accounts.owner.key();
+        stake_position.amount = 0;
+        stake_position.boosted_amount = 0;
+        stake_position.lock_end = 0;
+        stake_position.reward_debt = 0;
+        stake_position.penalty_debt = 0;
+        stake_position.unclaimed_rewards = 0;
+        stake_position.vote_lock_end = 0;
+        stake_position.discount_amount = 0;
+        stake_position.last_stake_time = 0;
+        stake_position.bump = ctx.bumps.stake_position;
+        Ok(())
+    }
+
+    /// Stakes governance tokens into the staking pool.
+    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        let pool_key = ctx.accounts.staking_pool.key();
+        ctx.accounts.staking_pool.sync(&pool_key, &mut ctx.accounts.fee_vault)?;
+        ctx.accounts.stake_position.settle_rewards(&ctx.accounts.staking_pool)?;
+
+        // Transfer the governance tokens into the stake vault
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.owner_stake_account.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.record_stake(amount, current_time)?;
+        staking_pool.total_staked = staking_pool.total_staked.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        stake_position.update_boost(staking_pool, current_time)?;
+        Ok(())
+    }
+
+    /// Unstakes governance tokens from the staking pool.
+    ///
+    /// Rewards earned so far stay claimable through `claim_rewards`. Unstaking while the
+    /// position is locked costs an early-exit penalty proportional to the remaining lock time,
+    /// which is shared among the remaining stakers.
+    pub fn unstake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        let pool_key = ctx.accounts.staking_pool.key();
+        ctx.accounts.staking_pool.sync(&pool_key, &mut ctx.accounts.fee_vault)?;
+        ctx.accounts.stake_position.settle_rewards(&ctx.accounts.staking_pool)?;
+
+        if amount > ctx.accounts.stake_position.amount {
+            return Err(ErrorCode::InsufficientStake.into());
+        }
+
+        // Tokens that voted stay staked until voting ends, so they can't vote again elsewhere
+        let current_time = Clock::get()?.unix_timestamp;
+        if current_time < ctx.accounts.stake_position.vote_lock_end {
+            return Err(ErrorCode::StakeLockedForVote.into());
+        }
+
+        // Charge the early-exit penalty, unless nobody else is left to receive it
+        let staking_pool = &ctx.accounts.staking_pool;
+        let stake_position = &ctx.accounts.stake_position;
+        let remaining_boosted = staking_pool.total_boosted - stake_position.boosted_amount;
+        let penalty = if remaining_boosted == 0 {
+            0
+        } else {
+            staking_pool.early_exit_penalty(amount, stake_position.lock_end, current_time)
+        };
+
+        // Return the governance tokens from the stake vault
+        let bump = ctx.accounts.staking_pool.bump;
+        let seeds: &[&[u8]] = &[STAKING_POOL_SEED, &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.owner_stake_account.to_account_info(),
+            authority: ctx.accounts.staking_pool.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount - penalty)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.amount -= amount;
+        staking_pool.total_staked -= amount - penalty;
+        if penalty > 0 {
+            let increment = (penalty as u128)
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(ErrorCode::MathOverflow)?
+                / remaining_boosted as u128;
+            staking_pool.penalty_per_share = staking_pool.penalty_per_share.checked_add(increment).ok_or(ErrorCode::MathOverflow)?;
+        }
+        stake_position.update_boost(staking_pool, current_time)?;
+        Ok(())
+    }
+
+    /// Locks the signer's stake until `current time + lock_duration`.
+    ///
+    /// Locks can only be extended, never shortened. The reward boost and voting power are
+    /// recomputed from the new lock.
+    pub fn lock_stake(ctx: Context<LockStake>, lock_duration: i64) -> Result<()> {
+        let staking_pool = &ctx.accounts.staking_pool;
+        if staking_pool.max_lock_duration == 0 || lock_duration <= 0 || lock_duration > staking_pool.max_lock_duration {
+            return Err(ErrorCode::InvalidLockDuration.into());
+        }
+
+        let pool_key = ctx.accounts.staking_pool.key();
+        ctx.accounts.staking_pool.sync(&pool_key, &mut ctx.accounts.fee_vault)?;
+        ctx.accounts.stake_position.settle_rewards(&ctx.accounts.staking_pool)?;
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let stake_position = &mut ctx.accounts.stake_position;
+        let lock_end = current_time.checked_add(lock_duration).ok_or(ErrorCode::MathOverflow)?;
+        if lock_end < stake_position.lock_end {
+            return Err(ErrorCode::InvalidLockDuration.into());
+        }
+        stake_position.lock_end = lock_end;
+        stake_position.update_boost(&mut ctx.accounts.staking_pool, current_time)?;
+        Ok(())
+    }
+
+    /// Recomputes the reward boost of any stake position.
+    ///
+    /// Boosts decay linearly with the remaining lock time but are only recomputed when a
+    /// position is touched. Anyone can call this to apply the decay to a position.
+    pub fn poke_stake_position(ctx: Context<PokeStakePosition>) -> Result<()> {
+        let pool_key = ctx.accounts.staking_pool.key();
+        ctx.accounts.staking_pool.sync(&pool_key, &mut ctx.accounts.fee_vault)?;
+        ctx.accounts.stake_position.settle_rewards(&ctx.accounts.staking_pool)?;
+        ctx.accounts.stake_position.update_boost(&mut ctx.accounts.staking_pool, Clock::get()?.unix_timestamp)
+    }
+
+    /// Pays out the fee rewards a staker has earned.
+    ///
+    /// Rewards are held in the fee vault of the reward mint until they are claimed.
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let pool_key = ctx.accounts.staking_pool.key();
+        ctx.accounts.staking_pool.sync(&pool_key, &mut ctx.accounts.fee_vault)?;
+        ctx.accounts.stake_position.settle_rewards(&ctx.accounts.staking_pool)?;
+
+        let amount = ctx.accounts.stake_position.unclaimed_rewards;
+        if amount == 0 {
+            return Err(ErrorCode::NoFeesToClaim.into());
+        }
+        ctx.accounts.stake_position.unclaimed_rewards = 0;
+        ctx.accounts.stake_position.update_boost(&mut ctx.accounts.staking_pool, Clock::get()?.unix_timestamp)?;
+
+        let mint = ctx.accounts.fee_vault.mint;
+        let bump = ctx.accounts.fee_vault.bump;
+        let seeds: &[&[u8]] = &[FEE_VAULT_SEED, mint.as_ref(), &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.fee_vault_token_account.to_account_info(),
+            to: ctx.accounts.owner_reward_account.to_account_info(),
+            authority: ctx.accounts.fee_vault.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+
+    /// Creates the buyback configuration for a treasury mint.
+    ///
+    /// Buybacks spend funds from the treasury of `input_mint`, so that treasury must exist.
+    pub fn initialize_buyback(ctx: Context<InitializeBuyback>, params: BuybackParams) -> Result<()> {
+        validate_buyback_params(&params)?;
+        let buyback_config = &mut ctx.accounts.buyback_config;
+        buyback_config.input_mint = ctx.accounts.input_mint.key();
+        buyback_config.input_decimals = ctx.accounts.input_mint.decimals;
+        buyback_config.governance_token_mint = ctx.accounts.governance_token_mint.key();
+        buyback_config.governance_token_decimals = ctx.accounts.governance_token_mint.decimals;
+        buyback_config.buyback_token_account = ctx.accounts.buyback_token_account.key();
+        buyback_config.params = params;
+        buyback_config.period_start = Clock::get()?.unix_timestamp;
+        buyback_config.spent_in_period = 0;
+        buyback_config.bump = ctx.bumps.buyback_config;
+        Ok(())
+    }
+
+    /// Updates the buyback parameters (swap program, oracles, and rate limits).
+    pub fn update_buyback(ctx: Context<UpdateBuyback>, params: BuybackParams) -> Result<()> {
+        validate_buyback_params(&params)?;
+        ctx.accounts.buyback_config.params = params;
+        Ok(())
+    }
+
+    /// Swaps treasury funds for the governance token and burns the tokens received.
+    ///
+    /// Anyone can call this. The swap is executed by CPI into the whitelisted swap program
+    /// using `swap_data` and the remaining accounts, with the treasury PDA signing for the
+    /// input tokens. The output must reach the buyback token account, be at least
+    /// `min_amount_out`, and be within `max_slippage_bps` of the oracle price, and spending is
+    /// capped per period. The transaction reverts if it lands after `deadline`.
+    pub fn buyback<'info>(
+        ctx: Context<'_, '_, '_, 'info, Buyback<'info>>,
+        amount_in: u64,
+        swap_data: Vec<u8>,
+        min_amount_out: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        require_before_deadline(deadline)?;
+        let buyback_config = &mut ctx.accounts.buyback_config;
+        let params = buyback_config.params.clone();
+
+        // Roll the rate limit period and enforce the cap
+        let current_time = Clock::get()?.unix_timestamp;
+        if current_time >= buyback_config.period_start.saturating_add(params.period_duration) {
+            buyback_config.period_start = current_time;
+            buyback_config.spent_in_period = 0;
+        }
+        let spent_in_period = buyback_config.spent_in_period.checked_add(amount_in).ok_or(ErrorCode::MathOverflow)?;
+        if spent_in_period > params.max_amount_per_period {
+            return Err(ErrorCode::BuybackLimitExceeded.into());
+        }
+
+        let treasury_before = ctx.accounts.treasury_token_account.amount;
+        let bought_before = ctx.accounts.buyback_token_account.amount;
+
+        // Execute the swap with the treasury PDA signing for the input tokens
+        let treasury_key = ctx.accounts.treasury.key();
+        let account_metas = ctx
+            .remaining_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer || account.key() == treasury_key,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let swap_ix = Instruction {
+            program_id: ctx.accounts.swap_program.key(),
+            accounts: account_metas,
+            data: swap_data,
+        };
+        let mint = ctx.accounts.treasury.mint;
+        let treasury_bump = ctx.accounts.treasury.bump;
+        let treasury_seeds: &[&[u8]] = &[TREASURY_SEED, mint.as_ref(), &[treasury_bump]];
+        invoke_signed(&swap_ix, ctx.remaining_accounts, &[treasury_seeds])?;
+
+        ctx.accounts.treasury_token_account.reload()?;
+        ctx.accounts.buyback_token_account.reload()?;
+        let spent = treasury_before.saturating_sub(ctx.accounts.treasury_token_account.amount);
+        let bought = ctx.accounts.buyback_token_account.amount.saturating_sub(bought_before);
+        if spent > amount_in {
+            return Err(ErrorCode::BuybackLimitExceeded.into());
+        }
+
+        // Require a fill close to the oracle price
+        let input_price = load_oracle_price(&ctx.accounts.input_oracle.to_account_info(), &OracleConfig::DEFAULT)?;
+        let governance_token_price = load_oracle_price(&ctx.accounts.governance_token_oracle.to_account_info(), &OracleConfig::DEFAULT)?;
+        let buyback_config = &mut ctx.accounts.buyback_config;
+        let expected = convert_amount(
+            spent,
+            &input_price,
+            buyback_config.input_decimals,
+            &governance_token_price,
+            buyback_config.governance_token_decimals,
+        )?;
+        let min_out = (expected as u128 * (10000 - params.max_slippage_bps) as u128 / 10000) as u64;
+        if bought < min_out.max(min_amount_out) {
+            return Err(ErrorCode::SlippageExceeded.into());
+        }
+        buyback_config.spent_in_period = buyback_config.spent_in_period.checked_add(spent).ok_or(ErrorCode::MathOverflow)?;
+
+        // Burn the governance tokens that were bought
+        let input_mint = buyback_config.input_mint;
+        let buyback_bump = buyback_config.bump;
+        let buyback_seeds: &[&[u8]] = &[BUYBACK_SEED, input_mint.as_ref(), &[buyback_bump]];
+        let signer_seeds = &[buyback_seeds];
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.governance_token_mint.to_account_info(),
+            from: ctx.accounts.buyback_token_account.to_account_info(),
+            authority: ctx.accounts.buyback_config.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::burn(cpi_ctx, bought)?;
+
+        Ok(())
+    }
+
+    /// Sets the fee discount tiers for governance token stakers.
+    ///
+    /// Tiers must be sorted by increasing `min_staked` with discounts of at most 100%. A
+    /// staker gets the discount of the highest tier their stake qualifies for.
+    pub fn set_fee_discount_tiers(ctx: Context<UpdateGovernance>, tiers: Vec<FeeDiscountTier>) -> Result<()> {
+        validate_fee_discount_tiers(&tiers)?;
+        ctx.accounts.governance.fee_discount_tiers = tiers;
+        Ok(())
+    }
+
+    /// Creates the liquidity-mining emissions schedule for a market.
+    ///
+    /// The schedule's reward vault must be funded by transferring reward tokens into it.
+    /// Escrows opened while the schedule exists earn emissions for their writers.
+    pub fn initialize_emissions(ctx: Context<InitializeEmissions>, epoch_duration: i64, emission_per_epoch: u64) -> Result<()> {
+        if epoch_duration <= 0 {
+            return Err(ErrorCode::InvalidEmissionsParams.into());
+        }
+        let emissions_schedule = &mut ctx.accounts.emissions_schedule;
+        emissions_schedule.market = ctx.accounts.market.key();
+        emissions_schedule.reward_mint = ctx.accounts.reward_mint.key();
+        emissions_schedule.reward_vault = ctx.accounts.reward_vault.key();
+        emissions_schedule.epoch_duration = epoch_duration;
+        emissions_schedule.emission_per_epoch = emission_per_epoch;
+        emissions_schedule.total_open_notional = 0;
+        emissions_schedule.reward_per_notional = 0;
+        emissions_schedule.last_update = Clock::get()?.unix_timestamp;
+        emissions_schedule.bump = ctx.bumps.emissions_schedule;
+        Ok(())
+    }
+
+    /// Changes the emission rate of a market's schedule from now on.
+    pub fn set_emission_rate(ctx: Context<SetEmissionRate>, epoch_duration: i64, emission_per_epoch: u64) -> Result<()> {
+        if epoch_duration <= 0 {
+            return Err(ErrorCode::InvalidEmissionsParams.into());
+        }
+        let emissions_schedule = &mut ctx.accounts.emissions_schedule;
+        emissions_schedule.update(Clock::get()?.unix_timestamp)?;
+        emissions_schedule.epoch_duration = epoch_duration;
+        emissions_schedule.emission_per_epoch = emission_per_epoch;
+        Ok(())
+    }
+
+    /// Creates the signer's emissions position in a market.
+    pub fn create_writer_emissions(ctx: Context<CreateWriterEmissions>) -> Result<()> {
+        let writer_emissions = &mut ctx.accounts.writer_emissions;
+        writer_emissions.owner = ctx.accounts.owner.key();
+        writer_emissions.market = ctx.accounts.market.key();
+        writer_emissions.open_notional = 0;
+        writer_emissions.reward_debt = 0;
+        writer_emissions.unclaimed_emissions = 0;
+        writer_emissions.bump = ctx.bumps.writer_emissions;
+        Ok(())
+    }
+
+    /// Pays out the liquidity-mining emissions a writer has earned.
+    pub fn claim_emissions(ctx: Context<ClaimEmissions>) -> Result<()> {
+        let emissions_schedule = &mut ctx.accounts.emissions_schedule;
+        let writer_emissions = &mut ctx.accounts.writer_emissions;
+        emissions_schedule.update(Clock::get()?.unix_timestamp)?;
+        writer_emissions.settle_emissions(emissions_schedule)?;
+
+        let amount = writer_emissions.unclaimed_emissions;
+        if amount == 0 {
+            return Err(ErrorCode::NoEmissionsToClaim.into());
+        }
+        writer_emissions.unclaimed_emissions = 0;
+
+        let market = emissions_schedule.market;
+        let bump = emissions_schedule.bump;
+        let seeds: &[&[u8]] = &[EMISSIONS_SEED, market.as_ref(), &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.reward_vault.to_account_info(),
+            to: ctx.accounts.owner_reward_account.to_account_info(),
+            authority: ctx.accounts.emissions_schedule.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        Ok(())
+    }
+
+    /// Creates the signer's points and stats account.
+    ///
+    /// Every user needs one before writing or settling options.
+    pub fn create_user_stats(ctx: Context<CreateUserStats>) -> Result<()> {
+        let user_stats = &mut ctx.accounts.user_stats;
+        user_stats.owner = ctx.accounts.owner.key();
+        user_stats.points = 0;
+        user_stats.notional_points = 0;
+        user_stats.premium_points = 0;
+        user_stats.fee_points = 0;
+        user_stats.escrows_written = 0;
+        user_stats.open_notional = Vec::new();
+        user_stats.bump = ctx.bumps.user_stats;
+        Ok(())
+    }
+
+    /// Sets the pause flags of a market.
+    ///
+    /// Each `PAUSE_*` bit blocks one kind of activity in this market only, so governance can
+    /// quarantine a troubled collateral mint while other markets keep trading.
+    pub fn set_market_pause(ctx: Context<UpdateMarket>, paused_flags: u8) -> Result<()> {
+        ctx.accounts.market.paused_flags = paused_flags;
+        ctx.accounts.audit(instruction::SetMarketPause::DISCRIMINATOR, paused_flags as u64)?;
+        Ok(())
+    }
+
+    /// Sets the maximum notional a single user can have written in a market.
+    ///
+    /// Limits concentration risk in thin markets. Zero removes the limit.
+    pub fn set_position_limit(ctx: Context<UpdateMarket>, max_open_notional_per_user: u64) -> Result<()> {
+        ctx.accounts.market.max_open_notional_per_user = max_open_notional_per_user;
+        ctx.accounts.audit(instruction::SetPositionLimit::DISCRIMINATOR, max_open_notional_per_user)?;
+        Ok(())
+    }
+
+    /// Sets the open interest cap of a market.
+    ///
+    /// New escrows are rejected once the collateral locked in the market's open escrows would
+    /// exceed the cap, bounding the protocol's exposure to a single asset. Zero removes the cap.
+    pub fn set_open_interest_cap(ctx: Context<UpdateMarket>, open_interest_cap: u64) -> Result<()> {
+        ctx.accounts.market.open_interest_cap = open_interest_cap;
+        ctx.accounts.audit(instruction::SetOpenInterestCap::DISCRIMINATOR, open_interest_cap)?;
+        Ok(())
+    }
+
+    /// Creates the signer's position account listing their open escrows.
+    ///
+    /// Every writer needs one before initializing escrows.
+    pub fn create_user_position(ctx: Context<CreateUserPosition>) -> Result<()> {
+        let user_position = &mut ctx.accounts.user_position;
+        user_position.owner = ctx.accounts.owner.key();
+        user_position.written_escrows = Vec::new();
+        user_position.bump = ctx.bumps.user_position;
+        Ok(())
+    }
+
+    /// Sets the band around the oracle price that new strikes must fall within.
+    ///
+    /// For example, 1000 and 50000 allow strikes from 10% to 500% of the current price.
+    /// A maximum of zero disables the band.
+    pub fn set_strike_band(ctx: Context<UpdateMarket>, min_strike_bps: u64, max_strike_bps: u64) -> Result<()> {
+        if max_strike_bps != 0 && min_strike_bps > max_strike_bps {
+            return Err(ErrorCode::InvalidStrikeBand.into());
+        }
+        let market = &mut ctx.accounts.market;
+        market.min_strike_bps = min_strike_bps;
+        market.max_strike_bps = max_strike_bps;
+        ctx.accounts.audit(instruction::SetStrikeBand::DISCRIMINATOR, max_strike_bps)?;
+        Ok(())
+    }
+
+    /// Sets the circuit breaker of a market.
+    ///
+    /// Settlement is refused while the oracle price deviates from its EMA by more than
+    /// `max_price_deviation_bps`; such escrows settle at a price set by governance with
+    /// `set_manual_settlement_price`. Zero disables the circuit breaker.
+    pub fn set_price_deviation_limit(ctx: Context<UpdateMarket>, max_price_deviation_bps: u64) -> Result<()> {
+        ctx.accounts.market.max_price_deviation_bps = max_price_deviation_bps;
+        ctx.accounts.audit(instruction::SetPriceDeviationLimit::DISCRIMINATOR, max_price_deviation_bps)?;
+        Ok(())
+    }
+
+    /// Sets the premium bounds of a market.
+    ///
+    /// Premium fills are refused when they stray further than `max_premium_multiple_bps` from
+    /// the escrow's fair value on the market's volatility curve, so writers can't be picked off
+    /// during volatility spikes. The multiple is at least 10000 (1x); zero disables the bounds.
+    pub fn set_premium_bounds(ctx: Context<UpdateMarket>, max_premium_multiple_bps: u64) -> Result<()> {
+        if max_premium_multiple_bps != 0 && max_premium_multiple_bps < 10000 {
+            return Err(ErrorCode::InvalidPremiumBounds.into());
+        }
+        ctx.accounts.market.max_premium_multiple_bps = max_premium_multiple_bps;
+        ctx.accounts.audit(instruction::SetPremiumBounds::DISCRIMINATOR, max_premium_multiple_bps)?;
+        Ok(())
+    }
+
+    /// Sets the settlement price of an expired escrow manually.
+    ///
+    /// This is the dispute path for escrows whose automatic settlement is blocked by the
+    /// circuit breaker or a broken oracle.
+    pub fn set_manual_settlement_price(ctx: Context<SetManualSettlementPrice>, settlement_price: u64) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if Clock::get()?.unix_timestamp < escrow_account.expiration {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if settlement_price == 0 {
+            return Err(ErrorCode::InvalidOraclePrice.into());
+        }
+        escrow_account.manual_settlement_price = settlement_price;
+        escrow_account.price_submitter = ctx.accounts.governance_authority.key();
+        escrow_account.price_disputable_until = 0;
+        ctx.accounts.audit(instruction::SetManualSettlementPrice::DISCRIMINATOR, settlement_price)?;
+        Ok(())
+    }
+
+    /// Sets the staleness and confidence limits for a market's oracles.
+    ///
+    /// Prices older than the limits, or whose confidence interval is too wide a fraction of
+    /// the price, are rejected with dedicated errors instead of being settled on.
+    pub fn set_oracle_config(ctx: Context<UpdateMarket>, oracle_config: OracleConfig) -> Result<()> {
+        if oracle_config.max_age_secs == 0 || oracle_config.max_confidence_bps > 10000 {
+            return Err(ErrorCode::InvalidOracleConfig.into());
+        }
+        ctx.accounts.market.oracle_config = oracle_config;
+        ctx.accounts.audit(instruction::SetOracleConfig::DISCRIMINATOR, 0)?;
+        Ok(())
+    }
+
+    /// Sets the loyalty points weights of a market.
+    pub fn set_points_weights(ctx: Context<UpdateMarket>, points_weights: PointsWeights) -> Result<()> {
+        ctx.accounts.market.points_weights = points_weights;
+        ctx.accounts.audit(instruction::SetPointsWeights::DISCRIMINATOR, 0)?;
+        Ok(())
+    }
+
+    /// Sets the rules for on-chain proposals.
+    ///
+    /// A voting period of zero disables proposals.
+    pub fn set_proposal_params(ctx: Context<UpdateGovernance>, proposal_params: ProposalParams) -> Result<()> {
+        ProposalAction::SetProposalParams(proposal_params.clone()).validate()?;
+        ctx.accounts.governance.proposal_params = proposal_params;
+        Ok(())
+    }
+
+    /// Creates a proposal to change a governance parameter.
+    ///
+    /// The proposer needs at least the proposal threshold in voting power. Voting opens
+    /// immediately and lasts for the governance voting period.
+    pub fn create_proposal(ctx: Context<CreateProposal>, action: ProposalAction) -> Result<()> {
+        let params = &ctx.accounts.governance.proposal_params;
+        if params.voting_period == 0 {
+            return Err(ErrorCode::ProposalsDisabled.into());
+        }
+        action.validate()?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let voting_power = ctx.accounts.stake_position.voting_power(&ctx.accounts.staking_pool, current_time);
+        if voting_power < params.proposal_threshold {
+            return Err(ErrorCode::InsufficientVotingPower.into());
+        }
+
+        let voting_ends_at = current_time.checked_add(params.voting_period).ok_or(ErrorCode::MathOverflow)?;
+        let executable_at = voting_ends_at.checked_add(params.execution_delay).ok_or(ErrorCode::MathOverflow)?;
+        let governance = &mut ctx.accounts.governance;
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.governance = governance.key();
+        proposal.id = governance.proposal_count;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.action = action;
+        proposal.votes_for = 0;
+        proposal.votes_against = 0;
+        proposal.voting_ends_at = voting_ends_at;
+        proposal.executable_at = executable_at;
+        proposal.executed = false;
+        proposal.bump = ctx.bumps.proposal;
+        governance.proposal_count += 1;
+        Ok(())
+    }
+
+    /// Casts the voter's staked voting power for or against a proposal.
+    ///
+    /// The voter's stake can't be withdrawn until voting ends, so the same tokens can't be
+    /// moved to another position and counted twice.
+    pub fn cast_vote(ctx: Context<CastVote>, support: bool) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let proposal = &mut ctx.accounts.proposal;
+        if current_time >= proposal.voting_ends_at {
+            return Err(ErrorCode::VotingClosed.into());
+        }
+        let voting_power = ctx.accounts.stake_position.voting_power(&ctx.accounts.staking_pool, current_time);
+        if voting_power == 0 {
+            return Err(ErrorCode::InsufficientVotingPower.into());
+        }
+        if support {
+            proposal.votes_for = proposal.votes_for.checked_add(voting_power).ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            proposal.votes_against = proposal.votes_against.checked_add(voting_power).ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        let stake_position = &mut ctx.accounts.stake_position;
+        stake_position.vote_lock_end = stake_position.vote_lock_end.max(proposal.voting_ends_at);
+
+        let vote_record = &mut ctx.accounts.vote_record;
+        vote_record.proposal = proposal.key();
+        vote_record.voter = ctx.accounts.voter.key();
+        vote_record.support = support;
+        vote_record.voting_power = voting_power;
+        vote_record.bump = ctx.bumps.vote_record;
+        Ok(())
+    }
+
+    /// Applies a passed proposal once its execution delay has elapsed.
+    ///
+    /// Anyone can execute a passed proposal.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let proposal = &mut ctx.accounts.proposal;
+        if proposal.executed {
+            return Err(ErrorCode::ProposalAlreadyExecuted.into());
+        }
+        if current_time < proposal.executable_at {
+            return Err(ErrorCode::ProposalNotExecutable.into());
+        }
+        let governance = &mut ctx.accounts.governance;
+        if !proposal.passed(&governance.proposal_params) {
+            return Err(ErrorCode::ProposalNotPassed.into());
+        }
+        proposal.action.apply(governance);
+        proposal.executed = true;
+        Ok(())
+    }
+
+    /// Creates the protocol-wide stats account.
+    pub fn initialize_protocol_stats(ctx: Context<InitializeProtocolStats>) -> Result<()> {
+        let protocol_stats = &mut ctx.accounts.protocol_stats;
+        protocol_stats.escrows_opened = 0;
+        protocol_stats.escrows_settled = 0;
+        protocol_stats.escrows_exercised = 0;
+        protocol_stats.open_escrows = 0;
+        protocol_stats.bump = ctx.bumps.protocol_stats;
+        Ok(())
+    }
+
+    /// Returns the health of a writer's open escrows in one market, without changing state.
+    ///
+    /// The escrows are passed as remaining accounts and must be listed in the writer's
+    /// position. Frontends and bots simulate this instruction to share the program's math.
+    pub fn get_portfolio_health<'info>(ctx: Context<'_, '_, 'info, 'info, GetPortfolioHealth<'info>>) -> Result<PortfolioHealth> {
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let spot_price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        let current_time = Clock::get()?.unix_timestamp;
+
+        let mut escrows = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut collateral: u64 = 0;
+        let mut obligations: u64 = 0;
+        for account_info in ctx.remaining_accounts.iter() {
+            let escrow_account: Account<EscrowAccount> = Account::try_from(account_info)?;
+            if !ctx.accounts.user_position.written_escrows.contains(account_info.key) {
+                return Err(ErrorCode::EscrowNotInPosition.into());
+            }
+            if escrow_account.collateral_mint != ctx.accounts.market.collateral_mint {
+                return Err(ErrorCode::IncorrectCollateralMint.into());
+            }
+            collateral = collateral.checked_add(escrow_account.collateral_amount).ok_or(ErrorCode::MathOverflow)?;
+            let obligation = match ctx.accounts.vol_config.as_ref() {
+                Some(vol_config) if escrow_account.payoff == Payoff::Vanilla => escrow_account.fair_value(spot_price, vol_config, current_time)?,
+                _ => escrow_account.intrinsic_value(spot_price)?,
+            };
+            obligations = obligations.checked_add(obligation).ok_or(ErrorCode::MathOverflow)?;
+            escrows.push(escrow_account);
+        }
+
+        let margin_requirement = match ctx.accounts.risk_config.as_ref() {
+            Some(risk_config) => risk_config.margin_requirement(&escrows, spot_price)?,
+            None => obligations,
+        };
+
+        let margin_ratio_bps = if obligations == 0 {
+            u64::MAX
+        } else {
+            u64::try_from(collateral as u128 * 10000 / obligations as u128).unwrap_or(u64::MAX)
+        };
+        Ok(PortfolioHealth {
+            spot_price,
+            collateral,
+            obligations,
+            margin_ratio_bps,
+            margin_requirement,
+            unrealized_pnl: -i64::try_from(obligations).map_err(|_| ErrorCode::MathOverflow)?,
+        })
+    }
+
+    /// Creates the risk configuration of a market with its shock scenarios.
+    pub fn initialize_risk_config(ctx: Context<InitializeRiskConfig>, scenarios: Vec<RiskScenario>) -> Result<()> {
+        validate_risk_scenarios(&scenarios)?;
+        let risk_config = &mut ctx.accounts.risk_config;
+        risk_config.market = ctx.accounts.market.key();
+        risk_config.scenarios = scenarios;
+        risk_config.bump = ctx.bumps.risk_config;
+        Ok(())
+    }
+
+    /// Replaces the shock scenarios of a market's risk configuration.
+    pub fn set_risk_scenarios(ctx: Context<SetRiskScenarios>, scenarios: Vec<RiskScenario>) -> Result<()> {
+        validate_risk_scenarios(&scenarios)?;
+        ctx.accounts.risk_config.scenarios = scenarios;
+        Ok(())
+    }
+
+    /// Creates the oracle registry of a market, aggregating extra feeds at settlement.
+    ///
+    /// Once created, settlement in the market uses the median of the market's collateral
+    /// oracle and the extra feeds, and fails if they disagree by more than `max_deviation_bps`.
+    pub fn initialize_oracle_registry(
+        ctx: Context<InitializeOracleRegistry>,
+        extra_feeds: Vec<Pubkey>,
+        max_deviation_bps: u64,
+    ) -> Result<()> {
+        validate_oracle_registry(&ctx.accounts.market, &extra_feeds, max_deviation_bps)?;
+        let oracle_registry = &mut ctx.accounts.oracle_registry;
+        oracle_registry.market = ctx.accounts.market.key();
+        oracle_registry.extra_feeds = extra_feeds;
+        oracle_registry.max_deviation_bps = max_deviation_bps;
+        oracle_registry.bump = ctx.bumps.oracle_registry;
+        ctx.accounts.market.uses_oracle_registry = true;
+        Ok(())
+    }
+
+    /// Replaces the extra feeds and deviation threshold of a market's oracle registry.
+    pub fn update_oracle_registry(ctx: Context<UpdateOracleRegistry>, extra_feeds: Vec<Pubkey>, max_deviation_bps: u64) -> Result<()> {
+        validate_oracle_registry(&ctx.accounts.market, &extra_feeds, max_deviation_bps)?;
+        let oracle_registry = &mut ctx.accounts.oracle_registry;
+        oracle_registry.extra_feeds = extra_feeds;
+        oracle_registry.max_deviation_bps = max_deviation_bps;
+        Ok(())
+    }
+
+    /// Creates the price history of a market, which the TWAP crank samples into.
+    pub fn initialize_price_history(ctx: Context<InitializePriceHistory>, min_crank_interval: i64) -> Result<()> {
+        if min_crank_interval <= 0 {
+            return Err(ErrorCode::InvalidTwapParams.into());
+        }
+        let mut price_history = ctx.accounts.price_history.load_init()?;
+        price_history.market = ctx.accounts.market.key();
+        price_history.min_crank_interval = min_crank_interval;
+        price_history.bump = ctx.bumps.price_history;
+        Ok(())
+    }
+
+    /// Records the market's current oracle price in its price history.
+    ///
+    /// Anyone can crank, at most once per `min_crank_interval`.
+    pub fn crank_twap(ctx: Context<CrankTwap>) -> Result<()> {
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        ctx.accounts.price_history.load_mut()?.record(price, Clock::get()?.unix_timestamp)
+    }
+
+    /// Reads the exchange rate of an LST market's stake pool into the market.
+    ///
+    /// Anyone can crank. The stake pool must mint the market's collateral and have been updated
+    /// this epoch.
+    pub fn refresh_lst_rate(ctx: Context<RefreshLstRate>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        if !market.lst_collateral {
+            return Err(ErrorCode::LstCollateralDisabled.into());
+        }
+        let epoch = Clock::get()?.epoch;
+        market.lst_exchange_rate = stake_pool_rate(&ctx.accounts.stake_pool, &market.collateral_mint, epoch)?;
+        market.lst_rate_epoch = epoch;
+        Ok(())
+    }
+
+    /// Sets the TWAP window a market settles on.
+    ///
+    /// Escrows then settle at the average price over the window before expiry, which is far
+    /// harder to manipulate than a single print. The price history must cover the window, so
+    /// it should be cranked at least a few times per window. Zero settles on the spot price.
+    pub fn set_twap_window(ctx: Context<UpdateMarket>, twap_window: i64) -> Result<()> {
+        if twap_window < 0 {
+            return Err(ErrorCode::InvalidTwapParams.into());
+        }
+        ctx.accounts.market.twap_window = twap_window;
+        ctx.accounts.audit(instruction::SetTwapWindow::DISCRIMINATOR, twap_window as u64)?;
+        Ok(())
+    }
+
+    /// Records a scheduled oracle observation on a path-dependent escrow.
+    ///
+    /// Anyone can crank once a sample is due. Samples are taken until expiry; missed
+    /// samples are skipped rather than back-filled with the current price.
+    pub fn record_observation(ctx: Context<RecordObservation>) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let interval = escrow_account.payoff.observation_interval().ok_or(ErrorCode::InvalidPayoff)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        if current_time > escrow_account.expiration {
+            return Err(ErrorCode::ObservationNotDue.into());
+        }
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        escrow_account.observations.record(price, interval, current_time)
+    }
+
+    /// Records that an escrow's barrier was touched, using the market's current oracle price.
+    ///
+    /// Anyone can crank before expiry. A touched knock-in barrier makes the option
+    /// exercisable; a touched knock-out barrier lets the escrow be settled right away,
+    /// returning the collateral to the writer.
+    pub fn record_barrier_touch(ctx: Context<RecordObservation>) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        let barrier = escrow_account.barrier.as_ref().ok_or(ErrorCode::BarrierNotTouched)?;
+        if escrow_account.barrier_touched || Clock::get()?.unix_timestamp > escrow_account.expiration {
+            return Err(ErrorCode::BarrierNotTouched.into());
+        }
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        if !barrier.is_touched(ctx.accounts.market.lst_price(to_strike_price(&price)?)?) {
+            return Err(ErrorCode::BarrierNotTouched.into());
+        }
+        escrow_account.barrier_touched = true;
+        Ok(())
+    }
+
+    /// Pays funding on a perpetual option to its writer, keeping it alive for more periods.
+    ///
+    /// The first payer becomes the option's holder; afterwards only the holder can pay.
+    /// Funding can't be paid once it has lapsed, since the escrow can then be settled.
+    pub fn pay_funding(ctx: Context<PayFunding>, periods: u32) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        let perpetual = escrow_account.perpetual.as_ref().ok_or(ErrorCode::InvalidPerpetualTerms)?;
+        escrow_account.require_allowed(ctx.accounts.payer.key)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.holder != Pubkey::default() && escrow_account.holder != ctx.accounts.payer.key() {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        if escrow_account.holder != Pubkey::default() && escrow_account.is_funding_lapsed(current_time) {
+            return Err(ErrorCode::FundingLapsed.into());
+        }
+        if periods == 0 {
+            return Err(ErrorCode::InvalidPerpetualTerms.into());
+        }
+        let amount = perpetual.funding_per_period.checked_mul(periods as u64).ok_or(ErrorCode::MathOverflow)?;
+        let extension = perpetual.funding_period.checked_mul(periods as i64).ok_or(ErrorCode::MathOverflow)?;
+
+        // Transfer the funding from the holder to the writer
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.payer_collateral_account.to_account_info(),
+            to: ctx.accounts.initializer_collateral_account.to_account_info(),
+            authority: ctx.accounts.payer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        if let Some(payer_stats) = ctx.accounts.payer_stats.as_mut() {
+            payer_stats.record_premium_paid(&ctx.accounts.market, amount)?;
+        }
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.holder = ctx.accounts.payer.key();
+        escrow_account.funded_until = escrow_account.funded_until.max(current_time).checked_add(extension).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Creates the basket of underlyings that a market's basket options settle on.
+    ///
+    /// Only the governance authority can call this.
+    pub fn initialize_basket(ctx: Context<InitializeBasket>, components: Vec<BasketComponent>) -> Result<()> {
+        validate_basket(&components)?;
+        let basket = &mut ctx.accounts.basket;
+        basket.market = ctx.accounts.market.key();
+        basket.components = components;
+        basket.bump = ctx.bumps.basket;
+        Ok(())
+    }
+
+    /// Replaces the components of a market's basket.
+    ///
+    /// Only the governance authority can call this. Open basket options settle on the new basket.
+    pub fn update_basket(ctx: Context<UpdateBasket>, components: Vec<BasketComponent>) -> Result<()> {
+        validate_basket(&components)?;
+        ctx.accounts.basket.components = components;
+        Ok(())
+    }
+
+    /// Revokes the holder of a vesting option, freezing vesting at the current time.
+    ///
+    /// Only the writer can call this. The unvested part of any later payout returns to the writer.
+    pub fn revoke_vesting(ctx: Context<WriterUpdateEscrow>) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if escrow_account.vesting.is_none() {
+            return Err(ErrorCode::InvalidVestingSchedule.into());
+        }
+        if escrow_account.vesting_revoked_at > 0 {
+            return Err(ErrorCode::VestingAlreadyRevoked.into());
+        }
+        escrow_account.vesting_revoked_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Sets the premium per second a renter must stream to rent the escrow's exercise rights.
+    ///
+    /// Only the writer can call this; a rate of 0 stops new rentals. Existing rentals keep
+    /// streaming at their original rate.
+    pub fn set_rental_rate(ctx: Context<WriterUpdateEscrow>, rental_rate: u64) -> Result<()> {
+        ctx.accounts.escrow_account.rental_rate = rental_rate;
+        Ok(())
+    }
+
+    /// Rents the exercise rights of an escrow by prepaying premium that streams to its writer.
+    ///
+    /// In markets with premium bounds, the premium of renting until expiry must lie within the
+    /// bounds around the escrow's fair value.
+    pub fn start_rental(ctx: Context<StartRental>, deposit: u64) -> Result<()> {
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), ctx.accounts.renter.key)?;
+        let escrow_account = &ctx.accounts.escrow_account;
+        escrow_account.require_allowed(ctx.accounts.renter.key)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        if escrow_account.is_exercised
+            || current_time >= escrow_account.expiration
+            || escrow_account.rental_rate == 0
+            || escrow_account.renter != Pubkey::default()
+            || escrow_account.on_loan
+        {
+            return Err(ErrorCode::NotRentable.into());
+        }
+        if deposit < escrow_account.rental_rate {
+            return Err(ErrorCode::InvalidRentalDeposit.into());
+        }
+        let market = &ctx.accounts.market;
+        if market.max_premium_multiple_bps > 0 {
+            let collateral_oracle = ctx.accounts.collateral_oracle.as_ref().ok_or(ErrorCode::MissingOracleAccount)?;
+            let vol_config = ctx.accounts.vol_config.as_ref().ok_or(ErrorCode::MissingVolConfig)?;
+            let price = load_oracle_price(&collateral_oracle.to_account_info(), &market.oracle_config)?;
+            let fair_value = escrow_account.fair_value(market.lst_price(to_strike_price(&price)?)?, vol_config, current_time)?;
+            let premium = escrow_account.rental_rate.saturating_mul((escrow_account.expiration - current_time) as u64);
+            market.check_premium_bounds(premium, fair_value)?;
+        }
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.renter_collateral_account.to_account_info(),
+            to: ctx.accounts.rental_token_account.to_account_info(),
+            authority: ctx.accounts.renter.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, deposit)?;
+
+        let rental = &mut ctx.accounts.rental;
+        rental.escrow = ctx.accounts.escrow_account.key();
+        rental.renter = ctx.accounts.renter.key();
+        rental.rate_per_second = ctx.accounts.escrow_account.rental_rate;
+        rental.start = current_time;
+        rental.deposited = deposit;
+        rental.claimed = 0;
+        rental.token_account = ctx.accounts.rental_token_account.key();
+        rental.bump = ctx.bumps.rental;
+        ctx.accounts.escrow_account.renter = ctx.accounts.renter.key();
+        Ok(())
+    }
+
+    /// Prepays more premium into a rental, extending the renter's exercise rights.
+    ///
+    /// Lapsed rentals can't be topped up; they must be closed and started again.
+    pub fn top_up_rental(ctx: Context<TopUpRental>, amount: u64) -> Result<()> {
+        if ctx.accounts.rental.is_lapsed(Clock::get()?.unix_timestamp) {
+            return Err(ErrorCode::RentalLapsed.into());
+        }
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.renter_collateral_account.to_account_info(),
+            to: ctx.accounts.rental_token_account.to_account_info(),
+            authority: ctx.accounts.renter.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        let rental = &mut ctx.accounts.rental;
+        rental.deposited = rental.deposited.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Pays the premium streamed so far to the escrow's writer.
+    ///
+    /// Anyone can call this; the premium always goes to the writer.
+    pub fn claim_rental_premium(ctx: Context<ClaimRentalPremium>) -> Result<()> {
+        let rental = &ctx.accounts.rental;
+        let amount = rental.streamed(Clock::get()?.unix_timestamp) - rental.claimed;
+        let initializer_collateral_account = ctx.accounts.initializer_collateral_account.to_account_info();
+        pay_from_rental(
+            rental,
+            &ctx.accounts.rental_token_account,
+            initializer_collateral_account,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        ctx.accounts.rental.claimed += amount;
+        Ok(())
+    }
+
+    /// Ends a rental, paying the streamed premium to the writer and refunding the rest.
+    ///
+    /// The renter can end a rental at any time. Once it has lapsed, anyone can close it to
+    /// free the escrow's exercise rights for a new renter.
+    pub fn close_rental(ctx: Context<CloseRental>) -> Result<()> {
+        let rental = &ctx.accounts.rental;
+        let current_time = Clock::get()?.unix_timestamp;
+        if ctx.accounts.caller.key() != rental.renter && !rental.is_lapsed(current_time) {
+            return Err(ErrorCode::RentalNotLapsed.into());
+        }
+        let streamed = rental.streamed(current_time);
+        let initializer_collateral_account = ctx.accounts.initializer_collateral_account.to_account_info();
+        pay_from_rental(
+            rental,
+            &ctx.accounts.rental_token_account,
+            initializer_collateral_account,
+            &ctx.accounts.token_program,
+            streamed - rental.claimed,
+        )?;
+        let renter_collateral_account = ctx.accounts.renter_collateral_account.to_account_info();
+        pay_from_rental(
+            rental,
+            &ctx.accounts.rental_token_account,
+            renter_collateral_account,
+            &ctx.accounts.token_program,
+            rental.deposited - streamed,
+        )?;
+        if let Some(renter_stats) = ctx.accounts.renter_stats.as_mut() {
+            renter_stats.record_premium_paid(&ctx.accounts.market, streamed)?;
+        }
+
+        // Close the emptied token account, returning its rent to the renter
+        let escrow = rental.escrow;
+        let seeds: &[&[u8]] = &[RENTAL_SEED, escrow.as_ref(), &[rental.bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.rental_token_account.to_account_info(),
+            destination: ctx.accounts.renter.to_account_info(),
+            authority: ctx.accounts.rental.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::close_account(cpi_ctx)?;
+
+        ctx.accounts.escrow_account.renter = Pubkey::default();
+        Ok(())
+    }
+
+    /// Sets the moneyness at which settlement auto-exercises a market's escrows.
+    ///
+    /// Escrows settling less than `auto_exercise_threshold_bps` (of the strike) ITM expire
+    /// instead, the way clearing houses exercise by exception. Zero exercises anything ITM.
+    pub fn set_auto_exercise_threshold(ctx: Context<UpdateMarket>, auto_exercise_threshold_bps: u64) -> Result<()> {
+        if auto_exercise_threshold_bps > 10000 {
+            return Err(ErrorCode::InvalidAutoExerciseThreshold.into());
+        }
+        ctx.accounts.market.auto_exercise_threshold_bps = auto_exercise_threshold_bps;
+        ctx.accounts.audit(instruction::SetAutoExerciseThreshold::DISCRIMINATOR, auto_exercise_threshold_bps)?;
+        Ok(())
+    }
+
+    /// Opts the escrow in or out of auto-exercise at expiry.
+    ///
+    /// Only the holder can call this, and only before the market's exercise cutoff. An
+    /// opted-out escrow expires at settlement even if it's ITM.
+    pub fn set_auto_exercise_opt_out(ctx: Context<SetAutoExerciseOptOut>, opt_out: bool) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if escrow_account.holder_key() != Some(ctx.accounts.holder.key()) {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        if escrow_account.is_past_exercise_cutoff(&ctx.accounts.market, Clock::get()?.unix_timestamp) {
+            return Err(ErrorCode::ExerciseCutoffPassed.into());
+        }
+        escrow_account.auto_exercise_opt_out = opt_out;
+        Ok(())
+    }
+
+    /// Sets how long before expiry manual exercise stops for a market's escrows.
+    ///
+    /// Past the cutoff only settlement can run, so late manual exercises can't race the
+    /// settlement crank. Holders also can't change their auto-exercise choice after it.
+    pub fn set_exercise_cutoff(ctx: Context<UpdateMarket>, exercise_cutoff: i64) -> Result<()> {
+        if exercise_cutoff < 0 {
+            return Err(ErrorCode::InvalidExerciseCutoff.into());
+        }
+        ctx.accounts.market.exercise_cutoff = exercise_cutoff;
+        ctx.accounts.audit(instruction::SetExerciseCutoff::DISCRIMINATOR, exercise_cutoff as u64)?;
+        Ok(())
+    }
+
+    /// Sets how long holders have to claim settlement proceeds before writers can claw them back.
+    ///
+    /// A grace period of zero disables clawback.
+    pub fn set_claim_grace_period(ctx: Context<UpdateGovernance>, claim_grace_period: i64) -> Result<()> {
+        if claim_grace_period < 0 {
+            return Err(ErrorCode::InvalidTimelock.into());
+        }
+        ctx.accounts.governance.claim_grace_period = claim_grace_period;
+        Ok(())
+    }
+
+    /// Pulls the settlement proceeds of an ITM escrow to the holder entitled to them.
+    ///
+    /// The holder can sign itself or through a session key scoped to claims. Anyone else can
+    /// relay the claim (paying its transaction fees for a holder without SOL), as long as the
+    /// proceeds go to the holder's associated token account. When the collateral is wSOL, a
+    /// holder claiming itself can pass `unwrap_sol` to close its wSOL account and receive
+    /// native SOL instead.
+    pub fn claim_settlement(ctx: Context<ClaimSettlement>, unwrap_sol: bool) -> Result<()> {
+        let amount = ctx.accounts.escrow_account.unclaimed_payout;
+        if amount == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+        let payout_recipient = ctx.accounts.payout_recipient.key();
+        if ctx.accounts.claimer.key() != payout_recipient {
+            match &ctx.accounts.session {
+                Some(session) => {
+                    if session.holder != payout_recipient {
+                        return Err(ErrorCode::NotOptionHolder.into());
+                    }
+                    session.require_scope(SESSION_SCOPE_CLAIM, Clock::get()?.unix_timestamp)?;
+                }
+                None => {
+                    let holder_ata = get_associated_token_address(&payout_recipient, &ctx.accounts.escrow_account.collateral_mint);
+                    if ctx.accounts.recipient_collateral_account.key() != holder_ata {
+                        return Err(ErrorCode::InvalidProceedsAccount.into());
+                    }
+                }
+            }
+        }
+        transfer_from_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.escrow_collateral_account.to_account_info(),
+            ctx.accounts.recipient_collateral_account.to_account_info(),
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        ctx.accounts.escrow_account.unclaimed_payout = 0;
+        if unwrap_sol {
+            if ctx.accounts.claimer.key() != payout_recipient {
+                return Err(ErrorCode::UnwrapNotAllowed.into());
+            }
+            close_wrapped_sol(
+                &ctx.accounts.recipient_collateral_account,
+                ctx.accounts.claimer.to_account_info(),
+                &ctx.accounts.token_program,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns settlement proceeds the holder never claimed to the writer.
+    ///
+    /// Only the writer can call this, once the governance grace period has passed since
+    /// settlement.
+    pub fn clawback_unclaimed(ctx: Context<ClawbackUnclaimed>) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        let grace_period = ctx.accounts.governance.claim_grace_period;
+        if grace_period == 0 || !escrow_account.is_exercised {
+            return Err(ErrorCode::ClaimGracePeriodActive.into());
+        }
+        let claimable_until = escrow_account.settled_at.checked_add(grace_period).ok_or(ErrorCode::MathOverflow)?;
+        if Clock::get()?.unix_timestamp < claimable_until {
+            return Err(ErrorCode::ClaimGracePeriodActive.into());
+        }
+        let amount = escrow_account.unclaimed_payout;
+        if amount == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+        transfer_from_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.escrow_collateral_account.to_account_info(),
+            ctx.accounts.initializer_collateral_account.to_account_info(),
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        ctx.accounts.escrow_account.unclaimed_payout = 0;
+        Ok(())
+    }
+
+    /// Sets how long after creation a market's escrows have to be fully funded.
+    ///
+    /// Escrows still short of their collateral past the deadline can be reaped by anyone with
+    /// `reap_unfunded`. Zero disables reaping.
+    pub fn set_funding_deadline(ctx: Context<UpdateMarket>, funding_deadline: i64) -> Result<()> {
+        if funding_deadline < 0 {
+            return Err(ErrorCode::InvalidTimelock.into());
+        }
+        ctx.accounts.market.funding_deadline = funding_deadline;
+        ctx.accounts.audit(instruction::SetFundingDeadline::DISCRIMINATOR, funding_deadline as u64)?;
+        Ok(())
+    }
+
+    /// Cancels an escrow that wasn't fully funded within the market's funding deadline.
+    ///
+    /// Anyone can call this. Partial deposits are returned to the writer, and the escrow is
+    /// closed with `REAP_REWARD_BPS` of its rent paid to the caller and the rest to the writer.
+    /// In a backstopped market the escrow can only be reaped once the backstop pool can't
+    /// take it over with `backstop_default`.
+    pub fn reap_unfunded(ctx: Context<ReapUnfunded>) -> Result<()> {
+        let escrow_account = &ctx.accounts.cancel.escrow_account;
+        let funding_deadline = ctx.accounts.cancel.market.funding_deadline;
+        if escrow_account.is_exercised || escrow_account.deposited_amount >= escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowFunded.into());
+        }
+        let deadline = escrow_account.created_at.checked_add(funding_deadline).ok_or(ErrorCode::MathOverflow)?;
+        if funding_deadline == 0 || Clock::get()?.unix_timestamp < deadline {
+            return Err(ErrorCode::FundingDeadlineNotPassed.into());
+        }
+        if ctx.accounts.cancel.market.backstopped {
+            let backstop_pool = ctx.accounts.backstop_pool.as_ref().ok_or(ErrorCode::MissingBackstopAccount)?;
+            let backstop_token_account = ctx.accounts.backstop_token_account.as_ref().ok_or(ErrorCode::MissingBackstopAccount)?;
+            if backstop_token_account.key() != backstop_pool.token_account {
+                return Err(ErrorCode::MissingBackstopAccount.into());
+            }
+            let (shortfall, compensation, _) = backstop_pool.takeover_cost(escrow_account)?;
+            if shortfall + compensation <= backstop_pool.available(backstop_token_account.amount) {
+                return Err(ErrorCode::BackstopAvailable.into());
+            }
+        }
+        ctx.accounts.cancel.cancel(ctx.bumps.cancel.event_authority)?;
+
+        // Pay the caller's share of the rent; the rest goes to the writer when the escrow closes
+        let escrow_info = ctx.accounts.cancel.escrow_account.to_account_info();
+        let reward = escrow_info.lamports() * REAP_REWARD_BPS / 10000;
+        **escrow_info.try_borrow_mut_lamports()? -= reward;
+        **ctx.accounts.caller.to_account_info().try_borrow_mut_lamports()? += reward;
+        Ok(())
+    }
+
+    /// Moves the rounding dust left in a closed escrow's token account to the treasury and
+    /// closes the token account, returning its rent to the writer.
+    ///
+    /// Anyone can call this once the escrow is settled and its proceeds claimed. Balances above
+    /// `DUST_THRESHOLD` aren't dust and are left alone.
+    pub fn sweep_dust(ctx: Context<SweepDust>) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        if !escrow_account.is_exercised
+            || escrow_account.unclaimed_payout > 0
+            || escrow_account.unclaimed_writer_amount > 0
+            || escrow_account.unswept_fees > 0
+        {
+            return Err(ErrorCode::EscrowStillOpen.into());
+        }
+        let dust = ctx.accounts.escrow_collateral_account.amount;
+        if dust > DUST_THRESHOLD {
+            return Err(ErrorCode::BalanceAboveDustThreshold.into());
+        }
+        transfer_from_vault(
+            escrow_account,
+            ctx.accounts.escrow_collateral_account.to_account_info(),
+            ctx.accounts.treasury_token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            dust,
+        )?;
+        close_vault(
+            escrow_account,
+            ctx.accounts.escrow_collateral_account.to_account_info(),
+            ctx.accounts.initializer.to_account_info(),
+            &ctx.accounts.token_program,
+        )
+    }
+
+    /// Terminates an open escrow early with the consent of both the writer and the holder.
+    ///
+    /// The collateral is returned to the writer, who can pay the holder an agreed rebate (e.g.
+    /// the unused part of the premium) out of it. The escrow is closed.
+    pub fn terminate_by_consent(ctx: Context<TerminateByConsent>, rebate: u64) -> Result<()> {
+        let escrow_account = &ctx.accounts.cancel.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.holder_key() != Some(ctx.accounts.holder.key()) {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        ctx.accounts.cancel.cancel(ctx.bumps.cancel.event_authority)?;
+
+        // Pay the agreed rebate from the writer to the holder
+        if rebate > 0 {
+            let holder_collateral_account = ctx.accounts.holder_collateral_account.as_ref().ok_or(ErrorCode::MissingTokenAccount)?;
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.cancel.initializer_collateral_account.to_account_info(),
+                to: holder_collateral_account.to_account_info(),
+                authority: ctx.accounts.writer.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.cancel.token_program.to_account_info(), cpi_accounts);
+            token::transfer(cpi_ctx, rebate)?;
+        }
+        Ok(())
+    }
+
+    /// Rolls an expired escrow into a new one at a later expiry, reusing its collateral.
+    ///
+    /// Only the writer can call this. The current escrow settles exactly as in `settle_escrow`,
+    /// with any ITM proceeds held for the holder to claim. Instead of returning to the writer,
+    /// the remaining collateral moves into the new escrow's vault and backs the new escrow,
+    /// which keeps the old terms apart from the new expiry and, optionally, a new strike.
+    /// Exercise windows aren't carried over, and the new escrow isn't enrolled in emissions.
+    pub fn roll_position(ctx: Context<RollPosition>, new_expiration: i64, new_strike_price: Option<u64>) -> Result<()> {
+        let settle = &mut ctx.accounts.settle;
+        settle.market.require_not_paused(PAUSE_EXERCISE)?;
+        settle.market.require_not_paused(PAUSE_NEW_ESCROWS)?;
+        settle.market.require_whitelisted(&settle.whitelist_entry)?;
+        settle.market.require_settler(settle.series_settlers.as_deref(), settle.user.key)?;
+        settle.escrow_account.require_not_lent()?;
+        settle.escrow_account.require_not_on_loan()?;
+        let escrow_account = &settle.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.deposited_amount < escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowNotFunded.into());
+        }
+        let current_time = Clock::get()?.unix_timestamp;
+        if current_time < escrow_account.expiration {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if escrow_account.perpetual.is_some() || new_expiration <= current_time {
+            return Err(ErrorCode::InvalidRoll.into());
+        }
+        settle.market.validate_tenor(new_expiration, current_time)?;
+
+        // Settle the current escrow, keeping the writer's share in place
+        let is_itm = settle.pricing().auto_exercises(ctx.remaining_accounts)?;
+        let greeks = settle.greeks(current_time);
+        let collateral_amount = escrow_account.collateral_amount;
+        let fee = collateral_amount * escrow_account.fee_rate(settle.governance.settlement_fee_bps) / 10000;
+        let collateral_fee = settle.collect_fee(fee, is_itm)?;
+        let amount_after_fee = collateral_amount - collateral_fee;
+        let holder_amount = if is_itm { settle.pricing().holder_payout(amount_after_fee)? } else { 0 };
+        let holder = settle.escrow_account.holder_key();
+        if holder_amount > 0 && holder.is_none() {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        let rolled_amount = amount_after_fee - holder_amount;
+        let escrow_account = &mut settle.escrow_account;
+        escrow_account.payout_recipient = holder.unwrap_or_default();
+        escrow_account.unclaimed_payout = holder_amount;
+        escrow_account.is_exercised = true;
+        escrow_account.settled_at = current_time;
+        settle.release_emissions_notional()?;
+        settle.record_close(false);
+        emit_lifecycle_event(&EscrowSettled {
+            seq: settle.market_stats.next_event_seq(),
+            market: settle.market_stats.market,
+            escrow: settle.escrow_account.key(),
+            is_itm,
+            holder_amount,
+            writer_amount: rolled_amount,
+            fee: collateral_fee,
+            greeks,
+        }, settle.event_authority.as_ref(), ctx.bumps.settle.event_authority)?;
+        record_audit(
+            settle.audit_log.as_ref(),
+            settle.user.key(),
+            settle.escrow_account.key(),
+            instruction::RollPosition::DISCRIMINATOR,
+            collateral_amount,
+        )?;
+
+        // Open the new escrow on the remaining collateral
+        let mut rolled = (*settle.escrow_account).clone();
+        rolled.strike_price = new_strike_price.unwrap_or(rolled.strike_price);
+        if !matches!(rolled.payoff, Payoff::Basket | Payoff::Quanto { .. }) {
+            settle.market.validate_strike(rolled.strike_price, settle.collateral_oracle.as_ref())?;
+        }
+        rolled.payoff.validate(current_time, new_expiration, rolled_amount)?;
+        rolled.expiration = new_expiration;
+        rolled.collateral_amount = rolled_amount;
+        rolled.is_exercised = false;
+        rolled.emissions_notional = 0;
+        rolled.manual_settlement_price = 0;
+        rolled.price_submitter = Pubkey::default();
+        rolled.price_disputable_until = 0;
+        rolled.observations = Observations::new(&rolled.payoff, current_time);
+        rolled.barrier_touched = false;
+        rolled.exercise_windows = Vec::new();
+        rolled.holder = Pubkey::default();
+        rolled.funded_until = current_time;
+        rolled.renter = Pubkey::default();
+        rolled.auto_exercise_opt_out = false;
+        rolled.settled_at = 0;
+        rolled.payout_recipient = Pubkey::default();
+        rolled.unclaimed_payout = 0;
+        rolled.unclaimed_writer_amount = 0;
+        rolled.created_at = current_time;
+        rolled.deposited_amount = rolled_amount;
+        rolled.quoter = Pubkey::default();
+        rolled.unswept_fees = 0;
+        rolled.deposit_exchange_rate = settle.market.collateral_exchange_rate(settle.collateral_reserve.as_ref())?.unwrap_or(0);
+        rolled.vault_bump = ctx.bumps.new_escrow_collateral_account;
+        settle.transfer_collateral(ctx.accounts.new_escrow_collateral_account.to_account_info(), rolled_amount)?;
+        ctx.accounts.new_escrow_account.set_inner(rolled);
+
+        let settle = &mut ctx.accounts.settle;
+        settle.writer_stats.record_notional_written(&settle.market, rolled_amount)?;
+        settle.writer_stats.add_open_notional(&settle.market, rolled_amount)?;
+        settle.market.add_open_interest(rolled_amount)?;
+        let market_stats = &mut settle.market_stats;
+        market_stats.notional_volume = market_stats.notional_volume.saturating_add(rolled_amount);
+        market_stats.collateral_locked = market_stats.collateral_locked.saturating_add(rolled_amount);
+        market_stats.escrows_opened = market_stats.escrows_opened.saturating_add(1);
+        settle.protocol_stats.record_open();
+        let new_escrow_key = ctx.accounts.new_escrow_account.key();
+        let settle = &mut ctx.accounts.settle;
+        emit_lifecycle_event(&EscrowInitialized {
+            seq: settle.market_stats.next_event_seq(),
+            market: settle.market_stats.market,
+            escrow: new_escrow_key,
+            writer: settle.escrow_account.initializer_key,
+            strike_price: ctx.accounts.new_escrow_account.strike_price,
+            expiration: new_expiration,
+            collateral_amount: rolled_amount,
+        }, settle.event_authority.as_ref(), ctx.bumps.settle.event_authority)?;
+        settle.writer_position.add_written(new_escrow_key)
+    }
+
+    /// Sets the longest time to expiry allowed for a market's escrows.
+    ///
+    /// Zero allows any expiry. Perpetual options aren't subject to the limit.
+    pub fn set_max_tenor(ctx: Context<UpdateMarket>, max_tenor: i64) -> Result<()> {
+        if max_tenor < 0 {
+            return Err(ErrorCode::InvalidTimelock.into());
+        }
+        ctx.accounts.market.max_tenor = max_tenor;
+        ctx.accounts.audit(instruction::SetMaxTenor::DISCRIMINATOR, max_tenor as u64)?;
+        Ok(())
+    }
+
+    /// Pushes back the expiry of an open escrow with the consent of both the writer and the
+    /// holder, so OTC counterparties can restructure a deal without unwinding it.
+    ///
+    /// The new expiry must be later than the current one and within the market's maximum tenor.
+    pub fn extend_expiration(ctx: Context<ExtendExpiration>, new_expiration: i64) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        let current_time = Clock::get()?.unix_timestamp;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.holder_key() != Some(ctx.accounts.holder.key()) {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        escrow_account.require_not_on_loan()?;
+        if escrow_account.perpetual.is_some()
+            || current_time >= escrow_account.expiration
+            || new_expiration <= escrow_account.expiration
+        {
+            return Err(ErrorCode::InvalidExpirationExtension.into());
+        }
+        ctx.accounts.market.validate_tenor(new_expiration, current_time)?;
+        ctx.accounts.escrow_account.expiration = new_expiration;
+        Ok(())
+    }
+
+    /// Records the caller as having shown interest in (quoted or bid on) an open escrow.
+    ///
+    /// Only the first quoter is recorded. If the writer then cancels the escrow, it pays the
+    /// cancellation fee to the treasury; the quoter isn't paid, so registering interest can't
+    /// be farmed.
+    pub fn register_interest(ctx: Context<RegisterInterest>) -> Result<()> {
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), ctx.accounts.quoter.key)?;
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.require_allowed(ctx.accounts.quoter.key)?;
+        if escrow_account.is_exercised || escrow_account.quoter != Pubkey::default() {
+            return Err(ErrorCode::InterestAlreadyRegistered.into());
+        }
+        escrow_account.quoter = ctx.accounts.quoter.key();
+        Ok(())
+    }
+
+    /// Cancels an escrow that nobody holds yet, returning its collateral to the writer.
+    ///
+    /// Only the writer can call this. If a quoter has shown interest, the writer pays the
+    /// governance cancellation fee into the treasury of the collateral mint to discourage
+    /// bait-and-cancel listings.
+    pub fn cancel_escrow(ctx: Context<CancelByWriter>) -> Result<()> {
+        let escrow_account = &ctx.accounts.cancel.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.holder_key().is_some() {
+            return Err(ErrorCode::EscrowHasHolder.into());
+        }
+        let fee = if escrow_account.quoter != Pubkey::default() {
+            u64::try_from(escrow_account.collateral_amount as u128 * ctx.accounts.governance.cancellation_fee_bps as u128 / 10000)
+                .map_err(|_| ErrorCode::MathOverflow)?
+        } else {
+            0
+        };
+        ctx.accounts.cancel.cancel(ctx.bumps.cancel.event_authority)?;
+
+        if fee > 0 {
+            let treasury_token_account = ctx.accounts.treasury_token_account.as_ref().ok_or(ErrorCode::MissingTokenAccount)?;
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.cancel.initializer_collateral_account.to_account_info(),
+                to: treasury_token_account.to_account_info(),
+                authority: ctx.accounts.writer.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.cancel.token_program.to_account_info(), cpi_accounts);
+            token::transfer(cpi_ctx, fee)?;
+        }
+        Ok(())
+    }
+
+    /// Designates an account that can approve negotiated fee overrides alongside the
+    /// governance authority.
+    pub fn set_fee_admin(ctx: Context<UpdateGovernance>, fee_admin: Pubkey) -> Result<()> {
+        ctx.accounts.governance.fee_admin = fee_admin;
+        Ok(())
+    }
+
+    /// Pre-approves a negotiated fee rate for the next escrow a writer opens with an OTC
+    /// counterparty.
+    ///
+    /// Only the governance authority or the fee admin can call this.
+    pub fn approve_fee_override(ctx: Context<ApproveFeeOverride>, writer: Pubkey, counterparty: Pubkey, fee_bps: u64) -> Result<()> {
+        if fee_bps > MAX_FEE_BPS {
+            return Err(ErrorCode::FeeRateTooHigh.into());
+        }
+        let fee_approval = &mut ctx.accounts.fee_approval;
+        fee_approval.writer = writer;
+        fee_approval.counterparty = counterparty;
+        fee_approval.fee_bps = fee_bps;
+        fee_approval.bump = ctx.bumps.fee_approval;
+        Ok(())
+    }
+
+    /// Sets the compliance authority of a market.
+    ///
+    /// A market with a compliance authority is permissioned: only participants it has
+    /// whitelisted can open, fund, take, or exercise escrows there. Exits and claims stay open
+    /// so delisted participants can unwind. The default key makes the market permissionless.
+    pub fn set_compliance_authority(ctx: Context<UpdateMarket>, compliance_authority: Pubkey) -> Result<()> {
+        ctx.accounts.market.compliance_authority = compliance_authority;
+        ctx.accounts.audit(instruction::SetComplianceAuthority::DISCRIMINATOR, 0)?;
+        Ok(())
+    }
+
+    /// Admits a participant to a permissioned market.
+    pub fn add_to_whitelist(ctx: Context<AddToWhitelist>, participant: Pubkey) -> Result<()> {
+        let whitelist_entry = &mut ctx.accounts.whitelist_entry;
+        whitelist_entry.market = ctx.accounts.market.key();
+        whitelist_entry.participant = participant;
+        whitelist_entry.bump = ctx.bumps.whitelist_entry;
+        Ok(())
+    }
+
+    /// Removes a participant from a permissioned market, refunding the entry's rent.
+    pub fn remove_from_whitelist(_ctx: Context<RemoveFromWhitelist>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Sets the membership or credential token a market requires.
+    ///
+    /// In a token-gated market only holders of at least `min_amount` of `gate_mint` can open,
+    /// fund, rent, or bid on escrows. A soulbound credential is gated with its mint and a
+    /// minimum of one. The default mint removes the gate.
+    pub fn set_token_gate(ctx: Context<UpdateMarket>, gate_mint: Pubkey, min_amount: u64) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        market.gate_mint = gate_mint;
+        market.gate_min_amount = min_amount;
+        ctx.accounts.audit(instruction::SetTokenGate::DISCRIMINATOR, min_amount)?;
+        Ok(())
+    }
+
+    /// Lets staked keepers submit manual settlement prices in a market, or stops them.
+    ///
+    /// Meant for markets whose oracle is unreliable. Keepers are slashable for wrong prices.
+    pub fn set_keeper_price_inputs(ctx: Context<UpdateMarket>, enabled: bool) -> Result<()> {
+        ctx.accounts.market.keeper_price_inputs = enabled;
+        ctx.accounts.audit(instruction::SetKeeperPriceInputs::DISCRIMINATOR, enabled as u64)?;
+        Ok(())
+    }
+
+    /// Restricts settlement in a market to each series' configured settlers, or lifts it.
+    ///
+    /// For markets without a robust oracle, whose settlement relies on trusted settlers. Oracle
+    /// markets stay permissionless.
+    pub fn set_restricted_settlement(ctx: Context<UpdateMarket>, restricted: bool) -> Result<()> {
+        ctx.accounts.market.restricted_settlement = restricted;
+        ctx.accounts.audit(instruction::SetRestrictedSettlement::DISCRIMINATOR, restricted as u64)?;
+        Ok(())
+    }
+
+    /// Sets the key whose signed price attestations settle a market's escrows.
+    ///
+    /// For long-tail assets with no on-chain feed. The default key disables attestations.
+    pub fn set_attestation_signer(ctx: Context<UpdateMarket>, attestation_signer: Pubkey) -> Result<()> {
+        ctx.accounts.market.attestation_signer = attestation_signer;
+        ctx.accounts.audit(instruction::SetAttestationSigner::DISCRIMINATOR, 0)?;
+        Ok(())
+    }
+
+    /// Marks a market's collateral as an LST (e.g. jitoSOL) whose oracle prices the underlying.
+    ///
+    /// Margin, strike bands, ITM checks, and settlement then value the collateral at the oracle
+    /// price times the stake pool's exchange rate, so writers keep their staking yield while
+    /// covered. The rate must be refreshed each epoch with `refresh_lst_rate`.
+    pub fn set_lst_collateral(ctx: Context<UpdateMarket>, enabled: bool) -> Result<()> {
+        ctx.accounts.market.lst_collateral = enabled;
+        ctx.accounts.audit(instruction::SetLstCollateral::DISCRIMINATOR, enabled as u64)?;
+        Ok(())
+    }
+
+    /// Marks a market's collateral as interest-bearing shares of a lending reserve (e.g. cTokens).
+    ///
+    /// New escrows record the reserve's exchange rate when they open, and their payouts are
+    /// converted back into shares at the rate at settlement, so holders are paid in underlying
+    /// terms and the lending yield stays with the writer. Escrows already open keep paying out
+    /// raw shares.
+    pub fn set_interest_bearing_collateral(ctx: Context<UpdateMarket>, enabled: bool) -> Result<()> {
+        ctx.accounts.market.interest_bearing_collateral = enabled;
+        ctx.accounts.audit(instruction::SetInterestBearingCollateral::DISCRIMINATOR, enabled as u64)?;
+        Ok(())
+    }
+
+    /// Appoints an account to exercise on the holder's behalf, or removes it with the default key.
+    ///
+    /// The delegate can call `exercise_early` as the holder but can't redirect the proceeds,
+    /// which must go to a token account the holder owns. Any early-exercise surcharge is taken
+    /// out of those proceeds. The delegation lapses when the escrow changes holder.
+    pub fn set_exercise_delegate(ctx: Context<SetExerciseDelegate>, delegate: Pubkey) -> Result<()> {
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if escrow_account.holder_key() != Some(ctx.accounts.holder.key()) {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        escrow_account.exercise_delegate = delegate;
+        escrow_account.delegate_grantor = ctx.accounts.holder.key();
+        Ok(())
+    }
+
+    /// Authorizes a temporary key to exercise or claim on the holder's behalf until `expires_at`.
+    ///
+    /// `scope` is a bitmask of `SESSION_SCOPE_*` flags. Proceeds of anything the key does still
+    /// go to the holder's own token accounts.
+    pub fn create_session(ctx: Context<CreateSession>, session_key: Pubkey, expires_at: i64, scope: u8) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        if expires_at <= current_time
+            || expires_at - current_time > MAX_SESSION_DURATION
+            || scope == 0
+            || scope & !(SESSION_SCOPE_EXERCISE | SESSION_SCOPE_CLAIM) != 0
+        {
+            return Err(ErrorCode::InvalidSession.into());
+        }
+        let session = &mut ctx.accounts.session;
+        session.holder = ctx.accounts.holder.key();
+        session.session_key = session_key;
+        session.expires_at = expires_at;
+        session.scope = scope;
+        session.bump = ctx.bumps.session;
+        Ok(())
+    }
+
+    /// Revokes a session key, refunding its rent to the holder.
+    pub fn revoke_session(_ctx: Context<RevokeSession>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Authorizes keepers to fund an escrow from the depositor's token account once
+    /// `not_before` has passed.
+    ///
+    /// The depositor then approves the authorization PDA as the SPL delegate of the source
+    /// account (off-band or in the same transaction); the approved amount caps the pulls.
+    pub fn authorize_delegated_deposit(ctx: Context<AuthorizeDelegatedDeposit>, not_before: i64) -> Result<()> {
+        ctx.accounts.escrow_account.require_allowed(ctx.accounts.depositor.key)?;
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.escrow = ctx.accounts.escrow_account.key();
+        authorization.depositor = ctx.accounts.depositor.key();
+        authorization.source = ctx.accounts.source_collateral_account.key();
+        authorization.destination = ctx.accounts.escrow_collateral_account.key();
+        authorization.not_before = not_before;
+        authorization.bump = ctx.bumps.authorization;
+        Ok(())
+    }
+
+    /// Pulls collateral into an escrow as the depositor's SPL delegate.
+    ///
+    /// Permissionless, so keepers can fund escrows on a schedule or once off-chain conditions
+    /// are met without the depositor being online.
+    pub fn pull_delegated_deposit(ctx: Context<PullDelegatedDeposit>, amount: u64) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_DEPOSITS)?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        let depositor = ctx.accounts.authorization.depositor;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), &depositor)?;
+        ctx.accounts.escrow_account.require_allowed(&depositor)?;
+        if Clock::get()?.unix_timestamp < ctx.accounts.authorization.not_before {
+            return Err(ErrorCode::DepositNotYetAllowed.into());
+        }
+
+        let escrow = ctx.accounts.escrow_account.key();
+        let bump = ctx.accounts.authorization.bump;
+        let seeds: &[&[u8]] = &[DEPOSIT_AUTHORIZATION_SEED, escrow.as_ref(), depositor.as_ref(), &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.source_collateral_account.to_account_info(),
+            to: ctx.accounts.escrow_collateral_account.to_account_info(),
+            authority: ctx.accounts.authorization.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.record_deposit(amount)?;
+        escrow_account.vault_bump = ctx.bumps.escrow_collateral_account;
+        escrow_account.enroll_emissions(
+            ctx.accounts.emissions_schedule.as_mut(),
+            ctx.accounts.writer_emissions.as_mut(),
+            Clock::get()?.unix_timestamp,
+        )?;
+        let market_stats = &mut ctx.accounts.market_stats;
+        market_stats.collateral_locked = market_stats.collateral_locked.saturating_add(amount);
+        emit_lifecycle_event(&CollateralDeposited {
+            seq: market_stats.next_event_seq(),
+            market: market_stats.market,
+            escrow,
+            depositor,
+            amount,
+        }, ctx.accounts.event_authority.as_ref(), ctx.bumps.event_authority)?;
+        Ok(())
+    }
+
+    /// Withdraws a delegated-deposit authorization, refunding its rent to the depositor.
+    ///
+    /// The depositor should also revoke the SPL delegate approval on the source account.
+    pub fn revoke_delegated_deposit(_ctx: Context<RevokeDelegatedDeposit>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Settles a series of expired escrows in one transaction.
+    ///
+    /// The escrows share the market and expiry and are passed as remaining accounts in groups of
+    /// `SETTLE_MANY_GROUP_SIZE`: the escrow, its collateral token account, the writer's
+    /// position, and the writer's stats. Each escrow settles like
+    /// `settle_escrow`, without a staking fee discount, and at most `SETTLE_MANY_MAX_ESCROWS`
+    /// fit in one transaction. Basket and quanto escrows, escrows
+    /// enrolled in emissions or on interest-bearing collateral, and markets charging fees in
+    /// another mint need extra accounts and must be settled one at a time.
+    pub fn settle_many<'info>(ctx: Context<'_, '_, 'info, 'info, SettleMany<'info>>) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_EXERCISE)?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        if ctx.accounts.market.has_separate_fee_mint() {
+            return Err(ErrorCode::BatchUnsupported.into());
+        }
+        let remaining_accounts = ctx.remaining_accounts;
+        let groups = remaining_accounts.chunks_exact(SETTLE_MANY_GROUP_SIZE);
+        if remaining_accounts.is_empty() || !groups.remainder().is_empty() || groups.len() > SETTLE_MANY_MAX_ESCROWS {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+
+        let series_expiration = Account::<EscrowAccount>::try_from(&remaining_accounts[0])?.expiration;
+        let series_settlers = ctx.accounts.series_settlers.as_deref().filter(|settlers| settlers.expiration == series_expiration);
+        ctx.accounts.market.require_settler(series_settlers, ctx.accounts.user.key)?;
+        let mut series_price = None;
+        for group in groups {
+            ctx.accounts.settle_one(group, series_expiration, &mut series_price, ctx.bumps.event_authority)?;
+        }
+        Ok(())
+    }
+
+    /// Opens several escrows with the same expiry and terms in one transaction, e.g. a market
+    /// maker quoting a full chain of strikes.
+    ///
+    /// Each leg's new escrow account is passed, as a signer, in the remaining accounts in the
+    /// order of `legs`. Every escrow is validated and recorded like `initialize_escrow`, and
+    /// their initialization fees are collected in a single transfer. Negotiated fee overrides
+    /// apply to one escrow at a time and can't be used here.
+    pub fn initialize_escrows_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InitializeEscrowsBatch<'info>>,
+        expiration: i64,
+        legs: Vec<EscrowLeg>,
+        terms: OptionTerms,
+    ) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_NEW_ESCROWS)?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), ctx.accounts.initializer.key)?;
+        if legs.is_empty() || legs.len() > MAX_BATCH_ESCROWS || legs.len() != ctx.remaining_accounts.len() {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let collateral_mint = ctx.accounts.market.collateral_mint;
+        let lamports = Rent::get()?.minimum_balance(EscrowAccount::SPACE);
+        let deposit_exchange_rate = ctx.accounts.market.collateral_exchange_rate(ctx.accounts.collateral_reserve.as_ref())?.unwrap_or(0);
+        let mut fee: u64 = 0;
+        for (leg, escrow_info) in legs.iter().zip(ctx.remaining_accounts.iter()) {
+            let leg_expiration = ctx.accounts.market.validate_new_escrow(
+                ctx.accounts.collateral_oracle.as_ref(),
+                ctx.accounts.expiry_grid.as_deref(),
+                leg,
+                expiration,
+                &terms,
+                current_time,
+            )?;
+            let mut escrow_account = EscrowAccount::new(ctx.accounts.initializer.key(), leg, leg_expiration, collateral_mint, terms.clone(), current_time);
+            escrow_account.deposit_exchange_rate = deposit_exchange_rate;
+
+            // Create the escrow account, signed by its new keypair
+            let cpi_accounts = system_program::CreateAccount {
+                from: ctx.accounts.initializer.to_account_info(),
+                to: escrow_info.clone(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+            system_program::create_account(cpi_ctx, lamports, EscrowAccount::SPACE as u64, &crate::ID)?;
+
+            // Record the escrow like `initialize_escrow` does
+            let collateral_amount = leg.collateral_amount;
+            fee = fee
+                .checked_add(ctx.accounts.governance.init_fee(collateral_amount, leg_expiration.saturating_sub(current_time))?)
+                .ok_or(ErrorCode::MathOverflow)?;
+            ctx.accounts.initializer_stats.record_notional_written(&ctx.accounts.market, collateral_amount)?;
+            ctx.accounts.initializer_stats.add_open_notional(&ctx.accounts.market, collateral_amount)?;
+            ctx.accounts.market.add_open_interest(collateral_amount)?;
+            let market_stats = &mut ctx.accounts.market_stats;
+            market_stats.notional_volume = market_stats.notional_volume.saturating_add(collateral_amount);
+            market_stats.escrows_opened = market_stats.escrows_opened.saturating_add(1);
+            ctx.accounts.protocol_stats.record_open();
+            ctx.accounts.initializer_position.add_written(escrow_info.key())?;
+            emit_lifecycle_event(&EscrowInitialized {
+                seq: market_stats.next_event_seq(),
+                market: market_stats.market,
+                escrow: escrow_info.key(),
+                writer: ctx.accounts.initializer.key(),
+                strike_price: leg.strike_price,
+                expiration: leg_expiration,
+                collateral_amount,
+            }, ctx.accounts.event_authority.as_ref(), ctx.bumps.event_authority)?;
+            escrow_account.try_serialize(&mut &mut escrow_info.try_borrow_mut_data()?[..])?;
+        }
+
+        ctx.accounts.collect_fee(fee)
+    }
+
+    /// Moves the settlement fees accrued on an escrow into the fee vault and accrues them to the
+    /// fee collectors.
+    ///
+    /// Permissionless, so cranks can sweep fees outside the compute-constrained settlement path.
+    pub fn sweep_escrow_fees(ctx: Context<SweepEscrowFees>) -> Result<()> {
+        let fee = ctx.accounts.escrow_account.unswept_fees;
+        if fee == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+        transfer_from_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.escrow_collateral_account.to_account_info(),
+            ctx.accounts.fee_vault_token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            fee,
+        )?;
+        ctx.accounts.fee_vault.accrue(&ctx.accounts.governance.fee_collectors, fee)?;
+        ctx.accounts.escrow_account.unswept_fees = 0;
+        Ok(())
+    }
+
+    /// Grows an escrow created under an older, smaller layout to the current account space
+    /// and stamps it with the current layout version.
+    ///
+    /// New fields are appended at the end of the account, so the zero-filled tail decodes as
+    /// their defaults. Only escrows from `MIN_MIGRATABLE_ESCROW_VERSION` on share the current
+    /// fixed offsets; older escrows are rejected rather than reinterpreted. Callable by the
+    /// escrow's writer or the governance authority, who pays the extra rent.
+    pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+        let escrow_info = ctx.accounts.escrow_account.to_account_info();
+        {
+            let data = escrow_info.try_borrow_data()?;
+            if !data.starts_with(&EscrowAccount::DISCRIMINATOR) || data.len() <= ESCROW_VERSION_OFFSET {
+                return Err(ErrorCode::InvalidMigration.into());
+            }
+            if data[ESCROW_VERSION_OFFSET] < MIN_MIGRATABLE_ESCROW_VERSION {
+                return Err(ErrorCode::UnsupportedEscrowVersion.into());
+            }
+            let initializer_key = Pubkey::try_from(&data[ESCROW_INITIALIZER_KEY_OFFSET..ESCROW_INITIALIZER_KEY_OFFSET + 32])
+                .map_err(|_| ErrorCode::InvalidMigration)?;
+            let signer = ctx.accounts.authority.key();
+            if signer != initializer_key && signer != ctx.accounts.governance.governance_authority {
+                return Err(ErrorCode::NotEscrowWriter.into());
+            }
+        }
+        if escrow_info.data_len() < EscrowAccount::SPACE {
+            let rent = Rent::get()?.minimum_balance(EscrowAccount::SPACE).saturating_sub(escrow_info.lamports());
+            if rent > 0 {
+                let cpi_accounts = system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: escrow_info.clone(),
+                };
+                let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+                system_program::transfer(cpi_ctx, rent)?;
+            }
+            escrow_info.realloc(EscrowAccount::SPACE, true)?;
+        }
+        let mut escrow_account = EscrowAccount::try_deserialize(&mut &escrow_info.try_borrow_data()?[..])?;
+        if escrow_account.version >= ESCROW_ACCOUNT_VERSION {
+            return Err(ErrorCode::InvalidMigration.into());
+        }
+        escrow_account.version = ESCROW_ACCOUNT_VERSION;
+        let mut data = escrow_info.try_borrow_mut_data()?;
+        escrow_account.try_serialize(&mut &mut data[..])
+    }
+
+    /// Creates the audit log of a market.
+    ///
+    /// Once it exists, governance changes to the market, manual settlement prices, and
+    /// settlements that pass the log record who did what, when, and for how much, keeping the
+    /// last `AUDIT_LOG_ENTRIES` actions on chain.
+    pub fn initialize_audit_log(ctx: Context<InitializeAuditLog>) -> Result<()> {
+        let mut audit_log = ctx.accounts.audit_log.load_init()?;
+        audit_log.market = ctx.accounts.market.key();
+        audit_log.bump = ctx.bumps.audit_log;
+        Ok(())
+    }
+
+    /// Returns what settling an escrow now would pay, without changing state.
+    ///
+    /// Prices the escrow with the same code as `settle_escrow`, at the current oracle price
+    /// (or the escrow's own settlement price), so frontends can show the exact payout to the
+    /// holder, refund to the writer, and fee. Basket options take the basket's oracles as
+    /// remaining accounts. Passing the settler's stake position applies its fee discount.
+    pub fn preview_settlement<'info>(ctx: Context<'_, '_, 'info, 'info, PreviewSettlement<'info>>) -> Result<SettlementPreview> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        let pricing = SettlementPricing {
+            escrow_account,
+            market: &ctx.accounts.market,
+            collateral_oracle: ctx.accounts.collateral_oracle.as_ref(),
+            underlying_oracle: ctx.accounts.underlying_oracle.as_ref(),
+            basket: ctx.accounts.basket.as_ref(),
+            price_history: ctx.accounts.price_history.as_ref(),
+            oracle_registry: ctx.accounts.oracle_registry.as_ref(),
+            extra_oracles: [ctx.accounts.secondary_oracle.as_ref(), ctx.accounts.tertiary_oracle.as_ref()],
+            collateral_reserve: ctx.accounts.collateral_reserve.as_ref(),
+        };
+        let settlement_price = pricing.settlement_price(ctx.remaining_accounts)?;
+        let exercises = pricing.auto_exercises(ctx.remaining_accounts)?;
+
+        let collateral_amount = escrow_account.collateral_amount;
+        let fee = collateral_amount * escrow_account.fee_rate(ctx.accounts.governance.settlement_fee_bps) / 10000;
+        let fee = ctx.accounts.governance.apply_fee_discount(fee, ctx.accounts.stake_position.as_ref(), Clock::get()?.unix_timestamp);
+        let collateral_fee = if ctx.accounts.market.has_separate_fee_mint() { 0 } else { fee };
+        let amount_after_fee = collateral_amount - collateral_fee;
+        let holder_amount = if exercises { pricing.holder_payout(amount_after_fee)? } else { 0 };
+        Ok(SettlementPreview {
+            settlement_price,
+            exercises,
+            holder_amount,
+            writer_amount: amount_after_fee - holder_amount,
+            fee,
+        })
+    }
+
+    /// Returns an escrow's intrinsic value, moneyness, and time to expiry at the collateral
+    /// oracle's current price, without changing state.
+    ///
+    /// For frontends, keepers prioritizing ITM settlements, and risk dashboards. Basket and
+    /// quanto options aren't priced by the collateral oracle; use `preview_settlement` for them.
+    pub fn get_escrow_value(ctx: Context<GetEscrowValue>) -> Result<EscrowValue> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        if matches!(escrow_account.payoff, Payoff::Basket | Payoff::Quanto { .. }) {
+            return Err(ErrorCode::InvalidPayoff.into());
+        }
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let spot_price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        let intrinsic_value = escrow_account.intrinsic_value(spot_price)?;
+        Ok(EscrowValue {
+            spot_price,
+            intrinsic_value,
+            moneyness_bps: escrow_account.signed_moneyness_bps(spot_price),
+            is_itm: intrinsic_value > 0,
+            time_to_expiry: escrow_account.expiration.saturating_sub(Clock::get()?.unix_timestamp).max(0),
+        })
+    }
+
+    /// Returns the theoretical Black-Scholes premium of an option.
+    ///
+    /// Pure math over the inputs, so quoting modules and clients can sanity-check premiums.
+    /// `spot`, `strike`, and the result use `STRIKE_PRICE_DECIMALS`, `tenor` is in seconds, and the
+    /// risk-free rate and implied volatility are annualized basis points.
+    pub fn get_fair_value(
+        _ctx: Context<GetFairValue>,
+        option_type: OptionType,
+        spot: u64,
+        strike: u64,
+        tenor: i64,
+        rate_bps: i64,
+        vol_bps: u64,
+    ) -> Result<u64> {
+        math::black_scholes(option_type, spot, strike, tenor, rate_bps, vol_bps)
+    }
+
+    /// Creates the implied volatility curve of a market.
+    pub fn initialize_vol_config(
+        ctx: Context<InitializeVolConfig>,
+        updater: Pubkey,
+        rate_bps: i64,
+        max_staleness: i64,
+        points: Vec<VolPoint>,
+    ) -> Result<()> {
+        validate_vol_points(&points)?;
+        if max_staleness <= 0 || rate_bps.abs() > 10000 {
+            return Err(ErrorCode::InvalidVolConfig.into());
+        }
+        let vol_config = &mut ctx.accounts.vol_config;
+        vol_config.market = ctx.accounts.market.key();
+        vol_config.updater = updater;
+        vol_config.rate_bps = rate_bps;
+        vol_config.max_staleness = max_staleness;
+        vol_config.updated_at = Clock::get()?.unix_timestamp;
+        vol_config.points = points;
+        vol_config.bump = ctx.bumps.vol_config;
+        Ok(())
+    }
+
+    /// Changes the updater, rate, and staleness limit of a market's volatility curve.
+    pub fn update_vol_config(ctx: Context<UpdateVolConfig>, updater: Pubkey, rate_bps: i64, max_staleness: i64) -> Result<()> {
+        if max_staleness <= 0 || rate_bps.abs() > 10000 {
+            return Err(ErrorCode::InvalidVolConfig.into());
+        }
+        let vol_config = &mut ctx.accounts.vol_config;
+        vol_config.updater = updater;
+        vol_config.rate_bps = rate_bps;
+        vol_config.max_staleness = max_staleness;
+        Ok(())
+    }
+
+    /// Replaces the points of a market's volatility curve and marks it fresh.
+    ///
+    /// Callable by the curve's updater or the governance authority.
+    pub fn push_vol_points(ctx: Context<PushVolPoints>, points: Vec<VolPoint>) -> Result<()> {
+        let signer = ctx.accounts.authority.key();
+        if signer != ctx.accounts.vol_config.updater && signer != ctx.accounts.governance.governance_authority {
+            return Err(ErrorCode::NotVolUpdater.into());
+        }
+        validate_vol_points(&points)?;
+        let vol_config = &mut ctx.accounts.vol_config;
+        vol_config.points = points;
+        vol_config.updated_at = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Returns the fair value of a vanilla escrow from the market's volatility curve.
+    ///
+    /// The value is in units of the collateral, like `get_escrow_value`'s intrinsic value.
+    pub fn get_escrow_fair_value(ctx: Context<GetEscrowFairValue>) -> Result<u64> {
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let spot_price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        ctx.accounts.escrow_account.fair_value(spot_price, &ctx.accounts.vol_config, Clock::get()?.unix_timestamp)
+    }
+
+    /// Creates the keeper registry.
+    pub fn initialize_keeper_registry(
+        ctx: Context<InitializeKeeperRegistry>,
+        min_stake: u64,
+        unbonding_period: i64,
+        priority_window: i64,
+    ) -> Result<()> {
+        if unbonding_period < 0 || priority_window < 0 {
+            return Err(ErrorCode::InvalidKeeperParams.into());
+        }
+        let keeper_registry = &mut ctx.accounts.keeper_registry;
+        keeper_registry.stake_mint = ctx.accounts.stake_mint.key();
+        keeper_registry.stake_vault = ctx.accounts.stake_vault.key();
+        keeper_registry.min_stake = min_stake;
+        keeper_registry.unbonding_period = unbonding_period;
+        keeper_registry.priority_window = priority_window;
+        keeper_registry.total_staked = 0;
+        keeper_registry.bump = ctx.bumps.keeper_registry;
+        Ok(())
+    }
+
+    /// Changes the minimum stake, unbonding period, and priority window of the keeper registry.
+    pub fn update_keeper_registry(ctx: Context<UpdateKeeperRegistry>, min_stake: u64, unbonding_period: i64, priority_window: i64) -> Result<()> {
+        if unbonding_period < 0 || priority_window < 0 {
+            return Err(ErrorCode::InvalidKeeperParams.into());
+        }
+        let keeper_registry = &mut ctx.accounts.keeper_registry;
+        keeper_registry.min_stake = min_stake;
+        keeper_registry.unbonding_period = unbonding_period;
+        keeper_registry.priority_window = priority_window;
+        Ok(())
+    }
+
+    /// Creates an empty keeper stake account for the signer.
+    pub fn register_keeper(ctx: Context<RegisterKeeper>) -> Result<()> {
+        let keeper = &mut ctx.accounts.keeper;
+        keeper.owner = ctx.accounts.owner.key();
+        keeper.bonded = 0;
+        keeper.unbonding = 0;
+        keeper.unbonding_end = 0;
+        keeper.slashed = 0;
+        keeper.bump = ctx.bumps.keeper;
+        Ok(())
+    }
+
+    /// Bonds stake to a keeper.
+    pub fn bond_keeper_stake(ctx: Context<KeeperStake>, amount: u64) -> Result<()> {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.owner_stake_account.to_account_info(),
+            to: ctx.accounts.stake_vault.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        let keeper = &mut ctx.accounts.keeper;
+        keeper.bonded = keeper.bonded.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        let keeper_registry = &mut ctx.accounts.keeper_registry;
+        keeper_registry.total_staked = keeper_registry.total_staked.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Starts unbonding part of a keeper's stake.
+    ///
+    /// The stake stops counting towards activity at once but stays slashable until the
+    /// registry's unbonding period has passed. Unbonding more restarts the period.
+    pub fn unbond_keeper_stake(ctx: Context<KeeperStake>, amount: u64) -> Result<()> {
+        let unbonding_end = Clock::get()?.unix_timestamp.saturating_add(ctx.accounts.keeper_registry.unbonding_period);
+        let keeper = &mut ctx.accounts.keeper;
+        if amount == 0 || amount > keeper.bonded {
+            return Err(ErrorCode::InsufficientStake.into());
+        }
+        keeper.bonded -= amount;
+        keeper.unbonding += amount;
+        keeper.unbonding_end = unbonding_end;
+        Ok(())
+    }
+
+    /// Withdraws a keeper's unbonded stake once the unbonding period has passed.
+    pub fn withdraw_keeper_stake(ctx: Context<KeeperStake>) -> Result<()> {
+        let amount = ctx.accounts.keeper.unbonding;
+        if amount == 0 {
+            return Err(ErrorCode::InsufficientStake.into());
+        }
+        if Clock::get()?.unix_timestamp < ctx.accounts.keeper.unbonding_end {
+            return Err(ErrorCode::KeeperStakeUnbonding.into());
+        }
+
+        let bump = ctx.accounts.keeper_registry.bump;
+        let seeds: &[&[u8]] = &[KEEPER_REGISTRY_SEED, &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.owner_stake_account.to_account_info(),
+            authority: ctx.accounts.keeper_registry.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        ctx.accounts.keeper.unbonding = 0;
+        ctx.accounts.keeper_registry.total_staked -= amount;
+        Ok(())
+    }
+
+    /// Slashes a keeper for provably wrong settlement inputs.
+    ///
+    /// Takes bonded stake first, then unbonding stake, and sends it to `recipient_account`.
+    pub fn slash_keeper(ctx: Context<SlashKeeper>, amount: u64) -> Result<()> {
+        let keeper = &mut ctx.accounts.keeper;
+        let from_bonded = amount.min(keeper.bonded);
+        let from_unbonding = amount - from_bonded;
+        if from_unbonding > keeper.unbonding {
+            return Err(ErrorCode::InsufficientStake.into());
+        }
+        keeper.bonded -= from_bonded;
+        keeper.unbonding -= from_unbonding;
+        keeper.slashed = keeper.slashed.saturating_add(amount);
+
+        let bump = ctx.accounts.keeper_registry.bump;
+        let seeds: &[&[u8]] = &[KEEPER_REGISTRY_SEED, &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.stake_vault.to_account_info(),
+            to: ctx.accounts.recipient_account.to_account_info(),
+            authority: ctx.accounts.keeper_registry.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        ctx.accounts.keeper_registry.total_staked -= amount;
+        Ok(())
+    }
+
+    /// Sets the settlement price of an expired escrow as an active keeper.
+    ///
+    /// Only in markets that allow keeper price inputs, and only while no price has been set.
+    /// Escrows can't settle at the price until `KEEPER_PRICE_DISPUTE_WINDOW` has passed, giving
+    /// governance time to override it with `set_manual_settlement_price` and slash the keeper if
+    /// it was wrong.
+    pub fn submit_settlement_price(ctx: Context<SubmitSettlementPrice>, settlement_price: u64) -> Result<()> {
+        if !ctx.accounts.market.keeper_price_inputs {
+            return Err(ErrorCode::KeeperInputsDisabled.into());
+        }
+        if !ctx.accounts.keeper.is_active(&ctx.accounts.keeper_registry) {
+            return Err(ErrorCode::InsufficientStake.into());
+        }
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        let current_time = Clock::get()?.unix_timestamp;
+        if current_time < escrow_account.expiration {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if settlement_price == 0 {
+            return Err(ErrorCode::InvalidOraclePrice.into());
+        }
+        if escrow_account.manual_settlement_price > 0 {
+            return Err(ErrorCode::SettlementPriceAlreadySet.into());
+        }
+        escrow_account.manual_settlement_price = settlement_price;
+        escrow_account.price_submitter = ctx.accounts.owner.key();
+        escrow_account.price_disputable_until = current_time.checked_add(KEEPER_PRICE_DISPUTE_WINDOW).ok_or(ErrorCode::MathOverflow)?;
+        record_audit(
+            ctx.accounts.audit_log.as_ref(),
+            ctx.accounts.owner.key(),
+            ctx.accounts.escrow_account.key(),
+            instruction::SubmitSettlementPrice::DISCRIMINATOR,
+            settlement_price,
+        )
+    }
+
+    /// Creates the list of settlers of one series of a market.
+    pub fn initialize_series_settlers(ctx: Context<InitializeSeriesSettlers>, expiration: i64, settlers: Vec<Pubkey>) -> Result<()> {
+        if settlers.is_empty() || settlers.len() > MAX_SERIES_SETTLERS {
+            return Err(ErrorCode::InvalidSeriesSettlers.into());
+        }
+        let series_settlers = &mut ctx.accounts.series_settlers;
+        series_settlers.market = ctx.accounts.market.key();
+        series_settlers.expiration = expiration;
+        series_settlers.settlers = settlers;
+        series_settlers.bump = ctx.bumps.series_settlers;
+        Ok(())
+    }
+
+    /// Replaces the settlers of a series.
+    pub fn set_series_settlers(ctx: Context<SetSeriesSettlers>, settlers: Vec<Pubkey>) -> Result<()> {
+        if settlers.is_empty() || settlers.len() > MAX_SERIES_SETTLERS {
+            return Err(ErrorCode::InvalidSeriesSettlers.into());
+        }
+        ctx.accounts.series_settlers.settlers = settlers;
+        Ok(())
+    }
+
+    /// Sets the settlement price of an expired escrow from a signed price attestation.
+    ///
+    /// The transaction must verify the market's attestation signer's signature over
+    /// `price_attestation_message` with an ed25519 program instruction placed right before this
+    /// one. Anyone can relay the attestation. Governance can still override the price with
+    /// `set_manual_settlement_price`.
+    pub fn submit_price_attestation(ctx: Context<SubmitPriceAttestation>, settlement_price: u64) -> Result<()> {
+        let attestation_signer = ctx.accounts.market.attestation_signer;
+        if attestation_signer == Pubkey::default() {
+            return Err(ErrorCode::AttestationsDisabled.into());
+        }
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if Clock::get()?.unix_timestamp < escrow_account.expiration {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if settlement_price == 0 {
+            return Err(ErrorCode::InvalidOraclePrice.into());
+        }
+        if escrow_account.manual_settlement_price > 0 {
+            return Err(ErrorCode::SettlementPriceAlreadySet.into());
+        }
+        let message = price_attestation_message(&escrow_account.collateral_mint, escrow_account.expiration, settlement_price);
+        verify_ed25519_attestation(&ctx.accounts.instructions.to_account_info(), &attestation_signer, &message)?;
+        escrow_account.manual_settlement_price = settlement_price;
+        escrow_account.price_submitter = attestation_signer;
+        record_audit(
+            ctx.accounts.audit_log.as_ref(),
+            attestation_signer,
+            ctx.accounts.escrow_account.key(),
+            instruction::SubmitPriceAttestation::DISCRIMINATOR,
+            settlement_price,
+        )
+    }
+
+    /// Exercises an escrow early and swaps the proceeds into another mint through Jupiter.
+    ///
+    /// The escrow is exercised like `exercise_early`, paying the exerciser's own collateral
+    /// account, which the exerciser's signature then lets Jupiter spend. `swap_data` is the
+    /// Jupiter route instruction data and the remaining accounts are its accounts, so basket
+    /// options, which price on remaining accounts, can't use this. The swap may spend at most
+    /// the proceeds and must deliver at least `min_amount_out` to `output_token_account`, and
+    /// the transaction reverts if it lands after `deadline`.
+    pub fn exercise_and_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExerciseAndSwap<'info>>,
+        swap_data: Vec<u8>,
+        min_amount_out: u64,
+        deadline: i64,
+    ) -> Result<()> {
+        require_before_deadline(deadline)?;
+        let exercise = &mut ctx.accounts.exercise;
+        if exercise.escrow_account.payoff == Payoff::Basket {
+            return Err(ErrorCode::InvalidPayoff.into());
+        }
+        if exercise.proceeds_owner.key() != exercise.user.key() {
+            return Err(ErrorCode::InvalidProceedsAccount.into());
+        }
+        let holder_amount = exercise.exercise(&[], false, ctx.bumps.exercise.event_authority, instruction::ExerciseAndSwap::DISCRIMINATOR)?;
+
+        let exercise = &mut ctx.accounts.exercise;
+        exercise.user_collateral_account.reload()?;
+        let proceeds_before = exercise.user_collateral_account.amount;
+        let output_before = ctx.accounts.output_token_account.amount;
+
+        // Swap with the exerciser's signature, which this instruction already carries
+        let account_metas = ctx
+            .remaining_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let swap_ix = Instruction {
+            program_id: ctx.accounts.jupiter_program.key(),
+            accounts: account_metas,
+            data: swap_data,
+        };
+        invoke_signed(&swap_ix, ctx.remaining_accounts, &[])?;
+
+        let exercise = &mut ctx.accounts.exercise;
+        exercise.user_collateral_account.reload()?;
+        ctx.accounts.output_token_account.reload()?;
+        let spent = proceeds_before.saturating_sub(exercise.user_collateral_account.amount);
+        let received = ctx.accounts.output_token_account.amount.saturating_sub(output_before);
+        if spent > holder_amount {
+            return Err(ErrorCode::SwapExceedsProceeds.into());
+        }
+        if received < min_amount_out {
+            return Err(ErrorCode::SlippageExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// Whitelists a lending protocol for a market's idle collateral.
+    ///
+    /// Writers can then deposit their escrow's collateral into `lending_program` with
+    /// `lend_collateral`. `protocol_share_bps` of the yield goes to the treasury on recall.
+    pub fn initialize_lending_config(
+        ctx: Context<InitializeLendingConfig>,
+        lending_program: Pubkey,
+        receipt_mint: Pubkey,
+        protocol_share_bps: u64,
+    ) -> Result<()> {
+        if protocol_share_bps > 10000 {
+            return Err(ErrorCode::InvalidLendingConfig.into());
+        }
+        let lending_config = &mut ctx.accounts.lending_config;
+        lending_config.market = ctx.accounts.market.key();
+        lending_config.lending_program = lending_program;
+        lending_config.receipt_mint = receipt_mint;
+        lending_config.protocol_share_bps = protocol_share_bps;
+        lending_config.enabled = true;
+        lending_config.bump = ctx.bumps.lending_config;
+        Ok(())
+    }
+
+    /// Changes the protocol's share of lending yield and enables or disables new deposits.
+    ///
+    /// Disabling only blocks `lend_collateral`; lent collateral can always be recalled.
+    pub fn update_lending_config(ctx: Context<UpdateLendingConfig>, protocol_share_bps: u64, enabled: bool) -> Result<()> {
+        if protocol_share_bps > 10000 {
+            return Err(ErrorCode::InvalidLendingConfig.into());
+        }
+        let lending_config = &mut ctx.accounts.lending_config;
+        lending_config.protocol_share_bps = protocol_share_bps;
+        lending_config.enabled = enabled;
+        Ok(())
+    }
+
+    /// Deposits an escrow's idle collateral into the market's whitelisted lending protocol.
+    ///
+    /// Only the writer can opt in, and only for a funded, unsettled escrow. The deposit
+    /// instruction (`deposit_data`) is built off-chain for the protocol (e.g. Kamino or Solend)
+    /// and its accounts are passed as remaining accounts, the escrow's vault signing for the
+    /// collateral. The receipt tokens must land in a receipt account owned by the vault. Settlement, exercise, rolls, and cancels are blocked
+    /// until the collateral is recalled.
+    pub fn lend_collateral<'info>(
+        ctx: Context<'_, '_, '_, 'info, LendCollateral<'info>>,
+        deposit_data: Vec<u8>,
+    ) -> Result<()> {
+        if !ctx.accounts.lending_config.enabled {
+            return Err(ErrorCode::LendingDisabled.into());
+        }
+        let escrow_account = &ctx.accounts.escrow_account;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.deposited_amount < escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowNotFunded.into());
+        }
+        escrow_account.require_not_lent()?;
+
+        let collateral_before = ctx.accounts.escrow_collateral_account.amount;
+        let receipt_before = ctx.accounts.receipt_account.amount;
+        let vault_key = ctx.accounts.escrow_collateral_account.key();
+        let account_metas = ctx
+            .remaining_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer || account.key() == vault_key,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let deposit_ix = Instruction {
+            program_id: ctx.accounts.lending_program.key(),
+            accounts: account_metas,
+            data: deposit_data,
+        };
+        let escrow_key = ctx.accounts.escrow_account.key();
+        let seeds: &[&[u8]] = &[ESCROW_VAULT_SEED, escrow_key.as_ref(), &[ctx.accounts.escrow_account.vault_bump]];
+        invoke_signed(&deposit_ix, ctx.remaining_accounts, &[seeds])?;
+
+        ctx.accounts.escrow_collateral_account.reload()?;
+        ctx.accounts.receipt_account.reload()?;
+        let lent = collateral_before.saturating_sub(ctx.accounts.escrow_collateral_account.amount);
+        if lent == 0 || ctx.accounts.receipt_account.amount <= receipt_before {
+            return Err(ErrorCode::InvalidLendingDeposit.into());
+        }
+        ctx.accounts.escrow_account.lent_amount = lent;
+        record_audit(
+            ctx.accounts.audit_log.as_ref(),
+            ctx.accounts.initializer_key.key(),
+            ctx.accounts.escrow_account.key(),
+            instruction::LendCollateral::DISCRIMINATOR,
+            lent,
+        )?;
+        Ok(())
+    }
+
+    /// Withdraws an escrow's collateral from the lending protocol and splits the yield.
+    ///
+    /// The writer can recall at any time; anyone can once the escrow has expired, so a lent
+    /// escrow can always be settled. The withdrawal (`withdraw_data`) must return at least the
+    /// lent amount. The yield above it is split: `protocol_share_bps` to the treasury and the
+    /// rest to the writer, leaving exactly the lent collateral in the escrow.
+    pub fn recall_collateral<'info>(
+        ctx: Context<'_, '_, '_, 'info, RecallCollateral<'info>>,
+        withdraw_data: Vec<u8>,
+    ) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        let lent = escrow_account.lent_amount;
+        if lent == 0 {
+            return Err(ErrorCode::CollateralNotLent.into());
+        }
+        let current_time = Clock::get()?.unix_timestamp;
+        if ctx.accounts.user.key() != escrow_account.initializer_key && current_time < escrow_account.expiration {
+            return Err(ErrorCode::NotEscrowWriter.into());
+        }
+
+        let collateral_before = ctx.accounts.escrow_collateral_account.amount;
+        let vault_key = ctx.accounts.escrow_collateral_account.key();
+        let account_metas = ctx
+            .remaining_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer || account.key() == vault_key,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let withdraw_ix = Instruction {
+            program_id: ctx.accounts.lending_program.key(),
+            accounts: account_metas,
+            data: withdraw_data,
+        };
+        let escrow_key = ctx.accounts.escrow_account.key();
+        let seeds: &[&[u8]] = &[ESCROW_VAULT_SEED, escrow_key.as_ref(), &[ctx.accounts.escrow_account.vault_bump]];
+        invoke_signed(&withdraw_ix, ctx.remaining_accounts, &[seeds])?;
+
+        ctx.accounts.escrow_collateral_account.reload()?;
+        let returned = ctx.accounts.escrow_collateral_account.amount.saturating_sub(collateral_before);
+        if returned < lent {
+            return Err(ErrorCode::LendingLoss.into());
+        }
+
+        // Split the yield between the treasury and the writer
+        let yield_amount = returned - lent;
+        let protocol_share = (yield_amount as u128 * ctx.accounts.lending_config.protocol_share_bps as u128 / 10000) as u64;
+        let writer_share = yield_amount - protocol_share;
+        let accounts = &ctx.accounts;
+        for (to, amount) in [
+            (accounts.treasury_token_account.to_account_info(), protocol_share),
+            (accounts.initializer_collateral_account.to_account_info(), writer_share),
+        ] {
+            transfer_from_vault(&accounts.escrow_account, accounts.escrow_collateral_account.to_account_info(), to, &accounts.token_program, amount)?;
+        }
+        ctx.accounts.escrow_account.lent_amount = 0;
+        Ok(())
+    }
+
+    /// Whitelists a perp DEX program for hedging vaults.
+    ///
+    /// Venues with an adapter have their orders built on-chain; `Raw` venues take the
+    /// rebalancer's instruction data as is.
+    pub fn add_hedge_venue(ctx: Context<AddHedgeVenue>, program: Pubkey, adapter: HedgeAdapterKind) -> Result<()> {
+        if adapter != HedgeAdapterKind::Raw && adapter.adapter()?.program_id() != program {
+            return Err(ErrorCode::HedgeAdapterUnavailable.into());
+        }
+        let hedge_venue = &mut ctx.accounts.hedge_venue;
+        hedge_venue.program = program;
+        hedge_venue.adapter = adapter;
+        hedge_venue.bump = ctx.bumps.hedge_venue;
+        Ok(())
+    }
+
+    /// Removes a perp DEX from the hedging whitelist, refunding the rent to governance.
+    ///
+    /// Vaults on the venue can no longer rebalance; their owners unwind on the venue directly.
+    pub fn remove_hedge_venue(_ctx: Context<RemoveHedgeVenue>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Opens a writer's hedging vault on a whitelisted perp DEX.
+    ///
+    /// The vault PDA is meant to be the authority of the writer's account on the venue, so
+    /// trades can only go through `rebalance_hedge`. `rebalancer` may rebalance besides the
+    /// owner (the default key for none). `venue_market_index` and `base_lot_size` describe the
+    /// perp market for venues whose orders are built by an adapter.
+    pub fn initialize_hedge_vault(
+        ctx: Context<InitializeHedgeVault>,
+        rebalancer: Pubkey,
+        params: HedgeParams,
+        venue_market_index: u16,
+        base_lot_size: u64,
+    ) -> Result<()> {
+        let hedge_vault = &mut ctx.accounts.hedge_vault;
+        hedge_vault.owner = ctx.accounts.owner.key();
+        hedge_vault.market = ctx.accounts.market.key();
+        hedge_vault.venue = ctx.accounts.hedge_venue.program;
+        hedge_vault.venue_market_index = venue_market_index;
+        hedge_vault.base_lot_size = base_lot_size;
+        hedge_vault.rebalancer = rebalancer;
+        hedge_vault.params = params;
+        hedge_vault.hedge_position = 0;
+        hedge_vault.last_rebalance = 0;
+        hedge_vault.bump = ctx.bumps.hedge_vault;
+        Ok(())
+    }
+
+    /// Changes the rebalancer and the target delta and rebalance band of a hedging vault.
+    pub fn set_hedge_params(ctx: Context<SetHedgeParams>, rebalancer: Pubkey, params: HedgeParams) -> Result<()> {
+        let hedge_vault = &mut ctx.accounts.hedge_vault;
+        hedge_vault.rebalancer = rebalancer;
+        hedge_vault.params = params;
+        Ok(())
+    }
+
+    /// Opens or adjusts the vault's perp position to offset the delta of the owner's escrows.
+    ///
+    /// Meant to follow each write or settlement in the same transaction. All of the owner's
+    /// open escrows are the first `escrow_count` remaining accounts, in the order of their
+    /// position, and those of other markets or with non-vanilla payoffs are skipped; the rest
+    /// are the venue's accounts for the order, which the vault PDA signs. The net delta
+    /// (escrows plus the recorded perp position) must be outside `rebalance_band` of
+    /// `target_delta`, and the order's signed size `order_size` must bring it back within the
+    /// band. Venues with an adapter get a market order for `order_size` limited to
+    /// `limit_price`; `Raw` venues get `order_data`.
+    pub fn rebalance_hedge<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RebalanceHedge<'info>>,
+        escrow_count: u8,
+        order_size: i64,
+        limit_price: u64,
+        order_data: Vec<u8>,
+    ) -> Result<()> {
+        let hedge_vault = &ctx.accounts.hedge_vault;
+        let authority = ctx.accounts.authority.key();
+        if authority != hedge_vault.owner && authority != hedge_vault.rebalancer {
+            return Err(ErrorCode::NotHedgeRebalancer.into());
+        }
+        let escrow_count = escrow_count as usize;
+        if escrow_count > ctx.remaining_accounts.len() || escrow_count != ctx.accounts.user_position.written_escrows.len() {
+            return Err(ErrorCode::EscrowNotInPosition.into());
+        }
+        let (escrow_infos, venue_accounts) = ctx.remaining_accounts.split_at(escrow_count);
+
+        // Sum the delta of the owner's open escrows and the recorded perp position
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let spot_price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let mut net_delta = hedge_vault.hedge_position as i128;
+        for (account_info, escrow_key) in escrow_infos.iter().zip(ctx.accounts.user_position.written_escrows.iter()) {
+            if account_info.key != escrow_key {
+                return Err(ErrorCode::EscrowNotInPosition.into());
+            }
+            let escrow_account: Account<EscrowAccount> = Account::try_from(account_info)?;
+            if escrow_account.collateral_mint != ctx.accounts.market.collateral_mint
+                || escrow_account.is_exercised
+                || escrow_account.payoff != Payoff::Vanilla
+            {
+                continue;
+            }
+            net_delta += escrow_account.writer_delta(spot_price, &ctx.accounts.vol_config, current_time)? as i128;
+        }
+
+        // Only trade outside the band, and only towards the target
+        let params = &hedge_vault.params;
+        let deviation = net_delta - params.target_delta as i128;
+        let band = params.rebalance_band as i128;
+        if deviation.abs() <= band {
+            return Err(ErrorCode::HedgeWithinBand.into());
+        }
+        if (deviation + order_size as i128).abs() > band {
+            return Err(ErrorCode::InvalidHedgeOrder.into());
+        }
+
+        // Place the order with the vault PDA signing for its venue account
+        let vault_key = hedge_vault.key();
+        let account_metas = venue_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer || account.key() == vault_key,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let data = match ctx.accounts.hedge_venue.adapter {
+            HedgeAdapterKind::Raw => order_data,
+            kind => kind.adapter()?.order_data(&hedge::HedgeOrder {
+                market_index: hedge_vault.venue_market_index,
+                size: order_size,
+                decimals: ctx.accounts.market.collateral_decimals,
+                base_lot_size: hedge_vault.base_lot_size,
+                limit_price,
+            })?,
+        };
+        let order_ix = Instruction {
+            program_id: ctx.accounts.venue_program.key(),
+            accounts: account_metas,
+            data,
+        };
+        let mint = ctx.accounts.market.collateral_mint;
+        let owner = hedge_vault.owner;
+        let vault_seeds: &[&[u8]] = &[HEDGE_VAULT_SEED, mint.as_ref(), owner.as_ref(), &[hedge_vault.bump]];
+        invoke_signed(&order_ix, venue_accounts, &[vault_seeds])?;
+
+        let hedge_vault = &mut ctx.accounts.hedge_vault;
+        hedge_vault.hedge_position = hedge_vault.hedge_position.checked_add(order_size).ok_or(ErrorCode::MathOverflow)?;
+        hedge_vault.last_rebalance = current_time;
+        Ok(())
+    }
+
+    /// Creates a market's protocol-owned writer vault, which writes options with treasury
+    /// funds to bootstrap liquidity.
+    ///
+    /// The vault writes through a system-owned PDA (`POL_WRITER_SEED`) whose collateral token
+    /// account holds the vault's funds, so its escrows settle like any writer's and their
+    /// proceeds land back in the vault. `writer_lamports` funds the PDA's stats and position
+    /// accounts, which are created here. `operator` writes within `params`.
+    pub fn initialize_pol_vault(ctx: Context<InitializePolVault>, operator: Pubkey, params: PolVaultParams, writer_lamports: u64) -> Result<()> {
+        params.validate()?;
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        pol_vault.collateral_mint = ctx.accounts.market.collateral_mint;
+        pol_vault.writer = ctx.accounts.pol_writer.key();
+        pol_vault.token_account = ctx.accounts.pol_token_account.key();
+        pol_vault.operator = operator;
+        pol_vault.params = params;
+        pol_vault.principal = 0;
+        pol_vault.profits_swept = 0;
+        pol_vault.epoch = 0;
+        pol_vault.auto_compound = false;
+        pol_vault.epoch_premiums = 0;
+        pol_vault.bump = ctx.bumps.pol_vault;
+        pol_vault.writer_bump = ctx.bumps.pol_writer;
+
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.governance_authority.to_account_info(),
+            to: ctx.accounts.pol_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, writer_lamports)?;
+
+        let mint = ctx.accounts.market.collateral_mint;
+        let writer_seeds: &[&[u8]] = &[POL_WRITER_SEED, mint.as_ref(), &[ctx.bumps.pol_writer]];
+        create_writer_accounts(
+            ctx.accounts.pol_writer.to_account_info(),
+            writer_seeds,
+            ctx.accounts.writer_stats.to_account_info(),
+            ctx.accounts.writer_position.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.program.to_account_info(),
+        )
+    }
+
+    /// Sets whether a protocol-owned vault reinvests its auction premiums into its principal at
+    /// the start of each epoch, writing more with them, instead of leaving them to be swept to
+    /// the treasury.
+    pub fn set_pol_auto_compound(ctx: Context<UpdatePolVault>, auto_compound: bool) -> Result<()> {
+        ctx.accounts.pol_vault.auto_compound = auto_compound;
+        Ok(())
+    }
+
+    /// Changes the operator and the quoting parameters of a protocol-owned vault.
+    pub fn set_pol_vault_params(ctx: Context<UpdatePolVault>, operator: Pubkey, params: PolVaultParams) -> Result<()> {
+        params.validate()?;
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        pol_vault.operator = operator;
+        pol_vault.params = params;
+        Ok(())
+    }
+
+    /// Moves treasury funds into a protocol-owned vault.
+    ///
+    /// Subject to the treasury timelock like `withdraw_treasury`: the same amount, with the
+    /// vault's token account as destination, must have been queued and unlocked.
+    pub fn fund_pol_vault(ctx: Context<PolVaultTreasury>, amount: u64) -> Result<()> {
+        let destination = ctx.accounts.pol_token_account.key();
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.consume_withdrawal(amount, destination)?;
+        let mint = treasury.mint;
+        let seeds: &[&[u8]] = &[TREASURY_SEED, mint.as_ref(), &[treasury.bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.treasury_token_account.to_account_info(),
+            to: ctx.accounts.pol_token_account.to_account_info(),
+            authority: treasury.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        pol_vault.principal = pol_vault.principal.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Returns idle principal from a protocol-owned vault to the treasury.
+    pub fn defund_pol_vault(ctx: Context<PolVaultTreasury>, amount: u64) -> Result<()> {
+        let pol_vault = &ctx.accounts.pol_vault;
+        if amount > pol_vault.principal {
+            return Err(ErrorCode::InvalidPolVaultAmount.into());
+        }
+        let mint = pol_vault.collateral_mint;
+        let seeds: &[&[u8]] = &[POL_WRITER_SEED, mint.as_ref(), &[pol_vault.writer_bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.pol_token_account.to_account_info(),
+            to: ctx.accounts.treasury_token_account.to_account_info(),
+            authority: ctx.accounts.pol_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)?;
+
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        pol_vault.principal -= amount;
+        Ok(())
+    }
+
+    /// Sends a protocol-owned vault's profits to the treasury.
+    ///
+    /// Anyone can call this. The profit is the vault's idle funds plus the collateral locked in
+    /// its open escrows, less its principal; only the idle part can be swept. Premiums waiting
+    /// to be compounded aren't profit.
+    pub fn sweep_pol_profits(ctx: Context<SweepPolProfits>) -> Result<()> {
+        let pol_vault = &ctx.accounts.pol_vault;
+        let idle = ctx.accounts.pol_token_account.amount;
+        let open_notional = ctx.accounts.writer_stats.open_notional_in(&pol_vault.collateral_mint);
+        let compounding = if pol_vault.auto_compound { pol_vault.epoch_premiums } else { 0 };
+        let profit = idle
+            .saturating_add(open_notional)
+            .saturating_sub(pol_vault.principal)
+            .saturating_sub(compounding)
+            .min(idle);
+        if profit == 0 {
+            return Err(ErrorCode::InvalidPolVaultAmount.into());
+        }
+        let mint = pol_vault.collateral_mint;
+        let seeds: &[&[u8]] = &[POL_WRITER_SEED, mint.as_ref(), &[pol_vault.writer_bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.pol_token_account.to_account_info(),
+            to: ctx.accounts.treasury_token_account.to_account_info(),
+            authority: ctx.accounts.pol_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, profit)?;
+
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        pol_vault.profits_swept = pol_vault.profits_swept.saturating_add(profit);
+        Ok(())
+    }
+
+    /// Writes and funds an escrow from a protocol-owned vault.
+    ///
+    /// Only the vault's operator can write, and only series within the vault's parameters and
+    /// exposure cap. The escrow is created with `initialize_escrow` and funded with
+    /// `deposit_collateral`, both called through this program with the vault's writer PDA
+    /// signing: the first `init_account_count` remaining accounts are `initialize_escrow`'s
+    /// and the rest `deposit_collateral`'s for the new escrow. The operator pays the escrow's rent.
+    pub fn pol_write<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PolWrite<'info>>,
+        option_type: OptionType,
+        strike_price: u64,
+        expiration: i64,
+        collateral_amount: u64,
+        terms: OptionTerms,
+        init_account_count: u8,
+    ) -> Result<()> {
+        let pol_vault = &ctx.accounts.pol_vault;
+        let price = load_oracle_price(&ctx.accounts.collateral_oracle.to_account_info(), &ctx.accounts.market.oracle_config)?;
+        let spot_price = ctx.accounts.market.lst_price(to_strike_price(&price)?)?;
+        let open_notional = ctx.accounts.writer_stats.open_notional_in(&pol_vault.collateral_mint);
+        pol_vault.params.check_series(&option_type, strike_price, spot_price, expiration, Clock::get()?.unix_timestamp)?;
+        if open_notional.checked_add(collateral_amount).ok_or(ErrorCode::MathOverflow)? > pol_vault.params.max_exposure {
+            return Err(ErrorCode::PolExposureExceeded.into());
+        }
+
+        let collateral_mint = pol_vault.collateral_mint;
+        let escrow = instruction::InitializeEscrow { option_type, strike_price, expiration, collateral_amount, collateral_mint, terms };
+        ctx.accounts.write_escrow(ctx.remaining_accounts, init_account_count, escrow)
+    }
+
+    /// Creates a market's backstop liquidity pool.
+    ///
+    /// Designated LPs commit capital to the pool, which takes over the escrows of writers who
+    /// fail to fund them by the market's funding deadline (`backstop_default`) instead of letting
+    /// them be reaped. The defaulting writer's deposit is bought at `discount_bps` below its
+    /// value, and the discount is paid to the LPs as rewards. The taken-over escrows are held
+    /// by a system-owned PDA (`BACKSTOP_WRITER_SEED`) whose collateral token account holds the
+    /// pool's capital, so their proceeds land back in the pool. `writer_lamports` funds the
+    /// PDA's stats and position accounts, which are created here. Queued withdrawals are
+    /// processed every `epoch_duration` seconds.
+    pub fn initialize_backstop_pool(
+        ctx: Context<InitializeBackstopPool>,
+        discount_bps: u64,
+        epoch_duration: i64,
+        writer_lamports: u64,
+    ) -> Result<()> {
+        if discount_bps > 10000 {
+            return Err(ErrorCode::InvalidBackstopDiscount.into());
+        }
+        if epoch_duration <= 0 {
+            return Err(ErrorCode::InvalidTimelock.into());
+        }
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        backstop_pool.collateral_mint = ctx.accounts.market.collateral_mint;
+        backstop_pool.writer = ctx.accounts.backstop_writer.key();
+        backstop_pool.token_account = ctx.accounts.backstop_token_account.key();
+        backstop_pool.discount_bps = discount_bps;
+        backstop_pool.total_shares = 0;
+        backstop_pool.reward_per_share = 0;
+        backstop_pool.unclaimed_rewards = 0;
+        backstop_pool.epoch_duration = epoch_duration;
+        backstop_pool.epoch_end = Clock::get()?.unix_timestamp.checked_add(epoch_duration).ok_or(ErrorCode::MathOverflow)?;
+        backstop_pool.queued_shares = 0;
+        backstop_pool.pending_withdrawals = 0;
+        backstop_pool.withdrawal_queue = Vec::new();
+        backstop_pool.bump = ctx.bumps.backstop_pool;
+        backstop_pool.writer_bump = ctx.bumps.backstop_writer;
+        ctx.accounts.market.backstopped = true;
+
+        let cpi_accounts = system_program::Transfer {
+            from: ctx.accounts.governance_authority.to_account_info(),
+            to: ctx.accounts.backstop_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+        system_program::transfer(cpi_ctx, writer_lamports)?;
+
+        let mint = ctx.accounts.market.collateral_mint;
+        let writer_seeds: &[&[u8]] = &[BACKSTOP_WRITER_SEED, mint.as_ref(), &[ctx.bumps.backstop_writer]];
+        create_writer_accounts(
+            ctx.accounts.backstop_writer.to_account_info(),
+            writer_seeds,
+            ctx.accounts.writer_stats.to_account_info(),
+            ctx.accounts.writer_position.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.program.to_account_info(),
+        )
+    }
+
+    /// Sets the discount at which a backstop pool buys defaulting writers' deposits.
+    pub fn set_backstop_discount(ctx: Context<UpdateBackstopPool>, discount_bps: u64) -> Result<()> {
+        if discount_bps > 10000 {
+            return Err(ErrorCode::InvalidBackstopDiscount.into());
+        }
+        ctx.accounts.backstop_pool.discount_bps = discount_bps;
+        Ok(())
+    }
+
+    /// Sets the length of a backstop pool's withdrawal epochs, from the next epoch on.
+    pub fn set_backstop_epoch(ctx: Context<UpdateBackstopPool>, epoch_duration: i64) -> Result<()> {
+        if epoch_duration <= 0 {
+            return Err(ErrorCode::InvalidTimelock.into());
+        }
+        ctx.accounts.backstop_pool.epoch_duration = epoch_duration;
+        Ok(())
+    }
+
+    /// Designates a backstop liquidity provider, creating its position in the pool.
+    pub fn add_backstop_lp(ctx: Context<AddBackstopLp>, owner: Pubkey) -> Result<()> {
+        let backstop_lp = &mut ctx.accounts.backstop_lp;
+        backstop_lp.pool = ctx.accounts.backstop_pool.key();
+        backstop_lp.owner = owner;
+        backstop_lp.shares = 0;
+        backstop_lp.reward_debt = 0;
+        backstop_lp.unclaimed_rewards = 0;
+        backstop_lp.bump = ctx.bumps.backstop_lp;
+        Ok(())
+    }
+
+    /// Commits capital to a backstop pool.
+    ///
+    /// The LP receives pool shares at the pool's current value: its free capital plus the
+    /// collateral of the escrows it holds.
+    pub fn deposit_backstop(ctx: Context<BackstopLpTransfer>, amount: u64) -> Result<()> {
+        let value = ctx.accounts.pool_value()?;
+        let backstop_pool = &ctx.accounts.backstop_pool;
+        let shares = if backstop_pool.total_shares == 0 {
+            amount
+        } else if value == 0 {
+            return Err(ErrorCode::InvalidBackstopAmount.into());
+        } else {
+            u64::try_from(amount as u128 * backstop_pool.total_shares as u128 / value as u128).map_err(|_| ErrorCode::MathOverflow)?
+        };
+        if shares == 0 {
+            return Err(ErrorCode::InvalidBackstopAmount.into());
+        }
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.owner_collateral_account.to_account_info(),
+            to: ctx.accounts.backstop_token_account.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, amount)?;
+
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        let backstop_lp = &mut ctx.accounts.backstop_lp;
+        backstop_lp.settle_rewards(backstop_pool)?;
+        backstop_lp.shares = backstop_lp.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        backstop_pool.total_shares = backstop_pool.total_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        backstop_lp.reset_reward_debt(backstop_pool)
+    }
+
+    /// Redeems backstop pool shares for their value, out of the pool's free capital.
+    ///
+    /// Only possible while no withdrawals are queued; otherwise the LP must queue with
+    /// `request_backstop_withdrawal`, so that earlier requests are served first.
+    pub fn withdraw_backstop(ctx: Context<BackstopLpTransfer>, shares: u64) -> Result<()> {
+        if shares == 0 || shares > ctx.accounts.backstop_lp.shares {
+            return Err(ErrorCode::InvalidBackstopAmount.into());
+        }
+        if !ctx.accounts.backstop_pool.withdrawal_queue.is_empty() {
+            return Err(ErrorCode::WithdrawalsQueued.into());
+        }
+        let value = ctx.accounts.pool_value()?;
+        let backstop_pool = &ctx.accounts.backstop_pool;
+        let amount = u64::try_from(shares as u128 * value as u128 / backstop_pool.total_shares as u128).map_err(|_| ErrorCode::MathOverflow)?;
+        if amount > backstop_pool.available(ctx.accounts.backstop_token_account.amount) {
+            return Err(ErrorCode::InsufficientBackstopLiquidity.into());
+        }
+
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        let backstop_lp = &mut ctx.accounts.backstop_lp;
+        backstop_lp.settle_rewards(backstop_pool)?;
+        backstop_lp.shares -= shares;
+        backstop_pool.total_shares -= shares;
+        backstop_lp.reset_reward_debt(backstop_pool)?;
+        ctx.accounts.transfer_out(amount)
+    }
+
+    /// Pays a backstop LP the discount rewards it has earned.
+    pub fn claim_backstop_rewards(ctx: Context<BackstopLpTransfer>) -> Result<()> {
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        let backstop_lp = &mut ctx.accounts.backstop_lp;
+        backstop_lp.settle_rewards(backstop_pool)?;
+        let amount = backstop_lp.unclaimed_rewards;
+        if amount == 0 {
+            return Err(ErrorCode::NoFeesToClaim.into());
+        }
+        backstop_lp.unclaimed_rewards = 0;
+        backstop_pool.unclaimed_rewards = backstop_pool.unclaimed_rewards.saturating_sub(amount);
+        ctx.accounts.transfer_out(amount)
+    }
+
+    /// Takes over an escrow whose writer didn't fund it by the market's funding deadline.
+    ///
+    /// Anyone can call this. The backstop pool deposits the missing collateral into the escrow's
+    /// vault, where it stays in the program's custody, and pays the defaulting writer for its
+    /// partial deposit less the pool's discount, which is credited to the LPs as rewards. The escrow then belongs to the pool's writer PDA and settles normally.
+    pub fn backstop_default(ctx: Context<BackstopDefault>) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        let funding_deadline = ctx.accounts.market.funding_deadline;
+        if escrow_account.is_exercised || escrow_account.deposited_amount >= escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowFunded.into());
+        }
+        let deadline = escrow_account.created_at.checked_add(funding_deadline).ok_or(ErrorCode::MathOverflow)?;
+        if funding_deadline == 0 || Clock::get()?.unix_timestamp < deadline {
+            return Err(ErrorCode::FundingDeadlineNotPassed.into());
+        }
+        if escrow_account.renter != Pubkey::default() {
+            return Err(ErrorCode::EscrowRented.into());
+        }
+        escrow_account.require_not_lent()?;
+        escrow_account.require_not_on_loan()?;
+        let (shortfall, compensation, discount) = ctx.accounts.backstop_pool.takeover_cost(escrow_account)?;
+        if shortfall + compensation > ctx.accounts.backstop_pool.available(ctx.accounts.backstop_token_account.amount) {
+            return Err(ErrorCode::InsufficientBackstopLiquidity.into());
+        }
+
+        // Fund the escrow and buy out the defaulting writer with the pool's capital
+        let mint = ctx.accounts.backstop_pool.collateral_mint;
+        let seeds: &[&[u8]] = &[BACKSTOP_WRITER_SEED, mint.as_ref(), &[ctx.accounts.backstop_pool.writer_bump]];
+        let signer_seeds = &[seeds];
+        for (destination, amount) in [
+            (ctx.accounts.escrow_collateral_account.to_account_info(), shortfall),
+            (ctx.accounts.initializer_collateral_account.to_account_info(), compensation),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.backstop_token_account.to_account_info(),
+                to: destination,
+                authority: ctx.accounts.backstop_writer.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+            token::transfer(cpi_ctx, amount)?;
+        }
+        ctx.accounts.backstop_pool.accrue_rewards(discount)?;
+
+        // Move the escrow from the defaulting writer to the pool's writer
+        let escrow_key = ctx.accounts.escrow_account.key();
+        let collateral_amount = ctx.accounts.escrow_account.collateral_amount;
+        let notional = ctx.accounts.escrow_account.emissions_notional;
+        if notional > 0 {
+            let emissions_schedule = ctx.accounts.emissions_schedule.as_mut().ok_or(ErrorCode::MissingEmissionsAccount)?;
+            let writer_emissions = ctx.accounts.writer_emissions.as_mut().ok_or(ErrorCode::MissingEmissionsAccount)?;
+            emissions_schedule.remove_notional(writer_emissions, notional, Clock::get()?.unix_timestamp)?;
+        }
+        ctx.accounts.writer_position.remove_written(&escrow_key);
+        ctx.accounts.writer_stats.remove_open_notional(&mint, collateral_amount);
+        ctx.accounts.backstop_position.add_written(escrow_key)?;
+        ctx.accounts.backstop_stats.add_open_notional(&ctx.accounts.market, collateral_amount)?;
+        let market_stats = &mut ctx.accounts.market_stats;
+        market_stats.collateral_locked = market_stats.collateral_locked.saturating_add(shortfall);
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.initializer_key = ctx.accounts.backstop_pool.writer;
+        escrow_account.deposited_amount = collateral_amount;
+        escrow_account.vault_bump = ctx.bumps.escrow_collateral_account;
+        escrow_account.emissions_notional = 0;
+        Ok(())
+    }
+
+    /// Opens the issuance auction of a protocol-owned vault's current epoch.
+    ///
+    /// Instead of selling at a premium set by the operator, the escrows the vault wrote this
+    /// epoch (passed as remaining accounts) are sold in a sealed-bid, uniform-price auction:
+    /// bids are committed with `commit_bid` until `commit_duration` has passed, revealed with
+    /// `reveal_bid` during the following `reveal_duration`, and then filled or refunded with
+    /// `settle_auction`, every winner paying the lowest winning price. The escrows are held by
+    /// the vault until sold, so unsold escrows pay out to it.
+    ///
+    /// If the market has premium bounds, each escrow's fair value is recorded when the auction
+    /// opens and bids must lie within the bounds of every one of them.
+    ///
+    /// Starting an epoch compounds the previous epoch's premiums into the vault's principal if
+    /// the vault auto-compounds.
+    pub fn start_issuance_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StartIssuanceAuction<'info>>,
+        reserve_price: u64,
+        commit_duration: i64,
+        reveal_duration: i64,
+    ) -> Result<()> {
+        if ctx.remaining_accounts.is_empty() || ctx.remaining_accounts.len() > MAX_AUCTION_LOTS {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        if reserve_price == 0 || commit_duration <= 0 || reveal_duration <= 0 {
+            return Err(ErrorCode::InvalidAuctionParams.into());
+        }
+        let current_time = Clock::get()?.unix_timestamp;
+        let market = &ctx.accounts.market;
+        let spot_price = if market.max_premium_multiple_bps > 0 {
+            let collateral_oracle = ctx.accounts.collateral_oracle.as_ref().ok_or(ErrorCode::MissingOracleAccount)?;
+            let price = load_oracle_price(&collateral_oracle.to_account_info(), &market.oracle_config)?;
+            Some(market.lst_price(to_strike_price(&price)?)?)
+        } else {
+            None
+        };
+        let pol_vault = &ctx.accounts.pol_vault;
+        let mut escrows = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut fair_values = Vec::new();
+        for account_info in ctx.remaining_accounts.iter() {
+            let mut escrow_account: Account<EscrowAccount> = Account::try_from(account_info)?;
+            if escrow_account.initializer_key != pol_vault.writer
+                || escrow_account.collateral_mint != pol_vault.collateral_mint
+                || escrow_account.is_exercised
+                || escrow_account.deposited_amount < escrow_account.collateral_amount
+                || escrow_account.holder != Pubkey::default()
+                || escrow_account.rental_rate > 0
+                || escrow_account.perpetual.is_some()
+                || escrow_account.vesting.is_some()
+                || escrows.contains(account_info.key)
+            {
+                return Err(ErrorCode::EscrowNotAuctionable.into());
+            }
+            if let Some(spot_price) = spot_price {
+                let vol_config = ctx.accounts.vol_config.as_ref().ok_or(ErrorCode::MissingVolConfig)?;
+                fair_values.push(escrow_account.fair_value(spot_price, vol_config, current_time)?);
+            }
+            escrow_account.holder = pol_vault.writer;
+            escrow_account.exit(&crate::ID)?;
+            escrows.push(account_info.key());
+        }
+
+        let auction = &mut ctx.accounts.auction;
+        auction.pol_vault = pol_vault.key();
+        auction.epoch = pol_vault.epoch;
+        auction.escrows = escrows;
+        auction.fair_values = fair_values;
+        auction.reserve_price = reserve_price;
+        auction.commit_end = current_time.checked_add(commit_duration).ok_or(ErrorCode::MathOverflow)?;
+        auction.reveal_end = auction.commit_end.checked_add(reveal_duration).ok_or(ErrorCode::MathOverflow)?;
+        auction.winners = Vec::new();
+        auction.lots_sold = 0;
+        auction.bump = ctx.bumps.auction;
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        let amount = std::mem::take(&mut pol_vault.epoch_premiums);
+        if pol_vault.auto_compound && amount > 0 {
+            pol_vault.principal = pol_vault.principal.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            emit!(PremiumsCompounded {
+                pol_vault: pol_vault.key(),
+                epoch: pol_vault.epoch,
+                amount,
+                principal: pol_vault.principal,
+            });
+        }
+        pol_vault.epoch += 1;
+        Ok(())
+    }
+
+    /// Commits a sealed bid to an issuance auction.
+    ///
+    /// `commitment` is the SHA-256 hash of the price per escrow and quantity (both
+    /// little-endian), a 32-byte salt, and the bidder's key. `deposit` must cover the bid when
+    /// revealed; whatever isn't spent is refunded on settlement.
+    pub fn commit_bid(ctx: Context<CommitBid>, commitment: [u8; 32], deposit: u64) -> Result<()> {
+        if Clock::get()?.unix_timestamp >= ctx.accounts.auction.commit_end {
+            return Err(ErrorCode::AuctionPhaseClosed.into());
+        }
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.bidder_collateral_account.to_account_info(),
+            to: ctx.accounts.auction_token_account.to_account_info(),
+            authority: ctx.accounts.bidder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, deposit)?;
+
+        let bid = &mut ctx.accounts.bid;
+        bid.auction = ctx.accounts.auction.key();
+        bid.bidder = ctx.accounts.bidder.key();
+        bid.commitment = commitment;
+        bid.deposit = deposit;
+        bid.revealed = false;
+        bid.bump = ctx.bumps.bid;
+        Ok(())
+    }
+
+    /// Reveals a sealed bid once bidding has closed.
+    ///
+    /// The bid must match its commitment, be at or above the reserve price, lie within the
+    /// market's premium bounds, and be covered by the deposit. It joins the winning bids if it
+    /// ranks among the best for the lots on sale.
+    pub fn reveal_bid(ctx: Context<RevealBid>, price: u64, quantity: u8, salt: [u8; 32]) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let auction = &mut ctx.accounts.auction;
+        if current_time < auction.commit_end || current_time >= auction.reveal_end {
+            return Err(ErrorCode::AuctionPhaseClosed.into());
+        }
+        let bid = &mut ctx.accounts.bid;
+        let hash = hashv(&[&price.to_le_bytes(), &[quantity], &salt, bid.bidder.as_ref()]);
+        if bid.revealed || hash.to_bytes() != bid.commitment {
+            return Err(ErrorCode::InvalidBidReveal.into());
+        }
+        let cost = (price as u128).checked_mul(quantity as u128).ok_or(ErrorCode::MathOverflow)?;
+        if quantity == 0 || price < auction.reserve_price || cost > bid.deposit as u128 {
+            return Err(ErrorCode::InvalidBidReveal.into());
+        }
+        auction.check_premium_bounds(&ctx.accounts.market, price)?;
+        bid.revealed = true;
+        auction.add_bid(bid.bidder, price, quantity);
+        Ok(())
+    }
+
+    /// Fills or refunds a bid once its issuance auction has ended.
+    ///
+    /// Anyone can call this. A winning bid is assigned the next unsold escrows, passed as
+    /// remaining accounts in auction order, at the clearing price, which goes to the vault;
+    /// the rest of the deposit is refunded and the bid closed. If governance has since tightened
+    /// the market's premium bounds past the clearing price, nothing is filled and the whole
+    /// deposit is refunded.
+    pub fn settle_auction<'info>(ctx: Context<'_, '_, 'info, 'info, SettleAuction<'info>>) -> Result<()> {
+        let auction = &ctx.accounts.auction;
+        if Clock::get()?.unix_timestamp < auction.reveal_end {
+            return Err(ErrorCode::AuctionNotEnded.into());
+        }
+        let bidder = ctx.accounts.bid.bidder;
+        let quantity = if auction.check_premium_bounds(&ctx.accounts.market, auction.clearing_price()).is_ok() {
+            auction.filled_quantity(&bidder) as usize
+        } else {
+            0
+        };
+        let start = auction.lots_sold as usize;
+        if ctx.remaining_accounts.len() != quantity {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        for (account_info, escrow_key) in ctx.remaining_accounts.iter().zip(&auction.escrows[start..start + quantity]) {
+            if account_info.key != escrow_key {
+                return Err(ErrorCode::InvalidBatch.into());
+            }
+            let mut escrow_account: Account<EscrowAccount> = Account::try_from(account_info)?;
+            escrow_account.holder = bidder;
+            escrow_account.exit(&crate::ID)?;
+        }
+
+        let cost = auction.clearing_price() * quantity as u64;
+        let refund = ctx.accounts.bid.deposit - cost;
+        let pol_vault = auction.pol_vault;
+        let epoch = auction.epoch.to_le_bytes();
+        let seeds: &[&[u8]] = &[ISSUANCE_AUCTION_SEED, pol_vault.as_ref(), &epoch, &[auction.bump]];
+        let signer_seeds = &[seeds];
+        for (destination, amount) in [
+            (ctx.accounts.pol_token_account.to_account_info(), cost),
+            (ctx.accounts.bidder_collateral_account.to_account_info(), refund),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.auction_token_account.to_account_info(),
+                to: destination,
+                authority: ctx.accounts.auction.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+            token::transfer(cpi_ctx, amount)?;
+        }
+        ctx.accounts.auction.lots_sold += quantity as u8;
+        if let Some(bidder_stats) = ctx.accounts.bidder_stats.as_mut() {
+            bidder_stats.record_premium_paid(&ctx.accounts.market, cost)?;
+        }
+        let pol_vault = &mut ctx.accounts.pol_vault;
+        pol_vault.epoch_premiums = pol_vault.epoch_premiums.saturating_add(cost);
+        Ok(())
+    }
+
+    /// Queues backstop pool shares for withdrawal at the end of the pool's epoch.
+    ///
+    /// Queued shares stop earning discount rewards but keep their share of the pool's gains
+    /// and losses until processed. Adding to an existing request increases it.
+    pub fn request_backstop_withdrawal(ctx: Context<BackstopWithdrawal>, shares: u64) -> Result<()> {
+        if shares == 0 || shares > ctx.accounts.backstop_lp.shares {
+            return Err(ErrorCode::InvalidBackstopAmount.into());
+        }
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        let backstop_lp = &mut ctx.accounts.backstop_lp;
+        let owner = ctx.accounts.owner.key();
+        backstop_lp.settle_rewards(backstop_pool)?;
+        backstop_lp.shares -= shares;
+        backstop_lp.reset_reward_debt(backstop_pool)?;
+        backstop_pool.queued_shares = backstop_pool.queued_shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        if !backstop_pool.withdrawal_queue.contains(&owner) {
+            if backstop_pool.withdrawal_queue.len() >= MAX_QUEUED_WITHDRAWALS {
+                return Err(ErrorCode::WithdrawalQueueFull.into());
+            }
+            backstop_pool.withdrawal_queue.push(owner);
+        }
+
+        let request = &mut ctx.accounts.request;
+        request.pool = backstop_pool.key();
+        request.owner = owner;
+        request.shares = request.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        request.requested_at = Clock::get()?.unix_timestamp;
+        request.bump = ctx.bumps.request;
+        Ok(())
+    }
+
+    /// Cancels the unprocessed part of a queued withdrawal, returning the shares to the LP.
+    ///
+    /// Amounts already processed stay claimable.
+    pub fn cancel_backstop_withdrawal(ctx: Context<BackstopWithdrawal>) -> Result<()> {
+        let shares = ctx.accounts.request.shares;
+        if shares == 0 {
+            return Err(ErrorCode::WithdrawalNotQueued.into());
+        }
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        let backstop_lp = &mut ctx.accounts.backstop_lp;
+        backstop_lp.settle_rewards(backstop_pool)?;
+        backstop_lp.shares = backstop_lp.shares.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        backstop_lp.reset_reward_debt(backstop_pool)?;
+        backstop_pool.queued_shares -= shares;
+        backstop_pool.withdrawal_queue.retain(|owner| *owner != ctx.accounts.request.owner);
+        ctx.accounts.request.shares = 0;
+        Ok(())
+    }
+
+    /// Pays out the processed part of a queued withdrawal.
+    pub fn claim_backstop_withdrawal(ctx: Context<BackstopWithdrawal>) -> Result<()> {
+        let amount = ctx.accounts.request.withdrawable;
+        if amount == 0 {
+            return Err(ErrorCode::InvalidBackstopAmount.into());
+        }
+        ctx.accounts.request.withdrawable = 0;
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        backstop_pool.pending_withdrawals -= amount;
+
+        let mint = backstop_pool.collateral_mint;
+        let seeds: &[&[u8]] = &[BACKSTOP_WRITER_SEED, mint.as_ref(), &[backstop_pool.writer_bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.backstop_token_account.to_account_info(),
+            to: ctx.accounts.owner_collateral_account.to_account_info(),
+            authority: ctx.accounts.backstop_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)
+    }
+
+    /// Processes a backstop pool's queued withdrawals at the end of its epoch.
+    ///
+    /// Anyone can call this once the epoch has ended, passing every queued request as remaining
+    /// accounts in queue order. Requests are filled at the pool's current value per share, out
+    /// of its free capital; if that doesn't cover them all, each is filled pro rata and the
+    /// remainder carries over to the next epoch. Filled amounts are set aside for claiming.
+    pub fn process_backstop_withdrawals<'info>(ctx: Context<'_, '_, 'info, 'info, ProcessBackstopWithdrawals<'info>>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let backstop_pool = &ctx.accounts.backstop_pool;
+        if current_time < backstop_pool.epoch_end {
+            return Err(ErrorCode::EpochNotEnded.into());
+        }
+        if ctx.remaining_accounts.len() != backstop_pool.withdrawal_queue.len() {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        let balance = ctx.accounts.backstop_token_account.amount;
+        let free = backstop_pool.available(balance) as u128;
+        let value = backstop_pool.value(balance, ctx.accounts.backstop_stats.open_notional_in(&backstop_pool.collateral_mint))? as u128;
+        let total_shares = backstop_pool.total_shares as u128;
+        let queued_value = (backstop_pool.queued_shares as u128 * value).checked_div(total_shares).unwrap_or(0);
+
+        let pool_key = backstop_pool.key();
+        let mut filled_shares: u64 = 0;
+        let mut filled_value: u64 = 0;
+        let mut drained = Vec::new();
+        for (account_info, owner) in ctx.remaining_accounts.iter().zip(&backstop_pool.withdrawal_queue) {
+            let mut request: Account<WithdrawalRequest> = Account::try_from(account_info)?;
+            if request.pool != pool_key || request.owner != *owner {
+                return Err(ErrorCode::InvalidBatch.into());
+            }
+            let shares = if queued_value <= free {
+                request.shares
+            } else {
+                (request.shares as u128 * free / queued_value) as u64
+            };
+            let amount = (shares as u128 * value / total_shares) as u64;
+            request.shares -= shares;
+            request.withdrawable = request.withdrawable.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            if request.shares == 0 {
+                drained.push(*owner);
+            }
+            request.exit(&crate::ID)?;
+            filled_shares += shares;
+            filled_value += amount;
+        }
+
+        let backstop_pool = &mut ctx.accounts.backstop_pool;
+        backstop_pool.total_shares -= filled_shares;
+        backstop_pool.queued_shares -= filled_shares;
+        backstop_pool.pending_withdrawals = backstop_pool.pending_withdrawals.checked_add(filled_value).ok_or(ErrorCode::MathOverflow)?;
+        backstop_pool.withdrawal_queue.retain(|owner| !drained.contains(owner));
+        backstop_pool.epoch_end = current_time.checked_add(backstop_pool.epoch_duration).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Creates the strategy configuration of a protocol-owned vault.
+    ///
+    /// Once created, `process_epoch` writes the vault's escrow for each expiry of the strategy,
+    /// with the strike, expiry, and size derived from the configuration rather than chosen by
+    /// the operator. Only the governance authority can call this.
+    pub fn initialize_strategy_config(ctx: Context<InitializeStrategyConfig>, params: StrategyParams) -> Result<()> {
+        params.validate()?;
+        let strategy_config = &mut ctx.accounts.strategy_config;
+        strategy_config.pol_vault = ctx.accounts.pol_vault.key();
+        strategy_config.params = params;
+        strategy_config.last_expiration = 0;
+        strategy_config.voting = StrategyVoting {
+            share_mint: Pubkey::default(),
+            quorum_votes: 0,
+            voting_period: 0,
+            notice_period: 0,
+        };
+        strategy_config.proposal_count = 0;
+        strategy_config.bump = ctx.bumps.strategy_config;
+        Ok(())
+    }
+
+    /// Replaces a vault's strategy parameters, from its next epoch on.
+    pub fn set_strategy_params(ctx: Context<UpdateStrategyConfig>, params: StrategyParams) -> Result<()> {
+        params.validate()?;
+        ctx.accounts.strategy_config.params = params;
+        Ok(())
+    }
+
+    /// Writes a protocol-owned vault's escrow for the next expiry of its strategy.
+    ///
+    /// The expiry is the strategy's next cadence boundary, which must not have been written
+    /// yet. The strike is either a fixed distance out of the money or the strike with the target
+    /// delta under the market's volatility surface, and the size is the strategy's share of the
+    /// vault's value, capped by its free funds and exposure limit. The vault's quoting
+    /// parameters still apply. Accounts are passed as for `pol_write`.
+    pub fn process_epoch<'info>(ctx: Context<'_, '_, 'info, 'info, ProcessEpoch<'info>>, init_account_count: u8) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let write = &ctx.accounts.write;
+        let pol_vault = &write.pol_vault;
+        let params = &ctx.accounts.strategy_config.params;
+        let expiration = params.next_expiration(current_time)?;
+        if expiration <= ctx.accounts.strategy_config.last_expiration {
+            return Err(ErrorCode::EpochAlreadyProcessed.into());
+        }
+
+        let price = load_oracle_price(&write.collateral_oracle.to_account_info(), &write.market.oracle_config)?;
+        let spot_price = write.market.lst_price(to_strike_price(&price)?)?;
+        let strike_price = params.select_strike(spot_price, expiration - current_time, ctx.accounts.vol_config.as_deref(), current_time)?;
+        pol_vault.params.check_series(&params.option_type, strike_price, spot_price, expiration, current_time)?;
+
+        let idle = ctx.accounts.pol_token_account.amount;
+        let open_notional = write.writer_stats.open_notional_in(&pol_vault.collateral_mint);
+        let value = idle.saturating_add(open_notional);
+        let collateral_amount = (value as u128 * params.max_tvl_bps as u128 / 10000) as u64;
+        let collateral_amount = collateral_amount.min(idle).min(pol_vault.params.max_exposure.saturating_sub(open_notional));
+        if collateral_amount == 0 {
+            return Err(ErrorCode::PolExposureExceeded.into());
+        }
+
+        let escrow = instruction::InitializeEscrow {
+            option_type: params.option_type.clone(),
+            strike_price,
+            expiration,
+            collateral_amount,
+            collateral_mint: pol_vault.collateral_mint,
+            terms: OptionTerms::vanilla(),
+        };
+        write.write_escrow(ctx.remaining_accounts, init_account_count, escrow)?;
+        ctx.accounts.strategy_config.last_expiration = expiration;
+        Ok(())
+    }
+
+    /// Lets the holders of a vault's shares vote on changes to its strategy.
+    ///
+    /// Votes are weighted by the shares of `share_mint` each voter locks until voting ends. A
+    /// passed proposal takes effect `notice_period` (at least `MIN_STRATEGY_NOTICE_PERIOD`)
+    /// after voting ends, giving share holders time to exit. A zero voting period disables
+    /// share holder votes; governance can still set the strategy directly.
+    pub fn set_strategy_voting(ctx: Context<UpdateStrategyConfig>, voting: StrategyVoting) -> Result<()> {
+        if voting.voting_period < 0
+            || (voting.voting_period > 0 && (voting.share_mint == Pubkey::default() || voting.notice_period < MIN_STRATEGY_NOTICE_PERIOD))
+        {
+            return Err(ErrorCode::InvalidStrategyParams.into());
+        }
+        ctx.accounts.strategy_config.voting = voting;
+        Ok(())
+    }
+
+    /// Proposes a new strategy for a vault. The proposer must hold some of the vault's shares.
+    pub fn propose_strategy(ctx: Context<ProposeStrategy>, params: StrategyParams) -> Result<()> {
+        let voting = &ctx.accounts.strategy_config.voting;
+        if voting.voting_period == 0 {
+            return Err(ErrorCode::ProposalsDisabled.into());
+        }
+        if ctx.accounts.proposer_share_account.amount == 0 {
+            return Err(ErrorCode::InsufficientVotingPower.into());
+        }
+        params.validate()?;
+
+        let voting_ends_at = Clock::get()?.unix_timestamp.checked_add(voting.voting_period).ok_or(ErrorCode::MathOverflow)?;
+        let strategy_config = &mut ctx.accounts.strategy_config;
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.strategy_config = strategy_config.key();
+        proposal.id = strategy_config.proposal_count;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.params = params;
+        proposal.votes_for = 0;
+        proposal.votes_against = 0;
+        proposal.voting_ends_at = voting_ends_at;
+        proposal.effective_at = voting_ends_at.checked_add(strategy_config.voting.notice_period).ok_or(ErrorCode::MathOverflow)?;
+        proposal.executed = false;
+        proposal.bump = ctx.bumps.proposal;
+        strategy_config.proposal_count += 1;
+        Ok(())
+    }
+
+    /// Votes on a strategy proposal with `shares` of the vault, locked until voting ends.
+    pub fn vote_strategy(ctx: Context<VoteStrategy>, support: bool, shares: u64) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        if Clock::get()?.unix_timestamp >= proposal.voting_ends_at {
+            return Err(ErrorCode::VotingClosed.into());
+        }
+        if shares == 0 {
+            return Err(ErrorCode::InsufficientVotingPower.into());
+        }
+        if support {
+            proposal.votes_for = proposal.votes_for.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        } else {
+            proposal.votes_against = proposal.votes_against.checked_add(shares).ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.voter_share_account.to_account_info(),
+            to: ctx.accounts.vote_share_account.to_account_info(),
+            authority: ctx.accounts.voter.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, shares)?;
+
+        let vote = &mut ctx.accounts.vote;
+        vote.proposal = ctx.accounts.proposal.key();
+        vote.voter = ctx.accounts.voter.key();
+        vote.support = support;
+        vote.shares = shares;
+        vote.bump = ctx.bumps.vote;
+        Ok(())
+    }
+
+    /// Returns the shares locked with a strategy vote once voting has ended.
+    pub fn withdraw_strategy_vote(ctx: Context<WithdrawStrategyVote>) -> Result<()> {
+        let proposal = &ctx.accounts.proposal;
+        if Clock::get()?.unix_timestamp < proposal.voting_ends_at {
+            return Err(ErrorCode::VotingNotEnded.into());
+        }
+        let id = proposal.id.to_le_bytes();
+        let seeds: &[&[u8]] = &[STRATEGY_PROPOSAL_SEED, proposal.strategy_config.as_ref(), &id, &[proposal.bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vote_share_account.to_account_info(),
+            to: ctx.accounts.voter_share_account.to_account_info(),
+            authority: proposal.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, ctx.accounts.vote.shares)
+    }
+
+    /// Applies a passed strategy proposal once its notice period has elapsed.
+    ///
+    /// Anyone can execute a passed proposal.
+    pub fn execute_strategy_proposal(ctx: Context<ExecuteStrategyProposal>) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        if proposal.executed {
+            return Err(ErrorCode::ProposalAlreadyExecuted.into());
+        }
+        if Clock::get()?.unix_timestamp < proposal.effective_at {
+            return Err(ErrorCode::ProposalNotExecutable.into());
+        }
+        let strategy_config = &mut ctx.accounts.strategy_config;
+        if !proposal.passed(&strategy_config.voting) {
+            return Err(ErrorCode::ProposalNotPassed.into());
+        }
+        strategy_config.params = proposal.params.clone();
+        proposal.executed = true;
+        Ok(())
+    }
+
+    /// Offers the escrow's option on loan for `term` seconds in exchange for an upfront `fee`.
+    ///
+    /// Only the holder of an option sold to it can lend it, and not while it's rented, vesting,
+    /// or perpetual. If the borrower exercises, the lender keeps `lender_share_bps` of the
+    /// proceeds.
+    pub fn offer_option_loan(ctx: Context<OfferOptionLoan>, fee: u64, term: i64, lender_share_bps: u64) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        if escrow_account.holder != ctx.accounts.lender.key()
+            || escrow_account.renter != Pubkey::default()
+            || escrow_account.vesting.is_some()
+            || escrow_account.perpetual.is_some()
+        {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if term <= 0 || lender_share_bps > 10000 {
+            return Err(ErrorCode::InvalidLoanTerms.into());
+        }
+
+        let option_loan = &mut ctx.accounts.option_loan;
+        option_loan.escrow = ctx.accounts.escrow_account.key();
+        option_loan.lender = ctx.accounts.lender.key();
+        option_loan.borrower = Pubkey::default();
+        option_loan.fee = fee;
+        option_loan.term = term;
+        option_loan.lender_share_bps = lender_share_bps;
+        option_loan.end = 0;
+        option_loan.bump = ctx.bumps.option_loan;
+        Ok(())
+    }
+
+    /// Borrows an offered option, paying the fee to the lender.
+    ///
+    /// The borrower becomes the holder until the term is over, which must be before the
+    /// market's exercise cutoff so the option is back with the lender by settlement.
+    pub fn take_option_loan(ctx: Context<TakeOptionLoan>) -> Result<()> {
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        ctx.accounts.market.require_gate_token(ctx.accounts.gate_token_account.as_deref(), ctx.accounts.borrower.key)?;
+        let escrow_account = &ctx.accounts.escrow_account;
+        escrow_account.require_allowed(ctx.accounts.borrower.key)?;
+        escrow_account.require_not_on_loan()?;
+        let option_loan = &ctx.accounts.option_loan;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.holder != option_loan.lender || escrow_account.renter != Pubkey::default() {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        let current_time = Clock::get()?.unix_timestamp;
+        let end = current_time.checked_add(option_loan.term).ok_or(ErrorCode::MathOverflow)?;
+        if end > escrow_account.expiration.saturating_sub(ctx.accounts.market.exercise_cutoff) {
+            return Err(ErrorCode::InvalidLoanTerms.into());
+        }
+
+        if option_loan.fee > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.borrower_collateral_account.to_account_info(),
+                to: ctx.accounts.lender_collateral_account.to_account_info(),
+                authority: ctx.accounts.borrower.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+            token::transfer(cpi_ctx, option_loan.fee)?;
+        }
+
+        let option_loan = &mut ctx.accounts.option_loan;
+        option_loan.borrower = ctx.accounts.borrower.key();
+        option_loan.end = end;
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        escrow_account.holder = ctx.accounts.borrower.key();
+        escrow_account.on_loan = true;
+        Ok(())
+    }
+
+    /// Ends an option loan, returning the option to the lender.
+    ///
+    /// The lender can withdraw an offer nobody took at any time. Once the term is over, or the
+    /// borrower has exercised, anyone can end the loan, so the borrower can't keep the option.
+    pub fn end_option_loan(ctx: Context<EndOptionLoan>) -> Result<()> {
+        let option_loan = &ctx.accounts.option_loan;
+        let escrow_account = &mut ctx.accounts.escrow_account;
+        if option_loan.borrower == Pubkey::default() {
+            if ctx.accounts.caller.key() != option_loan.lender {
+                return Err(ErrorCode::NotOptionHolder.into());
+            }
+            return Ok(());
+        }
+        if !escrow_account.is_exercised && Clock::get()?.unix_timestamp < option_loan.end {
+            return Err(ErrorCode::LoanNotEnded.into());
+        }
+        escrow_account.holder = option_loan.lender;
+        escrow_account.on_loan = false;
+        Ok(())
+    }
+
+    /// Creates a template of escrow parameters that escrows can be cloned from.
+    ///
+    /// The template fixes the collateral mint, option type, collateral per escrow, and terms;
+    /// only the strike and expiry are chosen when cloning. The parameters are validated by
+    /// `initialize_escrow` on each clone.
+    pub fn create_template(
+        ctx: Context<CreateTemplate>,
+        template_id: u64,
+        collateral_mint: Pubkey,
+        option_type: OptionType,
+        collateral_amount: u64,
+        terms: OptionTerms,
+    ) -> Result<()> {
+        if collateral_amount == 0 {
+            return Err(ErrorCode::InvalidTemplate.into());
+        }
+        let template = &mut ctx.accounts.template;
+        template.creator = ctx.accounts.creator.key();
+        template.template_id = template_id;
+        template.collateral_mint = collateral_mint;
+        template.option_type = option_type;
+        template.collateral_amount = collateral_amount;
+        template.terms = terms;
+        template.fee_override_bps = None;
+        template.bump = ctx.bumps.template;
+        Ok(())
+    }
+
+    /// Sets the fee rate of escrows cloned from a template, or clears it with `None`.
+    ///
+    /// Only the governance authority or the fee admin can call this. The rate replaces every
+    /// protocol fee rate after the escrow is opened, as a negotiated fee override does.
+    pub fn set_template_fee_override(ctx: Context<SetTemplateFeeOverride>, fee_bps: Option<u64>) -> Result<()> {
+        if fee_bps.is_some_and(|fee_bps| fee_bps > MAX_FEE_BPS) {
+            return Err(ErrorCode::FeeRateTooHigh.into());
+        }
+        ctx.accounts.template.fee_override_bps = fee_bps;
+        Ok(())
+    }
+
+    /// Closes a template, returning its rent to the creator. Escrows already cloned are unaffected.
+    pub fn close_template(_ctx: Context<CloseTemplate>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Opens an escrow from a template at `strike_price` and `expiration`.
+    ///
+    /// The escrow is created with `initialize_escrow`, called through this program with the
+    /// template's parameters; the remaining accounts are `initialize_escrow`'s, with the
+    /// signer as the initializer. The template's fee override, if any, is then applied.
+    pub fn clone_from_template<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CloneFromTemplate<'info>>,
+        strike_price: u64,
+        expiration: i64,
+    ) -> Result<()> {
+        let init_accounts = ctx.remaining_accounts;
+        if init_accounts.len() < 2 || init_accounts[1].key() != ctx.accounts.writer.key() {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        let template = &ctx.accounts.template;
+        let escrow = instruction::InitializeEscrow {
+            option_type: template.option_type.clone(),
+            strike_price,
+            expiration,
+            collateral_amount: template.collateral_amount,
+            collateral_mint: template.collateral_mint,
+            terms: template.terms.clone(),
+        };
+        let account_metas = init_accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.key(),
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect();
+        let ix = Instruction { program_id: crate::ID, accounts: account_metas, data: escrow.data() };
+        let mut account_infos = init_accounts.to_vec();
+        account_infos.push(ctx.accounts.program.to_account_info());
+        invoke_signed(&ix, &account_infos, &[])?;
+
+        // A fee approval consumed by the escrow takes precedence over the template's rate
+        if let Some(fee_bps) = template.fee_override_bps {
+            let mut escrow_account = Account::<EscrowAccount>::try_from(&init_accounts[0])?;
+            if escrow_account.fee_override_bps.is_none() {
+                escrow_account.fee_override_bps = Some(fee_bps);
+                escrow_account.exit(&crate::ID)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a market's grid of standard expiries, e.g. Fridays at 08:00 UTC.
+    ///
+    /// Once created, new escrows in the market must expire on the grid, so listed series line
+    /// up into chains. Perpetual options are exempt.
+    pub fn initialize_expiry_grid(ctx: Context<InitializeExpiryGrid>, cadence: i64, offset: i64) -> Result<()> {
+        ExpiryGrid::validate(cadence, offset)?;
+        let expiry_grid = &mut ctx.accounts.expiry_grid;
+        expiry_grid.market = ctx.accounts.market.key();
+        expiry_grid.cadence = cadence;
+        expiry_grid.offset = offset;
+        expiry_grid.bump = ctx.bumps.expiry_grid;
+        ctx.accounts.market.uses_expiry_grid = true;
+        Ok(())
+    }
+
+    /// Replaces a market's grid of standard expiries, or stops enforcing it with a zero cadence.
+    ///
+    /// Escrows already open keep their expiries.
+    pub fn update_expiry_grid(ctx: Context<UpdateExpiryGrid>, cadence: i64, offset: i64) -> Result<()> {
+        if cadence != 0 {
+            ExpiryGrid::validate(cadence, offset)?;
+            let expiry_grid = &mut ctx.accounts.expiry_grid;
+            expiry_grid.cadence = cadence;
+            expiry_grid.offset = offset;
+        }
+        ctx.accounts.market.uses_expiry_grid = cadence != 0;
+        Ok(())
+    }
+
+    /// Returns the market's next standard expiry, for UIs and writers listing a new series.
+    pub fn get_next_standard_expiry(ctx: Context<GetNextStandardExpiry>) -> Result<i64> {
+        ctx.accounts.expiry_grid.next_standard_expiry(Clock::get()?.unix_timestamp)
+    }
+
+    /// Lists a fungible series of vanilla options on a market's collateral.
+    ///
+    /// The series is identified by its type, strike, and expiry, which are validated like a new
+    /// escrow's. Each option token is backed by `collateral_per_option`. Series are only
+    /// supported in markets whose fees are charged in the collateral.
+    pub fn create_option_series(
+        ctx: Context<CreateOptionSeries>,
+        option_type: OptionType,
+        strike_price: u64,
+        expiration: i64,
+        collateral_per_option: u64,
+    ) -> Result<()> {
+        let market = &ctx.accounts.market;
+        market.require_not_paused(PAUSE_NEW_ESCROWS)?;
+        if market.has_separate_fee_mint() || collateral_per_option == 0 {
+            return Err(ErrorCode::SeriesUnsupported.into());
+        }
+        let leg = EscrowLeg { option_type: option_type.clone(), strike_price, collateral_amount: collateral_per_option };
+        market.validate_new_escrow(
+            ctx.accounts.collateral_oracle.as_ref(),
+            ctx.accounts.expiry_grid.as_deref(),
+            &leg,
+            expiration,
+            &OptionTerms::vanilla(),
+            Clock::get()?.unix_timestamp,
+        )?;
+
+        let series = &mut ctx.accounts.series;
+        series.collateral_mint = market.collateral_mint;
+        series.option_type = option_type;
+        series.strike_price = strike_price;
+        series.expiration = expiration;
+        series.collateral_per_option = collateral_per_option;
+        series.option_mint = ctx.accounts.option_mint.key();
+        series.writer_mint = ctx.accounts.writer_mint.key();
+        series.vault = ctx.accounts.vault.key();
+        series.open_interest = 0;
+        series.total_units = 0;
+        series.exercised = 0;
+        series.settled = false;
+        series.settlement_price = 0;
+        series.payout_per_option = 0;
+        series.residual_per_option = 0;
+        series.bump = ctx.bumps.series;
+        Ok(())
+    }
+
+    /// Writes `amount` options of a series, locking their collateral and minting the option
+    /// tokens to the writer, along with writer tokens for its share of the series' pool.
+    pub fn write_series_options(ctx: Context<WriteSeriesOptions>, amount: u64) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_NEW_ESCROWS)?;
+        ctx.accounts.market.require_whitelisted(&ctx.accounts.whitelist_entry)?;
+        let series = &ctx.accounts.series;
+        if Clock::get()?.unix_timestamp >= series.expiration {
+            return Err(ErrorCode::SeriesExpired.into());
+        }
+        if amount == 0 {
+            return Err(ErrorCode::InvalidSeriesAmount.into());
+        }
+        let units = series.units_for(amount)?;
+        let collateral = amount.checked_mul(series.collateral_per_option).ok_or(ErrorCode::MathOverflow)?;
+        ctx.accounts.market.add_open_interest(collateral)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.writer_collateral_account.to_account_info(),
+            to: ctx.accounts.vault.to_account_info(),
+            authority: ctx.accounts.writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::transfer(cpi_ctx, collateral)?;
+
+        let strike_price = series.strike_price.to_le_bytes();
+        let expiration = series.expiration.to_le_bytes();
+        let seeds: &[&[u8]] = &[
+            OPTION_SERIES_SEED,
+            series.collateral_mint.as_ref(),
+            &strike_price,
+            &expiration,
+            &[OptionSeries::option_type_seed(&series.option_type)],
+            &[series.bump],
+        ];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.option_mint.to_account_info(),
+            to: ctx.accounts.writer_option_account.to_account_info(),
+            authority: series.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::mint_to(cpi_ctx, amount)?;
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.writer_mint.to_account_info(),
+            to: ctx.accounts.writer_token_account.to_account_info(),
+            authority: series.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::mint_to(cpi_ctx, units)?;
+
+        let series = &mut ctx.accounts.series;
+        let series_writer = &mut ctx.accounts.series_writer;
+        series_writer.sync_assigned(series);
+        series.open_interest = series.open_interest.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        series.total_units = series.total_units.checked_add(units).ok_or(ErrorCode::MathOverflow)?;
+        series_writer.series = series.key();
+        series_writer.writer = ctx.accounts.writer.key();
+        series_writer.written = series_writer.written.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        series_writer.units = series_writer.units.checked_add(units).ok_or(ErrorCode::MathOverflow)?;
+        series_writer.bump = ctx.bumps.series_writer;
+        Ok(())
+    }
+
+    /// Settles an expired series once, recording what each option token and each written
+    /// option get back.
+    ///
+    /// Permissionless. The series settles at the market's oracle settlement price, as an
+    /// escrow would. The settlement fee on the whole collateral moves into the fee vault; the
+    /// rest stays in the series vault until holders claim with `claim_payout` and writers with
+    /// `claim_series_collateral`.
+    pub fn settle_series(ctx: Context<SettleSeries>) -> Result<()> {
+        ctx.accounts.market.require_not_paused(PAUSE_EXERCISE)?;
+        let series = &ctx.accounts.series;
+        if series.settled {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if Clock::get()?.unix_timestamp < series.expiration {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        let settlement_price = ctx.accounts.settlement_price()?;
+        let is_itm = series.is_itm(settlement_price);
+
+        let fee_per_option = u64::try_from(series.collateral_per_option as u128 * ctx.accounts.governance.settlement_fee_bps as u128 / 10000)
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        let amount_after_fee = series.collateral_per_option - fee_per_option;
+        let payout_per_option = if is_itm { amount_after_fee } else { 0 };
+        let fee = series.open_interest.checked_mul(fee_per_option).ok_or(ErrorCode::MathOverflow)?;
+        let collateral = series.open_interest.checked_mul(series.collateral_per_option).ok_or(ErrorCode::MathOverflow)?;
+        let fee_vault_token_account = ctx.accounts.fee_vault_token_account.to_account_info();
+        pay_from_series(series, &ctx.accounts.vault, fee_vault_token_account, &ctx.accounts.token_program, fee)?;
+        ctx.accounts.fee_vault.accrue(&ctx.accounts.governance.fee_collectors, fee)?;
+        let market = &mut ctx.accounts.market;
+        market.open_interest = market.open_interest.saturating_sub(collateral);
+
+        let series = &mut ctx.accounts.series;
+        series.settled = true;
+        series.settlement_price = settlement_price;
+        series.payout_per_option = payout_per_option;
+        series.residual_per_option = amount_after_fee - payout_per_option;
+        Ok(())
+    }
+
+    /// Burns `amount` of the holder's option tokens of a settled series, paying their share of
+    /// the settlement.
+    pub fn claim_payout(ctx: Context<ClaimPayout>, amount: u64) -> Result<()> {
+        let series = &ctx.accounts.series;
+        if !series.settled {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        let payout = amount.checked_mul(series.payout_per_option).ok_or(ErrorCode::MathOverflow)?;
+        if payout == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.option_mint.to_account_info(),
+            from: ctx.accounts.holder_option_account.to_account_info(),
+            authority: ctx.accounts.holder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::burn(cpi_ctx, amount)?;
+        let holder_collateral_account = ctx.accounts.holder_collateral_account.to_account_info();
+        pay_from_series(series, &ctx.accounts.vault, holder_collateral_account, &ctx.accounts.token_program, payout)
+    }
+
+    /// Burns `units` writer tokens of a settled series for their pro-rata share of the
+    /// collateral left in the pool.
+    ///
+    /// Options assigned to early exercises have already paid out, so the tokens only claim
+    /// their share of the open interest at settlement. When the caller wrote options itself,
+    /// passing its record adds the claim to the collateral released to it.
+    pub fn claim_series_collateral(ctx: Context<ClaimSeriesCollateral>, units: u64) -> Result<()> {
+        let series = &ctx.accounts.series;
+        if !series.settled {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if units == 0 {
+            return Err(ErrorCode::InvalidSeriesAmount.into());
+        }
+        let residual = series.open_options(units).checked_mul(series.residual_per_option).ok_or(ErrorCode::MathOverflow)?;
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.writer_mint.to_account_info(),
+            from: ctx.accounts.writer_token_account.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::burn(cpi_ctx, units)?;
+        if let Some(series_writer) = ctx.accounts.series_writer.as_mut() {
+            series_writer.sync_assigned(series);
+            series_writer.released = series_writer.released.checked_add(residual).ok_or(ErrorCode::MathOverflow)?;
+        }
+        let collateral_account = ctx.accounts.collateral_account.to_account_info();
+        pay_from_series(series, &ctx.accounts.vault, collateral_account, &ctx.accounts.token_program, residual)
+    }
+
+    /// Exercises `amount` of the holder's option tokens before expiry, burning them for their
+    /// collateral less the exercise fee.
+    ///
+    /// The options must be ITM at the market's oracle price. The exercise is assigned to the
+    /// series' writers pro rata to their open options.
+    pub fn exercise_series_options(ctx: Context<ExerciseSeriesOptions>, amount: u64) -> Result<()> {
+        let settle = &ctx.accounts.settle;
+        settle.market.require_not_paused(PAUSE_EXERCISE)?;
+        let series = &settle.series;
+        let current_time = Clock::get()?.unix_timestamp;
+        if series.settled || current_time >= series.expiration.saturating_sub(settle.market.exercise_cutoff) {
+            return Err(ErrorCode::ExerciseCutoffPassed.into());
+        }
+        if amount == 0 || amount > series.open_interest {
+            return Err(ErrorCode::InvalidSeriesAmount.into());
+        }
+        if !series.is_itm(settle.settlement_price()?) {
+            return Err(ErrorCode::SeriesOutOfTheMoney.into());
+        }
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.option_mint.to_account_info(),
+            from: ctx.accounts.holder_option_account.to_account_info(),
+            authority: ctx.accounts.holder.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(settle.token_program.to_account_info(), cpi_accounts);
+        token::burn(cpi_ctx, amount)?;
+
+        let collateral = amount.checked_mul(series.collateral_per_option).ok_or(ErrorCode::MathOverflow)?;
+        let fee = u64::try_from(collateral as u128 * settle.governance.exercise_fee_bps as u128 / 10000).map_err(|_| ErrorCode::MathOverflow)?;
+        let fee_vault_token_account = settle.fee_vault_token_account.to_account_info();
+        pay_from_series(series, &settle.vault, fee_vault_token_account, &settle.token_program, fee)?;
+        let holder_collateral_account = ctx.accounts.holder_collateral_account.to_account_info();
+        pay_from_series(series, &settle.vault, holder_collateral_account, &settle.token_program, collateral - fee)?;
+
+        let settle = &mut ctx.accounts.settle;
+        settle.fee_vault.accrue(&settle.governance.fee_collectors, fee)?;
+        settle.market.open_interest = settle.market.open_interest.saturating_sub(collateral);
+        let series = &mut settle.series;
+        series.open_interest -= amount;
+        series.exercised = series.exercised.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Updates a writer's record of the options assigned to it. Permissionless.
+    pub fn sync_series_writer(ctx: Context<SyncSeriesWriter>) -> Result<()> {
+        ctx.accounts.series_writer.sync_assigned(&ctx.accounts.series);
+        Ok(())
+    }
+
+    /// Pulls the collateral owed to the writer of a settled or exercised escrow.
+    ///
+    /// Settlement only records what the writer is owed, so a missing or frozen writer token
+    /// account can't block the crank; the writer claims it here when convenient.
+    pub fn claim_writer_settlement(ctx: Context<ClaimWriterSettlement>) -> Result<()> {
+        let amount = ctx.accounts.escrow_account.unclaimed_writer_amount;
+        if amount == 0 {
+            return Err(ErrorCode::NothingToClaim.into());
+        }
+        transfer_from_vault(
+            &ctx.accounts.escrow_account,
+            ctx.accounts.escrow_collateral_account.to_account_info(),
+            ctx.accounts.initializer_collateral_account.to_account_info(),
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+        ctx.accounts.escrow_account.unclaimed_writer_amount = 0;
+        Ok(())
+    }
+
+    /// Splits the holder's option into `shares` fraction share tokens, minted to the holder.
+    ///
+    /// Only the holder of an option sold to it can split it, and not while it's rented,
+    /// vesting, perpetual, or on loan. The escrow's fraction account becomes its holder, so
+    /// the shares can be traded or syndicated while the option settles at expiry.
+    pub fn fractionalize_escrow(ctx: Context<FractionalizeEscrow>, shares: u64) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        escrow_account.require_not_on_loan()?;
+        if escrow_account.holder != ctx.accounts.holder.key()
+            || escrow_account.renter != Pubkey::default()
+            || escrow_account.vesting.is_some()
+            || escrow_account.perpetual.is_some()
+        {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if shares == 0 {
+            return Err(ErrorCode::InvalidFractionShares.into());
+        }
+
+        let escrow_key = escrow_account.key();
+        let seeds: &[&[u8]] = &[ESCROW_FRACTION_SEED, escrow_key.as_ref(), &[ctx.bumps.escrow_fraction]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            to: ctx.accounts.holder_share_account.to_account_info(),
+            authority: ctx.accounts.escrow_fraction.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::mint_to(cpi_ctx, shares)?;
+
+        let escrow_fraction = &mut ctx.accounts.escrow_fraction;
+        escrow_fraction.escrow = escrow_key;
+        escrow_fraction.share_mint = ctx.accounts.share_mint.key();
+        escrow_fraction.proceeds_account = ctx.accounts.proceeds_account.key();
+        escrow_fraction.total_shares = shares;
+        escrow_fraction.bump = ctx.bumps.escrow_fraction;
+        ctx.accounts.escrow_account.holder = escrow_fraction.key();
+        Ok(())
+    }
+
+    /// Burns `shares` fraction shares of a settled escrow for their pro-rata part of its
+    /// proceeds.
+    ///
+    /// The proceeds must first be moved into the fraction's proceeds account with
+    /// `claim_settlement`, which anyone can call for it. Out-of-the-money shares burn for nothing.
+    pub fn claim_fraction_proceeds(ctx: Context<ClaimFractionProceeds>, shares: u64) -> Result<()> {
+        let escrow_account = &ctx.accounts.escrow_account;
+        if !escrow_account.is_exercised {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if escrow_account.unclaimed_payout > 0 {
+            return Err(ErrorCode::FractionProceedsPending.into());
+        }
+        let supply = ctx.accounts.share_mint.supply;
+        if shares == 0 || shares > supply {
+            return Err(ErrorCode::InvalidFractionShares.into());
+        }
+        let amount = u64::try_from(ctx.accounts.proceeds_account.amount as u128 * shares as u128 / supply as u128)
+            .map_err(|_| ErrorCode::MathOverflow)?;
+
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            from: ctx.accounts.owner_share_account.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token::burn(cpi_ctx, shares)?;
+
+        let escrow_fraction = &ctx.accounts.escrow_fraction;
+        let seeds: &[&[u8]] = &[ESCROW_FRACTION_SEED, escrow_fraction.escrow.as_ref(), &[escrow_fraction.bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.proceeds_account.to_account_info(),
+            to: ctx.accounts.recipient_collateral_account.to_account_info(),
+            authority: escrow_fraction.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)
+    }
+
+    /// Creates the holder registry of a series, run by `registrar`.
+    ///
+    /// Only the governance authority can call this, for series that must track holders on
+    /// chain instead of through option tokens.
+    pub fn initialize_holder_registry(ctx: Context<InitializeHolderRegistry>, registrar: Pubkey) -> Result<()> {
+        let mut holder_registry = ctx.accounts.holder_registry.load_init()?;
+        holder_registry.series = ctx.accounts.series.key();
+        holder_registry.registrar = registrar;
+        holder_registry.bump = ctx.bumps.holder_registry;
+        Ok(())
+    }
+
+    /// Burns `amount` of the registrar's option tokens and credits them to `holder`, admitting
+    /// it to the registry if it isn't yet.
+    ///
+    /// Only the registrar can call this. An `amount` of 0 admits a holder without crediting it.
+    pub fn add_registry_holder(ctx: Context<AddRegistryHolder>, holder: Pubkey, amount: u64) -> Result<()> {
+        if ctx.accounts.series.settled {
+            return Err(ErrorCode::SeriesExpired.into());
+        }
+        if amount > 0 {
+            let cpi_accounts = Burn {
+                mint: ctx.accounts.option_mint.to_account_info(),
+                from: ctx.accounts.registrar_option_account.to_account_info(),
+                authority: ctx.accounts.registrar.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+            token::burn(cpi_ctx, amount)?;
+        }
+        let mut holder_registry = ctx.accounts.holder_registry.load_mut()?;
+        holder_registry.credit(holder, amount)?;
+        holder_registry.registered = holder_registry.registered.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Debits `amount` options from `holder` and mints them back as option tokens, removing
+    /// the holder from the registry once it holds nothing.
+    ///
+    /// Only the registrar can call this.
+    pub fn remove_registry_holder(ctx: Context<RemoveRegistryHolder>, holder: Pubkey, amount: u64) -> Result<()> {
+        {
+            let mut holder_registry = ctx.accounts.holder_registry.load_mut()?;
+            holder_registry.debit(&holder, amount)?;
+            holder_registry.remove_if_empty(&holder);
+            holder_registry.registered -= amount;
+        }
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let series = &ctx.accounts.series;
+        let strike_price = series.strike_price.to_le_bytes();
+        let expiration = series.expiration.to_le_bytes();
+        let seeds: &[&[u8]] = &[
+            OPTION_SERIES_SEED,
+            series.collateral_mint.as_ref(),
+            &strike_price,
+            &expiration,
+            &[OptionSeries::option_type_seed(&series.option_type)],
+            &[series.bump],
+        ];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.option_mint.to_account_info(),
+            to: ctx.accounts.recipient_option_account.to_account_info(),
+            authority: series.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::mint_to(cpi_ctx, amount)
+    }
+
+    /// Transfers `amount` of the holder's registered options to another admitted holder.
+    pub fn transfer_registry_holding(ctx: Context<TransferRegistryHolding>, to: Pubkey, amount: u64) -> Result<()> {
+        let mut holder_registry = ctx.accounts.holder_registry.load_mut()?;
+        holder_registry.entry_mut(&to)?;
+        holder_registry.debit(ctx.accounts.holder.key, amount)?;
+        holder_registry.entry_mut(&to)?.amount += amount;
+        Ok(())
+    }
+
+    /// Pays the holder the payout of its registered options once the series has settled,
+    /// like `claim_payout` does for option tokens.
+    pub fn claim_registry_payout(ctx: Context<ClaimRegistryPayout>) -> Result<()> {
+        let series = &ctx.accounts.series;
+        if !series.settled {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        let amount = {
+            let mut holder_registry = ctx.accounts.holder_registry.load_mut()?;
+            let entry = holder_registry.entry_mut(ctx.accounts.holder.key)?;
+            let amount = entry.amount;
+            if amount == 0 {
+                return Err(ErrorCode::NothingToClaim.into());
+            }
+            entry.amount = 0;
+            holder_registry.registered -= amount;
+            amount
+        };
+        let payout = amount.checked_mul(series.payout_per_option).ok_or(ErrorCode::MathOverflow)?;
+        let holder_collateral_account = ctx.accounts.holder_collateral_account.to_account_info();
+        pay_from_series(series, &ctx.accounts.vault, holder_collateral_account, &ctx.accounts.token_program, payout)
+    }
+}
+
+/// Fixed-point math used to price options on-chain.
+///
+/// Values are `i128` scaled by `SCALE` (1e12). The routines trade a little precision for bounded
+/// compute: `ln` and `exp` use range reduction plus a short series, and `norm_cdf` uses the
+/// Abramowitz-Stegun approximation, which is accurate to about 1e-7.
+pub mod math {
+    use super::*;
+
+    pub const SCALE: i128 = 1_000_000_000_000;
+    const LN_2: i128 = 693_147_180_560;
+    const INV_SQRT_2PI: i128 = 398_942_280_401;
+    /// Inputs to `exp` beyond this bound overflow (or underflow to zero).
+    const EXP_BOUND: i128 = 40 * SCALE;
+
+    /// Multiplies two fixed-point numbers.
+    pub fn mul(a: i128, b: i128) -> Result<i128> {
+        a.checked_mul(b).map(|v| v / SCALE).ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Divides two fixed-point numbers.
+    pub fn div(a: i128, b: i128) -> Result<i128> {
+        if b == 0 {
+            return Err(ErrorCode::MathOverflow.into());
+        }
+        a.checked_mul(SCALE).map(|v| v / b).ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Natural logarithm of a positive fixed-point number.
+    ///
+    /// Reduces `x = m * 2^k` with `m` in [1, 2), then sums `ln(m) = 2 * atanh((m - 1) / (m + 1))`.
+    pub fn ln(x: i128) -> Result<i128> {
+        if x <= 0 {
+            return Err(ErrorCode::MathOverflow.into());
+        }
+        let (mut m, mut k) = (x, 0i128);
+        while m >= 2 * SCALE {
+            m /= 2;
+            k += 1;
+        }
+        while m < SCALE {
+            m *= 2;
+            k -= 1;
+        }
+        let z = div(m - SCALE, m + SCALE)?;
+        let z2 = mul(z, z)?;
+        let (mut term, mut sum, mut n) = (z, 0i128, 1i128);
+        while term != 0 {
+            sum += term / n;
+            term = mul(term, z2)?;
+            n += 2;
+        }
+        Ok(2 * sum + k * LN_2)
+    }
+
+    /// Exponential of a fixed-point number.
+    ///
+    /// Reduces `x = k * ln(2) + r` with `r` in [0, ln(2)), then sums the Taylor series of `e^r`.
+    pub fn exp(x: i128) -> Result<i128> {
+        if x < -EXP_BOUND {
+            return Ok(0);
+        }
+        if x > EXP_BOUND {
+            return Err(ErrorCode::MathOverflow.into());
+        }
+        let k = x.div_euclid(LN_2);
+        let r = x - k * LN_2;
+        let (mut term, mut sum, mut n) = (SCALE, SCALE, 1i128);
+        while term != 0 {
+            term = mul(term, r)? / n;
+            sum += term;
+            n += 1;
+        }
+        Ok(if k >= 0 { sum << k } else { sum >> -k })
+    }
+
+    /// Square root of a non-negative fixed-point number.
+    pub fn sqrt(x: i128) -> Result<i128> {
+        let scaled = u128::try_from(x).ok().and_then(|x| x.checked_mul(SCALE as u128)).ok_or(ErrorCode::MathOverflow)?;
+        if scaled == 0 {
+            return Ok(0);
+        }
+        let (mut root, mut next) = (scaled, (scaled >> 1) + 1);
+        while next < root {
+            root = next;
+            next = (root + scaled / root) >> 1;
+        }
+        Ok(root as i128)
+    }
+
+    /// Standard normal probability density.
+    pub fn norm_pdf(x: i128) -> Result<i128> {
+        mul(INV_SQRT_2PI, exp(-mul(x, x)? / 2)?)
+    }
+
+    /// Standard normal cumulative distribution (Abramowitz-Stegun 26.2.17).
+    pub fn norm_cdf(x: i128) -> Result<i128> {
+        const P: i128 = 231_641_900_000;
+        const B: [i128; 5] = [319_381_530_000, -356_563_782_000, 1_781_477_937_000, -1_821_255_978_000, 1_330_274_429_000];
+        let t = div(SCALE, SCALE + mul(P, x.abs())?)?;
+        let mut poly = 0i128;
+        for b in B.iter().rev() {
+            poly = mul(poly + b, t)?;
+        }
+        let tail = mul(norm_pdf(x)?, poly)?.clamp(0, SCALE);
+        Ok(if x >= 0 { SCALE - tail } else { tail })
+    }
+
+    /// Black-Scholes inputs converted to fixed point.
+    struct Inputs {
+        unit: i128,                 // One unit of STRIKE_PRICE_DECIMALS
+        s: i128,                    // Spot price
+        discounted_strike: i128,    // Strike discounted at the risk-free rate
+        sqrt_t: i128,               // Square root of the tenor in years
+        sigma_sqrt_t: i128,         // Volatility over the tenor
+        d1: Option<i128>,           // d1 of the model (None with no time or volatility left)
+    }
+
+    impl Inputs {
+        fn new(spot: u64, strike: u64, tenor: i64, rate_bps: i64, vol_bps: u64) -> Result<Self> {
+            if spot == 0 || strike == 0 || tenor < 0 {
+                return Err(ErrorCode::InvalidPricingInput.into());
+            }
+            let unit = 10i128.pow(STRIKE_PRICE_DECIMALS as u32);
+            let s = spot as i128 * SCALE / unit;
+            let k = strike as i128 * SCALE / unit;
+            let t = tenor as i128 * SCALE / SECONDS_PER_YEAR as i128;
+            let r = rate_bps as i128 * SCALE / 10000;
+            let sigma = vol_bps as i128 * SCALE / 10000;
+            let sqrt_t = sqrt(t)?;
+            let sigma_sqrt_t = mul(sigma, sqrt_t)?;
+            let d1 = if sigma_sqrt_t == 0 {
+                None
+            } else {
+                let drift = mul(r + mul(sigma, sigma)? / 2, t)?;
+                Some(div(ln(div(s, k)?)? + drift, sigma_sqrt_t)?)
+            };
+            Ok(Self { unit, s, discounted_strike: mul(k, exp(-mul(r, t)?)?)?, sqrt_t, sigma_sqrt_t, d1 })
+        }
+    }
+
+    /// Black-Scholes premium of a European option per unit of the underlying.
+    ///
+    /// `spot` and `strike` use `STRIKE_PRICE_DECIMALS` and so does the result. `tenor` is in
+    /// seconds, and `rate_bps` and `vol_bps` are annualized. With no time or volatility left the
+    /// premium is the discounted intrinsic value.
+    pub fn black_scholes(option_type: OptionType, spot: u64, strike: u64, tenor: i64, rate_bps: i64, vol_bps: u64) -> Result<u64> {
+        let Inputs { unit, s, discounted_strike, sigma_sqrt_t, d1, .. } = Inputs::new(spot, strike, tenor, rate_bps, vol_bps)?;
+        let premium = match d1 {
+            None => match option_type {
+                OptionType::Call => s - discounted_strike,
+                OptionType::Put => discounted_strike - s,
+            },
+            Some(d1) => {
+                let d2 = d1 - sigma_sqrt_t;
+                match option_type {
+                    OptionType::Call => mul(s, norm_cdf(d1)?)? - mul(discounted_strike, norm_cdf(d2)?)?,
+                    OptionType::Put => mul(discounted_strike, norm_cdf(-d2)?)? - mul(s, norm_cdf(-d1)?)?,
+                }
+            }
+        };
+        u64::try_from(premium.max(0) * unit / SCALE).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Black-Scholes delta, gamma, and vega of a European option per unit of the underlying.
+    ///
+    /// Takes the same inputs as `black_scholes`. With no time or volatility left, delta is 0 or
+    /// +/-1 depending on moneyness and gamma and vega are zero.
+    pub fn greeks(option_type: OptionType, spot: u64, strike: u64, tenor: i64, rate_bps: i64, vol_bps: u64) -> Result<Greeks> {
+        let Inputs { unit, s, discounted_strike, sqrt_t, sigma_sqrt_t, d1 } = Inputs::new(spot, strike, tenor, rate_bps, vol_bps)?;
+        let Some(d1) = d1 else {
+            let delta = match option_type {
+                OptionType::Call if s > discounted_strike => GREEKS_UNIT,
+                OptionType::Put if s < discounted_strike => -GREEKS_UNIT,
+                _ => 0,
+            };
+            return Ok(Greeks { delta, gamma: 0, vega: 0 });
+        };
+        let delta = match option_type {
+            OptionType::Call => norm_cdf(d1)?,
+            OptionType::Put => norm_cdf(d1)? - SCALE,
+        };
+        let pdf = norm_pdf(d1)?;
+        let gamma = div(pdf, sigma_sqrt_t)? / 100;
+        let vega = mul(mul(s, pdf)?, sqrt_t)? / 100;
+        let to_i64 = |value: i128, scale: i128| i64::try_from(value * scale / SCALE).map_err(|_| error!(ErrorCode::MathOverflow));
+        Ok(Greeks {
+            delta: to_i64(delta, GREEKS_UNIT as i128)?,
+            gamma: to_i64(gamma, GREEKS_UNIT as i128)?,
+            vega: to_i64(vega, unit)?,
+        })
+    }
+}
+
+/// Adapters encoding hedge orders for perp DEXes.
+///
+/// A venue whitelisted with an adapter has its orders built on-chain from the rebalance size,
+/// so the rebalancer can't send the venue arbitrary instructions signed by the vault. Each
+/// adapter is compiled in behind its own feature (`drift`, `mango`); new venues implement
+/// `HedgeAdapter` behind a new feature and a new `HedgeAdapterKind`.
+pub mod hedge {
+    use super::*;
+
+    /// A market order on a perp DEX changing a vault's position.
+    pub struct HedgeOrder {
+        pub market_index: u16,      // Perp market on the venue
+        pub size: i64,              // Signed change of the position, in native collateral units (positive buys)
+        pub decimals: u8,           // Decimals of the collateral
+        pub base_lot_size: u64,     // Native units per base lot (for venues quoting in lots)
+        pub limit_price: u64,       // Worst acceptable price, in the venue's price units
+    }
+
+    /// Encodes hedge orders for one perp DEX.
+    pub trait HedgeAdapter {
+        /// Program ID of the venue.
+        fn program_id(&self) -> Pubkey;
+
+        /// Returns the instruction data of `order`.
+        fn order_data(&self, order: &HedgeOrder) -> Result<Vec<u8>>;
+    }
+
+    /// Drift v2 (`dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH`), via `place_perp_order`.
+    ///
+    /// Sizes are converted to Drift's base precision (1e9) and `limit_price` is in its price
+    /// precision (1e6).
+    #[cfg(feature = "drift")]
+    pub struct DriftAdapter;
+
+    #[cfg(feature = "drift")]
+    impl HedgeAdapter for DriftAdapter {
+        fn program_id(&self) -> Pubkey {
+            Pubkey::new_from_array([
+                9, 84, 219, 190, 158, 201, 96, 201, 138, 122, 41, 63, 226, 19, 54, 150,
+                111, 225, 128, 209, 81, 174, 75, 129, 121, 86, 31, 137, 133, 74, 83, 246,
+            ])
+        }
+
+        fn order_data(&self, order: &HedgeOrder) -> Result<Vec<u8>> {
+            const PLACE_PERP_ORDER: [u8; 8] = [69, 161, 93, 202, 120, 126, 76, 185];
+            const BASE_PRECISION: u128 = 1_000_000_000;
+            let base_asset_amount = u64::try_from(order.size.unsigned_abs() as u128 * BASE_PRECISION / 10u128.pow(order.decimals as u32))
+                .map_err(|_| ErrorCode::MathOverflow)?;
+            let mut data = PLACE_PERP_ORDER.to_vec();
+            data.push(0); // order_type: Market
+            data.push(1); // market_type: Perp
+            data.push(if order.size > 0 { 0 } else { 1 }); // direction: Long or Short
+            data.push(0); // user_order_id
+            data.extend_from_slice(&base_asset_amount.to_le_bytes());
+            data.extend_from_slice(&order.limit_price.to_le_bytes());
+            data.extend_from_slice(&order.market_index.to_le_bytes());
+            data.push(0); // reduce_only
+            data.push(0); // post_only: None
+            data.push(0); // immediate_or_cancel
+            data.push(0); // max_ts: None
+            data.push(0); // trigger_price: None
+            data.push(0); // trigger_condition: Above
+            data.extend_from_slice(&[0; 4]); // oracle_price_offset, auction_duration, auction_start_price, auction_end_price: None
+            Ok(data)
+        }
+    }
+
+    /// Mango v4 (`4MangoMjqJ2firMokCjjGgoK8d4MXcrgL7XJaL3w6fVg`), via an immediate-or-cancel
+    /// `perp_place_order`.
+    ///
+    /// Sizes are converted to base lots of the perp market and `limit_price` is in price lots.
+    #[cfg(feature = "mango")]
+    pub struct MangoAdapter;
+
+    #[cfg(feature = "mango")]
+    impl HedgeAdapter for MangoAdapter {
+        fn program_id(&self) -> Pubkey {
+            Pubkey::new_from_array([
+                49, 216, 225, 125, 222, 15, 89, 193, 142, 7, 91, 152, 202, 157, 107, 101,
+                200, 250, 36, 237, 80, 109, 32, 108, 94, 190, 60, 24, 15, 2, 55, 127,
+            ])
+        }
+
+        fn order_data(&self, order: &HedgeOrder) -> Result<Vec<u8>> {
+            const PERP_PLACE_ORDER: [u8; 8] = [189, 196, 225, 201, 114, 172, 25, 166];
+            const ORDER_LIMIT: u8 = 10;
+            if order.base_lot_size == 0 {
+                return Err(ErrorCode::InvalidHedgeOrder.into());
+            }
+            let max_base_lots = i64::try_from(order.size.unsigned_abs() / order.base_lot_size).map_err(|_| ErrorCode::MathOverflow)?;
+            let price_lots = i64::try_from(order.limit_price).map_err(|_| ErrorCode::MathOverflow)?;
+            let mut data = PERP_PLACE_ORDER.to_vec();
+            data.push(if order.size > 0 { 0 } else { 1 }); // side: Bid or Ask
+            data.extend_from_slice(&price_lots.to_le_bytes());
+            data.extend_from_slice(&max_base_lots.to_le_bytes());
+            data.extend_from_slice(&i64::MAX.to_le_bytes()); // max_quote_lots
+            data.extend_from_slice(&0u64.to_le_bytes()); // client_order_id
+            data.push(1); // order_type: ImmediateOrCancel
+            data.push(0); // reduce_only
+            data.extend_from_slice(&0u64.to_le_bytes()); // expiry_timestamp: none
+            data.push(ORDER_LIMIT);
+            Ok(data)
+        }
+    }
+
+    impl HedgeAdapterKind {
+        /// Returns the adapter of the venue, failing if it wasn't compiled in.
+        pub fn adapter(&self) -> Result<&'static dyn HedgeAdapter> {
+            match self {
+                #[cfg(feature = "drift")]
+                HedgeAdapterKind::Drift => Ok(&DriftAdapter),
+                #[cfg(feature = "mango")]
+                HedgeAdapterKind::Mango => Ok(&MangoAdapter),
+                #[allow(unreachable_patterns)]
+                _ => Err(ErrorCode::HedgeAdapterUnavailable.into()),
+            }
+        }
+    }
+}
+
+/// Loads a recent price from a Pyth price feed account.
+fn load_oracle_price(oracle: &AccountInfo, config: &OracleConfig) -> Result<Price> {
+    load_checked_price(oracle, config, false)
+}
+
+/// Loads a recent exponentially-weighted moving average price from a Pyth price feed account.
+fn load_oracle_ema_price(oracle: &AccountInfo, config: &OracleConfig) -> Result<Price> {
+    load_checked_price(oracle, config, true)
+}
+
+/// Loads a price or EMA price and rejects it if it is stale or too uncertain.
+///
+/// Each failed check has its own error code so clients can tell why a price was refused.
+fn load_checked_price(oracle: &AccountInfo, config: &OracleConfig, ema: bool) -> Result<Price> {
+    let price_feed = load_price_feed_from_account_info(oracle).map_err(|_| ErrorCode::InvalidOracleAccount)?;
+    let clock = Clock::get()?;
+    let price = if ema {
+        price_feed.get_ema_price_no_older_than(clock.unix_timestamp, config.max_age_secs)
+    } else {
+        price_feed.get_price_no_older_than(clock.unix_timestamp, config.max_age_secs)
+    }
+    .ok_or(ErrorCode::StaleOraclePrice)?;
+    if price.price <= 0 {
+        return Err(ErrorCode::InvalidOraclePrice.into());
+    }
+    if config.max_age_slots > 0 {
+        let data = oracle.try_borrow_data()?;
+        let price_account = load_price_account(&data).map_err(|_| ErrorCode::InvalidOracleAccount)?;
+        if clock.slot.saturating_sub(price_account.agg.pub_slot) > config.max_age_slots {
+            return Err(ErrorCode::StaleOracleSlot.into());
+        }
+    }
+    if config.max_confidence_bps > 0 && price.conf as u128 * 10000 > price.price as u128 * config.max_confidence_bps as u128 {
+        return Err(ErrorCode::OracleConfidenceTooWide.into());
+    }
+    Ok(price)
+}
+
+/// Extra price feeds aggregated with a market's own collateral oracle at settlement.
+///
+/// Settlement uses the median of all feeds and fails if they disagree by more than
+/// `max_deviation_bps`, so a single broken or manipulated feed can't settle the market.
+#[account]
+#[derive(InitSpace)]
+pub struct OracleRegistry {
+    pub market: Pubkey,             // Market the feeds price
+    #[max_len(MAX_EXTRA_ORACLE_FEEDS)]
+    pub extra_feeds: Vec<Pubkey>,   // Pyth feeds aggregated with the market's collateral oracle
+    pub max_deviation_bps: u64,     // Largest allowed spread between feeds, as basis points of the median
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl OracleRegistry {
+    /// Reads every feed and returns their aggregated price, in `STRIKE_PRICE_DECIMALS`.
+    ///
+    /// `extra_oracles` must be the registry's extra feeds, in order.
+    pub fn aggregate_price(&self, primary_price: u64, extra_oracles: &[Option<&UncheckedAccount>], config: &OracleConfig) -> Result<u64> {
+        let mut prices = vec![primary_price];
+        for (feed, oracle) in self.extra_feeds.iter().zip(extra_oracles.iter()) {
+            let oracle = oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+            if oracle.key() != *feed {
+                return Err(ErrorCode::InvalidOracleAccount.into());
+            }
+            prices.push(to_strike_price(&load_oracle_price(&oracle.to_account_info(), config)?)?);
+        }
+        aggregate_prices(&mut prices, self.max_deviation_bps)
+    }
+}
+
+/// Grid of standard expiry times that a market's series snap to.
+///
+/// Standard expiries are `offset` seconds past each multiple of `cadence` since the Unix
+/// epoch, so a weekly cadence with an offset of 1 day and 8 hours lists Fridays at 08:00 UTC.
+#[account]
+#[derive(InitSpace)]
+pub struct ExpiryGrid {
+    pub market: Pubkey,             // Market whose expiries are standardized
+    pub cadence: i64,               // Seconds between standard expiries
+    pub offset: i64,                // Offset of the grid from the Unix epoch, in seconds (below the cadence)
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl ExpiryGrid {
+    /// Checks that the cadence is positive and the offset lies within it.
+    pub fn validate(cadence: i64, offset: i64) -> Result<()> {
+        if cadence <= 0 || offset < 0 || offset >= cadence {
+            return Err(ErrorCode::InvalidExpiryGrid.into());
+        }
+        Ok(())
+    }
+
+    /// Whether `expiration` is a standard expiry.
+    pub fn is_standard(&self, expiration: i64) -> bool {
+        (expiration - self.offset).rem_euclid(self.cadence) == 0
+    }
+
+    /// Returns the first standard expiry after `current_time`.
+    pub fn next_standard_expiry(&self, current_time: i64) -> Result<i64> {
+        next_grid_time(self.cadence, self.offset, current_time)
+    }
+}
+
+/// Weighted basket of underlyings (e.g. a SOL+ETH index) that basket options of a market settle on.
+///
+/// The basket's value is the sum of each component's oracle price times its weight, so strikes
+/// of basket options are quoted in index points with `STRIKE_PRICE_DECIMALS`.
+#[account]
+#[derive(InitSpace)]
+pub struct Basket {
+    pub market: Pubkey,                     // Market whose basket options settle on this basket
+    #[max_len(MAX_BASKET_COMPONENTS)]
+    pub components: Vec<BasketComponent>,   // Underlyings making up the basket
+    pub bump: u8,                           // Bump seed of this PDA
+}
+
+/// One underlying of a basket.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct BasketComponent {
+    pub oracle: Pubkey,             // Pyth feed pricing the underlying
+    pub weight: u64,                // Units of the underlying in the basket, in STRIKE_PRICE_DECIMALS
+}
+
+impl Basket {
+    /// Reads every component's oracle and returns the basket's value, in `STRIKE_PRICE_DECIMALS`.
+    ///
+    /// `oracles` must be the components' oracles, in order.
+    pub fn composite_price(&self, oracles: &[AccountInfo], config: &OracleConfig) -> Result<u64> {
+        if oracles.len() < self.components.len() {
+            return Err(ErrorCode::MissingOracleAccount.into());
+        }
+        let scale = 10u128.pow(STRIKE_PRICE_DECIMALS as u32);
+        let mut value: u128 = 0;
+        for (component, oracle) in self.components.iter().zip(oracles.iter()) {
+            if oracle.key() != component.oracle {
+                return Err(ErrorCode::InvalidOracleAccount.into());
+            }
+            let price = to_strike_price(&load_oracle_price(oracle, config)?)? as u128;
+            value = value.checked_add(price * component.weight as u128 / scale).ok_or(ErrorCode::MathOverflow)?;
+        }
+        u64::try_from(value).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+}
+
+/// Checks the components of a basket.
+fn validate_basket(components: &[BasketComponent]) -> Result<()> {
+    if components.is_empty() || components.len() > MAX_BASKET_COMPONENTS {
+        return Err(ErrorCode::InvalidBasket.into());
+    }
+    if components.iter().any(|component| component.weight == 0) {
+        return Err(ErrorCode::InvalidBasket.into());
+    }
+    Ok(())
+}
+
+/// Returns the median of the prices, failing if their spread exceeds `max_deviation_bps` of it.
+fn aggregate_prices(prices: &mut [u64], max_deviation_bps: u64) -> Result<u64> {
+    prices.sort_unstable();
+    let median = match prices.len() {
+        0 => return Err(ErrorCode::MissingOracleAccount.into()),
+        len if len % 2 == 1 => prices[len / 2],
+        len => ((prices[len / 2 - 1] as u128 + prices[len / 2] as u128) / 2) as u64,
+    };
+    let spread = prices[prices.len() - 1] - prices[0];
+    if spread as u128 * 10000 > median as u128 * max_deviation_bps as u128 {
+        return Err(ErrorCode::OracleFeedsDisagree.into());
+    }
+    Ok(median)
+}
+
+/// Ring buffer of cumulative price observations for a market, cranked periodically.
+///
+/// Each observation stores the running sum of price × seconds, so the time-weighted average
+/// price over any window covered by the buffer is the difference of two sums divided by the
+/// window length.
+///
+/// The buffer is zero-copy: it is read and written in place with `load`/`load_mut` rather
+/// than Borsh-decoded, so cranks and settlement only pay for the observations they touch.
+/// Fields are ordered so the `repr(C)` layout has no implicit padding.
+#[account(zero_copy)]
+pub struct PriceHistory {
+    pub market: Pubkey,                         // Market whose oracle is sampled
+    pub min_crank_interval: i64,                // Minimum seconds between observations
+    pub head: u16,                              // Index of the latest observation
+    pub len: u16,                               // Number of observations recorded, up to MAX_PRICE_OBSERVATIONS
+    pub bump: u8,                               // Bump seed of this PDA
+    pub padding: [u8; 3],                       // Aligns the observations to 16 bytes
+    pub observations: [PriceObservation; MAX_PRICE_OBSERVATIONS], // Observations, oldest overwritten first
+}
+
+/// A single price observation.
+#[zero_copy]
+pub struct PriceObservation {
+    pub timestamp: i64,             // Time of the observation (Unix timestamp)
+    pub price: u64,                 // Oracle price at that time, in STRIKE_PRICE_DECIMALS
+    pub cumulative_price: u128,     // Sum of price × seconds up to the observation
+}
+
+impl PriceHistory {
+    /// Records a new observation, overwriting the oldest one once the buffer is full.
+    pub fn record(&mut self, price: u64, current_time: i64) -> Result<()> {
+        let cumulative_price = match self.recorded().get(self.head as usize) {
+            Some(last) => {
+                if current_time - last.timestamp < self.min_crank_interval {
+                    return Err(ErrorCode::CrankTooEarly.into());
+                }
+                last.cumulative_price_at(current_time)?
+            }
+            None => 0,
+        };
+        let observation = PriceObservation { timestamp: current_time, price, cumulative_price };
+        if (self.len as usize) < MAX_PRICE_OBSERVATIONS {
+            self.head = self.len;
+            self.len += 1;
+        } else {
+            self.head = ((self.head as usize + 1) % MAX_PRICE_OBSERVATIONS) as u16;
+        }
+        self.observations[self.head as usize] = observation;
+        Ok(())
+    }
+
+    /// Returns the observations recorded so far, in buffer order.
+    fn recorded(&self) -> &[PriceObservation] {
+        &self.observations[..self.len as usize]
+    }
+
+    /// Returns the time-weighted average price over `[end - window, end]`.
+    ///
+    /// Fails if the buffer doesn't reach back to the start of the window, or if the last
+    /// observation before `end` is more than a window old.
+    pub fn twap(&self, end: i64, window: i64) -> Result<u64> {
+        let start = end - window;
+        let latest_before = |time: i64| {
+            self.recorded()
+                .iter()
+                .filter(|observation| observation.timestamp <= time)
+                .max_by_key(|observation| observation.timestamp)
+        };
+        let first = latest_before(start).ok_or(ErrorCode::TwapUnavailable)?;
+        let last = latest_before(end).ok_or(ErrorCode::TwapUnavailable)?;
+        if end - last.timestamp > window {
+            return Err(ErrorCode::TwapUnavailable.into());
+        }
+        let sum = last.cumulative_price_at(end)? - first.cumulative_price_at(start)?;
+        u64::try_from(sum / window as u128).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+}
+
+impl PriceObservation {
+    /// Extrapolates the cumulative price to a later time, holding the observed price.
+    fn cumulative_price_at(&self, time: i64) -> Result<u128> {
+        let elapsed = (time - self.timestamp).max(0) as u128;
+        self.cumulative_price
+            .checked_add(self.price as u128 * elapsed)
+            .ok_or(ErrorCode::MathOverflow.into())
+    }
+}
+
+/// Staleness and confidence limits applied when reading an oracle.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct OracleConfig {
+    pub max_age_secs: u64,          // Maximum age of a price in seconds
+    pub max_age_slots: u64,         // Maximum age of a price in slots (0 disables the check)
+    pub max_confidence_bps: u64,    // Maximum confidence interval as basis points of the price (0 disables the check)
+}
+
+impl OracleConfig {
+    /// Limits used when nothing else is configured.
+    pub const DEFAULT: OracleConfig = OracleConfig {
+        max_age_secs: MAX_ORACLE_PRICE_AGE,
+        max_age_slots: 0,
+        max_confidence_bps: 0,
+    };
+}
+
+/// Converts an oracle price to `STRIKE_PRICE_DECIMALS`.
+fn to_strike_price(price: &Price) -> Result<u64> {
+    let exponent = price.expo + STRIKE_PRICE_DECIMALS;
+    let scale = 10u128.checked_pow(exponent.unsigned_abs()).ok_or(ErrorCode::MathOverflow)?;
+    let scaled = if exponent >= 0 {
+        (price.price as u128).checked_mul(scale).ok_or(ErrorCode::MathOverflow)?
+    } else {
+        price.price as u128 / scale
+    };
+    u64::try_from(scaled).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Returns the native treasury of a Realms governance account.
+pub fn realms_native_treasury(governance_program: &Pubkey, realms_governance: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[REALMS_NATIVE_TREASURY_SEED, realms_governance.as_ref()], governance_program).0
+}
+
+/// Converts an amount of one token into the equivalent amount of another.
+///
+/// Both prices must be quoted in the same currency (e.g. USD). The result is
+/// `amount * from_price / to_price`, adjusted for price exponents and mint decimals.
+fn convert_amount(amount: u64, from_price: &Price, from_decimals: u8, to_price: &Price, to_decimals: u8) -> Result<u64> {
+    let exponent = from_price.expo + to_decimals as i32 - to_price.expo - from_decimals as i32;
+    let scale = 10u128.checked_pow(exponent.unsigned_abs()).ok_or(ErrorCode::MathOverflow)?;
+    let mut numerator = (amount as u128)
+        .checked_mul(from_price.price as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let mut denominator = to_price.price as u128;
+    if exponent >= 0 {
+        numerator = numerator.checked_mul(scale).ok_or(ErrorCode::MathOverflow)?;
+    } else {
+        denominator = denominator.checked_mul(scale).ok_or(ErrorCode::MathOverflow)?;
+    }
+    u64::try_from(numerator / denominator).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Transfers prepaid premium out of a rental's token account, signed by the rental PDA.
+fn pay_from_rental<'info>(
+    rental: &Account<'info, Rental>,
+    rental_token_account: &Account<'info, TokenAccount>,
+    to: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let seeds: &[&[u8]] = &[RENTAL_SEED, rental.escrow.as_ref(), &[rental.bump]];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = Transfer {
+        from: rental_token_account.to_account_info(),
+        to,
+        authority: rental.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Pays `amount` out of an option series' vault, signed by the series.
+fn pay_from_series<'info>(
+    series: &Account<'info, OptionSeries>,
+    vault: &Account<'info, TokenAccount>,
+    to: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let strike_price = series.strike_price.to_le_bytes();
+    let expiration = series.expiration.to_le_bytes();
+    let seeds: &[&[u8]] = &[
+        OPTION_SERIES_SEED,
+        series.collateral_mint.as_ref(),
+        &strike_price,
+        &expiration,
+        &[OptionSeries::option_type_seed(&series.option_type)],
+        &[series.bump],
+    ];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = Transfer {
+        from: vault.to_account_info(),
+        to,
+        authority: series.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Emits an escrow lifecycle event.
+///
+/// With the `event-cpi` feature, which enables anchor-lang's event-cpi instruction handler, an
+/// instruction given the event authority records the event as the data of a self-CPI signed by
+/// it, like Anchor's `emit_cpi!`, so indexers can recover it even when the transaction logs are
+/// truncated. Otherwise it is logged with `emit!`.
+fn emit_lifecycle_event<T: anchor_lang::Event>(event: &T, event_authority: Option<&UncheckedAccount>, bump: u8) -> Result<()> {
+    #[cfg(feature = "event-cpi")]
+    if let Some(event_authority) = event_authority {
+        let data: Vec<u8> = anchor_lang::event::EVENT_IX_TAG_LE.into_iter().chain(event.data()).collect();
+        let ix = Instruction::new_with_bytes(crate::ID, &data, vec![AccountMeta::new_readonly(event_authority.key(), true)]);
+        invoke_signed(&ix, &[event_authority.to_account_info()], &[&[EVENT_AUTHORITY_SEED, &[bump]]])?;
+        return Ok(());
+    }
+    #[cfg(not(feature = "event-cpi"))]
+    let _ = (event_authority, bump);
+    emit!(*event);
+    Ok(())
+}
+
+/// Fails if the transaction lands after `deadline` (a Unix timestamp).
+///
+/// Swap-based instructions take a deadline so a transaction held back by the network can't
+/// execute later at a stale price.
+fn require_before_deadline(deadline: i64) -> Result<()> {
+    if Clock::get()?.unix_timestamp > deadline {
+        return Err(ErrorCode::DeadlineExceeded.into());
+    }
+    Ok(())
+}
+
+/// Returns the message a market's attestation signer signs to attest a series' settlement price.
+///
+/// The message is `PRICE_ATTESTATION_DOMAIN`, the collateral mint, the expiry, and the price,
+/// with integers little-endian.
+pub fn price_attestation_message(collateral_mint: &Pubkey, expiration: i64, settlement_price: u64) -> Vec<u8> {
+    let mut message = PRICE_ATTESTATION_DOMAIN.to_vec();
+    message.extend_from_slice(collateral_mint.as_ref());
+    message.extend_from_slice(&expiration.to_le_bytes());
+    message.extend_from_slice(&settlement_price.to_le_bytes());
+    message
+}
+
+/// Checks that the instruction before the current one is an ed25519 program instruction
+/// verifying `signer`'s signature over `message`.
+///
+/// The ed25519 program fails the transaction on a bad signature, so only the signer, the
+/// message, and their location in the instruction's own data need checking here.
+fn verify_ed25519_attestation(instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    let index = current_index.checked_sub(1).ok_or(ErrorCode::InvalidAttestation)?;
+    let ix = load_instruction_at_checked(index as usize, instructions)?;
+    if ix.program_id != ed25519_program::ID || !ix.accounts.is_empty() {
+        return Err(ErrorCode::InvalidAttestation.into());
+    }
+
+    // Header: signature count and padding, then one set of seven u16 offsets
+    let data = &ix.data;
+    if data.len() < 16 || data[0] != 1 {
+        return Err(ErrorCode::InvalidAttestation.into());
+    }
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let public_key_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    // Signature, public key, and message must all come from this instruction's data
+    if read_u16(4) != u16::MAX || read_u16(8) != u16::MAX || read_u16(14) != u16::MAX {
+        return Err(ErrorCode::InvalidAttestation.into());
+    }
+    let public_key = data.get(public_key_offset..public_key_offset + 32).ok_or(ErrorCode::InvalidAttestation)?;
+    let signed_message = data.get(message_offset..message_offset + message_size).ok_or(ErrorCode::InvalidAttestation)?;
+    if public_key != signer.as_ref() || signed_message != message {
+        return Err(ErrorCode::InvalidAttestation.into());
+    }
+    Ok(())
+}
+
+/// Whether the settler of an escrow earns the crank fee.
+///
+/// The keeper registry must be passed to earn it. Once the registry exists, only active
+/// keepers earn the fee during its priority window after expiry, and anyone does after it.
+fn earns_crank_fee(keeper_registry: Option<&UncheckedAccount>, keeper: Option<&Account<Keeper>>, expiration: i64, current_time: i64) -> Result<bool> {
+    let Some(keeper_registry) = keeper_registry else {
+        return Ok(false);
+    };
+    if keeper_registry.data_is_empty() {
+        return Ok(true);
+    }
+    let data = keeper_registry.try_borrow_data()?;
+    let keeper_registry = KeeperRegistry::try_deserialize(&mut &data[..])?;
+    if current_time >= expiration.saturating_add(keeper_registry.priority_window) {
+        return Ok(true);
+    }
+    Ok(keeper.is_some_and(|keeper| keeper.is_active(&keeper_registry)))
+}
+
+/// Reads the exchange rate of the SPL stake pool minting `lst_mint`, as lamports of stake per
+/// pool token scaled by `LST_RATE_SCALE`.
+///
+/// The pool must have been updated in `epoch`, since its totals lag the rewards until it is.
+fn stake_pool_rate(stake_pool: &AccountInfo, lst_mint: &Pubkey, epoch: u64) -> Result<u64> {
+    const ACCOUNT_TYPE_STAKE_POOL: u8 = 1;
+    const POOL_MINT_OFFSET: usize = 162;
+    const TOTAL_LAMPORTS_OFFSET: usize = 258;
+    const POOL_TOKEN_SUPPLY_OFFSET: usize = 266;
+    const LAST_UPDATE_EPOCH_OFFSET: usize = 274;
+
+    if stake_pool.owner != &SPL_STAKE_POOL_PROGRAM_ID {
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+    let data = stake_pool.try_borrow_data()?;
+    let read_u64 = |offset: usize| {
+        data.get(offset..offset + 8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or(ErrorCode::InvalidStakePool)
+    };
+    if data.first() != Some(&ACCOUNT_TYPE_STAKE_POOL) || data.get(POOL_MINT_OFFSET..POOL_MINT_OFFSET + 32) != Some(lst_mint.as_ref()) {
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+    if read_u64(LAST_UPDATE_EPOCH_OFFSET)? != epoch {
+        return Err(ErrorCode::StaleExchangeRate.into());
+    }
+    let total_lamports = read_u64(TOTAL_LAMPORTS_OFFSET)?;
+    let pool_token_supply = read_u64(POOL_TOKEN_SUPPLY_OFFSET)?;
+    if total_lamports == 0 || pool_token_supply == 0 {
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+    u64::try_from(total_lamports as u128 * LST_RATE_SCALE as u128 / pool_token_supply as u128).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Reads the exchange rate of the SPL token-lending reserve whose collateral mint is
+/// `share_mint`, as underlying per share scaled by `EXCHANGE_RATE_SCALE`.
+///
+/// The reserve must have been refreshed in `slot`, like the lending program itself requires.
+fn reserve_exchange_rate(reserve: &AccountInfo, share_mint: &Pubkey, slot: u64) -> Result<u64> {
+    const RESERVE_VERSION: u8 = 1;
+    const LAST_UPDATE_SLOT_OFFSET: usize = 1;
+    const LAST_UPDATE_STALE_OFFSET: usize = 9;
+    const AVAILABLE_AMOUNT_OFFSET: usize = 171;
+    const BORROWED_AMOUNT_WADS_OFFSET: usize = 179;
+    const COLLATERAL_MINT_OFFSET: usize = 227;
+    const COLLATERAL_SUPPLY_OFFSET: usize = 259;
+    const WAD: u128 = 1_000_000_000_000_000_000;
+
+    if reserve.owner != &TOKEN_LENDING_PROGRAM_ID {
+        return Err(ErrorCode::InvalidReserveAccount.into());
+    }
+    let data = reserve.try_borrow_data()?;
+    let read = |offset: usize, len: usize| data.get(offset..offset + len).ok_or(ErrorCode::InvalidReserveAccount);
+    if data.first() != Some(&RESERVE_VERSION) || read(COLLATERAL_MINT_OFFSET, 32)? != share_mint.as_ref() {
+        return Err(ErrorCode::InvalidReserveAccount.into());
+    }
+    let last_update_slot = u64::from_le_bytes(read(LAST_UPDATE_SLOT_OFFSET, 8)?.try_into().unwrap());
+    if last_update_slot != slot || read(LAST_UPDATE_STALE_OFFSET, 1)?[0] != 0 {
+        return Err(ErrorCode::StaleExchangeRate.into());
+    }
+    let available = u64::from_le_bytes(read(AVAILABLE_AMOUNT_OFFSET, 8)?.try_into().unwrap()) as u128;
+    let borrowed_wads = u128::from_le_bytes(read(BORROWED_AMOUNT_WADS_OFFSET, 16)?.try_into().unwrap());
+    let share_supply = u64::from_le_bytes(read(COLLATERAL_SUPPLY_OFFSET, 8)?.try_into().unwrap()) as u128;
+    if share_supply == 0 {
+        return Err(ErrorCode::InvalidReserveAccount.into());
+    }
+    let total_liquidity = available.checked_add(borrowed_wads / WAD).ok_or(ErrorCode::MathOverflow)?;
+    let rate = total_liquidity.checked_mul(EXCHANGE_RATE_SCALE as u128).ok_or(ErrorCode::MathOverflow)? / share_supply;
+    u64::try_from(rate).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Returns the market's oracle-based settlement price for escrows expiring at `expiration`, in
+/// `STRIKE_PRICE_DECIMALS`.
+///
+/// This is the TWAP over the window before expiry if the market has one, or else the oracle
+/// price, guarded by the market's circuit breaker and aggregated with the feeds of its oracle
+/// registry. LST collateral is valued at its stake pool's exchange rate.
+///
+/// The oracle price must have been published within `SETTLEMENT_PRICE_TOLERANCE` of expiry
+/// (or of now, for escrows settling early), so a late crank can't settle at a later price.
+fn oracle_settlement_price(
+    market: &Market,
+    expiration: i64,
+    collateral_oracle: Option<&UncheckedAccount>,
+    price_history: Option<&AccountLoader<PriceHistory>>,
+    oracle_registry: Option<&Account<OracleRegistry>>,
+    extra_oracles: [Option<&UncheckedAccount>; 2],
+) -> Result<u64> {
+    let end = expiration.min(Clock::get()?.unix_timestamp);
+    if market.twap_window > 0 {
+        let price_history = price_history.ok_or(ErrorCode::TwapUnavailable)?;
+        return price_history.load()?.twap(end, market.twap_window);
+    }
+    let collateral_oracle = collateral_oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+    let price = load_oracle_price(&collateral_oracle.to_account_info(), &market.oracle_config)?;
+    if price.publish_time.abs_diff(end) > SETTLEMENT_PRICE_TOLERANCE as u64 {
+        return Err(ErrorCode::SettlementPriceNotAtExpiry.into());
+    }
+    if market.max_price_deviation_bps > 0 {
+        let ema_price = load_oracle_ema_price(&collateral_oracle.to_account_info(), &market.oracle_config)?;
+        market.check_price_deviation(&price, &ema_price)?;
+    }
+    let price = to_strike_price(&price)?;
+    if !market.uses_oracle_registry {
+        return market.lst_price(price);
+    }
+    let oracle_registry = oracle_registry.ok_or(ErrorCode::MissingOracleAccount)?;
+    market.lst_price(oracle_registry.aggregate_price(price, &extra_oracles, &market.oracle_config)?)
+}
+
+/// Returns the first time after `time` that is `offset` seconds past a multiple of `cadence`.
+fn next_grid_time(cadence: i64, offset: i64, time: i64) -> Result<i64> {
+    let periods = (time - offset).div_euclid(cadence) + 1;
+    periods
+        .checked_mul(cadence)
+        .and_then(|time| time.checked_add(offset))
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Closes a wSOL token account, unwrapping its whole balance into native SOL for its owner.
+///
+/// The owner must sign, as the token account's close authority.
+fn close_wrapped_sol<'info>(
+    account: &Account<'info, TokenAccount>,
+    owner: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    if account.mint != token::spl_token::native_mint::ID {
+        return Err(ErrorCode::NotWrappedSol.into());
+    }
+    let cpi_accounts = CloseAccount {
+        account: account.to_account_info(),
+        destination: owner.clone(),
+        authority: owner,
+    };
+    let cpi_ctx = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+    token::close_account(cpi_ctx)
+}
+
+/// Transfers `amount` out of an escrow's vault, skipping empty transfers.
+///
+/// The vault is its own token authority, so it signs with its PDA seeds.
+fn transfer_from_vault<'info>(
+    escrow_account: &Account<'info, EscrowAccount>,
+    vault: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let escrow_key = escrow_account.key();
+    let seeds: &[&[u8]] = &[ESCROW_VAULT_SEED, escrow_key.as_ref(), &[escrow_account.vault_bump]];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = Transfer { from: vault.clone(), to, authority: vault };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+    token::transfer(cpi_ctx, amount)
+}
+
+/// Closes an escrow's empty vault, returning its rent to `destination`.
+fn close_vault<'info>(
+    escrow_account: &Account<'info, EscrowAccount>,
+    vault: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let escrow_key = escrow_account.key();
+    let seeds: &[&[u8]] = &[ESCROW_VAULT_SEED, escrow_key.as_ref(), &[escrow_account.vault_bump]];
+    let signer_seeds = &[seeds];
+    let cpi_accounts = CloseAccount { account: vault.clone(), destination, authority: vault };
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+    token::close_account(cpi_ctx)
+}
+
+/// Creates the stats and position accounts of a system-owned writer PDA through this
+/// program, signed by the PDA, which pays for them.
+fn create_writer_accounts<'info>(
+    writer: AccountInfo<'info>,
+    writer_seeds: &[&[u8]],
+    user_stats: AccountInfo<'info>,
+    user_position: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    program: AccountInfo<'info>,
+) -> Result<()> {
+    let create_stats_ix = Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CreateUserStats {
+            user_stats: user_stats.key(),
+            owner: writer.key(),
+            system_program: system_program.key(),
+        }
+        .to_account_metas(None),
+        data: instruction::CreateUserStats {}.data(),
+    };
+    invoke_signed(
+        &create_stats_ix,
+        &[user_stats, writer.clone(), system_program.clone(), program.clone()],
+        &[writer_seeds],
+    )?;
+    let create_position_ix = Instruction {
+        program_id: crate::ID,
+        accounts: crate::accounts::CreateUserPosition {
+            user_position: user_position.key(),
+            owner: writer.key(),
+            system_program: system_program.key(),
+        }
+        .to_account_metas(None),
+        data: instruction::CreateUserPosition {}.data(),
+    };
+    invoke_signed(&create_position_ix, &[user_position, writer, system_program, program], &[writer_seeds])?;
+    Ok(())
+}
+
+/// Validates the extra feeds of an oracle registry.
+///
+/// A market aggregates two or three feeds in total, all distinct.
+fn validate_oracle_registry(market: &Market, extra_feeds: &[Pubkey], max_deviation_bps: u64) -> Result<()> {
+    if extra_feeds.is_empty() || extra_feeds.len() > MAX_EXTRA_ORACLE_FEEDS || max_deviation_bps > 10000 {
+        return Err(ErrorCode::InvalidOracleRegistry.into());
+    }
+    if extra_feeds.contains(&market.collateral_oracle) || (extra_feeds.len() == 2 && extra_feeds[0] == extra_feeds[1]) {
+        return Err(ErrorCode::InvalidOracleRegistry.into());
+    }
+    Ok(())
+}
+
+/// Validates buyback parameters.
+fn validate_buyback_params(params: &BuybackParams) -> Result<()> {
+    if params.period_duration <= 0 || params.max_slippage_bps > 10000 {
+        return Err(ErrorCode::InvalidBuybackParams.into());
+    }
+    Ok(())
+}
+
+/// Validates a list of fee collectors.
+///
+/// The list must contain between one and `MAX_FEE_COLLECTORS` entries and the weights
+/// must add up to exactly `TOTAL_FEE_WEIGHT_BPS`, so every fee is fully distributed.
+fn validate_fee_collectors(fee_collectors: &[FeeCollector]) -> Result<()> {
+    if fee_collectors.is_empty() || fee_collectors.len() > MAX_FEE_COLLECTORS {
+        return Err(ErrorCode::InvalidFeeCollectorCount.into());
+    }
+    let total_weight: u32 = fee_collectors.iter().map(|c| c.weight_bps as u32).sum();
+    if total_weight != TOTAL_FEE_WEIGHT_BPS as u32 {
+        return Err(ErrorCode::InvalidFeeCollectorWeights.into());
+    }
+    Ok(())
+}
+
+/// Validates a list of fee discount tiers.
+///
+/// At most `MAX_FEE_DISCOUNT_TIERS` tiers are allowed, sorted by strictly increasing
+/// `min_staked`, and no discount may exceed 100%.
+fn validate_fee_discount_tiers(tiers: &[FeeDiscountTier]) -> Result<()> {
+    if tiers.len() > MAX_FEE_DISCOUNT_TIERS {
+        return Err(ErrorCode::InvalidFeeDiscountTiers.into());
+    }
+    for (i, tier) in tiers.iter().enumerate() {
+        if tier.discount_bps > 10000 || (i > 0 && tier.min_staked <= tiers[i - 1].min_staked) {
+            return Err(ErrorCode::InvalidFeeDiscountTiers.into());
+        }
+    }
+    Ok(())
+}
+
+#[account]
+#[derive(InitSpace)]
+/// Structure to hold escrow account data.
+///
+/// This account stores the details of the escrow, such as the initializer (option writer),
+/// the type of option (Call or Put), strike price, expiration, collateral amount, and whether
+/// the option has been exercised.
+///
+/// Fixed-size fields come first, so the ones indexers filter on sit at the `ESCROW_*_OFFSET`
+/// byte offsets for `memcmp` filters; enums, options, and vectors, whose Borsh encoding varies
+/// in size, come last.
+pub struct EscrowAccount {
+    pub initializer_key: Pubkey,     // The user who initialized the escrow
+    pub holder: Pubkey,              // Holder paying funding on a perpetual option (default until first funded)
+    pub collateral_mint: Pubkey,     // Token mint for the collateral (SPL token)
+    pub expiration: i64,             // Expiration time (Unix timestamp)
+    pub is_exercised: bool,          // Indicates if the option has been exercised
+    pub version: u8,                 // Layout version of the account (0 if created before versioning)
+    pub option_type: OptionType,     // Call or Put option
+    pub strike_price: u64,           // Strike price for the option
+    pub collateral_amount: u64,      // Collateral amount deposited in the escrow
+    pub emissions_notional: u64,     // Notional enrolled in liquidity-mining emissions (0 if not enrolled)
+    pub manual_settlement_price: u64, // Settlement price set by governance or a keeper when the oracle can't be trusted (0 if unset)
+    pub observations: Observations,  // Oracle samples recorded over the option's life (for path-dependent payoffs)
+    pub barrier_touched: bool,       // Whether the barrier has been touched before expiry
+    pub funded_until: i64,           // End of the last funded period of a perpetual option (Unix timestamp)
+    pub vesting_revoked_at: i64,     // Time the writer revoked the vesting holder (0 if not revoked)
+    pub rental_rate: u64,            // Premium per second a renter streams to rent the exercise rights (0 if not rentable)
+    pub renter: Pubkey,              // Current renter of the exercise rights (default if not rented)
+    pub auto_exercise_opt_out: bool, // Whether the holder opted out of auto-exercise at expiry
+    pub settled_at: i64,             // Time the escrow was settled or exercised (Unix timestamp, 0 while open)
+    pub payout_recipient: Pubkey,    // Account entitled to claim the settlement proceeds
+    pub unclaimed_payout: u64,       // Settlement proceeds held in the escrow until claimed
+    pub created_at: i64,             // Time the escrow was initialized (Unix timestamp)
+    pub deposited_amount: u64,       // Collateral deposited so far
+    pub early_exercise_fee_bps: u64, // Surcharge paid to the writer on early exercise, in basis points of the collateral
+    pub quoter: Pubkey,              // First party to show interest in the escrow (default if none)
+    pub counterparty: Pubkey,        // OTC counterparty of a negotiated deal (default if none)
+    pub exercise_delegate: Pubkey,   // Account allowed to exercise on the holder's behalf (default if none)
+    pub delegate_grantor: Pubkey,    // Holder who appointed the delegate; the delegation lapses when the holder changes
+    pub unswept_fees: u64,           // Settlement fees still held in the escrow's token account until swept to the fee vault
+    // Variable-length fields, kept at the tail so the fields above sit at fixed offsets
+    pub payoff: Payoff,              // How the settlement price is determined
+    pub barrier: Option<Barrier>,    // Knock-in or knock-out barrier, if any
+    #[max_len(MAX_EXERCISE_WINDOWS)]
+    pub exercise_windows: Vec<ExerciseWindow>, // Windows for early exercise (Bermudan); empty allows it at any time
+    pub perpetual: Option<PerpetualTerms>, // Funding terms of an everlasting option, if any
+    pub vesting: Option<Vesting>,    // Vesting schedule of the holder's right to exercise, if any
+    pub fee_override_bps: Option<u64>, // Negotiated rate replacing every protocol fee rate for this escrow
+    #[max_len(MAX_ALLOWED_COUNTERPARTIES)]
+    pub allowed_counterparties: Vec<Pubkey>, // Only these accounts (and the writer) can deposit, take, or exercise a private OTC escrow; empty makes it public
+    pub price_submitter: Pubkey,     // Governance authority or keeper that set the manual settlement price (default if unset)
+    pub lent_amount: u64,            // Collateral deposited into the market's lending protocol (0 if none)
+    pub deposit_exchange_rate: u64,  // Underlying per share of interest-bearing collateral when opened, scaled by EXCHANGE_RATE_SCALE (0 for raw share payouts)
+    pub on_loan: bool,               // Whether the holder has lent the option out (the borrower is the holder until it's returned)
+    pub unclaimed_writer_amount: u64, // Settlement proceeds owed to the writer, held in the escrow until claimed
+    pub vault_bump: u8,              // Bump seed of the escrow's vault PDA (set when the vault is created)
+    pub price_disputable_until: i64, // End of the dispute window of a keeper-submitted settlement price (0 once final)
+    pub reserved: [u8; ACCOUNT_RESERVED_BYTES - 58], // Zeroed space for future fields
+}
+
+impl EscrowAccount {
+    /// Account space, including the discriminator.
+    pub const SPACE: usize = 8 + EscrowAccount::INIT_SPACE;
+
+    /// Returns the part of the collateral left after fees that an ITM holder receives.
+    ///
+    /// Binary options pay their fixed amount; other options pay all of it.
+    pub fn holder_payout(&self, amount_after_fee: u64) -> u64 {
+        match self.payoff {
+            Payoff::Binary { payout } => payout.min(amount_after_fee),
+            _ => amount_after_fee,
+        }
+    }
+
+    /// Returns the part of `amount` vested at `current_time`, freezing vesting at revocation.
+    ///
+    /// Escrows without a vesting schedule are fully vested.
+    pub fn vested_amount(&self, amount: u64, current_time: i64) -> u64 {
+        match &self.vesting {
+            None => amount,
+            Some(vesting) => {
+                let time = if self.vesting_revoked_at > 0 { current_time.min(self.vesting_revoked_at) } else { current_time };
+                vesting.vested_amount(amount, time)
+            }
+        }
+    }
+
+    /// Returns the account holding the exercise rights, if the escrow tracks one: the renter,
+    /// the vesting holder, or the holder paying a perpetual option's funding.
+    pub fn holder_key(&self) -> Option<Pubkey> {
+        if self.renter != Pubkey::default() {
+            return Some(self.renter);
+        }
+        if let Some(vesting) = &self.vesting {
+            return Some(vesting.holder);
+        }
+        if self.holder != Pubkey::default() {
+            return Some(self.holder);
+        }
+        None
+    }
+
+    /// Returns the holder `key` exercises for if it is the delegate appointed by the current
+    /// holder, or `None` otherwise.
+    pub fn delegating_holder(&self, key: &Pubkey) -> Option<Pubkey> {
+        let holder = self.holder_key()?;
+        if *key == self.exercise_delegate && holder == self.delegate_grantor {
+            return Some(holder);
+        }
+        None
+    }
+
+    /// Whether `current_time` is past the market's exercise cutoff, after which only
+    /// settlement can run.
+    pub fn is_past_exercise_cutoff(&self, market: &Market, current_time: i64) -> bool {
+        current_time >= self.expiration.saturating_sub(market.exercise_cutoff)
+    }
+
+    /// Returns how far the option is ITM at `spot_price`, in basis points of the strike.
+    pub fn moneyness_bps(&self, spot_price: u64) -> u64 {
+        let gain = match self.option_type {
+            OptionType::Call => spot_price.saturating_sub(self.strike_price),
+            OptionType::Put => self.strike_price.saturating_sub(spot_price),
+        };
+        if self.strike_price == 0 {
+            return if gain > 0 { u64::MAX } else { 0 };
+        }
+        u64::try_from(gain as u128 * 10000 / self.strike_price as u128).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the moneyness at `spot_price` in basis points of the strike: positive when the
+    /// option is ITM and negative when it is OTM.
+    pub fn signed_moneyness_bps(&self, spot_price: u64) -> i64 {
+        if self.strike_price == 0 {
+            return i64::MAX;
+        }
+        let diff = match self.option_type {
+            OptionType::Call => spot_price as i128 - self.strike_price as i128,
+            OptionType::Put => self.strike_price as i128 - spot_price as i128,
+        };
+        i64::try_from(diff * 10000 / self.strike_price as i128).unwrap_or(if diff > 0 { i64::MAX } else { i64::MIN })
+    }
+
+    /// Returns the Black-Scholes value of a vanilla escrow, in units of the collateral.
+    ///
+    /// Uses the market's volatility curve at the escrow's time to expiry. The collateral backs
+    /// the notional the same way as in `intrinsic_value`, and the value is capped at it.
+    pub fn fair_value(&self, spot_price: u64, vol_config: &VolConfig, current_time: i64) -> Result<u64> {
+        if self.payoff != Payoff::Vanilla {
+            return Err(ErrorCode::InvalidPayoff.into());
+        }
+        let tenor = self.expiration.saturating_sub(current_time).max(0);
+        let vol_bps = vol_config.implied_vol_bps(tenor, current_time)?;
+        let premium = math::black_scholes(self.option_type.clone(), spot_price, self.strike_price, tenor, vol_config.rate_bps, vol_bps)?;
+        let base = match self.option_type {
+            OptionType::Call => spot_price,
+            OptionType::Put => self.strike_price,
+        };
+        if base == 0 {
+            return Ok(0);
+        }
+        let value = self.collateral_amount as u128 * premium as u128 / base as u128;
+        Ok(value.min(self.collateral_amount as u128) as u64)
+    }
+
+    /// Returns the writer's delta on a vanilla escrow, in units of the collateral.
+    ///
+    /// This is the position's value sensitivity to the underlying, expressed as the amount of
+    /// collateral a perp position needs to offset it. The writer is short, so it is negative
+    /// for calls and positive for puts.
+    pub fn writer_delta(&self, spot_price: u64, vol_config: &VolConfig, current_time: i64) -> Result<i64> {
+        let greeks = self.greeks(spot_price, vol_config, current_time)?;
+        let base = match self.option_type {
+            OptionType::Call => spot_price,
+            OptionType::Put => self.strike_price,
+        };
+        if base == 0 {
+            return Ok(0);
+        }
+        let exposure = greeks.delta as i128 * self.collateral_amount as i128 * spot_price as i128 / (base as i128 * GREEKS_UNIT as i128);
+        i64::try_from(-exposure).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Returns the Black-Scholes greeks of a vanilla escrow per unit of the underlying.
+    pub fn greeks(&self, spot_price: u64, vol_config: &VolConfig, current_time: i64) -> Result<Greeks> {
+        if self.payoff != Payoff::Vanilla {
+            return Err(ErrorCode::InvalidPayoff.into());
+        }
+        let tenor = self.expiration.saturating_sub(current_time).max(0);
+        let vol_bps = vol_config.implied_vol_bps(tenor, current_time)?;
+        math::greeks(self.option_type.clone(), spot_price, self.strike_price, tenor, vol_config.rate_bps, vol_bps)
+    }
+
+    /// Returns a new, unfunded escrow, whose terms `Market::validate_new_escrow` has checked.
+    pub fn new(initializer: Pubkey, leg: &EscrowLeg, expiration: i64, collateral_mint: Pubkey, terms: OptionTerms, current_time: i64) -> Self {
+        let OptionTerms { payoff, barrier, exercise_windows, perpetual, vesting, early_exercise_fee_bps, allowed_counterparties } = terms;
+        Self {
+            initializer_key: initializer,
+            option_type: leg.option_type.clone(),
+            strike_price: leg.strike_price,
+            expiration,
+            collateral_amount: leg.collateral_amount,
+            collateral_mint,
+            is_exercised: false,
+            emissions_notional: 0,
+            manual_settlement_price: 0,
+            observations: Observations::new(&payoff, current_time),
+            payoff,
+            barrier,
+            barrier_touched: false,
+            exercise_windows,
+            perpetual,
+            holder: Pubkey::default(),
+            funded_until: current_time,
+            vesting,
+            vesting_revoked_at: 0,
+            rental_rate: 0,
+            renter: Pubkey::default(),
+            auto_exercise_opt_out: false,
+            settled_at: 0,
+            payout_recipient: Pubkey::default(),
+            unclaimed_payout: 0,
+            created_at: current_time,
+            deposited_amount: 0,
+            early_exercise_fee_bps,
+            quoter: Pubkey::default(),
+            counterparty: Pubkey::default(),
+            fee_override_bps: None,
+            allowed_counterparties,
+            exercise_delegate: Pubkey::default(),
+            delegate_grantor: Pubkey::default(),
+            unswept_fees: 0,
+            version: ESCROW_ACCOUNT_VERSION,
+            price_submitter: Pubkey::default(),
+            lent_amount: 0,
+            deposit_exchange_rate: 0,
+            on_loan: false,
+            unclaimed_writer_amount: 0,
+            vault_bump: 0,
+            price_disputable_until: 0,
+            reserved: [0; ACCOUNT_RESERVED_BYTES - 58],
+        }
+    }
+
+    /// Returns the settlement price the escrow determines on its own, if any: the manual price
+    /// set by governance, the average of an Asian option's observations, or the most favorable
+    /// observation of a lookback option.
+    ///
+    /// A price submitted by a keeper only counts once its dispute window has passed.
+    pub fn own_settlement_price(&self) -> Result<Option<u64>> {
+        if self.manual_settlement_price > 0 {
+            if Clock::get()?.unix_timestamp < self.price_disputable_until {
+                return Err(ErrorCode::SettlementPriceInDispute.into());
+            }
+            return Ok(Some(self.manual_settlement_price));
+        }
+        match self.payoff {
+            Payoff::Asian { .. } => self.observations.average().map(Some),
+            Payoff::Lookback { .. } => self.observations.extreme(&self.option_type).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Records a deposit of `amount` into the escrow's vault.
+    ///
+    /// Deposits can't exceed the collateral the escrow was written for, as funding checks and
+    /// payouts all trust `deposited_amount`.
+    pub fn record_deposit(&mut self, amount: u64) -> Result<()> {
+        let deposited_amount = self.deposited_amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        if deposited_amount > self.collateral_amount {
+            return Err(ErrorCode::DepositExceedsCollateral.into());
+        }
+        self.deposited_amount = deposited_amount;
+        Ok(())
+    }
+
+    /// Enrolls the escrow's notional in the market's liquidity-mining emissions once the escrow is
+    /// fully funded and the emissions accounts are passed.
+    ///
+    /// Notional only earns emissions while it is backed by collateral, so escrows aren't
+    /// enrolled when they are written.
+    pub fn enroll_emissions(
+        &mut self,
+        emissions_schedule: Option<&mut Account<EmissionsSchedule>>,
+        writer_emissions: Option<&mut Account<WriterEmissions>>,
+        current_time: i64,
+    ) -> Result<()> {
+        if self.deposited_amount < self.collateral_amount || self.emissions_notional > 0 {
+            return Ok(());
+        }
+        if let (Some(emissions_schedule), Some(writer_emissions)) = (emissions_schedule, writer_emissions) {
+            emissions_schedule.add_notional(writer_emissions, self.collateral_amount, current_time)?;
+            self.emissions_notional = self.collateral_amount;
+        }
+        Ok(())
+    }
+
+    /// Fails while the escrow's collateral is deposited in a lending protocol.
+    ///
+    /// Collateral must be recalled with `recall_collateral` before it can be paid out.
+    pub fn require_not_lent(&self) -> Result<()> {
+        if self.lent_amount > 0 {
+            return Err(ErrorCode::CollateralLent.into());
+        }
+        Ok(())
+    }
+
+    /// Fails while the option is lent out.
+    ///
+    /// The loan must be ended with `end_option_loan` before the escrow can settle or be cancelled.
+    pub fn require_not_on_loan(&self) -> Result<()> {
+        if self.on_loan {
+            return Err(ErrorCode::OptionOnLoan.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless `key` may interact with the escrow: anyone for public escrows, and only
+    /// the writer and named counterparties for private OTC escrows.
+    pub fn require_allowed(&self, key: &Pubkey) -> Result<()> {
+        if self.allowed_counterparties.is_empty() || *key == self.initializer_key || self.allowed_counterparties.contains(key) {
+            return Ok(());
+        }
+        Err(ErrorCode::NotAllowedCounterparty.into())
+    }
+
+    /// Returns the fee rate charged on this escrow: its negotiated override if it has one, or
+    /// else the governance rate.
+    pub fn fee_rate(&self, governance_fee_bps: u64) -> u64 {
+        self.fee_override_bps.unwrap_or(governance_fee_bps)
+    }
+
+    /// Whether a perpetual option's funding has lapsed, ending it.
+    pub fn is_funding_lapsed(&self, current_time: i64) -> bool {
+        self.perpetual.is_some() && current_time > self.funded_until
+    }
+
+    /// Whether a knock-out barrier has been touched, ending the option early.
+    pub fn is_knocked_out(&self) -> bool {
+        matches!(self.barrier, Some(Barrier { kind: BarrierKind::KnockOut, .. })) && self.barrier_touched
+    }
+
+    /// Whether the barrier state lets the option pay out: knock-in options must have touched
+    /// their barrier and knock-out options must not have.
+    pub fn barrier_allows_payout(&self) -> bool {
+        match &self.barrier {
+            None => true,
+            Some(barrier) => match barrier.kind {
+                BarrierKind::KnockIn => self.barrier_touched,
+                BarrierKind::KnockOut => !self.barrier_touched,
+            },
+        }
+    }
+
+    /// Returns the value of exercising the option at `spot_price`, in units of the collateral.
+    ///
+    /// Both prices use `STRIKE_PRICE_DECIMALS`. The collateral backs the full notional, so a
+    /// call pays `collateral * (spot - strike) / spot` and a put pays
+    /// `collateral * (strike - spot) / strike`. Binary options are worth their fixed payout.
+    pub fn intrinsic_value(&self, spot_price: u64) -> Result<u64> {
+        let (gain, base) = match self.option_type {
+            OptionType::Call => (spot_price.saturating_sub(self.strike_price), spot_price),
+            OptionType::Put => (self.strike_price.saturating_sub(spot_price), self.strike_price),
+        };
+        if gain == 0 || base == 0 {
+            return Ok(0);
+        }
+        if let Payoff::Binary { payout } = self.payoff {
+            return Ok(payout);
+        }
+        u64::try_from(self.collateral_amount as u128 * gain as u128 / base as u128).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+}
+
+/// Governance account storing key parameters for the protocol.
+///
+/// The governance account stores the fee rates (in basis points) for the protocol and the
+/// weighted list of fee collectors. It also stores the governance authority, which is allowed
+/// to update these parameters.
+#[account]
+#[derive(InitSpace)]
+pub struct Governance {
+    pub init_fee_bps: u64,                  // Annualized fee on escrow initialization in basis points (e.g., 500 = 5.00%)
+    pub settlement_fee_bps: u64,            // Fee on settlement at expiry in basis points
+    pub exercise_fee_bps: u64,              // Fee on early exercise in basis points
+    #[max_len(MAX_FEE_COLLECTORS)]
+    pub fee_collectors: Vec<FeeCollector>,  // Weighted recipients of protocol fees
+    pub governance_authority: Pubkey,       // Account authorized to update governance settings
+    pub treasury_timelock: i64,             // Delay in seconds before a queued treasury withdrawal can execute
+    #[max_len(MAX_FEE_DISCOUNT_TIERS)]
+    pub fee_discount_tiers: Vec<FeeDiscountTier>, // Fee discounts for governance token stakers, by stake size
+    pub proposal_count: u64,                // Number of proposals created (the next proposal's ID)
+    pub proposal_params: ProposalParams,    // Rules for on-chain proposals
+    pub claim_grace_period: i64,            // Seconds a holder has to claim settlement proceeds before the writer can claw them back (0 disables clawback)
+    pub cancellation_fee_bps: u64,          // Fee a writer pays the treasury for cancelling an escrow after interest was shown
+    pub min_init_fee_bps: u64,              // Floor on the tenor-pro-rated initialization fee, in basis points
+    pub fee_admin: Pubkey,                  // Account allowed to approve negotiated fee overrides, besides the authority
+    pub crank_fee_bps: u64,                 // Share of the collateral left after fees paid to whoever settles an expired escrow
+    pub pending_treasury_timelock: i64,     // Lower treasury timelock waiting to take effect (0 if none)
+    pub treasury_timelock_change_at: i64,   // Time the pending treasury timelock takes effect (Unix timestamp)
+    pub reserved: [u8; ACCOUNT_RESERVED_BYTES - 24], // Zeroed space for future fields
+}
+
+/// Rules for on-chain governance proposals.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct ProposalParams {
+    pub proposal_threshold: u64,    // Voting power needed to create a proposal
+    pub quorum_votes: u64,          // Total votes needed for a proposal to pass
+    pub voting_period: i64,         // Length of the voting period in seconds (0 disables proposals)
+    pub execution_delay: i64,       // Delay in seconds between the end of voting and execution
+}
+
+impl Governance {
+    /// Returns the treasury timelock in force at `current_time`: a pending decrease once it
+    /// has matured, and never less than `MIN_TREASURY_TIMELOCK`.
+    pub fn current_treasury_timelock(&self, current_time: i64) -> i64 {
+        let timelock = if self.pending_treasury_timelock > 0 && current_time >= self.treasury_timelock_change_at {
+            self.pending_treasury_timelock
+        } else {
+            self.treasury_timelock
+        };
+        timelock.max(MIN_TREASURY_TIMELOCK)
+    }
+
+    /// Returns the initialization fee for an escrow with `tenor` seconds to expiry.
+    ///
+    /// The fee rate is annual, so the fee is `init_fee_bps × collateral × tenor / 1 year`,
+    /// never less than `min_init_fee_bps` of the collateral. Tenors beyond a year (including
+    /// perpetual options) pay one year's fee, so weekly options aren't charged like LEAPs.
+    pub fn init_fee(&self, collateral_amount: u64, tenor: i64) -> Result<u64> {
+        let tenor = tenor.clamp(0, SECONDS_PER_YEAR) as u128;
+        let fee = collateral_amount as u128 * self.init_fee_bps as u128 * tenor / (10000 * SECONDS_PER_YEAR as u128);
+        let floor = collateral_amount as u128 * self.min_init_fee_bps as u128 / 10000;
+        u64::try_from(fee.max(floor)).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Applies the staking discount of the highest tier the stake qualifies for.
+    ///
+    /// Only stake held for `STAKE_DISCOUNT_MIN_AGE` counts, so tokens staked in the same
+    /// transaction (e.g. with a flash loan) earn no discount.
+    pub fn apply_fee_discount(&self, fee: u64, stake_position: Option<&Account<StakePosition>>, current_time: i64) -> u64 {
+        let staked = stake_position.map_or(0, |position| position.discount_stake(current_time));
+        let discount_bps = self
+            .fee_discount_tiers
+            .iter()
+            .filter(|tier| staked >= tier.min_staked)
+            .map(|tier| tier.discount_bps)
+            .max()
+            .unwrap_or(0);
+        fee - (fee as u128 * discount_bps as u128 / 10000) as u64
+    }
+}
+
+/// A negotiated fee rate pre-approved by governance for one writer and OTC counterparty.
+///
+/// The approval is consumed (closed) by the next escrow the writer initializes with it, which
+/// then charges `fee_bps` in place of every protocol fee rate, with no tenor pro-rating.
+#[account]
+#[derive(InitSpace)]
+pub struct FeeApproval {
+    pub writer: Pubkey,             // Writer allowed to use the rate
+    pub counterparty: Pubkey,       // OTC counterparty the rate was negotiated with
+    pub fee_bps: u64,               // Negotiated fee rate, in basis points
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A fee discount granted to stakers holding at least `min_staked` governance tokens.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct FeeDiscountTier {
+    pub min_staked: u64,    // Minimum staked governance tokens to qualify
+    pub discount_bps: u16,  // Discount on protocol fees in basis points
+}
+
+/// Market account storing per-collateral-mint settings.
+///
+/// The market records the mint in which fees are charged and the oracles used to convert
+/// collateral-denominated fees into that mint.
+#[account]
+#[derive(InitSpace)]
+pub struct Market {
+    pub collateral_mint: Pubkey,    // Collateral mint this market covers
+    pub collateral_decimals: u8,    // Decimals of the collateral mint
+    pub fee_mint: Pubkey,           // Mint fees are charged in (the collateral mint by default)
+    pub fee_decimals: u8,           // Decimals of the fee mint
+    pub collateral_oracle: Pubkey,  // Pyth price feed for the collateral mint
+    pub fee_oracle: Pubkey,         // Pyth price feed for the fee mint
+    pub points_weights: PointsWeights, // Loyalty points earned per unit of activity in this market
+    pub paused_flags: u8,           // Bitmask of PAUSE_* flags blocking activity in this market
+    pub max_open_notional_per_user: u64, // Maximum notional a single user can have written (0 for no limit)
+    pub open_interest: u64,         // Collateral currently locked in open escrows
+    pub open_interest_cap: u64,     // Maximum open interest (0 for no cap)
+    pub min_strike_bps: u64,        // Lowest allowed strike, in basis points of the oracle price
+    pub max_strike_bps: u64,        // Highest allowed strike, in basis points of the oracle price (0 disables the band)
+    pub max_price_deviation_bps: u64, // Largest allowed deviation of the settlement price from the EMA price (0 disables the circuit breaker)
+    pub oracle_config: OracleConfig, // Staleness and confidence limits for this market's oracles
+    pub uses_oracle_registry: bool, // Whether settlement aggregates the feeds of the market's oracle registry
+    pub twap_window: i64,           // Length of the pre-expiry TWAP window used for settlement (0 settles on the spot price)
+    pub auto_exercise_threshold_bps: u64, // Moneyness, in basis points of the strike, at which settlement auto-exercises (0 exercises anything ITM)
+    pub exercise_cutoff: i64,       // Seconds before expiry after which only settlement can run
+    pub funding_deadline: i64,      // Seconds after creation within which an escrow must be fully funded (0 disables reaping)
+    pub max_tenor: i64,             // Longest allowed time to expiry, in seconds (0 for no limit)
+    pub compliance_authority: Pubkey, // Authority managing the participant whitelist (default for a permissionless market)
+    pub gate_mint: Pubkey,          // Membership or credential mint participants must hold (default for no token gate)
+    pub gate_min_amount: u64,       // Minimum balance of the gate mint participants must hold
+    pub bump: u8,                   // Bump seed of this PDA
+    pub max_premium_multiple_bps: u64, // Largest allowed ratio between a fill's premium and its fair value, in basis points (0 disables the bounds)
+    pub keeper_price_inputs: bool,  // Whether staked keepers may submit manual settlement prices
+    pub restricted_settlement: bool, // Whether only each series' configured settlers may settle
+    pub attestation_signer: Pubkey, // Key whose ed25519-signed price attestations settle the market's escrows (default disables them)
+    pub lst_collateral: bool,       // Whether the collateral is an LST valued at its stake pool's exchange rate
+    pub lst_exchange_rate: u64,     // Underlying per unit of collateral, scaled by LST_RATE_SCALE, last read from the stake pool
+    pub lst_rate_epoch: u64,        // Epoch the exchange rate was read in
+    pub interest_bearing_collateral: bool, // Whether the collateral is a lending share (e.g. a cToken) whose payouts are in underlying terms
+    pub backstopped: bool,          // Whether a backstop pool takes over defaulting writers before their escrows are reaped
+    pub uses_expiry_grid: bool,     // Whether new escrows must expire on the market's grid of standard expiries
+    pub reserved: [u8; ACCOUNT_RESERVED_BYTES - 62], // Zeroed space for future fields
+}
+
+/// Loyalty points earned per 10000 units (of the collateral mint) of each activity.
+///
+/// Weights are set per market because amounts in different collateral mints aren't comparable.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct PointsWeights {
+    pub notional_written: u64,  // Points per 10000 units of notional written
+    pub premium_paid: u64,      // Points per 10000 units of premium paid
+    pub fees_paid: u64,         // Points per 10000 units of protocol fees paid
+}
+
+impl Market {
+    /// Checks a fill's premium against the escrow's fair value.
+    ///
+    /// The premium must lie within `max_premium_multiple_bps` of the fair value in either
+    /// direction, e.g. 20000 accepts anything from half to twice the fair value. A worthless
+    /// option only has the upper bound. Zero disables the check.
+    pub fn check_premium_bounds(&self, premium: u64, fair_value: u64) -> Result<()> {
+        if self.max_premium_multiple_bps == 0 {
+            return Ok(());
+        }
+        let multiple = self.max_premium_multiple_bps as u128;
+        let too_high = premium as u128 * 10000 > fair_value as u128 * multiple;
+        let too_low = (premium as u128) * multiple < fair_value as u128 * 10000;
+        if too_high || too_low {
+            return Err(ErrorCode::PremiumOutOfBounds.into());
+        }
+        Ok(())
+    }
+
+    /// Adds collateral to the market's open interest, enforcing the cap.
+    pub fn add_open_interest(&mut self, collateral_amount: u64) -> Result<()> {
+        self.open_interest = self.open_interest.checked_add(collateral_amount).ok_or(ErrorCode::MathOverflow)?;
+        if self.open_interest_cap > 0 && self.open_interest > self.open_interest_cap {
+            return Err(ErrorCode::OpenInterestCapExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if any of the given pause flags is set.
+    pub fn require_not_paused(&self, flags: u8) -> Result<()> {
+        if self.paused_flags & flags != 0 {
+            return Err(ErrorCode::MarketPaused.into());
+        }
+        Ok(())
+    }
+
+    /// Validates the terms of a new escrow in this market, returning its effective expiration
+    /// (perpetual options never expire).
+    pub fn validate_new_escrow(
+        &self,
+        collateral_oracle: Option<&UncheckedAccount>,
+        expiry_grid: Option<&ExpiryGrid>,
+        leg: &EscrowLeg,
+        expiration: i64,
+        terms: &OptionTerms,
+        current_time: i64,
+    ) -> Result<i64> {
+        // Basket and quanto strikes aren't quoted in the collateral oracle's price
+        if !matches!(terms.payoff, Payoff::Basket | Payoff::Quanto { .. }) {
+            self.validate_strike(leg.strike_price, collateral_oracle)?;
+        }
+        if let Some(perpetual) = &terms.perpetual {
+            if perpetual.funding_period <= 0 || perpetual.funding_per_period == 0 {
+                return Err(ErrorCode::InvalidPerpetualTerms.into());
+            }
+        }
+        // Perpetual options never expire
+        let expiration = if terms.perpetual.is_some() { i64::MAX } else { expiration };
+        if terms.perpetual.is_none() {
+            self.validate_tenor(expiration, current_time)?;
+            self.require_standard_expiry(expiry_grid, expiration)?;
+        }
+        terms.payoff.validate(current_time, expiration, leg.collateral_amount)?;
+        validate_exercise_windows(&terms.exercise_windows, expiration)?;
+        if let Some(vesting) = &terms.vesting {
+            vesting.validate()?;
+        }
+        if terms.early_exercise_fee_bps > 10000 {
+            return Err(ErrorCode::FeeRateTooHigh.into());
+        }
+        if terms.allowed_counterparties.len() > MAX_ALLOWED_COUNTERPARTIES {
+            return Err(ErrorCode::TooManyCounterparties.into());
+        }
+        Ok(expiration)
+    }
+
+    /// Returns whether only whitelisted participants can trade in this market.
+    pub fn is_permissioned(&self) -> bool {
+        self.compliance_authority != Pubkey::default()
+    }
+
+    /// Fails if settlement in the market is restricted and `settler` isn't one of the series'
+    /// settlers.
+    ///
+    /// The settler list's address, and so its series, is checked by the context's seeds.
+    pub fn require_settler(&self, series_settlers: Option<&SeriesSettlers>, settler: &Pubkey) -> Result<()> {
+        if !self.restricted_settlement {
+            return Ok(());
+        }
+        match series_settlers {
+            Some(series_settlers) if series_settlers.settlers.contains(settler) => Ok(()),
+            _ => Err(ErrorCode::NotSeriesSettler.into()),
+        }
+    }
+
+    /// Fails if the market is permissioned and the participant has no whitelist entry.
+    ///
+    /// The entry's address is checked by the context's seeds, so its presence is enough.
+    pub fn require_whitelisted<T>(&self, whitelist_entry: &Option<T>) -> Result<()> {
+        if self.is_permissioned() && whitelist_entry.is_none() {
+            return Err(ErrorCode::NotWhitelisted.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if the market is token-gated and the participant doesn't hold enough of the gate
+    /// mint in the given token account.
+    pub fn require_gate_token(&self, gate_token_account: Option<&TokenAccount>, participant: &Pubkey) -> Result<()> {
+        if self.gate_mint == Pubkey::default() {
+            return Ok(());
+        }
+        match gate_token_account {
+            Some(account) if account.owner == *participant
+                && account.mint == self.gate_mint
+                && account.amount >= self.gate_min_amount => Ok(()),
+            _ => Err(ErrorCode::MissingGateToken.into()),
+        }
+    }
+
+    /// Returns whether fees are charged in a mint other than the collateral.
+    pub fn has_separate_fee_mint(&self) -> bool {
+        self.fee_mint != self.collateral_mint
+    }
+
+    /// Checks that a strike lies within the market's band around the current oracle price.
+    pub fn validate_strike(&self, strike_price: u64, collateral_oracle: Option<&UncheckedAccount>) -> Result<()> {
+        if self.max_strike_bps == 0 {
+            return Ok(());
+        }
+        let collateral_oracle = collateral_oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+        let spot_price = self.lst_price(to_strike_price(&load_oracle_price(&collateral_oracle.to_account_info(), &self.oracle_config)?)?)? as u128;
+        let strike_price = strike_price as u128;
+        if strike_price * 10000 < spot_price * self.min_strike_bps as u128
+            || strike_price * 10000 > spot_price * self.max_strike_bps as u128
+        {
+            return Err(ErrorCode::StrikeOutOfBand.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if `expiration` is further out than the market's maximum tenor.
+    pub fn validate_tenor(&self, expiration: i64, current_time: i64) -> Result<()> {
+        if self.max_tenor > 0 && expiration.saturating_sub(current_time) > self.max_tenor {
+            return Err(ErrorCode::TenorTooLong.into());
+        }
+        Ok(())
+    }
+
+    /// Fails if the market lists on a grid of standard expiries and `expiration` isn't on it.
+    ///
+    /// The grid's address is checked by the context's seeds.
+    pub fn require_standard_expiry(&self, expiry_grid: Option<&ExpiryGrid>, expiration: i64) -> Result<()> {
+        if !self.uses_expiry_grid {
+            return Ok(());
+        }
+        let expiry_grid = expiry_grid.ok_or(ErrorCode::MissingExpiryGridAccount)?;
+        if !expiry_grid.is_standard(expiration) {
+            return Err(ErrorCode::NonStandardExpiry.into());
+        }
+        Ok(())
+    }
+
+    /// Converts a price of an LST's underlying (e.g. SOL) into a price of the market's LST
+    /// collateral, in `STRIKE_PRICE_DECIMALS`.
+    ///
+    /// Markets whose collateral isn't an LST return the price unchanged, so the oracle of an LST
+    /// market prices the underlying and the stake pool's exchange rate adds the staking yield.
+    pub fn lst_price(&self, price: u64) -> Result<u64> {
+        let Some(rate) = self.lst_rate()? else {
+            return Ok(price);
+        };
+        u64::try_from(price as u128 * rate as u128 / LST_RATE_SCALE as u128).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Like `lst_price`, for an oracle price used to convert amounts of collateral.
+    pub fn lst_oracle_price(&self, price: Price) -> Result<Price> {
+        let Some(rate) = self.lst_rate()? else {
+            return Ok(price);
+        };
+        let scaled = i64::try_from(price.price as i128 * rate as i128 / LST_RATE_SCALE as i128).map_err(|_| ErrorCode::MathOverflow)?;
+        let conf = u64::try_from(price.conf as u128 * rate as u128 / LST_RATE_SCALE as u128).map_err(|_| ErrorCode::MathOverflow)?;
+        Ok(Price { price: scaled, conf, ..price })
+    }
+
+    /// Returns the current exchange rate of interest-bearing collateral, or `None` for plain
+    /// collateral.
+    ///
+    /// `collateral_reserve` is the lending reserve whose shares are the collateral.
+    pub fn collateral_exchange_rate(&self, collateral_reserve: Option<&UncheckedAccount>) -> Result<Option<u64>> {
+        if !self.interest_bearing_collateral {
+            return Ok(None);
+        }
+        let collateral_reserve = collateral_reserve.ok_or(ErrorCode::MissingReserveAccount)?;
+        reserve_exchange_rate(collateral_reserve, &self.collateral_mint, Clock::get()?.slot).map(Some)
+    }
+
+    /// Returns the LST exchange rate, or `None` if the collateral isn't an LST.
+    ///
+    /// The rate must have been refreshed this epoch, since stake pools accrue rewards per epoch.
+    fn lst_rate(&self) -> Result<Option<u64>> {
+        if !self.lst_collateral {
+            return Ok(None);
+        }
+        if self.lst_rate_epoch != Clock::get()?.epoch {
+            return Err(ErrorCode::StaleExchangeRate.into());
+        }
+        Ok(Some(self.lst_exchange_rate))
+    }
+
+    /// Circuit breaker: fails if `price` deviates from the EMA price by more than the market allows.
+    ///
+    /// Protects settlement against a single manipulated print at expiry.
+    pub fn check_price_deviation(&self, price: &Price, ema_price: &Price) -> Result<()> {
+        if self.max_price_deviation_bps == 0 {
+            return Ok(());
+        }
+        let ema_price = ema_price.scale_to_exponent(price.expo).ok_or(ErrorCode::MathOverflow)?;
+        let deviation = (price.price as i128 - ema_price.price as i128).unsigned_abs();
+        if deviation * 10000 > ema_price.price.unsigned_abs() as u128 * self.max_price_deviation_bps as u128 {
+            return Err(ErrorCode::PriceDeviationTooHigh.into());
+        }
+        Ok(())
+    }
+
+    /// Converts an amount of collateral into the equivalent amount of the fee mint.
+    ///
+    /// Both oracles must quote their mint in the same currency (e.g. USD).
+    pub fn convert_to_fee_mint(
+        &self,
+        amount: u64,
+        collateral_oracle: Option<&UncheckedAccount>,
+        fee_oracle: Option<&UncheckedAccount>,
+    ) -> Result<u64> {
+        let collateral_oracle = collateral_oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+        let fee_oracle = fee_oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+        let collateral_price = self.lst_oracle_price(load_oracle_price(&collateral_oracle.to_account_info(), &self.oracle_config)?)?;
+        let fee_price = load_oracle_price(&fee_oracle.to_account_info(), &self.oracle_config)?;
+        convert_amount(amount, &collateral_price, self.collateral_decimals, &fee_price, self.fee_decimals)
+    }
+}
+
+/// Enum to select which protocol fee rate is being updated.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum FeeType {
+    Init,       // Fee charged by `initialize_escrow`
+    Settlement, // Fee charged by `settle_escrow`
+    Exercise,   // Fee charged by `exercise_early`
+    Cancellation, // Fee charged by `cancel_escrow` once a quoter has shown interest
+    InitFloor,  // Minimum fee charged by `initialize_escrow`, however short the tenor
+    Crank,      // Incentive paid to whoever settles an expired escrow
+}
+
+/// A single recipient of protocol fees and its share.
+///
+/// Fees are split across all collectors proportionally to `weight_bps`, which lets the
+/// protocol route fees to the treasury, insurance fund, and stakers in one step.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct FeeCollector {
+    pub recipient: Pubkey,  // Wallet or PDA entitled to claim this share of protocol fees
+    pub weight_bps: u16,    // Share of each fee in basis points (all weights sum to 10000)
+}
+
+/// Fee vault holding the protocol fees collected in a single token mint.
+///
+/// Fees are transferred into the vault's token account as part of user actions and accrued
+/// to the fee collectors as pending claims. Collectors withdraw them with `claim_fees`.
+#[account]
+#[derive(InitSpace)]
+pub struct FeeVault {
+    pub mint: Pubkey,           // Token mint of the fees held by this vault
+    pub token_account: Pubkey,  // Token account holding the fees (owned by this PDA)
+    pub bump: u8,               // Bump seed of this PDA
+    #[max_len(MAX_FEE_CLAIMS)]
+    pub claims: Vec<FeeClaim>,  // Fees accrued to each collector and not yet claimed
+}
+
+/// Protocol treasury holding tokens of a single mint.
+///
+/// The treasury token account is owned by this PDA, so treasury funds can only move through
+/// `withdraw_treasury`, which is gated by governance and optionally timelocked.
+#[account]
+#[derive(InitSpace)]
+pub struct Treasury {
+    pub mint: Pubkey,                                   // Token mint held by the treasury
+    pub token_account: Pubkey,                          // Token account holding the funds (owned by this PDA)
+    pub bump: u8,                                       // Bump seed of this PDA
+    pub pending_withdrawal: Option<PendingWithdrawal>,  // Withdrawal waiting for the timelock to pass
+}
+
+impl Treasury {
+    /// Consumes the queued withdrawal of `amount` to `destination`, failing unless it was
+    /// queued and has unlocked.
+    pub fn consume_withdrawal(&mut self, amount: u64, destination: Pubkey) -> Result<()> {
+        let pending = self.pending_withdrawal.take().ok_or(ErrorCode::WithdrawalNotQueued)?;
+        if pending.amount != amount || pending.destination != destination {
+            return Err(ErrorCode::WithdrawalNotQueued.into());
+        }
+        if Clock::get()?.unix_timestamp < pending.unlock_time {
+            return Err(ErrorCode::TimelockNotExpired.into());
+        }
+        Ok(())
+    }
+}
+
+/// A treasury withdrawal queued by governance.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct PendingWithdrawal {
+    pub amount: u64,         // Amount to withdraw
+    pub destination: Pubkey, // Token account receiving the funds
+    pub unlock_time: i64,    // Earliest time the withdrawal can execute (Unix timestamp)
+}
+
+/// Fees accrued to a single collector in a fee vault.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct FeeClaim {
+    pub recipient: Pubkey,  // Collector entitled to the fees
+    pub amount: u64,        // Unclaimed fee amount
+}
+
+impl FeeVault {
+    /// Accrues a fee to the collectors according to their weights.
+    ///
+    /// The last collector receives any rounding remainder so that the full fee is always
+    /// accounted for.
+    pub fn accrue(&mut self, fee_collectors: &[FeeCollector], fee: u64) -> Result<()> {
+        let mut remaining_fee = fee;
+        for (i, collector) in fee_collectors.iter().enumerate() {
+            let share = if i == fee_collectors.len() - 1 {
+                remaining_fee
+            } else {
+                (fee as u128 * collector.weight_bps as u128 / TOTAL_FEE_WEIGHT_BPS as u128) as u64
+            };
+            remaining_fee -= share;
+            if share == 0 {
+                continue;
+            }
+            self.credit(collector.recipient, share)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `amount` to the pending fees of `recipient`.
+    ///
+    /// The last claim slot is reserved for the treasury of the vault's mint: once the other
+    /// slots are taken, fees for a collector without a claim go to the treasury instead, so
+    /// accruing on a user's path never fails.
+    pub fn credit(&mut self, recipient: Pubkey, amount: u64) -> Result<()> {
+        match self.claims.iter_mut().find(|c| c.recipient == recipient) {
+            Some(claim) => claim.amount = claim.amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?,
+            None => {
+                if self.claims.len() >= MAX_FEE_CLAIMS - 1 {
+                    let (treasury, _) = Pubkey::find_program_address(&[TREASURY_SEED, self.mint.as_ref()], &crate::ID);
+                    if recipient != treasury {
+                        return self.credit(treasury, amount);
+                    }
+                }
+                if self.claims.len() >= MAX_FEE_CLAIMS {
+                    return Err(ErrorCode::FeeVaultFull.into());
+                }
+                self.claims.push(FeeClaim { recipient, amount });
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the pending fees of a collector.
+    pub fn take_claim(&mut self, recipient: &Pubkey) -> Result<u64> {
+        self.pop_claim(recipient).ok_or(ErrorCode::NoFeesToClaim.into())
+    }
+
+    /// Removes and returns the pending fees of a collector, if it has any.
+    pub fn pop_claim(&mut self, recipient: &Pubkey) -> Option<u64> {
+        let index = self.claims.iter().position(|c| c.recipient == *recipient)?;
+        Some(self.claims.remove(index).amount)
+    }
+}
+
+/// Staking pool sharing protocol fees with governance token stakers.
+///
+/// The pool is a fee collector: its share of fees stays in the fee vault of the reward mint
+/// as a pending claim and is folded into `reward_per_share` before every stake change, which
+/// gives every staker exactly the rewards accrued while they were staked. Rewards and early-exit
+/// penalties are shared by boosted amount, so longer locks earn more.
+#[account]
+#[derive(InitSpace)]
+pub struct StakingPool {
+    pub stake_mint: Pubkey,            // Governance token mint that is staked
+    pub reward_mint: Pubkey,           // Fee mint in which rewards are paid
+    pub stake_vault: Pubkey,           // Token account holding staked tokens (owned by this PDA)
+    pub total_staked: u64,             // Total governance tokens staked
+    pub total_boosted: u64,            // Sum of all positions' boosted amounts
+    pub reward_per_share: u128,        // Rewards accrued per boosted token, scaled by REWARD_PRECISION
+    pub penalty_per_share: u128,       // Early-exit penalties accrued per boosted token, scaled by REWARD_PRECISION
+    pub max_lock_duration: i64,        // Longest allowed lock in seconds (0 disables locking)
+    pub max_boost_bps: u64,            // Extra reward weight of a maximum-length lock in basis points
+    pub early_exit_penalty_bps: u64,   // Penalty for exiting with a full maximum lock remaining
+    pub bump: u8,                      // Bump seed of this PDA
+}
+
+impl StakingPool {
+    /// Moves the pool's pending fee claim into the reward-per-share accumulator.
+    ///
+    /// While nothing is staked the claim is left in the fee vault, so it is distributed to
+    /// the first stakers instead of being lost.
+    pub fn sync(&mut self, pool_key: &Pubkey, fee_vault: &mut FeeVault) -> Result<()> {
+        if self.total_boosted == 0 {
+            return Ok(());
+        }
+        if let Some(amount) = fee_vault.pop_claim(pool_key) {
+            let increment = (amount as u128)
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(ErrorCode::MathOverflow)?
+                / self.total_boosted as u128;
+            self.reward_per_share = self.reward_per_share.checked_add(increment).ok_or(ErrorCode::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the fraction of the maximum lock still remaining, in basis points.
+    pub fn remaining_lock_bps(&self, lock_end: i64, current_time: i64) -> u64 {
+        if self.max_lock_duration == 0 || lock_end <= current_time {
+            return 0;
+        }
+        let remaining = (lock_end - current_time).min(self.max_lock_duration);
+        (remaining as u128 * 10000 / self.max_lock_duration as u128) as u64
+    }
+
+    /// Returns the reward weight of `amount` tokens locked until `lock_end`.
+    pub fn boosted_amount(&self, amount: u64, lock_end: i64, current_time: i64) -> Result<u64> {
+        let boost_bps = self.max_boost_bps as u128 * self.remaining_lock_bps(lock_end, current_time) as u128 / 10000;
+        u64::try_from(amount as u128 * (10000 + boost_bps) / 10000).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Returns the penalty for unstaking `amount` tokens locked until `lock_end`.
+    pub fn early_exit_penalty(&self, amount: u64, lock_end: i64, current_time: i64) -> u64 {
+        let penalty_bps = self.early_exit_penalty_bps as u128 * self.remaining_lock_bps(lock_end, current_time) as u128 / 10000;
+        (amount as u128 * penalty_bps / 10000) as u64
+    }
+}
+
+/// A staker's position in the staking pool.
+#[account]
+#[derive(InitSpace)]
+pub struct StakePosition {
+    pub owner: Pubkey,             // Staker owning this position
+    pub amount: u64,               // Governance tokens staked
+    pub boosted_amount: u64,       // Reward weight of the position, including the lock boost
+    pub lock_end: i64,             // End of the lock (Unix timestamp, 0 if never locked)
+    pub reward_debt: u128,         // Rewards already accounted for, scaled by REWARD_PRECISION
+    pub penalty_debt: u128,        // Penalty shares already accounted for, scaled by REWARD_PRECISION
+    pub unclaimed_rewards: u64,    // Rewards earned but not yet claimed
+    pub vote_lock_end: i64,        // End of the latest voting period this position voted in
+    pub discount_amount: u64,      // Stake held for STAKE_DISCOUNT_MIN_AGE as of the last stake
+    pub last_stake_time: i64,      // Time of the last stake (Unix timestamp)
+    pub bump: u8,                  // Bump seed of this PDA
+}
+
+impl StakePosition {
+    /// Adds `amount` newly staked at `current_time`.
+    ///
+    /// The new tokens only count toward fee discounts once they have been held for
+    /// `STAKE_DISCOUNT_MIN_AGE`; until then the discount uses the stake that had already aged.
+    pub fn record_stake(&mut self, amount: u64, current_time: i64) -> Result<()> {
+        self.discount_amount = self.discount_stake(current_time);
+        self.amount = self.amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        self.last_stake_time = current_time;
+        Ok(())
+    }
+
+    /// Returns the stake that counts toward fee discounts at `current_time`.
+    pub fn discount_stake(&self, current_time: i64) -> u64 {
+        if current_time.saturating_sub(self.last_stake_time) >= STAKE_DISCOUNT_MIN_AGE {
+            self.amount
+        } else {
+            self.discount_amount.min(self.amount)
+        }
+    }
+
+    /// Moves the rewards earned since the last update into `unclaimed_rewards` and compounds
+    /// the position's share of early-exit penalties into `amount`.
+    ///
+    /// Must be called after `StakingPool::sync` and before `amount` or the lock changes.
+    pub fn settle_rewards(&mut self, pool: &StakingPool) -> Result<()> {
+        let accumulated = (self.boosted_amount as u128)
+            .checked_mul(pool.reward_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let earned_scaled = accumulated - self.reward_debt;
+        let earned = u64::try_from(earned_scaled / REWARD_PRECISION).map_err(|_| ErrorCode::MathOverflow)?;
+        self.unclaimed_rewards = self.unclaimed_rewards.checked_add(earned).ok_or(ErrorCode::MathOverflow)?;
+        // Keep the fractional remainder owed so rounding never loses rewards
+        self.reward_debt = accumulated - earned_scaled % REWARD_PRECISION;
+
+        // Penalty shares are governance tokens already held in the stake vault
+        let accumulated_penalty = (self.boosted_amount as u128)
+            .checked_mul(pool.penalty_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let penalty_scaled = accumulated_penalty - self.penalty_debt;
+        let penalty_share = u64::try_from(penalty_scaled / REWARD_PRECISION).map_err(|_| ErrorCode::MathOverflow)?;
+        self.amount = self.amount.checked_add(penalty_share).ok_or(ErrorCode::MathOverflow)?;
+        self.penalty_debt = accumulated_penalty - penalty_scaled % REWARD_PRECISION;
+        Ok(())
+    }
+
+    /// Recomputes the boosted amount after `amount` or the lock has changed, or to apply decay.
+    ///
+    /// Updates the pool's total and resets the reward and penalty debts, so it must be called
+    /// after `settle_rewards`.
+    pub fn update_boost(&mut self, pool: &mut StakingPool, current_time: i64) -> Result<()> {
+        let boosted_amount = pool.boosted_amount(self.amount, self.lock_end, current_time)?;
+        pool.total_boosted = (pool.total_boosted - self.boosted_amount)
+            .checked_add(boosted_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.boosted_amount = boosted_amount;
+        self.reward_debt = (boosted_amount as u128)
+            .checked_mul(pool.reward_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.penalty_debt = (boosted_amount as u128)
+            .checked_mul(pool.penalty_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Returns the governance voting power of the position.
+    ///
+    /// Staked tokens count once, plus up to once more for the remaining lock time, decaying
+    /// linearly to the plain staked amount when the lock ends.
+    pub fn voting_power(&self, pool: &StakingPool, current_time: i64) -> u64 {
+        let lock_bonus = self.amount as u128 * pool.remaining_lock_bps(self.lock_end, current_time) as u128 / 10000;
+        self.amount.saturating_add(lock_bonus as u64)
+    }
+}
+
+/// Buyback configuration for one treasury mint.
+///
+/// Governance whitelists the swap program and oracles used to buy the governance token with
+/// treasury funds, and caps how much can be spent per period.
+#[account]
+#[derive(InitSpace)]
+pub struct BuybackConfig {
+    pub input_mint: Pubkey,                 // Treasury mint spent on buybacks
+    pub input_decimals: u8,                 // Decimals of the input mint
+    pub governance_token_mint: Pubkey,      // Governance token mint bought and burned
+    pub governance_token_decimals: u8,      // Decimals of the governance token mint
+    pub buyback_token_account: Pubkey,      // Token account receiving bought tokens (owned by this PDA)
+    pub params: BuybackParams,              // Governance-controlled buyback parameters
+    pub period_start: i64,                  // Start of the current rate limit period (Unix timestamp)
+    pub spent_in_period: u64,               // Input tokens spent in the current period
+    pub bump: u8,                           // Bump seed of this PDA
+}
+
+/// Governance-controlled parameters of a buyback configuration.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct BuybackParams {
+    pub swap_program: Pubkey,               // Whitelisted DEX program used for the swap
+    pub input_oracle: Pubkey,               // Pyth price feed for the input mint
+    pub governance_token_oracle: Pubkey,    // Pyth price feed for the governance token
+    pub max_amount_per_period: u64,         // Maximum input tokens spent per period
+    pub period_duration: i64,               // Length of a rate limit period in seconds
+    pub max_slippage_bps: u64,              // Maximum shortfall from the oracle price in basis points
+}
+
+/// Liquidity-mining emissions schedule for option writers in one market.
+///
+/// `emission_per_epoch` reward tokens are emitted every `epoch_duration` seconds and shared
+/// among writers in proportion to their open notional and the time it was at risk.
+#[account]
+#[derive(InitSpace)]
+pub struct EmissionsSchedule {
+    pub market: Pubkey,                // Market whose writers earn emissions
+    pub reward_mint: Pubkey,           // Mint of the emitted reward token
+    pub reward_vault: Pubkey,          // Token account funding the emissions (owned by this PDA)
+    pub epoch_duration: i64,           // Length of an emissions epoch in seconds
+    pub emission_per_epoch: u64,       // Reward tokens emitted per epoch
+    pub total_open_notional: u64,      // Open notional of all enrolled escrows
+    pub reward_per_notional: u128,     // Rewards emitted per unit of notional, scaled by REWARD_PRECISION
+    pub last_update: i64,              // Last time `reward_per_notional` was updated
+    pub bump: u8,                      // Bump seed of this PDA
+}
+
+impl EmissionsSchedule {
+    /// Accrues the emissions since the last update to the open notional.
+    ///
+    /// Nothing is emitted while no notional is open.
+    pub fn update(&mut self, current_time: i64) -> Result<()> {
+        if current_time > self.last_update && self.total_open_notional > 0 && self.epoch_duration > 0 {
+            let elapsed = (current_time - self.last_update) as u128;
+            let emitted = self.emission_per_epoch as u128 * elapsed / self.epoch_duration as u128;
+            let increment = emitted
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(ErrorCode::MathOverflow)?
+                / self.total_open_notional as u128;
+            self.reward_per_notional = self.reward_per_notional.checked_add(increment).ok_or(ErrorCode::MathOverflow)?;
+        }
+        self.last_update = self.last_update.max(current_time);
+        Ok(())
+    }
+
+    /// Adds notional written by a writer.
+    pub fn add_notional(&mut self, writer_emissions: &mut WriterEmissions, notional: u64, current_time: i64) -> Result<()> {
+        self.update(current_time)?;
+        writer_emissions.settle_emissions(self)?;
+        writer_emissions.open_notional = writer_emissions.open_notional.checked_add(notional).ok_or(ErrorCode::MathOverflow)?;
+        self.total_open_notional = self.total_open_notional.checked_add(notional).ok_or(ErrorCode::MathOverflow)?;
+        writer_emissions.reset_reward_debt(self)
+    }
+
+    /// Removes notional that is no longer at risk.
+    pub fn remove_notional(&mut self, writer_emissions: &mut WriterEmissions, notional: u64, current_time: i64) -> Result<()> {
+        self.update(current_time)?;
+        writer_emissions.settle_emissions(self)?;
+        writer_emissions.open_notional = writer_emissions.open_notional.saturating_sub(notional);
+        self.total_open_notional = self.total_open_notional.saturating_sub(notional);
+        writer_emissions.reset_reward_debt(self)
+    }
+}
+
+/// A writer's liquidity-mining position in a market.
+#[account]
+#[derive(InitSpace)]
+pub struct WriterEmissions {
+    pub owner: Pubkey,                 // Writer earning the emissions
+    pub market: Pubkey,                // Market the position belongs to
+    pub open_notional: u64,            // Notional of the writer's open enrolled escrows
+    pub reward_debt: u128,             // Emissions already accounted for, scaled by REWARD_PRECISION
+    pub unclaimed_emissions: u64,      // Emissions earned but not yet claimed
+    pub bump: u8,                      // Bump seed of this PDA
+}
+
+impl WriterEmissions {
+    /// Moves the emissions earned since the last update into `unclaimed_emissions`.
+    pub fn settle_emissions(&mut self, schedule: &EmissionsSchedule) -> Result<()> {
+        let accumulated = (self.open_notional as u128)
+            .checked_mul(schedule.reward_per_notional)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let earned_scaled = accumulated - self.reward_debt;
+        let earned = u64::try_from(earned_scaled / REWARD_PRECISION).map_err(|_| ErrorCode::MathOverflow)?;
+        self.unclaimed_emissions = self.unclaimed_emissions.checked_add(earned).ok_or(ErrorCode::MathOverflow)?;
+        self.reward_debt = accumulated - earned_scaled % REWARD_PRECISION;
+        Ok(())
+    }
+
+    /// Resets the reward debt after `open_notional` has changed.
+    pub fn reset_reward_debt(&mut self, schedule: &EmissionsSchedule) -> Result<()> {
+        self.reward_debt = (self.open_notional as u128)
+            .checked_mul(schedule.reward_per_notional)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Price and volatility shock scenarios used to compute margin requirements for a market.
+///
+/// The requirement is the worst-case loss of a portfolio across all scenarios, SPAN-style,
+/// rather than a flat percentage of notional.
+#[account]
+#[derive(InitSpace)]
+pub struct RiskConfig {
+    pub market: Pubkey,                     // Market the scenarios apply to
+    #[max_len(MAX_RISK_SCENARIOS)]
+    pub scenarios: Vec<RiskScenario>,       // Shock scenarios to evaluate
+    pub bump: u8,                           // Bump seed of this PDA
+}
+
+/// A single shock scenario.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct RiskScenario {
+    pub price_shock_bps: i32,   // Move of the underlying price in basis points (e.g. -3000 = -30%)
+    pub vol_shock_bps: u64,     // Extra time value from a volatility spike, as basis points of notional
+}
+
+impl RiskConfig {
+    /// Returns the worst-case loss of the escrows across all scenarios.
+    ///
+    /// A scenario's loss on an escrow is its intrinsic value at the shocked price plus the
+    /// volatility add-on, capped at the escrow's collateral.
+    pub fn margin_requirement(&self, escrows: &[Account<EscrowAccount>], spot_price: u64) -> Result<u64> {
+        let mut worst_loss: u64 = 0;
+        for scenario in self.scenarios.iter() {
+            let shocked_price = (spot_price as i128 * (10000 + scenario.price_shock_bps as i128) / 10000).max(0);
+            let shocked_price = u64::try_from(shocked_price).map_err(|_| ErrorCode::MathOverflow)?;
+            let mut loss: u64 = 0;
+            for escrow_account in escrows.iter() {
+                let vol_addon = (escrow_account.collateral_amount as u128 * scenario.vol_shock_bps as u128 / 10000) as u64;
+                let escrow_loss = escrow_account
+                    .intrinsic_value(shocked_price)?
+                    .saturating_add(vol_addon)
+                    .min(escrow_account.collateral_amount);
+                loss = loss.checked_add(escrow_loss).ok_or(ErrorCode::MathOverflow)?;
+            }
+            worst_loss = worst_loss.max(loss);
+        }
+        Ok(worst_loss)
+    }
+}
+
+/// Validates a list of risk scenarios.
+fn validate_risk_scenarios(scenarios: &[RiskScenario]) -> Result<()> {
+    if scenarios.is_empty() || scenarios.len() > MAX_RISK_SCENARIOS {
+        return Err(ErrorCode::InvalidRiskScenarios.into());
+    }
+    if scenarios.iter().any(|scenario| scenario.price_shock_bps < -10000 || scenario.vol_shock_bps > 10000) {
+        return Err(ErrorCode::InvalidRiskScenarios.into());
+    }
+    Ok(())
+}
+
+/// Health of a writer's open escrows in one market, returned by `get_portfolio_health`.
+///
+/// Escrows are fully collateralized, so there is no liquidation price: the margin ratio can
+/// only fall to 100% when every option is deep in the money.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct PortfolioHealth {
+    pub spot_price: u64,            // Oracle price of the collateral, in STRIKE_PRICE_DECIMALS
+    pub collateral: u64,            // Collateral backing the escrows
+    pub obligations: u64,           // Value owed to holders at the spot price (fair value with a vol curve, else intrinsic)
+    pub margin_ratio_bps: u64,      // Collateral over obligations in basis points (u64::MAX if nothing is owed)
+    pub margin_requirement: u64,    // Worst-case loss across the market's risk scenarios (obligations if none)
+    pub unrealized_pnl: i64,        // Writer's unrealized PnL, in units of the collateral
+}
+
+/// A user's open escrows, so wallets can render a portfolio with one account fetch.
+///
+/// Escrows don't record their holder until settlement, so only escrows written by the user
+/// are listed.
+#[account]
+#[derive(InitSpace)]
+pub struct UserPosition {
+    pub owner: Pubkey,              // User the position belongs to
+    #[max_len(MAX_OPEN_ESCROWS_PER_USER)]
+    pub written_escrows: Vec<Pubkey>, // Open escrows written by the user
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl UserPosition {
+    /// Lists a newly written escrow.
+    pub fn add_written(&mut self, escrow: Pubkey) -> Result<()> {
+        if self.written_escrows.len() >= MAX_OPEN_ESCROWS_PER_USER {
+            return Err(ErrorCode::TooManyOpenEscrows.into());
+        }
+        self.written_escrows.push(escrow);
+        Ok(())
+    }
+
+    /// Removes a closed escrow from the list.
+    pub fn remove_written(&mut self, escrow: &Pubkey) {
+        self.written_escrows.retain(|key| key != escrow);
+    }
+}
+
+/// Protocol-wide activity counters.
+///
+/// Amounts in different mints can't be summed, so the global stats only count escrows;
+/// volumes and fees are tracked per market in `MarketStats`.
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolStats {
+    pub escrows_opened: u64,        // Escrows ever initialized
+    pub escrows_settled: u64,       // Escrows settled at expiry
+    pub escrows_exercised: u64,     // Escrows exercised early
+    pub escrows_cancelled: u64,     // Escrows closed without settlement
+    pub open_escrows: u64,          // Escrows currently open
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl ProtocolStats {
+    /// Records a newly opened escrow.
+    pub fn record_open(&mut self) {
+        self.escrows_opened = self.escrows_opened.saturating_add(1);
+        self.open_escrows = self.open_escrows.saturating_add(1);
+    }
+
+    /// Records a settled or early-exercised escrow.
+    pub fn record_close(&mut self, exercised_early: bool) {
+        if exercised_early {
+            self.escrows_exercised = self.escrows_exercised.saturating_add(1);
+        } else {
+            self.escrows_settled = self.escrows_settled.saturating_add(1);
+        }
+        self.open_escrows = self.open_escrows.saturating_sub(1);
+    }
+
+    /// Records an escrow closed without settlement.
+    pub fn record_cancel(&mut self) {
+        self.escrows_cancelled = self.escrows_cancelled.saturating_add(1);
+        self.open_escrows = self.open_escrows.saturating_sub(1);
+    }
+}
+
+/// Cumulative activity of one market, in units of its collateral mint.
+///
+/// Current open interest is kept on the `Market` account itself, next to its cap.
+#[account]
+#[derive(InitSpace)]
+pub struct MarketStats {
+    pub market: Pubkey,             // Market the stats belong to
+    pub notional_volume: u64,       // Lifetime notional written
+    pub collateral_locked: u64,     // Collateral currently deposited in open escrows (TVL)
+    pub fees_collected: u64,        // Lifetime protocol fees, after staking discounts
+    pub escrows_opened: u64,        // Escrows ever initialized
+    pub escrows_closed: u64,        // Escrows settled or exercised
+    pub event_seq: u64,             // Sequence number of the last event emitted for the market
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl MarketStats {
+    /// Returns the sequence number of the next event emitted for the market.
+    ///
+    /// Numbers increase by one per event, so indexers can detect missed events and order them
+    /// across transactions.
+    pub fn next_event_seq(&mut self) -> u64 {
+        self.event_seq += 1;
+        self.event_seq
+    }
+}
+
+/// A user's loyalty points and activity stats.
+///
+/// Updated by every instruction that writes options or pays protocol fees, so a future
+/// airdrop or rebate can be computed from on-chain state alone.
+#[account]
+#[derive(InitSpace)]
+pub struct UserStats {
+    pub owner: Pubkey,              // User the stats belong to
+    pub points: u64,                // Total loyalty points
+    pub notional_points: u64,       // Points earned by writing options
+    pub premium_points: u64,        // Points earned by paying premium
+    pub fee_points: u64,            // Points earned by paying protocol fees
+    pub escrows_written: u64,       // Number of escrows initialized
+    #[max_len(MAX_USER_OPEN_MARKETS)]
+    pub open_notional: Vec<OpenNotional>, // Notional currently written, per collateral mint
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Notional a user currently has written in one collateral mint.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct OpenNotional {
+    pub collateral_mint: Pubkey,    // Collateral mint of the market
+    pub amount: u64,                // Open notional in units of the collateral mint
+}
+
+impl UserStats {
+    /// Adds open notional in a market, enforcing the market's per-user position limit.
+    pub fn add_open_notional(&mut self, market: &Market, notional: u64) -> Result<()> {
+        let index = match self.open_notional.iter().position(|entry| entry.collateral_mint == market.collateral_mint) {
+            Some(index) => index,
+            None => {
+                if self.open_notional.len() >= MAX_USER_OPEN_MARKETS {
+                    return Err(ErrorCode::TooManyOpenMarkets.into());
+                }
+                self.open_notional.push(OpenNotional { collateral_mint: market.collateral_mint, amount: 0 });
+                self.open_notional.len() - 1
+            }
+        };
+        let entry = &mut self.open_notional[index];
+        entry.amount = entry.amount.checked_add(notional).ok_or(ErrorCode::MathOverflow)?;
+        if market.max_open_notional_per_user > 0 && entry.amount > market.max_open_notional_per_user {
+            return Err(ErrorCode::PositionLimitExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// Removes open notional in a collateral mint, freeing the slot once nothing is left open.
+    pub fn remove_open_notional(&mut self, collateral_mint: &Pubkey, notional: u64) {
+        if let Some(index) = self.open_notional.iter().position(|entry| entry.collateral_mint == *collateral_mint) {
+            let entry = &mut self.open_notional[index];
+            entry.amount = entry.amount.saturating_sub(notional);
+            if entry.amount == 0 {
+                self.open_notional.swap_remove(index);
+            }
+        }
+    }
+
+    /// Returns the notional currently written in a collateral mint.
+    pub fn open_notional_in(&self, collateral_mint: &Pubkey) -> u64 {
+        self.open_notional
+            .iter()
+            .find(|entry| entry.collateral_mint == *collateral_mint)
+            .map_or(0, |entry| entry.amount)
+    }
+
+    /// Records notional written in a market.
+    pub fn record_notional_written(&mut self, market: &Market, notional: u64) -> Result<()> {
+        let points = points_for(notional, market.points_weights.notional_written)?;
+        self.notional_points = self.notional_points.saturating_add(points);
+        self.escrows_written = self.escrows_written.saturating_add(1);
+        self.add_points(points)
+    }
+
+    /// Records premium paid in a market.
+    pub fn record_premium_paid(&mut self, market: &Market, premium: u64) -> Result<()> {
+        let points = points_for(premium, market.points_weights.premium_paid)?;
+        self.premium_points = self.premium_points.saturating_add(points);
+        self.add_points(points)
+    }
+
+    /// Records protocol fees paid in a market, in units of its collateral mint.
+    pub fn record_fees_paid(&mut self, market: &Market, fee: u64) -> Result<()> {
+        let points = points_for(fee, market.points_weights.fees_paid)?;
+        self.fee_points = self.fee_points.saturating_add(points);
+        self.add_points(points)
+    }
+
+    fn add_points(&mut self, points: u64) -> Result<()> {
+        self.points = self.points.saturating_add(points);
+        Ok(())
+    }
+}
+
+/// Returns the points earned for `amount` at `weight` points per 10000 units.
+fn points_for(amount: u64, weight: u64) -> Result<u64> {
+    u64::try_from(amount as u128 * weight as u128 / 10000).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// A governance parameter change put to a vote of the stakers.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum ProposalAction {
+    SetFeeRate { fee_type: FeeType, fee_bps: u64 },     // Change one protocol fee rate
+    SetFeeCollectors(#[max_len(MAX_FEE_COLLECTORS)] Vec<FeeCollector>), // Replace the weighted fee collectors
+    SetFeeDiscountTiers(#[max_len(MAX_FEE_DISCOUNT_TIERS)] Vec<FeeDiscountTier>), // Replace the staker fee discount tiers
+    SetProposalParams(ProposalParams),                  // Change the proposal rules themselves
+    TransferGovernance(Pubkey),                         // Hand the governance authority to another account
+}
+
+impl ProposalAction {
+    /// Checks that the action could be applied, so invalid proposals are rejected up front.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            ProposalAction::SetFeeRate { fee_bps, .. } => {
+                if *fee_bps > MAX_FEE_BPS {
+                    return Err(ErrorCode::FeeRateTooHigh.into());
+                }
+            }
+            ProposalAction::SetFeeCollectors(fee_collectors) => validate_fee_collectors(fee_collectors)?,
+            ProposalAction::SetFeeDiscountTiers(tiers) => validate_fee_discount_tiers(tiers)?,
+            ProposalAction::SetProposalParams(params) => {
+                if params.voting_period < 0 || params.execution_delay < 0 {
+                    return Err(ErrorCode::InvalidProposalParams.into());
+                }
+            }
+            ProposalAction::TransferGovernance(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Applies the action to the governance account.
+    pub fn apply(&self, governance: &mut Governance) {
+        match self.clone() {
+            ProposalAction::SetFeeRate { fee_type, fee_bps } => match fee_type {
+                FeeType::Init => governance.init_fee_bps = fee_bps,
+                FeeType::Settlement => governance.settlement_fee_bps = fee_bps,
+                FeeType::Exercise => governance.exercise_fee_bps = fee_bps,
+                FeeType::Cancellation => governance.cancellation_fee_bps = fee_bps,
+                FeeType::InitFloor => governance.min_init_fee_bps = fee_bps,
+                FeeType::Crank => governance.crank_fee_bps = fee_bps,
+            },
+            ProposalAction::SetFeeCollectors(fee_collectors) => governance.fee_collectors = fee_collectors,
+            ProposalAction::SetFeeDiscountTiers(tiers) => governance.fee_discount_tiers = tiers,
+            ProposalAction::SetProposalParams(params) => governance.proposal_params = params,
+            ProposalAction::TransferGovernance(new_governance_authority) => {
+                governance.governance_authority = new_governance_authority
+            }
+        }
+    }
+}
+
+/// A governance proposal voted on by stakers.
+#[account]
+#[derive(InitSpace)]
+pub struct Proposal {
+    pub governance: Pubkey,         // Governance account the proposal changes
+    pub id: u64,                    // Sequential proposal ID
+    pub proposer: Pubkey,           // Staker who created the proposal
+    pub action: ProposalAction,     // Parameter change to apply if the proposal passes
+    pub votes_for: u64,             // Voting power in favor
+    pub votes_against: u64,         // Voting power against
+    pub voting_ends_at: i64,        // End of the voting period (Unix timestamp)
+    pub executable_at: i64,         // Earliest execution time (Unix timestamp)
+    pub executed: bool,             // Whether the proposal has been executed
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl Proposal {
+    /// Whether the proposal reached quorum with more votes for than against.
+    pub fn passed(&self, params: &ProposalParams) -> bool {
+        let total_votes = self.votes_for as u128 + self.votes_against as u128;
+        self.votes_for > self.votes_against && total_votes >= params.quorum_votes as u128
+    }
+}
+
+/// Record of a staker's vote on a proposal, which prevents voting twice.
+#[account]
+#[derive(InitSpace)]
+pub struct VoteRecord {
+    pub proposal: Pubkey,           // Proposal voted on
+    pub voter: Pubkey,              // Staker who voted
+    pub support: bool,              // Whether the vote was in favor
+    pub voting_power: u64,          // Voting power cast
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Enum to define the option type (Call or Put).
+///
+/// This enum specifies the type of option being created: either a Call option (buy) or a Put option (sell).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum OptionType {
+    Call, // Call option gives the buyer the right to buy
+    Put,  // Put option gives the buyer the right to sell
+}
+
+/// Type, strike, and size of one escrow, such as one strike of a chain opened in a batch.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct EscrowLeg {
+    pub option_type: OptionType,        // Call or Put option
+    pub strike_price: u64,              // Strike price for the option
+    pub collateral_amount: u64,         // Collateral amount of the escrow
+}
+
+/// Terms of an option beyond its type, strike, and expiry.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct OptionTerms {
+    pub payoff: Payoff,                 // How the settlement price is determined
+    pub barrier: Option<Barrier>,       // Knock-in or knock-out barrier, if any
+    #[max_len(MAX_EXERCISE_WINDOWS)]
+    pub exercise_windows: Vec<ExerciseWindow>, // Windows for early exercise (Bermudan); empty allows it at any time
+    pub perpetual: Option<PerpetualTerms>, // Funding terms of an everlasting option with no expiry, if any
+    pub vesting: Option<Vesting>,       // Vesting schedule of the holder's right to exercise, if any
+    pub early_exercise_fee_bps: u64,    // Surcharge paid to the writer on early exercise, in basis points of the collateral
+    #[max_len(MAX_ALLOWED_COUNTERPARTIES)]
+    pub allowed_counterparties: Vec<Pubkey>, // Counterparties of a private OTC escrow; empty makes it public
+}
+
+impl OptionTerms {
+    /// Terms of a plain public option, exercisable at any time before the cutoff.
+    pub fn vanilla() -> Self {
+        OptionTerms {
+            payoff: Payoff::Vanilla,
+            barrier: None,
+            exercise_windows: Vec::new(),
+            perpetual: None,
+            vesting: None,
+            early_exercise_fee_bps: 0,
+            allowed_counterparties: Vec::new(),
+        }
+    }
+}
+
+/// A fungible series of vanilla options, represented by SPL option tokens.
+///
+/// Writers lock `collateral_per_option` in the series vault for each option token they mint.
+/// Settlement is split so it scales to many holders: `settle_series` records the outcome once,
+/// and each holder then burns its tokens for its share with `claim_payout`. Like an escrow, an
+/// ITM option pays its whole collateral less the settlement fee.
+///
+/// The writers form a pool backing the whole series. Each receives writer tokens, units of the
+/// series' open interest in proportion to its contribution, so options exercised early are
+/// assigned to all writers pro rata and the collateral left after settlement is shared the
+/// same way: `units` writer tokens back `units * open_interest / total_units` open options.
+#[account]
+#[derive(InitSpace)]
+pub struct OptionSeries {
+    pub collateral_mint: Pubkey,    // Token mint for the collateral
+    pub option_type: OptionType,    // Call or Put option
+    pub strike_price: u64,          // Strike price of the options
+    pub expiration: i64,            // Expiration time (Unix timestamp)
+    pub collateral_per_option: u64, // Collateral locked for each option token
+    pub option_mint: Pubkey,        // Mint of the series' option tokens
+    pub writer_mint: Pubkey,        // Mint of the series' writer tokens
+    pub vault: Pubkey,              // Token account holding the series' collateral
+    pub open_interest: u64,         // Option tokens written and neither exercised nor settled
+    pub total_units: u64,           // Writer tokens minted against the open interest
+    pub exercised: u64,             // Option tokens exercised before expiry
+    pub settled: bool,              // Whether the series has been settled
+    pub settlement_price: u64,      // Price the series settled at (0 until settled)
+    pub payout_per_option: u64,     // Collateral each option token claims after settlement
+    pub residual_per_option: u64,   // Collateral returned to writers per option written after settlement
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl OptionSeries {
+    /// Returns the writer units to issue for `amount` newly written options.
+    ///
+    /// Fails once every option written has been exercised, as earlier units back nothing.
+    pub fn units_for(&self, amount: u64) -> Result<u64> {
+        if self.total_units == 0 {
+            return Ok(amount);
+        }
+        if self.open_interest == 0 {
+            return Err(ErrorCode::SeriesUnsupported.into());
+        }
+        u64::try_from(amount as u128 * self.total_units as u128 / self.open_interest as u128).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Returns the open options backed by `units` writer units.
+    pub fn open_options(&self, units: u64) -> u64 {
+        if self.total_units == 0 {
+            return 0;
+        }
+        (units as u128 * self.open_interest as u128 / self.total_units as u128) as u64
+    }
+
+    /// Whether the series' options are ITM at `price`.
+    pub fn is_itm(&self, price: u64) -> bool {
+        match self.option_type {
+            OptionType::Call => price > self.strike_price,
+            OptionType::Put => price < self.strike_price,
+        }
+    }
+
+    /// Returns the seed identifying the option type in the series' address.
+    pub fn option_type_seed(option_type: &OptionType) -> u8 {
+        match option_type {
+            OptionType::Call => 0,
+            OptionType::Put => 1,
+        }
+    }
+}
+
+/// A writer's record of its contribution to an option series.
+///
+/// Its writer tokens can change hands, so the record tracks what the writer itself wrote: how
+/// much of it was assigned, and how much collateral the writer claimed back.
+#[account]
+#[derive(InitSpace)]
+pub struct SeriesWriter {
+    pub series: Pubkey,             // Series written
+    pub writer: Pubkey,             // Writer of the options
+    pub written: u64,               // Option tokens written
+    pub units: u64,                 // Writer tokens minted to the writer
+    pub assigned: u64,              // Options written that were assigned to early exercises, as of the last update
+    pub released: u64,              // Collateral the writer claimed back after settlement
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl SeriesWriter {
+    /// Updates the options assigned to the writer from the series' exercises so far.
+    pub fn sync_assigned(&mut self, series: &OptionSeries) {
+        self.assigned = self.written.saturating_sub(series.open_options(self.units));
+    }
+}
+
+/// Fractional ownership of an escrow's option, split into SPL share tokens.
+///
+/// The fraction account becomes the escrow's holder, so the option can no longer be exercised
+/// early and settles at expiry. Its settlement proceeds are claimed into the fraction's
+/// proceeds account, from which each share claims a pro-rata part.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowFraction {
+    pub escrow: Pubkey,             // Escrow whose option is split
+    pub share_mint: Pubkey,         // Mint of the fraction shares
+    pub proceeds_account: Pubkey,   // Token account receiving the escrow's settlement proceeds
+    pub total_shares: u64,          // Shares minted when the option was split
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Registry of a series' holders for deployments that can't hand out SPL option tokens.
+///
+/// The registrar admits holders and moves options in and out of the registry: option tokens
+/// it adds are burned and credited to a holder's entry, and options it removes are minted back
+/// as tokens. Holders can transfer options only to other admitted holders, and claim their
+/// payout from the series vault once it settles. Like the price history, it is zero-copy, so
+/// an update only touches the entries it needs. Fields are ordered so the `repr(C)` layout
+/// has no implicit padding.
+#[account(zero_copy)]
+pub struct HolderRegistry {
+    pub series: Pubkey,                     // Series whose holders are registered
+    pub registrar: Pubkey,                  // Account admitting holders and moving options in and out
+    pub registered: u64,                    // Options held through the registry
+    pub len: u16,                           // Number of holders admitted, up to MAX_REGISTRY_HOLDERS
+    pub bump: u8,                           // Bump seed of this PDA
+    pub padding: [u8; 5],                   // Aligns the entries to 8 bytes
+    pub entries: [HolderEntry; MAX_REGISTRY_HOLDERS], // Admitted holders, in no particular order
+}
+
+/// A holder's options in a holder registry.
+#[zero_copy]
+pub struct HolderEntry {
+    pub holder: Pubkey,             // Admitted holder
+    pub amount: u64,                // Options held
+}
+
+impl HolderRegistry {
+    /// Returns the entry of `holder`, if it is admitted.
+    pub fn entry_mut(&mut self, holder: &Pubkey) -> Result<&mut HolderEntry> {
+        self.entries[..self.len as usize]
+            .iter_mut()
+            .find(|entry| entry.holder == *holder)
+            .ok_or(ErrorCode::HolderNotRegistered.into())
+    }
+
+    /// Credits `amount` options to `holder`, admitting it if it isn't yet.
+    pub fn credit(&mut self, holder: Pubkey, amount: u64) -> Result<()> {
+        if let Ok(entry) = self.entry_mut(&holder) {
+            entry.amount = entry.amount.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+            return Ok(());
+        }
+        if self.len as usize == MAX_REGISTRY_HOLDERS {
+            return Err(ErrorCode::HolderRegistryFull.into());
+        }
+        self.entries[self.len as usize] = HolderEntry { holder, amount };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Debits `amount` options from `holder`.
+    pub fn debit(&mut self, holder: &Pubkey, amount: u64) -> Result<()> {
+        let entry = self.entry_mut(holder)?;
+        entry.amount = entry.amount.checked_sub(amount).ok_or(ErrorCode::InsufficientHolding)?;
+        Ok(())
+    }
+
+    /// Removes `holder`'s entry once it holds nothing, freeing its slot.
+    pub fn remove_if_empty(&mut self, holder: &Pubkey) {
+        let len = self.len as usize;
+        if let Some(index) = self.entries[..len].iter().position(|entry| entry.holder == *holder && entry.amount == 0) {
+            self.entries[index] = self.entries[len - 1];
+            self.len -= 1;
+        }
+    }
+}
+
+/// A standard product: everything about an escrow except its strike and expiry.
+///
+/// Anyone can open an escrow from a template with `clone_from_template`, so desks can list a
+/// consistent series and UIs can surface the template as a standard product.
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowTemplate {
+    pub creator: Pubkey,                // Account that created the template
+    pub template_id: u64,               // Creator-chosen identifier of the template
+    pub collateral_mint: Pubkey,        // Token mint for the collateral
+    pub option_type: OptionType,        // Call or Put option
+    pub collateral_amount: u64,         // Collateral of each escrow (the contract multiplier)
+    pub terms: OptionTerms,             // Payoff style, barrier, and exercise windows of each escrow
+    pub fee_override_bps: Option<u64>,  // Fee rate approved by the fee admin for escrows cloned from the template, if any
+    pub bump: u8,                       // Bump seed of this PDA
+}
+
+/// Rental of an escrow's exercise rights, paid for by streaming premium to the writer.
+///
+/// The renter prepays premium into the rental's token account, which streams to the writer at
+/// the escrow's rental rate. The rights lapse once the prepaid premium is used up.
+#[account]
+#[derive(InitSpace)]
+pub struct Rental {
+    pub escrow: Pubkey,             // Escrow whose exercise rights are rented
+    pub renter: Pubkey,             // Renter holding the exercise rights
+    pub rate_per_second: u64,       // Premium streamed to the writer per second
+    pub start: i64,                 // Start of the stream (Unix timestamp)
+    pub deposited: u64,             // Total premium prepaid by the renter
+    pub claimed: u64,               // Premium already claimed by the writer
+    pub token_account: Pubkey,      // Token account holding the prepaid premium
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl Rental {
+    /// Returns the premium streamed to the writer by `current_time`.
+    pub fn streamed(&self, current_time: i64) -> u64 {
+        let elapsed = current_time.saturating_sub(self.start).max(0) as u128;
+        (elapsed * self.rate_per_second as u128).min(self.deposited as u128) as u64
+    }
+
+    /// Whether the prepaid premium has run out, lapsing the rights.
+    pub fn is_lapsed(&self, current_time: i64) -> bool {
+        self.streamed(current_time) >= self.deposited
+    }
+}
+
+/// A holder's loan of its option to a borrower for a fixed term.
+///
+/// The borrower pays the fee upfront and holds the option until `end`. If it exercises, the
+/// lender keeps `lender_share_bps` of the proceeds. Once the term is over, the option returns
+/// to the lender.
+#[account]
+#[derive(InitSpace)]
+pub struct OptionLoan {
+    pub escrow: Pubkey,             // Escrow whose option is lent
+    pub lender: Pubkey,             // Holder lending the option
+    pub borrower: Pubkey,           // Borrower holding the option (default while the loan is only offered)
+    pub fee: u64,                   // Upfront fee paid by the borrower to the lender
+    pub term: i64,                  // Length of the loan in seconds
+    pub lender_share_bps: u64,      // Share of any exercise proceeds kept by the lender, in basis points
+    pub end: i64,                   // End of the loan (Unix timestamp, 0 while only offered)
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl OptionLoan {
+    /// Returns the lender's share of `amount` of exercise proceeds.
+    pub fn lender_share(&self, amount: u64) -> u64 {
+        (amount as u128 * self.lender_share_bps as u128 / 10000) as u64
+    }
+}
+
+/// Vesting schedule of a holder's right to exercise, e.g. for contributor compensation.
+///
+/// Nothing vests before the cliff; from then on the right vests linearly from `start` until
+/// `end`. A cliff-only schedule sets `cliff` equal to `end`. If the writer revokes the holder,
+/// vesting stops and the unvested part of any payout returns to the writer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct Vesting {
+    pub holder: Pubkey,             // Beneficiary whose right to exercise vests
+    pub start: i64,                 // Start of vesting (Unix timestamp)
+    pub cliff: i64,                 // Time before which nothing is vested (Unix timestamp)
+    pub end: i64,                   // Time at which everything is vested (Unix timestamp)
+}
+
+impl Vesting {
+    /// Checks that `start <= cliff <= end`.
+    pub fn validate(&self) -> Result<()> {
+        if self.start > self.cliff || self.cliff > self.end || self.holder == Pubkey::default() {
+            return Err(ErrorCode::InvalidVestingSchedule.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the part of `amount` vested at `time`.
+    pub fn vested_amount(&self, amount: u64, time: i64) -> u64 {
+        if time < self.cliff {
+            return 0;
+        }
+        if time >= self.end {
+            return amount;
+        }
+        (amount as u128 * (time - self.start) as u128 / (self.end - self.start) as u128) as u64
+    }
+}
+
+/// Funding terms of a perpetual (everlasting) option.
+///
+/// The holder keeps the option alive by paying funding to the writer each period. Once
+/// funding lapses, anyone can settle the escrow and the collateral returns to the writer.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct PerpetualTerms {
+    pub funding_period: i64,        // Length of a funding period in seconds
+    pub funding_per_period: u64,    // Funding owed per period, in units of the collateral
+}
+
+/// A period during which a Bermudan option can be exercised early.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct ExerciseWindow {
+    pub start: i64,     // Start of the window (Unix timestamp)
+    pub end: i64,       // End of the window (Unix timestamp)
+}
+
+/// Validates the exercise windows of a Bermudan option.
+///
+/// Windows must be non-empty, sorted, non-overlapping, and end by expiration.
+fn validate_exercise_windows(windows: &[ExerciseWindow], expiration: i64) -> Result<()> {
+    if windows.len() > MAX_EXERCISE_WINDOWS {
+        return Err(ErrorCode::InvalidExerciseWindows.into());
+    }
+    for (i, window) in windows.iter().enumerate() {
+        if window.start >= window.end || window.end > expiration || (i > 0 && window.start <= windows[i - 1].end) {
+            return Err(ErrorCode::InvalidExerciseWindows.into());
+        }
+    }
+    Ok(())
+}
+
+/// How an escrow's settlement price is determined.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum Payoff {
+    Vanilla,                                // Settles on the market's settlement price at expiry
+    Asian { observation_interval: i64 },    // Settles on the average of scheduled observations over the option's life
+    Binary { payout: u64 },                 // Pays a fixed amount of collateral if ITM, and nothing otherwise
+    Lookback { observation_interval: i64 }, // Settles on the highest (calls) or lowest (puts) scheduled observation
+    Basket,                                 // Settles on the weighted value of the market's basket, in index points
+    Quanto { underlying_oracle: Pubkey, payout_per_point: u64 }, // Pays a fixed quote amount per point of the underlying's moneyness
+}
+
+impl Payoff {
+    /// Checks the payoff parameters against the option's life and collateral.
+    pub fn validate(&self, current_time: i64, expiration: i64, collateral_amount: u64) -> Result<()> {
+        match self {
+            Payoff::Vanilla | Payoff::Basket => {}
+            Payoff::Binary { payout } => {
+                if *payout == 0 || *payout > collateral_amount {
+                    return Err(ErrorCode::InvalidPayoff.into());
+                }
+            }
+            Payoff::Quanto { payout_per_point, .. } => {
+                if *payout_per_point == 0 {
+                    return Err(ErrorCode::InvalidPayoff.into());
+                }
+            }
+            Payoff::Asian { observation_interval } | Payoff::Lookback { observation_interval } => {
+                if *observation_interval <= 0 || *observation_interval > expiration - current_time {
+                    return Err(ErrorCode::InvalidPayoff.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the payoff depends on observations recorded over the option's life.
+    pub fn observation_interval(&self) -> Option<i64> {
+        match self {
+            Payoff::Vanilla | Payoff::Binary { .. } | Payoff::Basket | Payoff::Quanto { .. } => None,
+            Payoff::Asian { observation_interval } | Payoff::Lookback { observation_interval } => Some(*observation_interval),
+        }
+    }
+}
+
+/// A price level that activates (knock-in) or cancels (knock-out) an option when touched.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct Barrier {
+    pub kind: BarrierKind,              // Whether touching the barrier activates or cancels the option
+    pub direction: BarrierDirection,    // Whether the barrier is touched from below or above
+    pub level: u64,                     // Barrier price, in STRIKE_PRICE_DECIMALS
+}
+
+impl Barrier {
+    /// Whether `price` touches the barrier.
+    pub fn is_touched(&self, price: u64) -> bool {
+        match self.direction {
+            BarrierDirection::Up => price >= self.level,
+            BarrierDirection::Down => price <= self.level,
+        }
+    }
+}
+
+/// Effect of touching a barrier.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum BarrierKind {
+    KnockIn,    // The option only becomes exercisable once the barrier is touched
+    KnockOut,   // The option is cancelled and the collateral returned once the barrier is touched
+}
+
+/// Side from which a barrier is touched.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum BarrierDirection {
+    Up,     // Touched when the price rises to or above the level
+    Down,   // Touched when the price falls to or below the level
+}
+
+/// Oracle samples recorded on an escrow by the `record_observation` crank.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct Observations {
+    pub count: u32,                 // Number of samples recorded
+    pub sum: u128,                  // Sum of the sampled prices, in STRIKE_PRICE_DECIMALS
+    pub max: u64,                   // Highest sampled price, in STRIKE_PRICE_DECIMALS
+    pub min: u64,                   // Lowest sampled price, in STRIKE_PRICE_DECIMALS
+    pub next_time: i64,             // Time of the next scheduled sample (Unix timestamp)
+}
+
+impl Observations {
+    /// Starts the observation schedule of a payoff, with the first sample one interval out.
+    pub fn new(payoff: &Payoff, current_time: i64) -> Self {
+        Observations {
+            count: 0,
+            sum: 0,
+            max: 0,
+            min: 0,
+            next_time: payoff.observation_interval().map_or(0, |interval| current_time + interval),
+        }
+    }
+
+    /// Records a sample and schedules the next one, skipping any missed slots.
+    pub fn record(&mut self, price: u64, interval: i64, current_time: i64) -> Result<()> {
+        if current_time < self.next_time {
+            return Err(ErrorCode::ObservationNotDue.into());
+        }
+        if self.count == 0 {
+            self.max = price;
+            self.min = price;
+        } else {
+            self.max = self.max.max(price);
+            self.min = self.min.min(price);
+        }
+        self.count += 1;
+        self.sum = self.sum.checked_add(price as u128).ok_or(ErrorCode::MathOverflow)?;
+        self.next_time += interval * ((current_time - self.next_time) / interval + 1);
+        Ok(())
+    }
+
+    /// Returns the average of the recorded samples.
+    pub fn average(&self) -> Result<u64> {
+        if self.count == 0 {
+            return Err(ErrorCode::NoObservations.into());
+        }
+        Ok((self.sum / self.count as u128) as u64)
+    }
+
+    /// Returns the most favorable recorded sample for the holder: the highest for calls and
+    /// the lowest for puts.
+    pub fn extreme(&self, option_type: &OptionType) -> Result<u64> {
+        if self.count == 0 {
+            return Err(ErrorCode::NoObservations.into());
+        }
+        match option_type {
+            OptionType::Call => Ok(self.max),
+            OptionType::Put => Ok(self.min),
+        }
+    }
+}
+
+/// A participant's admission to a permissioned market, granted by its compliance authority.
+#[account]
+#[derive(InitSpace)]
+pub struct WhitelistEntry {
+    pub market: Pubkey,             // Market the participant is admitted to
+    pub participant: Pubkey,        // Admitted participant
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A short-lived key a holder authorizes to exercise or claim on its behalf without further
+/// wallet prompts.
+#[account]
+#[derive(InitSpace)]
+pub struct SessionKey {
+    pub holder: Pubkey,             // Holder who authorized the key
+    pub session_key: Pubkey,        // Temporary key
+    pub expires_at: i64,            // Time after which the key is no longer valid
+    pub scope: u8,                  // Bitmask of SESSION_SCOPE_* flags the key is allowed
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl SessionKey {
+    /// Fails if the key has expired or isn't allowed the given scope.
+    pub fn require_scope(&self, scope: u8, current_time: i64) -> Result<()> {
+        if current_time >= self.expires_at || self.scope & scope != scope {
+            return Err(ErrorCode::InvalidSession.into());
+        }
+        Ok(())
+    }
+}
+
+/// A depositor's standing authorization for keepers to pull collateral into an escrow.
+///
+/// The depositor approves this PDA as the SPL delegate of the source account; the amount
+/// approved caps what keepers can pull, and the accounts recorded here pin where it goes.
+#[account]
+#[derive(InitSpace)]
+pub struct DepositAuthorization {
+    pub escrow: Pubkey,             // Escrow the collateral is for
+    pub depositor: Pubkey,          // Owner of the source account
+    pub source: Pubkey,             // Depositor's token account the collateral is pulled from
+    pub destination: Pubkey,        // Escrow's vault the collateral is pulled into
+    pub not_before: i64,            // Earliest time keepers can pull (for scheduled funding)
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Emitted when an escrow is opened.
+#[event]
+pub struct EscrowInitialized {
+    pub seq: u64,                   // Market event sequence number
+    pub market: Pubkey,             // Market of the escrow
+    pub escrow: Pubkey,             // Escrow opened
+    pub writer: Pubkey,             // Writer of the option
+    pub strike_price: u64,          // Strike price of the option
+    pub expiration: i64,            // Expiration time (Unix timestamp)
+    pub collateral_amount: u64,     // Collateral backing the option
+}
+
+/// Emitted when collateral is deposited into an escrow.
+#[event]
+pub struct CollateralDeposited {
+    pub seq: u64,                   // Market event sequence number
+    pub market: Pubkey,             // Market of the escrow
+    pub escrow: Pubkey,             // Escrow funded
+    pub depositor: Pubkey,          // Owner of the deposited tokens
+    pub amount: u64,                // Collateral deposited
+}
+
+/// Emitted when an escrow is settled at expiry.
+#[event]
+pub struct EscrowSettled {
+    pub seq: u64,                   // Market event sequence number
+    pub market: Pubkey,             // Market of the escrow
+    pub escrow: Pubkey,             // Escrow settled
+    pub is_itm: bool,               // Whether the holder was paid
+    pub holder_amount: u64,         // Proceeds held for the holder to claim
+    pub writer_amount: u64,         // Collateral returned to the writer
+    pub fee: u64,                   // Fee taken out of the collateral
+    pub greeks: Option<Greeks>,     // Greeks at the settlement price (None without a fresh vol curve or for exotic payoffs)
+}
+
+/// Sensitivities of an option per unit of the underlying, from the Black-Scholes model.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct Greeks {
+    pub delta: i64,                 // Change in premium per unit move of spot, in millionths
+    pub gamma: i64,                 // Change in delta for a 1% move of spot, in millionths
+    pub vega: i64,                  // Change in premium for one point of volatility, in STRIKE_PRICE_DECIMALS
+}
+
+/// Emitted when an escrow is exercised early.
+#[event]
+pub struct EscrowExercised {
+    pub seq: u64,                   // Market event sequence number
+    pub market: Pubkey,             // Market of the escrow
+    pub escrow: Pubkey,             // Escrow exercised
+    pub exerciser: Pubkey,          // Holder receiving the proceeds
+    pub holder_amount: u64,         // Proceeds paid to the holder
+    pub writer_amount: u64,         // Collateral returned to the writer
+    pub fee: u64,                   // Fee taken out of the collateral
+    pub greeks: Option<Greeks>,     // Greeks at exercise (None without a fresh vol curve or for exotic payoffs)
+}
+
+/// Emitted when an escrow is closed without settlement.
+#[event]
+pub struct EscrowCancelled {
+    pub seq: u64,                   // Market event sequence number
+    pub market: Pubkey,             // Market of the escrow
+    pub escrow: Pubkey,             // Escrow closed
+    pub refunded: u64,              // Deposited collateral returned to the writer
+}
+
+/// Emitted when a protocol-owned vault reinvests an epoch's auction premiums into its principal.
+#[event]
+pub struct PremiumsCompounded {
+    pub pol_vault: Pubkey,          // Vault compounding
+    pub epoch: u64,                 // Epoch the premiums were compounded at the start of
+    pub amount: u64,                // Premiums added to the principal
+    pub principal: u64,             // Principal after compounding
+}
+
+/// Ring buffer of the latest administrative and settlement actions in a market.
+///
+/// Lets auditors reconstruct recent history without an external indexer. Like the price
+/// history, it is zero-copy, so recording an action doesn't decode the whole buffer.
+#[account(zero_copy)]
+pub struct AuditLog {
+    pub market: Pubkey,                     // Market whose actions are recorded
+    pub head: u16,                          // Index of the latest entry
+    pub len: u16,                           // Number of entries recorded, up to AUDIT_LOG_ENTRIES
+    pub bump: u8,                           // Bump seed of this PDA
+    pub padding: [u8; 3],                   // Aligns the entries to 8 bytes
+    pub entries: [AuditEntry; AUDIT_LOG_ENTRIES], // Entries, oldest overwritten first
+}
+
+/// A single audited action.
+#[zero_copy]
+pub struct AuditEntry {
+    pub actor: Pubkey,              // Signer who performed the action
+    pub target: Pubkey,             // Market or escrow acted on
+    pub instruction: [u8; 8],       // Anchor discriminator of the instruction
+    pub timestamp: i64,             // Time of the action (Unix timestamp)
+    pub amount: u64,                // Collateral settled, or the new value of a market parameter (0 if none)
+}
+
+impl AuditLog {
+    /// Records an entry, overwriting the oldest one once the buffer is full.
+    pub fn record(&mut self, entry: AuditEntry) {
+        if (self.len as usize) < AUDIT_LOG_ENTRIES {
+            self.head = self.len;
+            self.len += 1;
+        } else {
+            self.head = ((self.head as usize + 1) % AUDIT_LOG_ENTRIES) as u16;
+        }
+        self.entries[self.head as usize] = entry;
+    }
+}
+
+/// Records an action in a market's audit log, if the market keeps one.
+fn record_audit(audit_log: Option<&AccountLoader<AuditLog>>, actor: Pubkey, target: Pubkey, instruction: [u8; 8], amount: u64) -> Result<()> {
+    if let Some(audit_log) = audit_log {
+        let timestamp = Clock::get()?.unix_timestamp;
+        audit_log.load_mut()?.record(AuditEntry { actor, target, instruction, timestamp, amount });
+    }
+    Ok(())
+}
+
+/// What settling an escrow now would pay, returned by `preview_settlement`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct SettlementPreview {
+    pub settlement_price: u64,      // Price the escrow would settle at, in STRIKE_PRICE_DECIMALS
+    pub exercises: bool,            // Whether settlement would exercise the option
+    pub holder_amount: u64,         // Proceeds the holder could claim
+    pub writer_amount: u64,         // Collateral returned to the writer
+    pub fee: u64,                   // Settlement fee, in units of the collateral (paid in the fee mint if the market has one)
+}
+
+/// An escrow's value at the current oracle price, returned by `get_escrow_value`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct EscrowValue {
+    pub spot_price: u64,            // Oracle price of the collateral, in STRIKE_PRICE_DECIMALS
+    pub intrinsic_value: u64,       // Value of exercising now, in units of the collateral
+    pub moneyness_bps: i64,         // Distance from the strike in basis points, positive when ITM
+    pub is_itm: bool,               // Whether exercising now would pay the holder
+    pub time_to_expiry: i64,        // Seconds until expiry (0 once expired)
+}
+
+/// Implied volatility curve of a market, consumed by fair-value pricing and portfolio health.
+///
+/// Governance creates the curve and names an updater, such as a volatility oracle keeper, who
+/// pushes new points. Readers refuse the curve once it is older than `max_staleness`.
+#[account]
+#[derive(InitSpace)]
+pub struct VolConfig {
+    pub market: Pubkey,             // Market the curve prices
+    pub updater: Pubkey,            // Key allowed to push points besides the governance authority
+    pub rate_bps: i64,              // Annualized risk-free rate used alongside the curve
+    pub max_staleness: i64,         // Seconds after an update before the curve is refused
+    pub updated_at: i64,            // Timestamp of the last point update
+    #[max_len(MAX_VOL_POINTS)]
+    pub points: Vec<VolPoint>,      // Implied volatility by tenor, sorted by tenor
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Implied volatility at one tenor.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct VolPoint {
+    pub tenor: i64,     // Time to expiry in seconds
+    pub vol_bps: u64,   // Annualized implied volatility in basis points
+}
+
+impl VolConfig {
+    /// Returns the implied volatility at `tenor`, in basis points.
+    ///
+    /// Interpolates linearly between points and holds the first and last points flat beyond
+    /// the curve. Fails if the curve is stale.
+    pub fn implied_vol_bps(&self, tenor: i64, current_time: i64) -> Result<u64> {
+        if current_time.saturating_sub(self.updated_at) > self.max_staleness {
+            return Err(ErrorCode::StaleVolConfig.into());
+        }
+        let index = self.points.iter().position(|point| point.tenor >= tenor);
+        let (low, high) = match index {
+            Some(0) => return Ok(self.points[0].vol_bps),
+            Some(i) => (&self.points[i - 1], &self.points[i]),
+            None => return self.points.last().map(|point| point.vol_bps).ok_or(ErrorCode::InvalidVolConfig.into()),
+        };
+        let weight = (tenor - low.tenor) as i128;
+        let span = (high.tenor - low.tenor) as i128;
+        let vol = low.vol_bps as i128 + (high.vol_bps as i128 - low.vol_bps as i128) * weight / span;
+        Ok(vol as u64)
+    }
+}
+
+/// Validates the points of a volatility curve.
+fn validate_vol_points(points: &[VolPoint]) -> Result<()> {
+    if points.is_empty() || points.len() > MAX_VOL_POINTS {
+        return Err(ErrorCode::InvalidVolConfig.into());
+    }
+    if points.iter().any(|point| point.tenor <= 0 || point.vol_bps == 0 || point.vol_bps > MAX_VOL_BPS) {
+        return Err(ErrorCode::InvalidVolConfig.into());
+    }
+    if points.windows(2).any(|pair| pair[0].tenor >= pair[1].tenor) {
+        return Err(ErrorCode::InvalidVolConfig.into());
+    }
+    Ok(())
+}
+
+/// Registry of settlement keepers, who stake tokens to take part in the keeper network.
+///
+/// Keepers with at least `min_stake` bonded are active: they get priority on crank rewards for
+/// `priority_window` seconds after expiry, and may submit manual settlement prices in markets
+/// that allow it. Governance slashes keepers whose inputs were provably wrong, so stake stays
+/// slashable until its unbonding period has passed.
+#[account]
+#[derive(InitSpace)]
+pub struct KeeperRegistry {
+    pub stake_mint: Pubkey,         // Mint keepers stake
+    pub stake_vault: Pubkey,        // Token account holding keeper stake
+    pub min_stake: u64,             // Bonded stake needed to be an active keeper
+    pub unbonding_period: i64,      // Seconds unbonding stake stays slashable before it can be withdrawn
+    pub priority_window: i64,       // Seconds after expiry during which only active keepers earn crank rewards
+    pub total_staked: u64,          // Stake held for all keepers, bonded or unbonding
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A keeper's stake in the keeper registry.
+#[account]
+#[derive(InitSpace)]
+pub struct Keeper {
+    pub owner: Pubkey,              // Keeper
+    pub bonded: u64,                // Stake counting towards activity
+    pub unbonding: u64,             // Stake waiting out the unbonding period
+    pub unbonding_end: i64,         // When the unbonding stake can be withdrawn
+    pub slashed: u64,               // Total stake slashed so far
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl Keeper {
+    /// Whether the keeper has enough bonded stake to be active.
+    pub fn is_active(&self, registry: &KeeperRegistry) -> bool {
+        self.bonded > 0 && self.bonded >= registry.min_stake
+    }
+}
+
+/// Accounts allowed to settle one series of a market with restricted settlement.
+///
+/// A series is the market's escrows sharing one expiry.
+#[account]
+#[derive(InitSpace)]
+pub struct SeriesSettlers {
+    pub market: Pubkey,             // Market of the series
+    pub expiration: i64,            // Expiry of the series
+    #[max_len(MAX_SERIES_SETTLERS)]
+    pub settlers: Vec<Pubkey>,      // Accounts allowed to settle the series
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A market's whitelisted lending protocol for idle escrow collateral.
+#[account]
+#[derive(InitSpace)]
+pub struct LendingConfig {
+    pub market: Pubkey,             // Market whose collateral may be lent
+    pub lending_program: Pubkey,    // Lending protocol program the collateral is deposited into
+    pub receipt_mint: Pubkey,       // Mint of the protocol's receipt tokens (e.g. cTokens)
+    pub protocol_share_bps: u64,    // Share of the yield paid to the treasury, in basis points
+    pub enabled: bool,              // Whether new deposits are allowed
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A perp DEX program whitelisted by governance for hedging vaults.
+#[account]
+#[derive(InitSpace)]
+pub struct HedgeVenue {
+    pub program: Pubkey,            // Perp DEX program orders are sent to
+    pub adapter: HedgeAdapterKind,  // Adapter building the venue's orders
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// How orders for a hedging venue are built.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, InitSpace)]
+pub enum HedgeAdapterKind {
+    Raw,    // The rebalancer supplies the instruction data
+    Drift,  // Built by `hedge::DriftAdapter` (feature `drift`)
+    Mango,  // Built by `hedge::MangoAdapter` (feature `mango`)
+}
+
+/// A writer's vault hedging the delta of their escrows in one market with a perp position.
+///
+/// The vault PDA is the authority of the writer's account on the venue. `hedge_position` is the
+/// perp position the vault has traded, in units of the collateral, positive for long.
+#[account]
+#[derive(InitSpace)]
+pub struct HedgeVault {
+    pub owner: Pubkey,              // Writer whose escrows are hedged
+    pub market: Pubkey,             // Market of the hedged escrows
+    pub venue: Pubkey,              // Whitelisted perp DEX program the vault trades on
+    pub venue_market_index: u16,    // Perp market on the venue (for venues with an adapter)
+    pub base_lot_size: u64,         // Native units per base lot of the perp market (for venues quoting in lots)
+    pub rebalancer: Pubkey,         // Key allowed to rebalance besides the owner (default for none)
+    pub params: HedgeParams,        // Hedging strategy parameters
+    pub hedge_position: i64,        // Signed perp position traded by the vault, in collateral units
+    pub last_rebalance: i64,        // Timestamp of the last rebalance
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Hedging strategy parameters of a hedging vault.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct HedgeParams {
+    pub target_delta: i64,          // Net delta to hedge to, in collateral units (0 for delta-neutral)
+    pub rebalance_band: u64,        // Deviation from the target tolerated before rebalancing, in collateral units
+}
+
+/// A market's protocol-owned writer vault, writing options with treasury funds.
+///
+/// Its escrows are written by the system-owned `writer` PDA, whose collateral token account
+/// holds the vault's idle funds and receives its escrows' proceeds.
+#[account]
+#[derive(InitSpace)]
+pub struct PolVault {
+    pub collateral_mint: Pubkey,    // Collateral mint of the market the vault writes in
+    pub writer: Pubkey,             // System-owned PDA writing the vault's escrows
+    pub token_account: Pubkey,      // Writer's collateral token account holding the vault's funds
+    pub operator: Pubkey,           // Key allowed to write escrows within the parameters
+    pub params: PolVaultParams,     // Series the vault quotes and its exposure cap
+    pub principal: u64,             // Treasury funds currently committed to the vault
+    pub profits_swept: u64,         // Profits returned to the treasury so far
+    pub epoch: u64,                 // Epoch of the next issuance auction
+    pub auto_compound: bool,        // Whether auction premiums are reinvested into principal instead of swept
+    pub epoch_premiums: u64,        // Auction premiums collected since the last epoch began
+    pub bump: u8,                   // Bump seed of this PDA
+    pub writer_bump: u8,            // Bump seed of the writer PDA
+}
+
+/// Series a protocol-owned vault may write, and its maximum exposure.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct PolVaultParams {
+    pub quote_calls: bool,          // Whether the vault writes calls
+    pub quote_puts: bool,           // Whether the vault writes puts
+    pub min_strike_bps: u64,        // Lowest strike, in basis points of the oracle price
+    pub max_strike_bps: u64,        // Highest strike, in basis points of the oracle price
+    pub max_tenor: i64,             // Longest time to expiry, in seconds
+    pub max_exposure: u64,          // Most collateral the vault may have locked in open escrows
+}
+
+impl PolVaultParams {
+    /// Checks the parameters are consistent.
+    pub fn validate(&self) -> Result<()> {
+        if self.min_strike_bps > self.max_strike_bps || self.max_tenor <= 0 {
+            return Err(ErrorCode::InvalidPolVaultParams.into());
+        }
+        Ok(())
+    }
+
+    /// Fails unless the vault quotes the series.
+    pub fn check_series(&self, option_type: &OptionType, strike_price: u64, spot_price: u64, expiration: i64, current_time: i64) -> Result<()> {
+        let quoted = match option_type {
+            OptionType::Call => self.quote_calls,
+            OptionType::Put => self.quote_puts,
+        };
+        let strike = strike_price as u128 * 10000;
+        let spot = spot_price as u128;
+        let tenor = expiration.saturating_sub(current_time);
+        if !quoted
+            || strike < spot * self.min_strike_bps as u128
+            || strike > spot * self.max_strike_bps as u128
+            || tenor <= 0
+            || tenor > self.max_tenor
+        {
+            return Err(ErrorCode::SeriesNotQuoted.into());
+        }
+        Ok(())
+    }
+}
+
+/// A market's backstop liquidity pool, taking over the escrows of defaulting writers.
+///
+/// Taken-over escrows are held by the system-owned `writer` PDA, whose collateral token
+/// account holds the pool's capital. LPs own the pool's capital through shares, and earn the
+/// discounts on defaulting writers' deposits as rewards, tracked like staking rewards.
+#[account]
+#[derive(InitSpace)]
+pub struct BackstopPool {
+    pub collateral_mint: Pubkey,    // Collateral mint of the market the pool backstops
+    pub writer: Pubkey,             // System-owned PDA holding the escrows taken over
+    pub token_account: Pubkey,      // Writer's collateral token account holding the pool's capital
+    pub discount_bps: u64,          // Discount on defaulting writers' deposits, in basis points
+    pub total_shares: u64,          // Shares issued to LPs
+    pub reward_per_share: u128,     // Discount rewards accrued per share, scaled by REWARD_PRECISION
+    pub unclaimed_rewards: u64,     // Rewards held in the token account but owed to LPs
+    pub epoch_duration: i64,        // Length of a withdrawal epoch in seconds
+    pub epoch_end: i64,             // End of the current withdrawal epoch (Unix timestamp)
+    pub queued_shares: u64,         // Shares queued for withdrawal, included in `total_shares`
+    pub pending_withdrawals: u64,   // Processed withdrawals held in the token account until claimed
+    #[max_len(MAX_QUEUED_WITHDRAWALS)]
+    pub withdrawal_queue: Vec<Pubkey>, // Owners of the queued withdrawals, in request order
+    pub bump: u8,                   // Bump seed of this PDA
+    pub writer_bump: u8,            // Bump seed of the writer PDA
+}
+
+impl BackstopPool {
+    /// Returns the pool's free capital out of its token account balance, excluding rewards and
+    /// processed withdrawals owed.
+    pub fn available(&self, balance: u64) -> u64 {
+        balance.saturating_sub(self.unclaimed_rewards).saturating_sub(self.pending_withdrawals)
+    }
+
+    /// Returns the pool's value: its free capital plus the collateral of the escrows it holds.
+    pub fn value(&self, balance: u64, open_notional: u64) -> Result<u64> {
+        self.available(balance).checked_add(open_notional).ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Returns the collateral the pool must deposit to take over an escrow, what it pays the
+    /// defaulting writer for its deposit, and the discount kept for the LPs.
+    pub fn takeover_cost(&self, escrow_account: &EscrowAccount) -> Result<(u64, u64, u64)> {
+        let shortfall = escrow_account.collateral_amount.saturating_sub(escrow_account.deposited_amount);
+        let discount = u64::try_from(escrow_account.deposited_amount as u128 * self.discount_bps as u128 / 10000)
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        Ok((shortfall, escrow_account.deposited_amount - discount, discount))
+    }
+
+    /// Credits a takeover discount to the LPs.
+    ///
+    /// Shares queued for withdrawal don't earn rewards; with nothing else left, the discount
+    /// stays in the pool's value instead.
+    pub fn accrue_rewards(&mut self, amount: u64) -> Result<()> {
+        let earning_shares = self.total_shares - self.queued_shares;
+        if amount == 0 || earning_shares == 0 {
+            return Ok(());
+        }
+        let increment = (amount as u128)
+            .checked_mul(REWARD_PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?
+            / earning_shares as u128;
+        self.reward_per_share = self.reward_per_share.checked_add(increment).ok_or(ErrorCode::MathOverflow)?;
+        self.unclaimed_rewards = self.unclaimed_rewards.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// A designated backstop LP's position in a backstop pool.
+#[account]
+#[derive(InitSpace)]
+pub struct BackstopLp {
+    pub pool: Pubkey,               // Backstop pool the position is in
+    pub owner: Pubkey,              // LP owning the position
+    pub shares: u64,                // Pool shares held
+    pub reward_debt: u128,          // Rewards already accounted for, scaled by REWARD_PRECISION
+    pub unclaimed_rewards: u64,     // Rewards earned but not yet claimed
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl BackstopLp {
+    /// Moves the rewards earned since the last update into `unclaimed_rewards`.
+    ///
+    /// Must be called before `shares` changes.
+    pub fn settle_rewards(&mut self, pool: &BackstopPool) -> Result<()> {
+        let accumulated = (self.shares as u128)
+            .checked_mul(pool.reward_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let earned_scaled = accumulated - self.reward_debt;
+        let earned = u64::try_from(earned_scaled / REWARD_PRECISION).map_err(|_| ErrorCode::MathOverflow)?;
+        self.unclaimed_rewards = self.unclaimed_rewards.checked_add(earned).ok_or(ErrorCode::MathOverflow)?;
+        self.reward_debt = accumulated - earned_scaled % REWARD_PRECISION;
+        Ok(())
+    }
+
+    /// Resets the reward debt after `shares` changed.
+    pub fn reset_reward_debt(&mut self, pool: &BackstopPool) -> Result<()> {
+        self.reward_debt = (self.shares as u128)
+            .checked_mul(pool.reward_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// Sealed-bid, uniform-price auction of the escrows a protocol-owned vault wrote in one epoch.
+///
+/// Revealed bids are kept ranked by price, trimmed to the lots on sale, so when bidding ends
+/// `winners` holds the fills and the last winner's price clears the auction.
+#[account]
+#[derive(InitSpace)]
+pub struct IssuanceAuction {
+    pub pol_vault: Pubkey,          // Vault selling the escrows
+    pub epoch: u64,                 // Vault epoch the auction belongs to
+    #[max_len(MAX_AUCTION_LOTS)]
+    pub escrows: Vec<Pubkey>,       // Escrows on sale, assigned to winners in this order
+    #[max_len(MAX_AUCTION_LOTS)]
+    pub fair_values: Vec<u64>,      // Fair value of each escrow when the auction opened (empty if the market had no premium bounds)
+    pub reserve_price: u64,         // Lowest accepted price per escrow
+    pub commit_end: i64,            // End of the bidding phase (Unix timestamp)
+    pub reveal_end: i64,            // End of the reveal phase (Unix timestamp)
+    #[max_len(MAX_AUCTION_LOTS)]
+    pub winners: Vec<WinningBid>,   // Revealed bids currently winning, best price first
+    pub lots_sold: u8,              // Escrows assigned to winners so far
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A revealed bid winning escrows in an issuance auction.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct WinningBid {
+    pub bidder: Pubkey,             // Bidder placing the bid
+    pub price: u64,                 // Price bid per escrow
+    pub quantity: u8,               // Escrows the bid currently wins
+}
+
+impl IssuanceAuction {
+    /// Ranks a revealed bid among the winners, behind earlier bids at the same price, and drops
+    /// whatever no longer fits in the lots on sale.
+    pub fn add_bid(&mut self, bidder: Pubkey, price: u64, quantity: u8) {
+        let index = self.winners.iter().position(|winner| winner.price < price).unwrap_or(self.winners.len());
+        self.winners.insert(index, WinningBid { bidder, price, quantity });
+        let mut remaining = self.escrows.len() as u8;
+        self.winners.retain_mut(|winner| {
+            winner.quantity = winner.quantity.min(remaining);
+            remaining -= winner.quantity;
+            winner.quantity > 0
+        });
+    }
+
+    /// Checks a price per escrow against the market's premium bounds around the fair value of
+    /// every escrow on sale.
+    pub fn check_premium_bounds(&self, market: &Market, price: u64) -> Result<()> {
+        self.fair_values.iter().try_for_each(|fair_value| market.check_premium_bounds(price, *fair_value))
+    }
+
+    /// Returns the price every winner pays per escrow: the lowest winning price.
+    pub fn clearing_price(&self) -> u64 {
+        self.winners.last().map_or(self.reserve_price, |winner| winner.price)
+    }
+
+    /// Returns the number of escrows a bidder won.
+    pub fn filled_quantity(&self, bidder: &Pubkey) -> u8 {
+        self.winners.iter().find(|winner| winner.bidder == *bidder).map_or(0, |winner| winner.quantity)
+    }
+}
+
+/// A bidder's sealed bid in an issuance auction.
+#[account]
+#[derive(InitSpace)]
+pub struct AuctionBid {
+    pub auction: Pubkey,            // Auction the bid is in
+    pub bidder: Pubkey,             // Bidder placing the bid
+    pub commitment: [u8; 32],       // Hash of the price, quantity, salt, and bidder
+    pub deposit: u64,               // Collateral deposited to cover the bid
+    pub revealed: bool,             // Whether the bid has been revealed
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// A backstop LP's withdrawal queued for the end of the pool's epoch.
+///
+/// Unfilled shares carry over from epoch to epoch until processed or cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawalRequest {
+    pub pool: Pubkey,               // Backstop pool the withdrawal is from
+    pub owner: Pubkey,              // LP withdrawing
+    pub shares: u64,                // Shares still waiting to be processed
+    pub withdrawable: u64,          // Processed amount ready to be claimed
+    pub requested_at: i64,          // When shares were last added to the request (Unix timestamp)
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Strategy of a protocol-owned vault, tunable by governance without redeploying.
+#[account]
+#[derive(InitSpace)]
+pub struct StrategyConfig {
+    pub pol_vault: Pubkey,          // Vault following the strategy
+    pub params: StrategyParams,     // Strike selection, expiry cadence, and allocation
+    pub last_expiration: i64,       // Expiry of the latest escrow written by `process_epoch`
+    pub voting: StrategyVoting,     // How the vault's share holders vote on strategy changes
+    pub proposal_count: u64,        // Strategy proposals created so far
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Rules of share holder votes on a vault's strategy.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct StrategyVoting {
+    pub share_mint: Pubkey,         // Mint of the vault's shares, which carry the votes
+    pub quorum_votes: u64,          // Total shares that must vote for a proposal to pass
+    pub voting_period: i64,         // Length of the voting period in seconds (0 disables share holder votes)
+    pub notice_period: i64,         // Delay between the end of voting and the new strategy taking effect
+}
+
+/// A share holders' proposal to change a vault strategy.
+#[account]
+#[derive(InitSpace)]
+pub struct StrategyProposal {
+    pub strategy_config: Pubkey,    // Strategy the proposal changes
+    pub id: u64,                    // Sequential proposal number
+    pub proposer: Pubkey,           // Share holder who created the proposal
+    pub params: StrategyParams,     // Proposed strategy
+    pub votes_for: u64,             // Shares voted in favor
+    pub votes_against: u64,         // Shares voted against
+    pub voting_ends_at: i64,        // End of voting (Unix timestamp)
+    pub effective_at: i64,          // Earliest time the strategy can take effect (Unix timestamp)
+    pub executed: bool,             // Whether the strategy has been applied
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+impl StrategyProposal {
+    /// Returns whether the proposal won a majority of the votes with the quorum reached.
+    pub fn passed(&self, voting: &StrategyVoting) -> bool {
+        let total_votes = self.votes_for as u128 + self.votes_against as u128;
+        self.votes_for > self.votes_against && total_votes >= voting.quorum_votes as u128
+    }
+}
+
+/// Record of a share holder's vote on a strategy proposal, with the shares locked until voting ends.
+#[account]
+#[derive(InitSpace)]
+pub struct StrategyVote {
+    pub proposal: Pubkey,           // Proposal voted on
+    pub voter: Pubkey,              // Share holder who voted
+    pub support: bool,              // Whether the vote was in favor
+    pub shares: u64,                // Shares locked with the vote
+    pub bump: u8,                   // Bump seed of this PDA
+}
+
+/// Knobs of a vault strategy.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub struct StrategyParams {
+    pub option_type: OptionType,    // Type of option written
+    pub strike_selection: StrikeSelection, // How the strike is chosen
+    pub expiry_cadence: i64,        // Time between expiries in seconds (e.g. a week)
+    pub expiry_offset: i64,         // Offset of the expiries from multiples of the cadence (e.g. to Friday 08:00 UTC)
+    pub max_tvl_bps: u64,           // Largest share of the vault's value written per epoch, in basis points
+}
+
+/// How a vault strategy chooses its strike.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace)]
+pub enum StrikeSelection {
+    OtmBps(u64),                    // Fixed distance out of the money, in basis points of spot
+    TargetDelta(u64),               // Strike whose absolute delta is closest to this, in millionths (GREEKS_UNIT)
+}
+
+impl StrategyParams {
+    /// Checks the parameters are consistent.
+    pub fn validate(&self) -> Result<()> {
+        let strike_ok = match self.strike_selection {
+            StrikeSelection::OtmBps(otm_bps) => otm_bps < 10000,
+            StrikeSelection::TargetDelta(delta) => delta > 0 && delta < GREEKS_UNIT as u64,
+        };
+        if !strike_ok
+            || self.expiry_cadence <= 0
+            || self.expiry_offset < 0
+            || self.expiry_offset >= self.expiry_cadence
+            || self.max_tvl_bps == 0
+            || self.max_tvl_bps > 10000
+        {
+            return Err(ErrorCode::InvalidStrategyParams.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the first expiry of the cadence after `current_time`.
+    pub fn next_expiration(&self, current_time: i64) -> Result<i64> {
+        next_grid_time(self.expiry_cadence, self.expiry_offset, current_time)
+    }
+
+    /// Returns the strike to write at `spot_price` for an option expiring in `tenor` seconds.
+    ///
+    /// Target-delta strikes need the market's volatility surface and are found by bisection
+    /// between a quarter and four times spot.
+    pub fn select_strike(&self, spot_price: u64, tenor: i64, vol_config: Option<&VolConfig>, current_time: i64) -> Result<u64> {
+        let target_delta = match self.strike_selection {
+            StrikeSelection::OtmBps(otm_bps) => {
+                let bps = match self.option_type {
+                    OptionType::Call => 10000 + otm_bps,
+                    OptionType::Put => 10000 - otm_bps,
+                };
+                return u64::try_from(spot_price as u128 * bps as u128 / 10000).map_err(|_| ErrorCode::MathOverflow.into());
+            }
+            StrikeSelection::TargetDelta(delta) => delta as i64,
+        };
+        let vol_config = vol_config.ok_or(ErrorCode::InvalidVolConfig)?;
+        let vol_bps = vol_config.implied_vol_bps(tenor, current_time)?;
+        let (mut low, mut high) = (spot_price / 4, spot_price.saturating_mul(4));
+        for _ in 0..STRIKE_SEARCH_STEPS {
+            let strike = low + (high - low) / 2;
+            let delta = math::greeks(self.option_type.clone(), spot_price, strike, tenor, vol_config.rate_bps, vol_bps)?.delta.abs();
+            // Call deltas fall as the strike rises, put deltas grow
+            let raise_strike = match self.option_type {
+                OptionType::Call => delta > target_delta,
+                OptionType::Put => delta < target_delta,
+            };
+            if raise_strike {
+                low = strike;
+            } else {
+                high = strike;
+            }
+        }
+        Ok(low + (high - low) / 2)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for initializing the escrow.
+///
+/// This struct defines the context for the `initialize_escrow` instruction, specifying
+/// the accounts involved, including the escrow account, the initializer, the collateral
+/// accounts, the market and its fee vault, and the governance account.
+#[instruction(option_type: OptionType, strike_price: u64, expiration: i64, collateral_amount: u64, collateral_mint: Pubkey, terms: OptionTerms)]
+pub struct InitializeEscrow<'info> {
+    #[account(init, payer = initializer, space = EscrowAccount::SPACE)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow account to store option details
+    #[account(mut)]
+    pub initializer: Signer<'info>,                      // The initializer (creator of the escrow)
+    #[account(mut)]
+    pub initializer_collateral_account: Account<'info, TokenAccount>,  // Initializer's token account for collateral
+    #[account(mut, constraint = initializer_fee_account.mint == market.fee_mint @ ErrorCode::IncorrectFeeMint)]
+    pub initializer_fee_account: Option<Account<'info, TokenAccount>>, // Initializer's fee mint account (if fees aren't in collateral)
+    #[account(mut, seeds = [MARKET_SEED, collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                  // Market for the collateral mint
+    #[account(mut, seeds = [MARKET_STATS_SEED, collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,       // Stats of the market
+    #[account(mut, seeds = [PROTOCOL_STATS_SEED], bump = protocol_stats.bump)]
+    pub protocol_stats: Account<'info, ProtocolStats>,   // Protocol-wide stats
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (if fees aren't in collateral or strikes are banded)
+    /// CHECK: Lending reserve, checked against the collateral mint and parsed on use
+    pub collateral_reserve: Option<UncheckedAccount<'info>>, // Lending reserve of interest-bearing collateral (for its exchange rate)
+    #[account(seeds = [EXPIRY_GRID_SEED, collateral_mint.as_ref()], bump = expiry_grid.bump)]
+    pub expiry_grid: Option<Account<'info, ExpiryGrid>>, // Market's grid of standard expiries (required if the market lists on one)
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.fee_oracle)]
+    pub fee_oracle: Option<UncheckedAccount<'info>>,     // Fee mint price feed (if fees aren't in collateral)
+    #[account(mut, seeds = [FEE_VAULT_SEED, market.fee_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,             // Fee vault for the market's fee mint
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account receiving the fee
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,          // Governance account storing fee rates and fee collectors
+    #[account(seeds = [STAKE_POSITION_SEED, initializer.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Option<Account<'info, StakePosition>>, // Initializer's stake position (for a fee discount)
+    #[account(mut, seeds = [USER_STATS_SEED, initializer.key().as_ref()], bump = initializer_stats.bump)]
+    pub initializer_stats: Account<'info, UserStats>,    // Initializer's points and stats
+    #[account(mut, seeds = [USER_POSITION_SEED, initializer.key().as_ref()], bump = initializer_position.bump)]
+    pub initializer_position: Account<'info, UserPosition>, // Initializer's open escrows
+    #[account(mut, close = initializer, constraint = fee_approval.writer == initializer.key() @ ErrorCode::NotEscrowWriter)]
+    pub fee_approval: Option<Account<'info, FeeApproval>>, // Negotiated fee override consumed by this escrow (if any)
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), initializer.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Initializer's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // Initializer's gate mint account (required in token-gated markets)
+    pub system_program: Program<'info, System>,          // System program for account creation
+    pub token_program: Program<'info, Token>,            // Token program for handling SPL tokens
+    pub rent: Sysvar<'info, Rent>,                       // Rent system for account initialization
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+}
+
+impl<'info> InitializeEscrow<'info> {
+    /// Collects the initialization fee into the fee vault.
+    ///
+    /// The fee is paid from the initializer's collateral account, or converted into the
+    /// market's fee mint and paid from the initializer's fee account. Stakers get the
+    /// discount of their staking tier.
+    fn collect_fee(&mut self, fee: u64) -> Result<()> {
+        let fee = self.governance.apply_fee_discount(fee, self.stake_position.as_ref(), Clock::get()?.unix_timestamp);
+        self.initializer_stats.record_fees_paid(&self.market, fee)?;
+        self.market_stats.fees_collected = self.market_stats.fees_collected.saturating_add(fee);
+        let (from, fee_amount) = if self.market.has_separate_fee_mint() {
+            let initializer_fee_account = self.initializer_fee_account.as_ref().ok_or(ErrorCode::MissingFeeAccount)?;
+            let fee_amount = self.market.convert_to_fee_mint(fee, self.collateral_oracle.as_ref(), self.fee_oracle.as_ref())?;
+            (initializer_fee_account.to_account_info(), fee_amount)
+        } else {
+            (self.initializer_collateral_account.to_account_info(), fee)
+        };
+
+        let cpi_accounts_fee = Transfer {
+            from,
+            to: self.fee_vault_token_account.to_account_info(),
+            authority: self.initializer.to_account_info(),
+        };
+        let cpi_ctx_fee = CpiContext::new(self.token_program.to_account_info(), cpi_accounts_fee);
+        token::transfer(cpi_ctx_fee, fee_amount)?;
+        self.fee_vault.accrue(&self.governance.fee_collectors, fee_amount)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for depositing collateral into the escrow.
+///
+/// This struct defines the context for the `deposit_collateral` instruction, specifying
+/// the user's collateral account, the escrow account, and the necessary programs.
+pub struct DepositCollateral<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow account receiving collateral
+    #[account(mut)]
+    pub user: Signer<'info>,                              // User depositing collateral
+    #[account(mut)]
+    pub user_collateral_account: Account<'info, TokenAccount>,  // User's token account for depositing collateral
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = escrow_collateral_account
+    )]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral (created on the first deposit)
+    #[account(address = escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the escrow
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the collateral mint (for pause flags)
+    #[account(mut, seeds = [MARKET_STATS_SEED, escrow_account.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,        // Stats of the market
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), user.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // User's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // User's gate mint account (required in token-gated markets)
+    #[account(mut, seeds = [EMISSIONS_SEED, market.key().as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Option<Account<'info, EmissionsSchedule>>, // Market's emissions schedule (to enroll the escrow once funded)
+    #[account(
+        mut,
+        seeds = [WRITER_EMISSIONS_SEED, market.key().as_ref(), escrow_account.initializer_key.as_ref()],
+        bump = writer_emissions.bump
+    )]
+    pub writer_emissions: Option<Account<'info, WriterEmissions>>, // Writer's emissions position (to enroll the escrow once funded)
+    pub system_program: Program<'info, System>,           // System program for creating the vault
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+}
+
+#[derive(Accounts)]
+/// Context for settling the escrow when the option expires.
+///
+/// This struct defines the context for the `settle_escrow` and `exercise_early` instructions,
+/// specifying the involved accounts, including the escrow, the user, the initializer, the
+/// market, and the governance and fee vault accounts.
+pub struct SettleEscrow<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow account storing option details
+    #[account(mut)]
+    pub user: Signer<'info>,                              // The user settling the option
+    /// CHECK: Checked on exercise to be the user or the holder it acts for
+    pub proceeds_owner: UncheckedAccount<'info>,          // Owner of the account receiving the holder's proceeds
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = proceeds_owner
+    )]
+    pub user_collateral_account: Account<'info, TokenAccount>,  // Proceeds owner's associated token account (receiving collateral if ITM)
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral
+    /// CHECK: Validated against the escrow's writer
+    #[account(address = escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub initializer: UncheckedAccount<'info>,             // Writer of the escrow
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = initializer
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Initializer's associated token account (receiving collateral if OTM)
+    #[account(address = escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the escrow
+    #[account(mut, constraint = user_fee_account.mint == market.fee_mint @ ErrorCode::IncorrectFeeMint)]
+    pub user_fee_account: Option<Account<'info, TokenAccount>>, // User's fee mint account (if fees aren't in collateral)
+    #[account(mut, seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market for the collateral mint
+    #[account(mut, seeds = [MARKET_STATS_SEED, escrow_account.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,        // Stats of the market
+    #[account(mut, seeds = [PROTOCOL_STATS_SEED], bump = protocol_stats.bump)]
+    pub protocol_stats: Account<'info, ProtocolStats>,    // Protocol-wide stats
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (unless governance set the settlement price)
+    /// CHECK: Lending reserve, checked against the collateral mint and parsed on use
+    pub collateral_reserve: Option<UncheckedAccount<'info>>, // Lending reserve of interest-bearing collateral (for its exchange rate)
+    #[account(seeds = [ORACLE_REGISTRY_SEED, escrow_account.collateral_mint.as_ref()], bump = oracle_registry.bump)]
+    pub oracle_registry: Option<Account<'info, OracleRegistry>>, // Market's oracle registry (if it aggregates feeds)
+    #[account(seeds = [BASKET_SEED, escrow_account.collateral_mint.as_ref()], bump = basket.bump)]
+    pub basket: Option<Account<'info, Basket>>,  // Market's basket of underlyings (for basket options)
+    /// CHECK: Pyth price feed, checked against the quanto payoff and parsed on use
+    pub underlying_oracle: Option<UncheckedAccount<'info>>, // Underlying price feed (for quanto options)
+    #[account(seeds = [RENTAL_SEED, escrow_account.key().as_ref()], bump = rental.bump)]
+    pub rental: Option<Account<'info, Rental>>,  // Rental of the exercise rights (if rented)
+    #[account(seeds = [OPTION_LOAN_SEED, escrow_account.key().as_ref()], bump = option_loan.bump)]
+    pub option_loan: Option<Account<'info, OptionLoan>>, // Loan of the option (if lent out)
+    #[account(seeds = [PRICE_HISTORY_SEED, escrow_account.collateral_mint.as_ref()], bump = price_history.load()?.bump)]
+    pub price_history: Option<AccountLoader<'info, PriceHistory>>, // Market's price history (if it settles on a TWAP)
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub secondary_oracle: Option<UncheckedAccount<'info>>, // First extra feed of the oracle registry
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub tertiary_oracle: Option<UncheckedAccount<'info>>,  // Second extra feed of the oracle registry
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.fee_oracle)]
+    pub fee_oracle: Option<UncheckedAccount<'info>>,      // Fee mint price feed (if fees aren't in collateral)
+    #[account(mut, seeds = [FEE_VAULT_SEED, market.fee_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault for the market's fee mint
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account receiving the fee
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,           // Governance account storing fee rates and fee collectors
+    #[account(seeds = [STAKE_POSITION_SEED, user.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Option<Account<'info, StakePosition>>, // User's stake position (for a fee discount)
+    #[account(mut, seeds = [USER_STATS_SEED, user.key().as_ref()], bump = user_stats.bump)]
+    pub user_stats: Option<Account<'info, UserStats>>,    // User's points and stats (to earn fee points)
+    #[account(mut, seeds = [USER_STATS_SEED, escrow_account.initializer_key.as_ref()], bump = writer_stats.bump)]
+    pub writer_stats: Account<'info, UserStats>,          // Writer's points and stats
+    #[account(mut, seeds = [USER_POSITION_SEED, escrow_account.initializer_key.as_ref()], bump = writer_position.bump)]
+    pub writer_position: Account<'info, UserPosition>,    // Writer's open escrows
+    #[account(mut, seeds = [EMISSIONS_SEED, market.key().as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Option<Account<'info, EmissionsSchedule>>, // Market's emissions schedule (if the escrow is enrolled)
+    #[account(
+        mut,
+        seeds = [WRITER_EMISSIONS_SEED, market.key().as_ref(), escrow_account.initializer_key.as_ref()],
+        bump = writer_emissions.bump
+    )]
+    pub writer_emissions: Option<Account<'info, WriterEmissions>>, // Writer's emissions position (if the escrow is enrolled)
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), user.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // User's whitelist entry (required in permissioned markets)
+    #[account(constraint = session.session_key == user.key() @ ErrorCode::InvalidSession)]
+    pub session: Option<Account<'info, SessionKey>>,      // Holder's session key, if the user exercises through one
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    pub associated_token_program: Program<'info, AssociatedToken>, // Associated token program for creating missing accounts
+    pub system_program: Program<'info, System>,           // System program for account creation
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+    #[account(mut, seeds = [AUDIT_LOG_SEED, escrow_account.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+    #[account(seeds = [VOL_CONFIG_SEED, escrow_account.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Option<Account<'info, VolConfig>>,    // Market's volatility curve (to report greeks in events)
+    /// CHECK: Keeper registry PDA, possibly uninitialized, parsed by `earns_crank_fee`
+    #[account(seeds = [KEEPER_REGISTRY_SEED], bump)]
+    pub keeper_registry: Option<UncheckedAccount<'info>>, // Keeper registry (to earn the crank fee)
+    #[account(seeds = [KEEPER_SEED, user.key().as_ref()], bump = keeper.bump)]
+    pub keeper: Option<Account<'info, Keeper>>,           // Settler's keeper stake (for priority on the crank fee)
+    #[account(
+        seeds = [SERIES_SETTLERS_SEED, escrow_account.collateral_mint.as_ref(), &escrow_account.expiration.to_le_bytes()],
+        bump = series_settlers.bump
+    )]
+    pub series_settlers: Option<Account<'info, SeriesSettlers>>, // Settlers of the escrow's series (in markets with restricted settlement)
+}
+
+/// The accounts that price an escrow at settlement.
+///
+/// Shared by the settlement instructions and `preview_settlement`, so previews run the same
+/// code as the settlement they predict.
+pub struct SettlementPricing<'a, 'info> {
+    pub escrow_account: &'a EscrowAccount,                              // Escrow being priced
+    pub market: &'a Market,                                             // Market of the escrow
+    pub collateral_oracle: Option<&'a UncheckedAccount<'info>>,         // Collateral price feed
+    pub underlying_oracle: Option<&'a UncheckedAccount<'info>>,         // Underlying price feed (for quanto options)
+    pub basket: Option<&'a Account<'info, Basket>>,                     // Market's basket (for basket options)
+    pub price_history: Option<&'a AccountLoader<'info, PriceHistory>>,  // Market's price history (if it settles on a TWAP)
+    pub oracle_registry: Option<&'a Account<'info, OracleRegistry>>,    // Market's oracle registry (if it aggregates feeds)
+    pub extra_oracles: [Option<&'a UncheckedAccount<'info>>; 2],        // Extra feeds of the oracle registry
+    pub collateral_reserve: Option<&'a UncheckedAccount<'info>>,        // Lending reserve (for interest-bearing collateral)
+}
+
+impl<'a, 'info> SettlementPricing<'a, 'info> {
+    /// Returns the part of the collateral left after fees that an ITM holder receives.
+    ///
+    /// Quanto options pay `payout_per_point` of the quote currency (e.g. USD) per point of
+    /// moneyness, converted into collateral at the collateral oracle's price and capped at the
+    /// collateral. Other payoffs are priced by the escrow itself. Only the vested part of the
+    /// payout goes to the holder of a vesting option; the rest returns to the writer.
+    pub fn holder_payout(&self, amount_after_fee: u64) -> Result<u64> {
+        let payout = self.full_holder_payout(amount_after_fee)?;
+        Ok(self.escrow_account.vested_amount(payout, Clock::get()?.unix_timestamp))
+    }
+
+    /// Returns the holder's payout before vesting is applied.
+    ///
+    /// Escrows on interest-bearing collateral are priced in underlying terms: the payout is
+    /// worked out on the underlying the shares were worth when the escrow opened, then paid in
+    /// shares at the current exchange rate, so the yield accrued since stays with the writer.
+    pub fn full_holder_payout(&self, amount_after_fee: u64) -> Result<u64> {
+        let exchange_rate = self.exchange_rate()?;
+        let Payoff::Quanto { payout_per_point, .. } = self.escrow_account.payoff else {
+            let payout = self.escrow_account.holder_payout(amount_after_fee);
+            let Some(rate) = exchange_rate else {
+                return Ok(payout);
+            };
+            let shares = payout as u128 * self.escrow_account.deposit_exchange_rate as u128 / rate as u128;
+            return Ok(shares.min(amount_after_fee as u128) as u64);
+        };
+        let spot_price = self.settlement_price(&[])?;
+        let strike_price = self.escrow_account.strike_price;
+        let points = match self.escrow_account.option_type {
+            OptionType::Call => spot_price.saturating_sub(strike_price),
+            OptionType::Put => strike_price.saturating_sub(spot_price),
+        };
+        let quote_amount = u64::try_from(points as u128 * payout_per_point as u128 / 10u128.pow(STRIKE_PRICE_DECIMALS as u32))
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        let collateral_oracle = self.collateral_oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+        let collateral_price = self.market.lst_oracle_price(load_oracle_price(&collateral_oracle.to_account_info(), &self.market.oracle_config)?)?;
+        let quote_price = Price { price: 1, conf: 0, expo: 0, publish_time: collateral_price.publish_time };
+        let payout = convert_amount(
+            quote_amount,
+            &quote_price,
+            STRIKE_PRICE_DECIMALS as u8,
+            &collateral_price,
+            self.market.collateral_decimals,
+        )?;
+        let Some(rate) = exchange_rate else {
+            return Ok(payout.min(amount_after_fee));
+        };
+        let shares = payout as u128 * EXCHANGE_RATE_SCALE as u128 / rate as u128;
+        Ok(shares.min(amount_after_fee as u128) as u64)
+    }
+
+    /// Returns the current exchange rate of the escrow's interest-bearing collateral, or `None`
+    /// if it pays out raw shares: plain collateral, or escrows opened before the market was
+    /// marked interest-bearing.
+    fn exchange_rate(&self) -> Result<Option<u64>> {
+        if self.escrow_account.deposit_exchange_rate == 0 {
+            return Ok(None);
+        }
+        self.market.collateral_exchange_rate(self.collateral_reserve)
+    }
+
+    /// Whether settlement exercises the option (exercise by exception).
+    ///
+    /// The option must be ITM by at least the market's auto-exercise threshold, and its holder
+    /// must not have opted out; anything else expires and the collateral returns to the writer.
+    pub fn auto_exercises(&self, basket_oracles: &[AccountInfo]) -> Result<bool> {
+        if self.escrow_account.auto_exercise_opt_out || !self.is_itm(basket_oracles)? {
+            return Ok(false);
+        }
+        let threshold_bps = self.market.auto_exercise_threshold_bps;
+        if threshold_bps == 0 {
+            return Ok(true);
+        }
+        Ok(self.escrow_account.moneyness_bps(self.settlement_price(basket_oracles)?) >= threshold_bps)
+    }
+
+    /// Whether the option pays out to the holder, given its barrier, funding, and settlement price.
+    ///
+    /// Basket options take the oracles of the market's basket as remaining accounts, in order.
+    pub fn is_itm(&self, basket_oracles: &[AccountInfo]) -> Result<bool> {
+        if !self.escrow_account.barrier_allows_payout() || self.escrow_account.is_funding_lapsed(Clock::get()?.unix_timestamp) {
+            return Ok(false);
+        }
+        Ok(self.escrow_account.intrinsic_value(self.settlement_price(basket_oracles)?)? > 0)
+    }
+
+    /// Returns the price the escrow settles at, in `STRIKE_PRICE_DECIMALS`.
+    ///
+    /// Unless governance has set a manual settlement price for the escrow, Asian options settle
+    /// on the average of their observations and lookback options on their most favorable
+    /// observation, basket options on the weighted value of the market's basket, and quanto
+    /// options on their own underlying's oracle price. Other options settle on the TWAP over the window
+    /// before expiry if the market has one, or else the oracle price, guarded by the market's
+    /// circuit breaker and aggregated with the feeds of its oracle registry.
+    pub fn settlement_price(&self, basket_oracles: &[AccountInfo]) -> Result<u64> {
+        if let Some(price) = self.escrow_account.own_settlement_price()? {
+            return Ok(price);
+        }
+        if let Payoff::Quanto { underlying_oracle, .. } = self.escrow_account.payoff {
+            let oracle = self.underlying_oracle.ok_or(ErrorCode::MissingOracleAccount)?;
+            if oracle.key() != underlying_oracle {
+                return Err(ErrorCode::InvalidOracleAccount.into());
+            }
+            return to_strike_price(&load_oracle_price(&oracle.to_account_info(), &self.market.oracle_config)?);
+        }
+        if let Payoff::Basket = self.escrow_account.payoff {
+            let basket = self.basket.ok_or(ErrorCode::MissingOracleAccount)?;
+            return basket.composite_price(basket_oracles, &self.market.oracle_config);
+        }
+        oracle_settlement_price(
+            self.market,
+            self.escrow_account.expiration,
+            self.collateral_oracle,
+            self.price_history,
+            self.oracle_registry,
+            self.extra_oracles,
+        )
+    }
+}
+
+impl<'info> SettleEscrow<'info> {
+    /// Returns the accounts pricing the escrow.
+    fn pricing(&self) -> SettlementPricing<'_, 'info> {
+        SettlementPricing {
+            escrow_account: &self.escrow_account,
+            market: &self.market,
+            collateral_oracle: self.collateral_oracle.as_ref(),
+            underlying_oracle: self.underlying_oracle.as_ref(),
+            basket: self.basket.as_ref(),
+            price_history: self.price_history.as_ref(),
+            oracle_registry: self.oracle_registry.as_ref(),
+            extra_oracles: [self.secondary_oracle.as_ref(), self.tertiary_oracle.as_ref()],
+            collateral_reserve: self.collateral_reserve.as_ref(),
+        }
+    }
+
+    /// Returns the escrow's greeks at its settlement price, for the lifecycle events.
+    ///
+    /// Reporting never blocks settlement: without a vol curve, or if it is stale or the payoff
+    /// isn't vanilla, there are no greeks.
+    fn greeks(&self, current_time: i64) -> Option<Greeks> {
+        let vol_config = self.vol_config.as_ref()?;
+        let price = self.pricing().settlement_price(&[]).ok()?;
+        self.escrow_account.greeks(price, vol_config, current_time).ok()
+    }
+
+    /// Returns the crank incentive owed to the settler of an expired escrow.
+    ///
+    /// Paid only into the settler's own token account, and only once the escrow has expired.
+    fn crank_fee(&self, amount_after_fee: u64, current_time: i64) -> Result<u64> {
+        if self.proceeds_owner.key() != self.user.key()
+            || current_time < self.escrow_account.expiration
+            || !earns_crank_fee(self.keeper_registry.as_ref(), self.keeper.as_ref(), self.escrow_account.expiration, current_time)?
+        {
+            return Ok(0);
+        }
+        Ok((amount_after_fee as u128 * self.governance.crank_fee_bps as u128 / 10000) as u64)
+    }
+
+    /// Exercises the escrow early, as `exercise_early` describes, and returns the holder's
+    /// proceeds.
+    ///
+    /// `instruction` is the discriminator recorded in the audit log.
+    fn exercise(&mut self, remaining_accounts: &[AccountInfo], unwrap_sol: bool, event_bump: u8, instruction: [u8; 8]) -> Result<u64> {
+        self.market.require_not_paused(PAUSE_EXERCISE)?;
+        self.market.require_whitelisted(&self.whitelist_entry)?;
+        let escrow_account = &self.escrow_account;
+        escrow_account.require_allowed(self.user.key)?;
+        escrow_account.require_not_lent()?;
+
+        // Ensure the option has not been exercised yet
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+
+        // Exercise pays out of the full collateral, so the vault must hold all of it
+        if escrow_account.deposited_amount < escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowNotFunded.into());
+        }
+
+        // Ensure it's an American option to allow early exercise
+        if escrow_account.option_type != OptionType::Call && escrow_account.option_type != OptionType::Put {
+            return Err(ErrorCode::CannotExerciseEarly.into());
+        }
+
+        // Manual exercise stops at the market's cutoff, leaving the rest to settlement
+        let current_time = Clock::get()?.unix_timestamp;
+        if escrow_account.is_past_exercise_cutoff(&self.market, current_time) {
+            return Err(ErrorCode::ExerciseCutoffPassed.into());
+        }
+
+        // Bermudan options can only be exercised within one of their windows
+        let windows = &escrow_account.exercise_windows;
+        if !windows.is_empty() && !windows.iter().any(|window| current_time >= window.start && current_time <= window.end) {
+            return Err(ErrorCode::OutsideExerciseWindow.into());
+        }
+
+        // Average-price and lookback options have no meaningful price before all observations are in
+        if escrow_account.payoff.observation_interval().is_some() {
+            return Err(ErrorCode::CannotExerciseEarly.into());
+        }
+
+        // A session key or delegate exercises as the holder who authorized it, but the proceeds
+        // still go to the holder
+        let delegating_holder = match &self.session {
+            Some(session) => {
+                session.require_scope(SESSION_SCOPE_EXERCISE, current_time)?;
+                Some(session.holder)
+            }
+            None => escrow_account.delegating_holder(self.user.key),
+        };
+        let exerciser = delegating_holder.unwrap_or(self.user.key());
+        if self.proceeds_owner.key() != exerciser {
+            return Err(ErrorCode::InvalidProceedsAccount.into());
+        }
+
+        // Only the holder of a funded perpetual option can exercise it
+        if escrow_account.perpetual.is_some() {
+            if escrow_account.is_funding_lapsed(current_time) {
+                return Err(ErrorCode::FundingLapsed.into());
+            }
+            if exerciser != escrow_account.holder {
+                return Err(ErrorCode::NotOptionHolder.into());
+            }
+        }
+
+        // Only the holder can exercise an escrow sold to one, such as in an issuance auction
+        if escrow_account.renter == Pubkey::default()
+            && escrow_account.holder != Pubkey::default()
+            && exerciser != escrow_account.holder
+        {
+            return Err(ErrorCode::NotOptionHolder.into());
+        }
+
+        // Only the renter can exercise rented rights, and only while the premium keeps streaming
+        if escrow_account.renter != Pubkey::default() {
+            let rental = self.rental.as_ref().ok_or(ErrorCode::NotOptionHolder)?;
+            if exerciser != rental.renter {
+                return Err(ErrorCode::NotOptionHolder.into());
+            }
+            if rental.is_lapsed(current_time) {
+                return Err(ErrorCode::RentalLapsed.into());
+            }
+        }
+
+        // Only the vesting holder can exercise a vesting option, and only once part of it has vested
+        if let Some(vesting) = &escrow_account.vesting {
+            if exerciser != vesting.holder {
+                return Err(ErrorCode::NotOptionHolder.into());
+            }
+            if escrow_account.vested_amount(escrow_account.collateral_amount, current_time) == 0 {
+                return Err(ErrorCode::NothingVested.into());
+            }
+        }
+
+        // Knocked-out options can only be settled, and knock-in options need their barrier event
+        if escrow_account.is_knocked_out() {
+            return Err(ErrorCode::OptionKnockedOut.into());
+        }
+        if !escrow_account.barrier_allows_payout() {
+            return Err(ErrorCode::BarrierNotTouched.into());
+        }
+        let is_itm = self.pricing().is_itm(remaining_accounts)?;
+        let greeks = self.greeks(current_time);
+
+        // Calculate the fee, collect it, and compute the remaining collateral
+        let collateral_amount = escrow_account.collateral_amount;
+        let fee = collateral_amount * escrow_account.fee_rate(self.governance.exercise_fee_bps) / 10000;
+        let collateral_fee = self.collect_fee(fee, is_itm)?;
+        let amount_after_fee = collateral_amount - collateral_fee;
+
+        // Compensate the writer for the time value given up by exercising early. A session key or
+        // delegate can't spend the holder's tokens, so its surcharge is taken out of the proceeds.
+        let surcharge = u64::try_from(collateral_amount as u128 * self.escrow_account.early_exercise_fee_bps as u128 / 10000)
+            .map_err(|_| ErrorCode::MathOverflow)?;
+        let mut holder_amount = if is_itm { self.pricing().holder_payout(amount_after_fee)? } else { 0 };
+        if delegating_holder.is_some() {
+            holder_amount = holder_amount.checked_sub(surcharge).ok_or(ErrorCode::SurchargeExceedsPayout)?;
+        } else if surcharge > 0 {
+            let cpi_accounts = Transfer {
+                from: self.user_collateral_account.to_account_info(),
+                to: self.initializer_collateral_account.to_account_info(),
+                authority: self.user.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+            token::transfer(cpi_ctx, surcharge)?;
+        }
+
+        // A borrowed option pays the lender its share of the proceeds, held for it to claim
+        let (lender, lender_amount) = if self.escrow_account.on_loan {
+            let option_loan = self.option_loan.as_ref().ok_or(ErrorCode::MissingOptionLoanAccount)?;
+            (option_loan.lender, option_loan.lender_share(holder_amount))
+        } else {
+            (Pubkey::default(), 0)
+        };
+        let exerciser_amount = holder_amount - lender_amount;
+
+        // Handle early exercise based on whether the option is ITM or OTM
+        let user_collateral_account = self.user_collateral_account.to_account_info();
+        self.transfer_collateral(user_collateral_account, exerciser_amount)?;
+        self.escrow_account.unclaimed_writer_amount = amount_after_fee - holder_amount;
+        if unwrap_sol {
+            if exerciser != self.user.key() {
+                return Err(ErrorCode::UnwrapNotAllowed.into());
+            }
+            close_wrapped_sol(
+                &self.user_collateral_account,
+                self.user.to_account_info(),
+                &self.token_program,
+            )?;
+        }
+
+        // Mark the option as exercised
+        self.escrow_account.is_exercised = true;
+        self.escrow_account.settled_at = current_time;
+        if lender_amount > 0 {
+            self.escrow_account.payout_recipient = lender;
+            self.escrow_account.unclaimed_payout = lender_amount;
+        }
+        self.release_emissions_notional()?;
+        self.record_close(true);
+        emit_lifecycle_event(&EscrowExercised {
+            seq: self.market_stats.next_event_seq(),
+            market: self.market_stats.market,
+            escrow: self.escrow_account.key(),
+            exerciser,
+            holder_amount,
+            writer_amount: amount_after_fee - holder_amount,
+            fee: collateral_fee,
+            greeks,
+        }, self.event_authority.as_ref(), event_bump)?;
+        record_audit(
+            self.audit_log.as_ref(),
+            self.user.key(),
+            self.escrow_account.key(),
+            instruction,
+            collateral_amount,
+        )?;
+
+        Ok(exerciser_amount)
+    }
+
+    /// Transfers collateral out of the escrow, skipping empty transfers.
+    fn transfer_collateral(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        transfer_from_vault(&self.escrow_account, self.escrow_collateral_account.to_account_info(), to, &self.token_program, amount)
+    }
+
+    /// Collects a settlement or exercise fee.
+    ///
+    /// If the market charges fees in the collateral mint, the fee is taken out of the escrowed
+    /// collateral but left in the escrow's token account, accrued on the escrow until
+    /// `sweep_escrow_fees` moves it to the fee vault; this saves a CPI on the settlement path.
+    /// Otherwise it is converted into the fee mint and paid by the user from their fee account
+    /// into the fee vault. Stakers get the discount of their staking tier. The fee is credited
+    /// as paid by whoever bears it: the user, or the writer when OTM collateral is returned.
+    /// Returns the part of the fee taken out of the collateral.
+    fn collect_fee(&mut self, fee: u64, is_itm: bool) -> Result<u64> {
+        let fee = self.governance.apply_fee_discount(fee, self.stake_position.as_ref(), Clock::get()?.unix_timestamp);
+        self.market_stats.fees_collected = self.market_stats.fees_collected.saturating_add(fee);
+        if is_itm || self.market.has_separate_fee_mint() {
+            if let Some(user_stats) = self.user_stats.as_mut() {
+                user_stats.record_fees_paid(&self.market, fee)?;
+            }
+        } else {
+            self.writer_stats.record_fees_paid(&self.market, fee)?;
+        }
+        if !self.market.has_separate_fee_mint() {
+            self.escrow_account.unswept_fees = self.escrow_account.unswept_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+            return Ok(fee);
+        }
+
+        let user_fee_account = self.user_fee_account.as_ref().ok_or(ErrorCode::MissingFeeAccount)?;
+        let fee_amount = self.market.convert_to_fee_mint(fee, self.collateral_oracle.as_ref(), self.fee_oracle.as_ref())?;
+        let cpi_accounts_fee = Transfer {
+            from: user_fee_account.to_account_info(),
+            to: self.fee_vault_token_account.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+        let cpi_ctx_fee = CpiContext::new(self.token_program.to_account_info(), cpi_accounts_fee);
+        token::transfer(cpi_ctx_fee, fee_amount)?;
+        self.fee_vault.accrue(&self.governance.fee_collectors, fee_amount)?;
+        Ok(0)
+    }
+
+    /// Stops the escrow's notional from earning liquidity-mining emissions.
+    fn release_emissions_notional(&mut self) -> Result<()> {
+        let notional = self.escrow_account.emissions_notional;
+        if notional == 0 {
+            return Ok(());
+        }
+        let emissions_schedule = self.emissions_schedule.as_mut().ok_or(ErrorCode::MissingEmissionsAccount)?;
+        let writer_emissions = self.writer_emissions.as_mut().ok_or(ErrorCode::MissingEmissionsAccount)?;
+        emissions_schedule.remove_notional(writer_emissions, notional, Clock::get()?.unix_timestamp)?;
+        self.escrow_account.emissions_notional = 0;
+        Ok(())
+    }
+
+    /// Removes the closed escrow from the writer's position and open notional, the market's
+    /// open interest, and the protocol stats.
+    fn record_close(&mut self, exercised_early: bool) {
+        let escrow_account = &self.escrow_account;
+        self.writer_position.remove_written(&escrow_account.key());
+        self.writer_stats.remove_open_notional(&escrow_account.collateral_mint, escrow_account.collateral_amount);
+        self.market.open_interest = self.market.open_interest.saturating_sub(escrow_account.collateral_amount);
+        let market_stats = &mut self.market_stats;
+        market_stats.collateral_locked = market_stats.collateral_locked.saturating_sub(escrow_account.collateral_amount);
+        market_stats.escrows_closed = market_stats.escrows_closed.saturating_add(1);
+        self.protocol_stats.record_close(exercised_early);
+    }
+}
+
+#[derive(Accounts)]
+/// Context for updating governance settings.
+///
+/// This struct defines the context for the `update_governance`, `set_fee_rate`, and
+/// `transfer_governance` instructions, which allow the governance authority to update
+/// the fee rates, fee collectors, and the authority itself.
+pub struct UpdateGovernance<'info> {
+    #[account(mut, has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,  // Governance account to be updated
+    pub governance_authority: Signer<'info>,     // Governance authority account
+}
+
+#[derive(Accounts)]
+/// Context for initializing the governance account.
+///
+/// This struct defines the context for the `initialize_governance` instruction, which
+/// creates the governance account and sets the initial fee rates and fee collectors.
+pub struct InitializeGovernance<'info> {
+    #[account(init, payer = governance_authority, space = 8 + Governance::INIT_SPACE)]
+    pub governance: Account<'info, Governance>,           // Governance account to store protocol parameters
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Initial governance authority (e.g., program deployer)
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for creating the market of a collateral mint.
+///
+/// This struct defines the context for the `initialize_market` instruction, which can only
+/// be called by the governance authority.
+pub struct InitializeMarket<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + Market::INIT_SPACE,
+        seeds = [MARKET_SEED, collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub market: Account<'info, Market>,                   // Market account for the collateral mint
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + MarketStats::INIT_SPACE,
+        seeds = [MARKET_STATS_SEED, collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub market_stats: Account<'info, MarketStats>,        // Stats account for the market
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint the market covers
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the market
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for changing the fee mint of a market.
+///
+/// This struct defines the context for the `set_market_fee_mint` instruction, which can only
+/// be called by the governance authority.
+pub struct SetMarketFeeMint<'info> {
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market to update
+    pub fee_mint: Account<'info, Mint>,                   // New mint to charge fees in
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+    #[account(mut, seeds = [AUDIT_LOG_SEED, market.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+}
+
+impl<'info> SetMarketFeeMint<'info> {
+    /// Records the governance action in the market's audit log, if it keeps one.
+    fn audit(&self, instruction: [u8; 8], amount: u64) -> Result<()> {
+        record_audit(self.audit_log.as_ref(), self.governance_authority.key(), self.market.key(), instruction, amount)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for creating the fee vault of a token mint.
+///
+/// This struct defines the context for the `initialize_fee_vault` instruction, which creates
+/// the fee vault state account and its PDA-owned token account.
+pub struct InitializeFeeVault<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + FeeVault::INIT_SPACE,
+        seeds = [FEE_VAULT_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault state for the mint
+    #[account(
+        init,
+        payer = payer,
+        seeds = [FEE_VAULT_TOKENS_SEED, mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = fee_vault
+    )]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account holding the fees
+    pub mint: Account<'info, Mint>,                       // Token mint the vault collects fees in
+    #[account(mut)]
+    pub payer: Signer<'info>,                             // Account paying for the vault's rent
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the vault token account
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for claiming accrued fees.
+///
+/// This struct defines the context for the `claim_fees` instruction. The claim is paid to
+/// `recipient_token_account`, whose owner must be the fee collector.
+pub struct ClaimFees<'info> {
+    #[account(mut, seeds = [FEE_VAULT_SEED, fee_vault.mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault holding the accrued fees
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account holding the fees
+    #[account(mut, constraint = recipient_token_account.mint == fee_vault.mint @ ErrorCode::IncorrectCollateralMint)]
+    pub recipient_token_account: Account<'info, TokenAccount>, // Collector's token account receiving the fees
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for sweeping the staking pool's fees in a non-reward mint to the treasury.
+///
+/// This struct defines the context for the permissionless `sweep_staking_fees` instruction.
+pub struct SweepStakingFees<'info> {
+    #[account(seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool whose fees are swept
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED, fee_vault.mint.as_ref()],
+        bump = fee_vault.bump,
+        constraint = fee_vault.mint != staking_pool.reward_mint @ ErrorCode::StakingRewardsNotClaimable
+    )]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault of a mint stakers aren't paid in
+    #[account(seeds = [TREASURY_SEED, fee_vault.mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury of the same mint, credited with the fees
+}
+
+#[derive(Accounts)]
+/// Context for creating the treasury of a token mint.
+///
+/// This struct defines the context for the `initialize_treasury` instruction, which creates
+/// the treasury state account and its PDA-owned token account.
+pub struct InitializeTreasury<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Treasury::INIT_SPACE,
+        seeds = [TREASURY_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,               // Treasury state for the mint
+    #[account(
+        init,
+        payer = payer,
+        seeds = [TREASURY_TOKENS_SEED, mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = treasury
+    )]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Token account holding treasury funds
+    pub mint: Account<'info, Mint>,                       // Token mint held by the treasury
+    #[account(mut)]
+    pub payer: Signer<'info>,                             // Account paying for the treasury's rent
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the treasury token account
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for queueing or cancelling a treasury withdrawal.
+///
+/// This struct defines the context for the `queue_treasury_withdrawal` and
+/// `cancel_treasury_withdrawal` instructions, which can only be called by the governance authority.
+pub struct QueueTreasuryWithdrawal<'info> {
+    #[account(mut, seeds = [TREASURY_SEED, treasury.mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury to withdraw from
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account holding the timelock
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, destination: Pubkey)]
+/// Context for withdrawing from the treasury.
+///
+/// This struct defines the context for the `withdraw_treasury` instruction, which can only
+/// be called by the governance authority.
+pub struct WithdrawTreasury<'info> {
+    #[account(mut, seeds = [TREASURY_SEED, treasury.mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury to withdraw from
+    #[account(mut, address = treasury.token_account)]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Token account holding treasury funds
+    #[account(mut, address = destination)]
+    pub destination_token_account: Account<'info, TokenAccount>, // Token account receiving the funds
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account holding the timelock
+    pub governance_authority: Signer<'info>,              // Governance authority
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for creating the staking pool.
+///
+/// This struct defines the context for the `initialize_staking_pool` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeStakingPool<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + StakingPool::INIT_SPACE,
+        seeds = [STAKING_POOL_SEED],
+        bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool state
+    #[account(
+        init,
+        payer = governance_authority,
+        seeds = [STAKE_VAULT_SEED],
+        bump,
+        token::mint = stake_mint,
+        token::authority = staking_pool
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,        // Token account holding staked tokens
+    pub stake_mint: Account<'info, Mint>,                 // Governance token mint
+    pub reward_mint: Account<'info, Mint>,                // Fee mint in which rewards are paid
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the pool
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the stake vault
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for creating a stake position.
+///
+/// This struct defines the context for the `create_stake_position` instruction.
+pub struct CreateStakePosition<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + StakePosition::INIT_SPACE,
+        seeds = [STAKE_POSITION_SEED, owner.key().as_ref()],
+        bump
+    )]
+    pub stake_position: Account<'info, StakePosition>,    // Stake position to create
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // Staker owning the position
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for staking and unstaking governance tokens.
+///
+/// This struct defines the context for the `stake` and `unstake` instructions. The fee vault
+/// of the reward mint is needed to bring the pool's rewards up to date first.
+pub struct Stake<'info> {
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool
+    #[account(mut, seeds = [STAKE_POSITION_SEED, owner.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Account<'info, StakePosition>,    // Staker's position
+    pub owner: Signer<'info>,                             // Staker
+    #[account(mut, constraint = owner_stake_account.mint == staking_pool.stake_mint @ ErrorCode::IncorrectStakeMint)]
+    pub owner_stake_account: Account<'info, TokenAccount>, // Staker's governance token account
+    #[account(mut, address = staking_pool.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,        // Token account holding staked tokens
+    #[account(mut, seeds = [FEE_VAULT_SEED, staking_pool.reward_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault of the reward mint
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for claiming staking rewards.
+///
+/// This struct defines the context for the `claim_rewards` instruction.
+pub struct ClaimRewards<'info> {
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool
+    #[account(mut, seeds = [STAKE_POSITION_SEED, owner.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Account<'info, StakePosition>,    // Staker's position
+    pub owner: Signer<'info>,                             // Staker
+    #[account(mut, seeds = [FEE_VAULT_SEED, staking_pool.reward_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault of the reward mint
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account holding the rewards
+    #[account(mut, constraint = owner_reward_account.mint == staking_pool.reward_mint @ ErrorCode::IncorrectFeeMint)]
+    pub owner_reward_account: Account<'info, TokenAccount>, // Staker's token account receiving rewards
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for creating a buyback configuration.
+///
+/// This struct defines the context for the `initialize_buyback` instruction, which can only
+/// be called by the governance authority.
+pub struct InitializeBuyback<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + BuybackConfig::INIT_SPACE,
+        seeds = [BUYBACK_SEED, input_mint.key().as_ref()],
+        bump
+    )]
+    pub buyback_config: Account<'info, BuybackConfig>,    // Buyback configuration for the input mint
+    #[account(
+        init,
+        payer = governance_authority,
+        seeds = [BUYBACK_TOKENS_SEED, input_mint.key().as_ref()],
+        bump,
+        token::mint = governance_token_mint,
+        token::authority = buyback_config
+    )]
+    pub buyback_token_account: Account<'info, TokenAccount>, // Token account receiving bought governance tokens
+    #[account(seeds = [TREASURY_SEED, input_mint.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury funding the buybacks
+    pub input_mint: Account<'info, Mint>,                 // Treasury mint spent on buybacks
+    pub governance_token_mint: Account<'info, Mint>,      // Governance token mint
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the configuration
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the buyback token account
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for updating a buyback configuration.
+///
+/// This struct defines the context for the `update_buyback` instruction, which can only be
+/// called by the governance authority.
+pub struct UpdateBuyback<'info> {
+    #[account(mut, seeds = [BUYBACK_SEED, buyback_config.input_mint.as_ref()], bump = buyback_config.bump)]
+    pub buyback_config: Account<'info, BuybackConfig>,    // Buyback configuration to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for a buyback-and-burn.
+///
+/// This struct defines the context for the `buyback` instruction. The accounts required by
+/// the swap program are passed as remaining accounts.
+pub struct Buyback<'info> {
+    #[account(mut, seeds = [BUYBACK_SEED, buyback_config.input_mint.as_ref()], bump = buyback_config.bump)]
+    pub buyback_config: Account<'info, BuybackConfig>,    // Buyback configuration
+    #[account(seeds = [TREASURY_SEED, buyback_config.input_mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury funding the buyback
+    #[account(mut, address = treasury.token_account)]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Treasury token account spent by the swap
+    #[account(mut, address = buyback_config.buyback_token_account)]
+    pub buyback_token_account: Account<'info, TokenAccount>, // Token account receiving bought governance tokens
+    #[account(mut, address = buyback_config.governance_token_mint)]
+    pub governance_token_mint: Account<'info, Mint>,      // Governance token mint (burned from)
+    /// CHECK: Whitelisted swap program, validated against the buyback configuration
+    #[account(executable, address = buyback_config.params.swap_program)]
+    pub swap_program: UncheckedAccount<'info>,            // DEX program executing the swap
+    /// CHECK: Pyth price feed, validated against the buyback configuration and parsed on use
+    #[account(address = buyback_config.params.input_oracle)]
+    pub input_oracle: UncheckedAccount<'info>,            // Input mint price feed
+    /// CHECK: Pyth price feed, validated against the buyback configuration and parsed on use
+    #[account(address = buyback_config.params.governance_token_oracle)]
+    pub governance_token_oracle: UncheckedAccount<'info>, // Governance token price feed
+    pub token_program: Program<'info, Token>,             // Token program for the burn
+}
+
+#[derive(Accounts)]
+/// Context for setting the staking pool's lock parameters.
+///
+/// This struct defines the context for the `set_lock_params` instruction, which can only be
+/// called by the governance authority.
+pub struct SetLockParams<'info> {
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for locking a stake position.
+///
+/// This struct defines the context for the `lock_stake` instruction.
+pub struct LockStake<'info> {
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool
+    #[account(mut, seeds = [STAKE_POSITION_SEED, owner.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Account<'info, StakePosition>,    // Staker's position
+    pub owner: Signer<'info>,                             // Staker
+    #[account(mut, seeds = [FEE_VAULT_SEED, staking_pool.reward_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault of the reward mint
+}
+
+#[derive(Accounts)]
+/// Context for recomputing a stake position's boost.
+///
+/// This struct defines the context for the permissionless `poke_stake_position` instruction.
+pub struct PokeStakePosition<'info> {
+    #[account(mut, seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool
+    #[account(mut, seeds = [STAKE_POSITION_SEED, stake_position.owner.as_ref()], bump = stake_position.bump)]
+    pub stake_position: Account<'info, StakePosition>,    // Position to recompute
+    #[account(mut, seeds = [FEE_VAULT_SEED, staking_pool.reward_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault of the reward mint
+}
+
+#[derive(Accounts)]
+/// Context for creating a market's emissions schedule.
+///
+/// This struct defines the context for the `initialize_emissions` instruction, which can only
+/// be called by the governance authority.
+pub struct InitializeEmissions<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + EmissionsSchedule::INIT_SPACE,
+        seeds = [EMISSIONS_SEED, market.key().as_ref()],
+        bump
+    )]
+    pub emissions_schedule: Account<'info, EmissionsSchedule>, // Emissions schedule for the market
+    #[account(
+        init,
+        payer = governance_authority,
+        seeds = [EMISSIONS_VAULT_SEED, market.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = emissions_schedule
+    )]
+    pub reward_vault: Account<'info, TokenAccount>,       // Token account funding the emissions
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market whose writers earn emissions
+    pub reward_mint: Account<'info, Mint>,                // Mint of the emitted reward token
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the schedule
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the reward vault
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for changing a market's emission rate.
+///
+/// This struct defines the context for the `set_emission_rate` instruction, which can only be
+/// called by the governance authority.
+pub struct SetEmissionRate<'info> {
+    #[account(mut, seeds = [EMISSIONS_SEED, emissions_schedule.market.as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Account<'info, EmissionsSchedule>, // Emissions schedule to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for creating a writer's emissions position.
+///
+/// This struct defines the context for the `create_writer_emissions` instruction.
+pub struct CreateWriterEmissions<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + WriterEmissions::INIT_SPACE,
+        seeds = [WRITER_EMISSIONS_SEED, market.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub writer_emissions: Account<'info, WriterEmissions>, // Emissions position to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the position belongs to
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // Writer owning the position
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for claiming liquidity-mining emissions.
+///
+/// This struct defines the context for the `claim_emissions` instruction.
+pub struct ClaimEmissions<'info> {
+    #[account(mut, seeds = [EMISSIONS_SEED, emissions_schedule.market.as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Account<'info, EmissionsSchedule>, // Emissions schedule of the market
+    #[account(
+        mut,
+        seeds = [WRITER_EMISSIONS_SEED, emissions_schedule.market.as_ref(), owner.key().as_ref()],
+        bump = writer_emissions.bump
+    )]
+    pub writer_emissions: Account<'info, WriterEmissions>, // Writer's emissions position
+    pub owner: Signer<'info>,                             // Writer
+    #[account(mut, address = emissions_schedule.reward_vault)]
+    pub reward_vault: Account<'info, TokenAccount>,       // Token account funding the emissions
+    #[account(mut, constraint = owner_reward_account.mint == emissions_schedule.reward_mint @ ErrorCode::IncorrectRewardMint)]
+    pub owner_reward_account: Account<'info, TokenAccount>, // Writer's token account receiving emissions
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for creating a user's points and stats account.
+///
+/// This struct defines the context for the `create_user_stats` instruction.
+pub struct CreateUserStats<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + UserStats::INIT_SPACE,
+        seeds = [USER_STATS_SEED, owner.key().as_ref()],
+        bump
+    )]
+    pub user_stats: Account<'info, UserStats>,            // Stats account to create
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // User owning the stats
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for setting the settlement price of an escrow manually.
+///
+/// This struct defines the context for the `set_manual_settlement_price` instruction, which
+/// can only be called by the governance authority.
+pub struct SetManualSettlementPrice<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to settle manually
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+    #[account(mut, seeds = [AUDIT_LOG_SEED, escrow_account.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+}
+
+impl<'info> SetManualSettlementPrice<'info> {
+    /// Records the governance action in the market's audit log, if it keeps one.
+    fn audit(&self, instruction: [u8; 8], amount: u64) -> Result<()> {
+        record_audit(self.audit_log.as_ref(), self.governance_authority.key(), self.escrow_account.key(), instruction, amount)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for creating a user's position account.
+///
+/// This struct defines the context for the `create_user_position` instruction.
+pub struct CreateUserPosition<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [USER_POSITION_SEED, owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,      // Position account to create
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // User owning the position
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating a market's settings.
+///
+/// This struct defines the context for governance instructions that change a single market,
+/// such as `set_market_pause`, `set_position_limit`, `set_open_interest_cap`,
+/// `set_strike_band`, `set_price_deviation_limit`, `set_oracle_config`, `set_twap_window`,
+/// `set_auto_exercise_threshold`, `set_exercise_cutoff`, `set_funding_deadline`,
+/// `set_max_tenor`, `set_compliance_authority`, `set_token_gate`, and `set_points_weights`.
+pub struct UpdateMarket<'info> {
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+    #[account(mut, seeds = [AUDIT_LOG_SEED, market.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+}
+
+impl<'info> UpdateMarket<'info> {
+    /// Records the governance action in the market's audit log, if it keeps one.
+    fn audit(&self, instruction: [u8; 8], amount: u64) -> Result<()> {
+        record_audit(self.audit_log.as_ref(), self.governance_authority.key(), self.market.key(), instruction, amount)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for creating a governance proposal.
+///
+/// This struct defines the context for the `create_proposal` instruction. The proposal
+/// address is derived from the governance account and the next proposal ID.
+pub struct CreateProposal<'info> {
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,           // Governance account the proposal changes
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE,
+        seeds = [PROPOSAL_SEED, governance.key().as_ref(), governance.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal: Account<'info, Proposal>,               // Proposal to create
+    #[account(seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool
+    #[account(seeds = [STAKE_POSITION_SEED, proposer.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Account<'info, StakePosition>,    // Proposer's stake position
+    #[account(mut)]
+    pub proposer: Signer<'info>,                          // Staker creating the proposal
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for voting on a governance proposal.
+///
+/// This struct defines the context for the `cast_vote` instruction. The vote record can only
+/// be created once per voter and proposal.
+pub struct CastVote<'info> {
+    #[account(mut)]
+    pub proposal: Account<'info, Proposal>,               // Proposal voted on
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + VoteRecord::INIT_SPACE,
+        seeds = [VOTE_RECORD_SEED, proposal.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote_record: Account<'info, VoteRecord>,          // Voter's record on the proposal
+    #[account(seeds = [STAKING_POOL_SEED], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,        // Staking pool
+    #[account(mut, seeds = [STAKE_POSITION_SEED, voter.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Account<'info, StakePosition>,    // Voter's stake position
+    #[account(mut)]
+    pub voter: Signer<'info>,                             // Staker voting
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for executing a governance proposal.
+///
+/// This struct defines the context for the `execute_proposal` instruction.
+pub struct ExecuteProposal<'info> {
+    #[account(mut, has_one = governance)]
+    pub proposal: Account<'info, Proposal>,               // Proposal to execute
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,           // Governance account to update
+}
+
+#[derive(Accounts)]
+/// Context for creating the protocol-wide stats account.
+///
+/// This struct defines the context for the `initialize_protocol_stats` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeProtocolStats<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + ProtocolStats::INIT_SPACE,
+        seeds = [PROTOCOL_STATS_SEED],
+        bump
+    )]
+    pub protocol_stats: Account<'info, ProtocolStats>,    // Protocol stats account to create
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the account
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for reading the health of a writer's portfolio.
+///
+/// This struct defines the context for the `get_portfolio_health` view. The writer's escrows
+/// in the market are passed as remaining accounts.
+pub struct GetPortfolioHealth<'info> {
+    #[account(seeds = [USER_POSITION_SEED, user_position.owner.as_ref()], bump = user_position.bump)]
+    pub user_position: Account<'info, UserPosition>,      // Writer's open escrows
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrows
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+    #[account(seeds = [RISK_CONFIG_SEED, market.collateral_mint.as_ref()], bump = risk_config.bump)]
+    pub risk_config: Option<Account<'info, RiskConfig>>,  // Market's risk scenarios (for the margin requirement)
+    #[account(seeds = [VOL_CONFIG_SEED, market.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Option<Account<'info, VolConfig>>,    // Market's volatility curve (marks vanilla escrows to fair value)
+}
+
+#[derive(Accounts)]
+/// Context for creating the risk configuration of a market.
+///
+/// This struct defines the context for the `initialize_risk_config` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeRiskConfig<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + RiskConfig::INIT_SPACE,
+        seeds = [RISK_CONFIG_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub risk_config: Account<'info, RiskConfig>,          // Risk configuration to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the scenarios apply to
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the configuration
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating the risk scenarios of a market.
+///
+/// This struct defines the context for the `set_risk_scenarios` instruction.
+pub struct SetRiskScenarios<'info> {
+    #[account(mut)]
+    pub risk_config: Account<'info, RiskConfig>,          // Risk configuration to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for creating the oracle registry of a market.
+///
+/// This struct defines the context for the `initialize_oracle_registry` instruction, which
+/// can only be called by the governance authority.
+pub struct InitializeOracleRegistry<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + OracleRegistry::INIT_SPACE,
+        seeds = [ORACLE_REGISTRY_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub oracle_registry: Account<'info, OracleRegistry>,  // Oracle registry to create
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the feeds price
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the registry
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating the oracle registry of a market.
+///
+/// This struct defines the context for the `update_oracle_registry` instruction.
+pub struct UpdateOracleRegistry<'info> {
+    #[account(mut, has_one = market)]
+    pub oracle_registry: Account<'info, OracleRegistry>,  // Oracle registry to update
+    pub market: Account<'info, Market>,                   // Market the feeds price
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for creating the price history of a market.
+///
+/// This struct defines the context for the `initialize_price_history` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializePriceHistory<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + std::mem::size_of::<PriceHistory>(),
+        seeds = [PRICE_HISTORY_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub price_history: AccountLoader<'info, PriceHistory>, // Price history to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market whose oracle is sampled
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the history
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for cranking the TWAP of a market.
+///
+/// This struct defines the context for the permissionless `crank_twap` instruction.
+pub struct CrankTwap<'info> {
+    #[account(mut, has_one = market)]
+    pub price_history: AccountLoader<'info, PriceHistory>, // Price history to record into
+    pub market: Account<'info, Market>,                   // Market whose oracle is sampled
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+}
+
+#[derive(Accounts)]
+/// Context for refreshing the exchange rate of an LST market.
+///
+/// This struct defines the context for the permissionless `refresh_lst_rate` instruction.
+pub struct RefreshLstRate<'info> {
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // LST market to refresh
+    /// CHECK: Owner and pool mint are checked by `stake_pool_rate`
+    pub stake_pool: UncheckedAccount<'info>,              // SPL stake pool minting the collateral
+}
+
+#[derive(Accounts)]
+/// Context for recording an oracle observation on an escrow.
+///
+/// This struct defines the context for the permissionless `record_observation` and
+/// `record_barrier_touch` instructions.
+pub struct RecordObservation<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Path-dependent or barrier escrow to sample for
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow's collateral mint
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+}
+
+#[derive(Accounts)]
+/// Context for paying funding on a perpetual option.
+///
+/// This struct defines the context for the `pay_funding` instruction.
+pub struct PayFunding<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Perpetual escrow being funded
+    pub payer: Signer<'info>,                             // Holder paying the funding
+    #[account(mut, constraint = payer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub payer_collateral_account: Account<'info, TokenAccount>, // Holder's collateral mint account paying the funding
+    #[account(
+        mut,
+        constraint = initializer_collateral_account.owner == escrow_account.initializer_key,
+        constraint = initializer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's collateral mint account receiving the funding
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the points weights)
+    #[account(mut, seeds = [USER_STATS_SEED, payer.key().as_ref()], bump = payer_stats.bump)]
+    pub payer_stats: Option<Account<'info, UserStats>>,   // Holder's points and stats (to earn premium points)
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for creating the basket of a market.
+///
+/// This struct defines the context for the `initialize_basket` instruction, which can only be
+/// called by the governance authority.
+pub struct InitializeBasket<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + Basket::INIT_SPACE,
+        seeds = [BASKET_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub basket: Account<'info, Basket>,                   // Basket to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market whose basket options settle on the basket
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the basket
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating the basket of a market.
+///
+/// This struct defines the context for the `update_basket` instruction.
+pub struct UpdateBasket<'info> {
+    #[account(mut, has_one = market)]
+    pub basket: Account<'info, Basket>,                   // Basket to update
+    pub market: Account<'info, Market>,                   // Market whose basket options settle on the basket
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for updating an escrow as its writer.
+///
+/// This struct defines the context for the `revoke_vesting` and `set_rental_rate` instructions,
+/// which can only be called by the escrow's writer.
+pub struct WriterUpdateEscrow<'info> {
+    #[account(mut, constraint = escrow_account.initializer_key == initializer.key() @ ErrorCode::NotEscrowWriter)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to update
+    pub initializer: Signer<'info>,                       // Writer of the escrow
+}
+
+#[derive(Accounts)]
+/// Context for renting the exercise rights of an escrow.
+///
+/// This struct defines the context for the `start_rental` instruction.
+pub struct StartRental<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose exercise rights are rented
+    #[account(
+        init,
+        payer = renter,
+        space = 8 + Rental::INIT_SPACE,
+        seeds = [RENTAL_SEED, escrow_account.key().as_ref()],
+        bump
+    )]
+    pub rental: Account<'info, Rental>,                   // Rental to create
+    #[account(
+        init,
+        payer = renter,
+        seeds = [RENTAL_TOKENS_SEED, escrow_account.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = rental
+    )]
+    pub rental_token_account: Account<'info, TokenAccount>, // Token account holding the prepaid premium
+    #[account(address = escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint the premium is paid in
+    #[account(mut)]
+    pub renter: Signer<'info>,                            // Renter prepaying the premium
+    #[account(mut, constraint = renter_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub renter_collateral_account: Account<'info, TokenAccount>, // Renter's collateral mint account paying the premium
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the whitelist and premium bounds)
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), renter.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Renter's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // Renter's gate mint account (required in token-gated markets)
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Pyth price feed for the collateral mint (required with premium bounds)
+    #[account(seeds = [VOL_CONFIG_SEED, escrow_account.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Option<Account<'info, VolConfig>>,    // Market's volatility curve (required with premium bounds)
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for prepaying more premium into a rental.
+///
+/// This struct defines the context for the `top_up_rental` instruction.
+pub struct TopUpRental<'info> {
+    #[account(mut, has_one = renter)]
+    pub rental: Account<'info, Rental>,                   // Rental to extend
+    #[account(mut, address = rental.token_account)]
+    pub rental_token_account: Account<'info, TokenAccount>, // Token account holding the prepaid premium
+    pub renter: Signer<'info>,                            // Renter prepaying the premium
+    #[account(mut, constraint = renter_collateral_account.mint == rental_token_account.mint @ ErrorCode::IncorrectCollateralMint)]
+    pub renter_collateral_account: Account<'info, TokenAccount>, // Renter's collateral mint account paying the premium
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for paying streamed rental premium to the writer.
+///
+/// This struct defines the context for the permissionless `claim_rental_premium` instruction.
+pub struct ClaimRentalPremium<'info> {
+    #[account(mut, has_one = escrow)]
+    pub rental: Account<'info, Rental>,                   // Rental streaming the premium
+    #[account(mut, address = rental.token_account)]
+    pub rental_token_account: Account<'info, TokenAccount>, // Token account holding the prepaid premium
+    pub escrow: Account<'info, EscrowAccount>,            // Escrow whose exercise rights are rented
+    #[account(mut, constraint = initializer_collateral_account.owner == escrow.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's token account receiving the premium
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for ending a rental.
+///
+/// This struct defines the context for the `close_rental` instruction, which the renter can call
+/// at any time and anyone can call once the rental has lapsed.
+pub struct CloseRental<'info> {
+    #[account(mut, address = rental.escrow)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose exercise rights are rented
+    #[account(mut, has_one = renter, close = renter)]
+    pub rental: Account<'info, Rental>,                   // Rental to close
+    #[account(mut, address = rental.token_account)]
+    pub rental_token_account: Account<'info, TokenAccount>, // Token account holding the prepaid premium
+    /// CHECK: Receives the rental's rent, checked against the rental
+    #[account(mut)]
+    pub renter: UncheckedAccount<'info>,                  // Renter of the exercise rights
+    pub caller: Signer<'info>,                            // Renter, or anyone once the rental has lapsed
+    #[account(mut, constraint = renter_collateral_account.owner == rental.renter)]
+    pub renter_collateral_account: Account<'info, TokenAccount>, // Renter's token account receiving the refund
+    #[account(mut, constraint = initializer_collateral_account.owner == escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's token account receiving the premium
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the points weights)
+    #[account(mut, seeds = [USER_STATS_SEED, rental.renter.as_ref()], bump = renter_stats.bump)]
+    pub renter_stats: Option<Account<'info, UserStats>>,  // Renter's points and stats (to earn premium points)
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for opting an escrow in or out of auto-exercise.
+///
+/// This struct defines the context for the `set_auto_exercise_opt_out` instruction.
+pub struct SetAutoExerciseOptOut<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to update
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the exercise cutoff)
+    pub holder: Signer<'info>,                            // Holder of the exercise rights
+}
+
+#[derive(Accounts)]
+/// Context for claiming settlement proceeds.
+///
+/// This struct defines the context for the `claim_settlement` instruction, which can only be
+/// submitted by the holder entitled to the proceeds, one of its session keys, or a relayer paying
+/// into the holder's associated token account.
+pub struct ClaimSettlement<'info> {
+    #[account(mut, has_one = payout_recipient @ ErrorCode::NotOptionHolder)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Settled escrow holding the proceeds
+    /// CHECK: Validated against the escrow's payout recipient
+    pub payout_recipient: UncheckedAccount<'info>,        // Holder entitled to the proceeds
+    #[account(mut)]
+    pub claimer: Signer<'info>,                           // Holder, session key, or relayer submitting the claim
+    #[account(constraint = session.session_key == claimer.key() @ ErrorCode::InvalidSession)]
+    pub session: Option<Account<'info, SessionKey>>,      // Holder's session key (if the claimer isn't the holder)
+    #[account(
+        mut,
+        constraint = recipient_collateral_account.owner == payout_recipient.key(),
+        constraint = recipient_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub recipient_collateral_account: Account<'info, TokenAccount>, // Holder's token account receiving the proceeds
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for clawing back unclaimed settlement proceeds.
+///
+/// This struct defines the context for the `clawback_unclaimed` instruction, which can only be
+/// called by the escrow's writer.
+pub struct ClawbackUnclaimed<'info> {
+    #[account(mut, constraint = escrow_account.initializer_key == initializer.key() @ ErrorCode::NotEscrowWriter)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Settled escrow holding the proceeds
+    pub initializer: Signer<'info>,                       // Writer of the escrow
+    #[account(
+        mut,
+        constraint = initializer_collateral_account.owner == initializer.key(),
+        constraint = initializer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's token account receiving the proceeds
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral
+    pub governance: Account<'info, Governance>,           // Governance account storing the grace period
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Accounts for closing an escrow without settlement.
+///
+/// Shared by the `reap_unfunded`, `terminate_by_consent`, and `cancel_escrow` contexts. Deposits are returned
+/// to the writer, and the rent of the escrow and of its vault goes to the writer when they close.
+pub struct CancelEscrow<'info> {
+    #[account(mut, close = initializer)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to close
+    /// CHECK: Receives the escrow's rent, checked against the escrow
+    #[account(mut, address = escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub initializer: UncheckedAccount<'info>,             // Writer of the escrow
+    #[account(
+        mut,
+        constraint = initializer_collateral_account.owner == escrow_account.initializer_key @ ErrorCode::NotEscrowWriter,
+        constraint = initializer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's token account receiving the deposits
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Option<Account<'info, TokenAccount>>, // Escrow's vault holding collateral (if it was created), closed to the writer
+    #[account(mut, seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow
+    #[account(mut, seeds = [MARKET_STATS_SEED, escrow_account.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,        // Stats of the market
+    #[account(mut, seeds = [PROTOCOL_STATS_SEED], bump = protocol_stats.bump)]
+    pub protocol_stats: Account<'info, ProtocolStats>,    // Protocol-wide stats
+    #[account(mut, seeds = [USER_STATS_SEED, escrow_account.initializer_key.as_ref()], bump = writer_stats.bump)]
+    pub writer_stats: Account<'info, UserStats>,          // Writer's points and stats
+    #[account(mut, seeds = [USER_POSITION_SEED, escrow_account.initializer_key.as_ref()], bump = writer_position.bump)]
+    pub writer_position: Account<'info, UserPosition>,    // Writer's open escrows
+    #[account(mut, seeds = [EMISSIONS_SEED, market.key().as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Option<Account<'info, EmissionsSchedule>>, // Market's emissions schedule (if the escrow is enrolled)
+    #[account(
+        mut,
+        seeds = [WRITER_EMISSIONS_SEED, market.key().as_ref(), escrow_account.initializer_key.as_ref()],
+        bump = writer_emissions.bump
+    )]
+    pub writer_emissions: Option<Account<'info, WriterEmissions>>, // Writer's emissions position (if the escrow is enrolled)
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+}
+
+impl<'info> CancelEscrow<'info> {
+    /// Returns the deposits to the writer, closes the vault, and removes the cancelled escrow
+    /// from the writer's position and open notional, the market's open interest and emissions,
+    /// and the stats.
+    ///
+    /// The vault's whole balance goes to the writer, so tokens sent to it directly can't keep it
+    /// from closing.
+    ///
+    /// Rented escrows can't be cancelled until their rental is closed, nor lent options until
+    /// they are returned.
+    fn cancel(&mut self, event_bump: u8) -> Result<()> {
+        let escrow_account = &self.escrow_account;
+        if escrow_account.renter != Pubkey::default() {
+            return Err(ErrorCode::EscrowRented.into());
+        }
+        escrow_account.require_not_lent()?;
+        escrow_account.require_not_on_loan()?;
+        match self.escrow_collateral_account.as_ref() {
+            Some(escrow_collateral_account) => {
+                transfer_from_vault(
+                    escrow_account,
+                    escrow_collateral_account.to_account_info(),
+                    self.initializer_collateral_account.to_account_info(),
+                    &self.token_program,
+                    escrow_collateral_account.amount,
+                )?;
+                close_vault(
+                    escrow_account,
+                    escrow_collateral_account.to_account_info(),
+                    self.initializer.to_account_info(),
+                    &self.token_program,
+                )?;
+            }
+            None if escrow_account.deposited_amount > 0 => return Err(ErrorCode::InvalidDepositAccount.into()),
+            None => {}
+        }
+        let notional = escrow_account.emissions_notional;
+        if notional > 0 {
+            let emissions_schedule = self.emissions_schedule.as_mut().ok_or(ErrorCode::MissingEmissionsAccount)?;
+            let writer_emissions = self.writer_emissions.as_mut().ok_or(ErrorCode::MissingEmissionsAccount)?;
+            emissions_schedule.remove_notional(writer_emissions, notional, Clock::get()?.unix_timestamp)?;
+        }
+        self.writer_position.remove_written(&escrow_account.key());
+        self.writer_stats.remove_open_notional(&escrow_account.collateral_mint, escrow_account.collateral_amount);
+        self.market.open_interest = self.market.open_interest.saturating_sub(escrow_account.collateral_amount);
+        let market_stats = &mut self.market_stats;
+        market_stats.collateral_locked = market_stats.collateral_locked.saturating_sub(escrow_account.deposited_amount);
+        market_stats.escrows_closed = market_stats.escrows_closed.saturating_add(1);
+        emit_lifecycle_event(&EscrowCancelled {
+            seq: market_stats.next_event_seq(),
+            market: market_stats.market,
+            escrow: escrow_account.key(),
+            refunded: escrow_account.deposited_amount,
+        }, self.event_authority.as_ref(), event_bump)?;
+        self.protocol_stats.record_cancel();
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+/// Context for reaping an unfunded escrow.
+///
+/// This struct defines the context for the permissionless `reap_unfunded` instruction.
+pub struct ReapUnfunded<'info> {
+    pub cancel: CancelEscrow<'info>,                      // Escrow to close and its bookkeeping accounts
+    #[account(mut)]
+    pub caller: Signer<'info>,                            // Caller receiving part of the escrow's rent
+    #[account(seeds = [BACKSTOP_POOL_SEED, cancel.escrow_account.collateral_mint.as_ref()], bump = backstop_pool.bump)]
+    pub backstop_pool: Option<Account<'info, BackstopPool>>, // Market's backstop pool (required in backstopped markets)
+    pub backstop_token_account: Option<Account<'info, TokenAccount>>, // Backstop pool's token account (required in backstopped markets)
+}
+
+#[derive(Accounts)]
+/// Context for terminating an escrow by mutual consent.
+///
+/// This struct defines the context for the `terminate_by_consent` instruction, which must be
+/// signed by both the writer and the holder.
+pub struct TerminateByConsent<'info> {
+    pub cancel: CancelEscrow<'info>,                      // Escrow to close and its bookkeeping accounts
+    #[account(constraint = writer.key() == cancel.escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub writer: Signer<'info>,                            // Writer of the escrow (pays any rebate)
+    pub holder: Signer<'info>,                            // Holder of the exercise rights
+    #[account(
+        mut,
+        constraint = holder_collateral_account.owner == holder.key(),
+        constraint = holder_collateral_account.mint == cancel.escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub holder_collateral_account: Option<Account<'info, TokenAccount>>, // Holder's token account receiving the rebate (if any)
+}
+
+#[derive(Accounts)]
+/// Context for sweeping dust out of a closed escrow.
+///
+/// This struct defines the context for the permissionless `sweep_dust` instruction.
+pub struct SweepDust<'info> {
+    pub escrow_account: Account<'info, EscrowAccount>,    // Settled escrow
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding the dust
+    /// CHECK: Receives the token account's rent, checked against the escrow
+    #[account(mut, address = escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub initializer: UncheckedAccount<'info>,             // Writer of the escrow
+    #[account(seeds = [TREASURY_SEED, escrow_account.collateral_mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury for the collateral mint
+    #[account(mut, address = treasury.token_account)]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Treasury token account receiving the dust
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for rolling an escrow to a later expiry.
+///
+/// This struct defines the context for the `roll_position` instruction, which can only be
+/// called by the escrow's writer. The writer settles the current escrow as its `user`.
+pub struct RollPosition<'info> {
+    #[account(constraint = settle.user.key() == settle.escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub settle: SettleEscrow<'info>,                      // Escrow to settle and its settlement accounts
+    #[account(init, payer = initializer, space = EscrowAccount::SPACE)]
+    pub new_escrow_account: Account<'info, EscrowAccount>, // Escrow opened on the rolled collateral
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [ESCROW_VAULT_SEED, new_escrow_account.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = new_escrow_collateral_account
+    )]
+    pub new_escrow_collateral_account: Account<'info, TokenAccount>, // New escrow's vault receiving the rolled collateral
+    #[account(address = settle.escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the escrow
+    #[account(mut, address = settle.escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub initializer: Signer<'info>,                       // Writer paying for the new escrow
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for creating the vault
+}
+
+#[derive(Accounts)]
+/// Context for extending the expiry of an escrow.
+///
+/// This struct defines the context for the `extend_expiration` instruction, which must be
+/// signed by both the writer and the holder.
+pub struct ExtendExpiration<'info> {
+    #[account(mut, constraint = escrow_account.initializer_key == writer.key() @ ErrorCode::NotEscrowWriter)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to extend
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the maximum tenor)
+    pub writer: Signer<'info>,                            // Writer of the escrow
+    pub holder: Signer<'info>,                            // Holder of the exercise rights
+}
+
+#[derive(Accounts)]
+/// Context for showing interest in an escrow.
+///
+/// This struct defines the context for the `register_interest` instruction.
+pub struct RegisterInterest<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow the quoter is interested in
+    pub quoter: Signer<'info>,                            // Party quoting or bidding on the escrow
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the whitelist)
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), quoter.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Quoter's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // Quoter's gate mint account (required in token-gated markets)
+}
+
+#[derive(Accounts)]
+/// Context for cancelling an escrow as its writer.
+///
+/// This struct defines the context for the `cancel_escrow` instruction, which can only be
+/// called by the escrow's writer.
+pub struct CancelByWriter<'info> {
+    pub cancel: CancelEscrow<'info>,                      // Escrow to close and its bookkeeping accounts
+    #[account(constraint = writer.key() == cancel.escrow_account.initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub writer: Signer<'info>,                            // Writer of the escrow (pays any cancellation fee)
+    pub governance: Account<'info, Governance>,           // Governance account storing the cancellation fee
+    #[account(seeds = [TREASURY_SEED, cancel.escrow_account.collateral_mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Option<Account<'info, Treasury>>,       // Treasury of the collateral mint (required if a fee is due)
+    #[account(mut, address = treasury.as_ref().map(|treasury| treasury.token_account).unwrap_or_default())]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>, // Treasury's token account receiving the fee (if any)
+}
+
+#[derive(Accounts)]
+#[instruction(writer: Pubkey, counterparty: Pubkey)]
+/// Context for approving a negotiated fee override.
+///
+/// This struct defines the context for the `approve_fee_override` instruction, which can only
+/// be called by the governance authority or the fee admin.
+pub struct ApproveFeeOverride<'info> {
+    #[account(
+        init,
+        payer = approver,
+        space = 8 + FeeApproval::INIT_SPACE,
+        seeds = [FEE_APPROVAL_SEED, writer.as_ref(), counterparty.as_ref()],
+        bump
+    )]
+    pub fee_approval: Account<'info, FeeApproval>,        // Fee approval to create
+    #[account(
+        constraint = approver.key() == governance.governance_authority
+            || approver.key() == governance.fee_admin @ ErrorCode::NotFeeAdmin
+    )]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub approver: Signer<'info>,                          // Governance authority or fee admin paying for the approval
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+#[instruction(participant: Pubkey)]
+/// Context for whitelisting a participant.
+///
+/// This struct defines the context for the `add_to_whitelist` instruction, which can only be
+/// called by the market's compliance authority.
+pub struct AddToWhitelist<'info> {
+    #[account(
+        init,
+        payer = compliance_authority,
+        space = 8 + WhitelistEntry::INIT_SPACE,
+        seeds = [WHITELIST_SEED, market.key().as_ref(), participant.as_ref()],
+        bump
+    )]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,  // Whitelist entry to create
+    #[account(has_one = compliance_authority @ ErrorCode::NotComplianceAuthority)]
+    pub market: Account<'info, Market>,                   // Permissioned market
+    #[account(mut)]
+    pub compliance_authority: Signer<'info>,              // Market's compliance authority paying for the entry
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for removing a participant from a whitelist.
+///
+/// This struct defines the context for the `remove_from_whitelist` instruction, which can
+/// only be called by the market's compliance authority.
+pub struct RemoveFromWhitelist<'info> {
+    #[account(mut, close = compliance_authority, has_one = market)]
+    pub whitelist_entry: Account<'info, WhitelistEntry>,  // Whitelist entry to close
+    #[account(has_one = compliance_authority @ ErrorCode::NotComplianceAuthority)]
+    pub market: Account<'info, Market>,                   // Permissioned market
+    #[account(mut)]
+    pub compliance_authority: Signer<'info>,              // Market's compliance authority receiving the rent
+}
+
+#[derive(Accounts)]
+/// Context for appointing an exercise delegate.
+///
+/// This struct defines the context for the `set_exercise_delegate` instruction, which can only
+/// be called by the escrow's current holder.
+pub struct SetExerciseDelegate<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose exercise is delegated
+    pub holder: Signer<'info>,                            // Current holder of the exercise rights
+}
+
+#[derive(Accounts)]
+#[instruction(session_key: Pubkey)]
+/// Context for authorizing a session key.
+///
+/// This struct defines the context for the `create_session` instruction.
+pub struct CreateSession<'info> {
+    #[account(
+        init,
+        payer = holder,
+        space = 8 + SessionKey::INIT_SPACE,
+        seeds = [SESSION_SEED, holder.key().as_ref(), session_key.as_ref()],
+        bump
+    )]
+    pub session: Account<'info, SessionKey>,              // Session to create
+    #[account(mut)]
+    pub holder: Signer<'info>,                            // Holder authorizing the key
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for revoking a session key.
+///
+/// This struct defines the context for the `revoke_session` instruction, which can only be
+/// called by the holder who authorized the key.
+pub struct RevokeSession<'info> {
+    #[account(mut, close = holder, has_one = holder)]
+    pub session: Account<'info, SessionKey>,              // Session to close
+    #[account(mut)]
+    pub holder: Signer<'info>,                            // Holder who authorized the key
+}
+
+#[derive(Accounts)]
+/// Context for authorizing delegated deposits.
+///
+/// This struct defines the context for the `authorize_delegated_deposit` instruction.
+pub struct AuthorizeDelegatedDeposit<'info> {
+    #[account(
+        init,
+        payer = depositor,
+        space = 8 + DepositAuthorization::INIT_SPACE,
+        seeds = [DEPOSIT_AUTHORIZATION_SEED, escrow_account.key().as_ref(), depositor.key().as_ref()],
+        bump
+    )]
+    pub authorization: Account<'info, DepositAuthorization>, // Authorization to create
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to fund
+    #[account(mut)]
+    pub depositor: Signer<'info>,                         // Depositor authorizing the pulls
+    #[account(
+        constraint = source_collateral_account.owner == depositor.key(),
+        constraint = source_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub source_collateral_account: Account<'info, TokenAccount>, // Depositor's token account to pull from
+    /// CHECK: Escrow's vault PDA, which may not exist until the first deposit
+    #[account(seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump)]
+    pub escrow_collateral_account: UncheckedAccount<'info>, // Escrow's vault to pull into
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for pulling a delegated deposit.
+///
+/// This struct defines the context for the `pull_delegated_deposit` instruction, which anyone
+/// can call.
+pub struct PullDelegatedDeposit<'info> {
+    #[account(constraint = authorization.escrow == escrow_account.key() @ ErrorCode::InvalidDepositAccount)]
+    pub authorization: Account<'info, DepositAuthorization>, // Depositor's authorization (the SPL delegate)
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow receiving collateral
+    #[account(mut, address = authorization.source @ ErrorCode::InvalidDepositAccount)]
+    pub source_collateral_account: Account<'info, TokenAccount>, // Depositor's token account pulled from
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = escrow_collateral_account
+    )]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral (created on the first deposit)
+    #[account(address = escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the escrow
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the collateral mint (for pause flags)
+    #[account(mut, seeds = [MARKET_STATS_SEED, escrow_account.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,        // Stats of the market
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), authorization.depositor.as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Depositor's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // Depositor's gate mint account (required in token-gated markets)
+    #[account(mut, seeds = [EMISSIONS_SEED, market.key().as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Option<Account<'info, EmissionsSchedule>>, // Market's emissions schedule (to enroll the escrow once funded)
+    #[account(
+        mut,
+        seeds = [WRITER_EMISSIONS_SEED, market.key().as_ref(), escrow_account.initializer_key.as_ref()],
+        bump = writer_emissions.bump
+    )]
+    pub writer_emissions: Option<Account<'info, WriterEmissions>>, // Writer's emissions position (to enroll the escrow once funded)
+    #[account(mut)]
+    pub keeper: Signer<'info>,                            // Keeper submitting the pull (pays for the vault if needed)
+    pub system_program: Program<'info, System>,           // System program for creating the vault
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+}
+
+#[derive(Accounts)]
+/// Context for withdrawing a delegated-deposit authorization.
+///
+/// This struct defines the context for the `revoke_delegated_deposit` instruction, which can
+/// only be called by the depositor.
+pub struct RevokeDelegatedDeposit<'info> {
+    #[account(mut, close = depositor, has_one = depositor)]
+    pub authorization: Account<'info, DepositAuthorization>, // Authorization to close
+    #[account(mut)]
+    pub depositor: Signer<'info>,                         // Depositor who created the authorization
+}
+
+#[derive(Accounts)]
+/// Context for settling a series of escrows in one transaction.
+///
+/// This struct defines the context for the `settle_many` instruction. The escrows and their
+/// own accounts are passed as remaining accounts.
+pub struct SettleMany<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,                              // Crank operator settling the escrows
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the series
+    #[account(mut, seeds = [MARKET_STATS_SEED, market.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,        // Stats of the market
+    #[account(mut, seeds = [PROTOCOL_STATS_SEED], bump = protocol_stats.bump)]
+    pub protocol_stats: Account<'info, ProtocolStats>,    // Protocol-wide stats
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (unless every escrow prices itself)
+    #[account(seeds = [ORACLE_REGISTRY_SEED, market.collateral_mint.as_ref()], bump = oracle_registry.bump)]
+    pub oracle_registry: Option<Account<'info, OracleRegistry>>, // Market's oracle registry (if it aggregates feeds)
+    #[account(seeds = [PRICE_HISTORY_SEED, market.collateral_mint.as_ref()], bump = price_history.load()?.bump)]
+    pub price_history: Option<AccountLoader<'info, PriceHistory>>, // Market's price history (if it settles on a TWAP)
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub secondary_oracle: Option<UncheckedAccount<'info>>, // First extra feed of the oracle registry
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub tertiary_oracle: Option<UncheckedAccount<'info>>,  // Second extra feed of the oracle registry
+    pub governance: Account<'info, Governance>,           // Governance account storing fee rates
+    #[account(mut, seeds = [USER_STATS_SEED, user.key().as_ref()], bump = user_stats.bump)]
+    pub user_stats: Account<'info, UserStats>,            // Crank operator's points and stats
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), user.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Crank operator's whitelist entry (required in permissioned markets)
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+    #[account(mut, seeds = [AUDIT_LOG_SEED, market.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+    #[account(seeds = [VOL_CONFIG_SEED, market.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Option<Account<'info, VolConfig>>,    // Market's volatility curve (to report greeks in events)
+    /// CHECK: Keeper registry PDA, possibly uninitialized, parsed by `earns_crank_fee`
+    #[account(seeds = [KEEPER_REGISTRY_SEED], bump)]
+    pub keeper_registry: Option<UncheckedAccount<'info>>, // Keeper registry (to earn the crank fee)
+    #[account(seeds = [KEEPER_SEED, user.key().as_ref()], bump = keeper.bump)]
+    pub keeper: Option<Account<'info, Keeper>>,           // Crank operator's keeper stake (for priority on the crank fee)
+    #[account(
+        mut,
+        constraint = crank_collateral_account.owner == user.key() @ ErrorCode::InvalidBatch,
+        constraint = crank_collateral_account.mint == market.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub crank_collateral_account: Option<Account<'info, TokenAccount>>, // Crank operator's collateral account (receiving the crank fee)
+    #[account(
+        seeds = [SERIES_SETTLERS_SEED, market.collateral_mint.as_ref(), &series_settlers.expiration.to_le_bytes()],
+        bump = series_settlers.bump
+    )]
+    pub series_settlers: Option<Account<'info, SeriesSettlers>>, // Settlers of the series (in markets with restricted settlement)
+}
+
+impl<'info> SettleMany<'info> {
+    /// Returns the crank incentive owed to the operator for settling one escrow.
+    fn crank_fee(&self, amount_after_fee: u64, expiration: i64, current_time: i64) -> Result<u64> {
+        if self.crank_collateral_account.is_none() || !earns_crank_fee(self.keeper_registry.as_ref(), self.keeper.as_ref(), expiration, current_time)? {
+            return Ok(0);
+        }
+        Ok((amount_after_fee as u128 * self.governance.crank_fee_bps as u128 / 10000) as u64)
+    }
+
+
+    /// Settles one escrow of the batch from its group of remaining accounts.
+    ///
+    /// Escrows that don't price themselves share the series price, loaded on first use.
+    fn settle_one(&mut self, group: &'info [AccountInfo<'info>], series_expiration: i64, series_price: &mut Option<u64>, event_bump: u8) -> Result<()> {
+        let mut escrow_account = Account::<EscrowAccount>::try_from(&group[0])?;
+        let escrow_collateral_account = &group[1];
+        let mut writer_position = Account::<UserPosition>::try_from(&group[2])?;
+        let mut writer_stats = Account::<UserStats>::try_from(&group[3])?;
+
+        // Check the escrow belongs to the series and its accounts belong to its writer
+        let writer = escrow_account.initializer_key;
+        let current_time = Clock::get()?.unix_timestamp;
+        if escrow_account.is_exercised {
+            return Err(ErrorCode::OptionAlreadyExercised.into());
+        }
+        if escrow_account.deposited_amount < escrow_account.collateral_amount {
+            return Err(ErrorCode::EscrowNotFunded.into());
+        }
+        if escrow_account.collateral_mint != self.market.collateral_mint {
+            return Err(ErrorCode::IncorrectCollateralMint.into());
+        }
+        if escrow_account.expiration != series_expiration {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        if current_time < escrow_account.expiration {
+            return Err(ErrorCode::OptionNotExpired.into());
+        }
+        if matches!(escrow_account.payoff, Payoff::Basket | Payoff::Quanto { .. })
+            || escrow_account.emissions_notional > 0
+            || escrow_account.deposit_exchange_rate > 0
+        {
+            return Err(ErrorCode::BatchUnsupported.into());
+        }
+        escrow_account.require_allowed(self.user.key)?;
+        escrow_account.require_not_lent()?;
+        escrow_account.require_not_on_loan()?;
+        let writer_position_key = Pubkey::create_program_address(&[USER_POSITION_SEED, writer.as_ref(), &[writer_position.bump]], &crate::ID)
+            .map_err(|_| ErrorCode::InvalidBatch)?;
+        let writer_stats_key = Pubkey::create_program_address(&[USER_STATS_SEED, writer.as_ref(), &[writer_stats.bump]], &crate::ID)
+            .map_err(|_| ErrorCode::InvalidBatch)?;
+        let vault_key = Pubkey::create_program_address(&[ESCROW_VAULT_SEED, escrow_account.key().as_ref(), &[escrow_account.vault_bump]], &crate::ID)
+            .map_err(|_| ErrorCode::InvalidBatch)?;
+        if writer_position.key() != writer_position_key || writer_stats.key() != writer_stats_key || escrow_collateral_account.key() != vault_key {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+
+        // Decide whether the escrow exercises, as `settle_escrow` does
+        let price = match escrow_account.own_settlement_price()? {
+            Some(price) => price,
+            None => match *series_price {
+                Some(price) => price,
+                None => {
+                    let price = oracle_settlement_price(
+                        &self.market,
+                        series_expiration,
+                        self.collateral_oracle.as_ref(),
+                        self.price_history.as_ref(),
+                        self.oracle_registry.as_ref(),
+                        [self.secondary_oracle.as_ref(), self.tertiary_oracle.as_ref()],
+                    )?;
+                    *series_price = Some(price);
+                    price
+                }
+            },
+        };
+        let is_itm = escrow_account.barrier_allows_payout()
+            && !escrow_account.is_funding_lapsed(current_time)
+            && escrow_account.intrinsic_value(price)? > 0;
+        let threshold_bps = self.market.auto_exercise_threshold_bps;
+        let exercises = is_itm
+            && !escrow_account.auto_exercise_opt_out
+            && (threshold_bps == 0 || escrow_account.moneyness_bps(price) >= threshold_bps);
+
+        // Accrue the fee out of the collateral, to be swept to the fee vault later
+        let collateral_amount = escrow_account.collateral_amount;
+        let fee = collateral_amount * escrow_account.fee_rate(self.governance.settlement_fee_bps) / 10000;
+        self.market_stats.fees_collected = self.market_stats.fees_collected.saturating_add(fee);
+        if exercises {
+            self.user_stats.record_fees_paid(&self.market, fee)?;
+        } else {
+            writer_stats.record_fees_paid(&self.market, fee)?;
+        }
+        escrow_account.unswept_fees = escrow_account.unswept_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+
+        // Pay the crank incentive, then hold the holder's and the writer's proceeds for claiming
+        let amount_after_fee = collateral_amount - fee;
+        let crank_fee = self.crank_fee(amount_after_fee, escrow_account.expiration, current_time)?;
+        if let Some(crank_collateral_account) = self.crank_collateral_account.as_ref() {
+            transfer_from_vault(&escrow_account, escrow_collateral_account.clone(), crank_collateral_account.to_account_info(), &self.token_program, crank_fee)?;
+        }
+        let amount_after_fee = amount_after_fee - crank_fee;
+        let holder = escrow_account.holder_key();
+        let holder_amount = if exercises && holder.is_some() {
+            escrow_account.vested_amount(escrow_account.holder_payout(amount_after_fee), current_time)
+        } else {
+            0
+        };
+        let writer_amount = amount_after_fee - holder_amount;
+        escrow_account.payout_recipient = holder.unwrap_or_default();
+        escrow_account.unclaimed_payout = holder_amount;
+        escrow_account.unclaimed_writer_amount = writer_amount;
+        escrow_account.is_exercised = true;
+        escrow_account.settled_at = current_time;
+
+        // Close the escrow in the writer's position and the stats
+        writer_position.remove_written(&escrow_account.key());
+        writer_stats.remove_open_notional(&escrow_account.collateral_mint, collateral_amount);
+        self.market.open_interest = self.market.open_interest.saturating_sub(collateral_amount);
+        self.market_stats.collateral_locked = self.market_stats.collateral_locked.saturating_sub(collateral_amount);
+        self.market_stats.escrows_closed = self.market_stats.escrows_closed.saturating_add(1);
+        self.protocol_stats.record_close(false);
+        emit_lifecycle_event(&EscrowSettled {
+            seq: self.market_stats.next_event_seq(),
+            market: self.market_stats.market,
+            escrow: escrow_account.key(),
+            is_itm: exercises,
+            holder_amount,
+            writer_amount,
+            fee,
+            greeks: self.vol_config.as_ref().and_then(|vol_config| escrow_account.greeks(price, vol_config, current_time).ok()),
+        }, self.event_authority.as_ref(), event_bump)?;
+        record_audit(
+            self.audit_log.as_ref(),
+            self.user.key(),
+            escrow_account.key(),
+            crate::instruction::SettleMany::DISCRIMINATOR,
+            collateral_amount,
+        )?;
+
+        escrow_account.exit(&crate::ID)?;
+        writer_position.exit(&crate::ID)?;
+        writer_stats.exit(&crate::ID)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for opening several escrows at once.
+///
+/// This struct defines the context for the `initialize_escrows_batch` instruction. It mirrors
+/// `InitializeEscrow`, with the new escrow accounts passed as remaining accounts.
+pub struct InitializeEscrowsBatch<'info> {
+    #[account(mut)]
+    pub initializer: Signer<'info>,                      // The initializer (creator of the escrows)
+    #[account(mut)]
+    pub initializer_collateral_account: Account<'info, TokenAccount>,  // Initializer's token account for collateral
+    #[account(mut, constraint = initializer_fee_account.mint == market.fee_mint @ ErrorCode::IncorrectFeeMint)]
+    pub initializer_fee_account: Option<Account<'info, TokenAccount>>, // Initializer's fee mint account (if fees aren't in collateral)
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                  // Market for the collateral mint
+    #[account(mut, seeds = [MARKET_STATS_SEED, market.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,       // Stats of the market
+    #[account(mut, seeds = [PROTOCOL_STATS_SEED], bump = protocol_stats.bump)]
+    pub protocol_stats: Account<'info, ProtocolStats>,   // Protocol-wide stats
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (if fees aren't in collateral or strikes are banded)
+    /// CHECK: Lending reserve, checked against the collateral mint and parsed on use
+    pub collateral_reserve: Option<UncheckedAccount<'info>>, // Lending reserve of interest-bearing collateral (for its exchange rate)
+    #[account(seeds = [EXPIRY_GRID_SEED, market.collateral_mint.as_ref()], bump = expiry_grid.bump)]
+    pub expiry_grid: Option<Account<'info, ExpiryGrid>>, // Market's grid of standard expiries (required if the market lists on one)
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.fee_oracle)]
+    pub fee_oracle: Option<UncheckedAccount<'info>>,     // Fee mint price feed (if fees aren't in collateral)
+    #[account(mut, seeds = [FEE_VAULT_SEED, market.fee_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,             // Fee vault for the market's fee mint
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account receiving the fee
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,          // Governance account storing fee rates and fee collectors
+    #[account(seeds = [STAKE_POSITION_SEED, initializer.key().as_ref()], bump = stake_position.bump)]
+    pub stake_position: Option<Account<'info, StakePosition>>, // Initializer's stake position (for a fee discount)
+    #[account(mut, seeds = [USER_STATS_SEED, initializer.key().as_ref()], bump = initializer_stats.bump)]
+    pub initializer_stats: Account<'info, UserStats>,    // Initializer's points and stats
+    #[account(mut, seeds = [USER_POSITION_SEED, initializer.key().as_ref()], bump = initializer_position.bump)]
+    pub initializer_position: Account<'info, UserPosition>, // Initializer's open escrows
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), initializer.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Initializer's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // Initializer's gate mint account (required in token-gated markets)
+    pub system_program: Program<'info, System>,          // System program for account creation
+    pub token_program: Program<'info, Token>,            // Token program for handling SPL tokens
+    /// CHECK: Anchor's event-cpi authority PDA, signing the self-CPI that records events
+    #[account(seeds = [EVENT_AUTHORITY_SEED], bump)]
+    pub event_authority: Option<UncheckedAccount<'info>>, // Event authority, to record events as self-CPI data instead of logs
+    pub program: Option<Program<'info, crate::program::OptionsEscrow>>, // This program, invoked by the event self-CPI
+}
+
+impl<'info> InitializeEscrowsBatch<'info> {
+    /// Collects the summed initialization fee of the batch into the fee vault, as
+    /// `InitializeEscrow::collect_fee` does for a single escrow.
+    fn collect_fee(&mut self, fee: u64) -> Result<()> {
+        let fee = self.governance.apply_fee_discount(fee, self.stake_position.as_ref(), Clock::get()?.unix_timestamp);
+        self.initializer_stats.record_fees_paid(&self.market, fee)?;
+        self.market_stats.fees_collected = self.market_stats.fees_collected.saturating_add(fee);
+        let (from, fee_amount) = if self.market.has_separate_fee_mint() {
+            let initializer_fee_account = self.initializer_fee_account.as_ref().ok_or(ErrorCode::MissingFeeAccount)?;
+            let fee_amount = self.market.convert_to_fee_mint(fee, self.collateral_oracle.as_ref(), self.fee_oracle.as_ref())?;
+            (initializer_fee_account.to_account_info(), fee_amount)
+        } else {
+            (self.initializer_collateral_account.to_account_info(), fee)
+        };
+
+        let cpi_accounts_fee = Transfer {
+            from,
+            to: self.fee_vault_token_account.to_account_info(),
+            authority: self.initializer.to_account_info(),
+        };
+        let cpi_ctx_fee = CpiContext::new(self.token_program.to_account_info(), cpi_accounts_fee);
+        token::transfer(cpi_ctx_fee, fee_amount)?;
+        self.fee_vault.accrue(&self.governance.fee_collectors, fee_amount)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for sweeping an escrow's accrued fees.
+///
+/// This struct defines the context for the permissionless `sweep_escrow_fees` instruction.
+pub struct SweepEscrowFees<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow with accrued fees
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding the fees
+    #[account(mut, seeds = [FEE_VAULT_SEED, escrow_account.collateral_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault for the collateral mint
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account receiving the fees
+    pub governance: Account<'info, Governance>,           // Governance account storing the fee collectors
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for migrating an escrow to the current account layout.
+///
+/// This struct defines the context for the `migrate_escrow` instruction, which can only be
+/// called by the escrow's writer or the governance authority.
+pub struct MigrateEscrow<'info> {
+    /// CHECK: Escrow under an older layout, which can't be deserialized until it is grown; its discriminator, version, and writer are checked in the instruction
+    #[account(mut, owner = crate::ID)]
+    pub escrow_account: UncheckedAccount<'info>,          // Escrow to migrate
+    pub governance: Account<'info, Governance>,           // Governance account, whose authority may also migrate
+    #[account(mut)]
+    pub authority: Signer<'info>,                         // Escrow writer or governance authority (pays the extra rent)
+    pub system_program: Program<'info, System>,           // System program for the rent transfer
+}
+
+#[derive(Accounts)]
+/// Context for creating the audit log of a market.
+///
+/// This struct defines the context for the `initialize_audit_log` instruction, which can only
+/// be called by the governance authority.
+pub struct InitializeAuditLog<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + std::mem::size_of::<AuditLog>(),
+        seeds = [AUDIT_LOG_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub audit_log: AccountLoader<'info, AuditLog>,        // Audit log to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market whose actions are recorded
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the log
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for previewing the settlement of an escrow.
+///
+/// This struct defines the context for the read-only `preview_settlement` view. It takes the
+/// pricing accounts of `SettleEscrow`, none of them writable.
+pub struct PreviewSettlement<'info> {
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to preview
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market for the collateral mint
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (unless governance set the settlement price)
+    /// CHECK: Lending reserve, checked against the collateral mint and parsed on use
+    pub collateral_reserve: Option<UncheckedAccount<'info>>, // Lending reserve of interest-bearing collateral (for its exchange rate)
+    #[account(seeds = [ORACLE_REGISTRY_SEED, escrow_account.collateral_mint.as_ref()], bump = oracle_registry.bump)]
+    pub oracle_registry: Option<Account<'info, OracleRegistry>>, // Market's oracle registry (if it aggregates feeds)
+    #[account(seeds = [BASKET_SEED, escrow_account.collateral_mint.as_ref()], bump = basket.bump)]
+    pub basket: Option<Account<'info, Basket>>,  // Market's basket of underlyings (for basket options)
+    /// CHECK: Pyth price feed, checked against the quanto payoff and parsed on use
+    pub underlying_oracle: Option<UncheckedAccount<'info>>, // Underlying price feed (for quanto options)
+    #[account(seeds = [PRICE_HISTORY_SEED, escrow_account.collateral_mint.as_ref()], bump = price_history.load()?.bump)]
+    pub price_history: Option<AccountLoader<'info, PriceHistory>>, // Market's price history (if it settles on a TWAP)
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub secondary_oracle: Option<UncheckedAccount<'info>>, // First extra feed of the oracle registry
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub tertiary_oracle: Option<UncheckedAccount<'info>>,  // Second extra feed of the oracle registry
+    pub governance: Account<'info, Governance>,           // Governance account storing fee rates
+    pub stake_position: Option<Account<'info, StakePosition>>, // Settler's stake position (for a fee discount)
+}
+
+#[derive(Accounts)]
+/// Context for reading the value of an escrow.
+///
+/// This struct defines the context for the read-only `get_escrow_value` view.
+pub struct GetEscrowValue<'info> {
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to value
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+}
+
+#[derive(Accounts)]
+/// Context for the `get_fair_value` view, which only needs a placeholder account so the
+/// generated CPI client (which expects a lifetime) compiles.
+pub struct GetFairValue<'info> {
+    pub clock: Sysvar<'info, Clock>,                      // Clock sysvar (unused by the pricing math)
+}
+
+#[derive(Accounts)]
+/// Context for creating the volatility curve of a market.
+///
+/// This struct defines the context for the `initialize_vol_config` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeVolConfig<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + VolConfig::INIT_SPACE,
+        seeds = [VOL_CONFIG_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub vol_config: Account<'info, VolConfig>,            // Volatility curve to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the curve prices
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the curve
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating the settings of a market's volatility curve.
+///
+/// This struct defines the context for the `update_vol_config` instruction.
+pub struct UpdateVolConfig<'info> {
+    #[account(mut)]
+    pub vol_config: Account<'info, VolConfig>,            // Volatility curve to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for pushing new points to a market's volatility curve.
+///
+/// This struct defines the context for the `push_vol_points` instruction.
+pub struct PushVolPoints<'info> {
+    #[account(mut)]
+    pub vol_config: Account<'info, VolConfig>,            // Volatility curve to update
+    pub governance: Account<'info, Governance>,           // Governance account, whose authority may also push
+    pub authority: Signer<'info>,                         // Curve updater or governance authority
+}
+
+#[derive(Accounts)]
+/// Context for reading the fair value of an escrow.
+///
+/// This struct defines the context for the read-only `get_escrow_fair_value` view.
+pub struct GetEscrowFairValue<'info> {
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to value
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+    #[account(seeds = [VOL_CONFIG_SEED, escrow_account.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Account<'info, VolConfig>,            // Market's volatility curve
+}
+
+#[derive(Accounts)]
+/// Context for creating the keeper registry.
+///
+/// This struct defines the context for the `initialize_keeper_registry` instruction, which
+/// can only be called by the governance authority.
+pub struct InitializeKeeperRegistry<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + KeeperRegistry::INIT_SPACE,
+        seeds = [KEEPER_REGISTRY_SEED],
+        bump
+    )]
+    pub keeper_registry: Account<'info, KeeperRegistry>,  // Keeper registry to create
+    #[account(
+        init,
+        payer = governance_authority,
+        seeds = [KEEPER_STAKE_VAULT_SEED],
+        bump,
+        token::mint = stake_mint,
+        token::authority = keeper_registry
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,        // Token account holding keeper stake
+    pub stake_mint: Account<'info, Mint>,                 // Mint keepers stake
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the registry
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the stake vault
+    pub rent: Sysvar<'info, Rent>,                        // Rent system for account initialization
+}
+
+#[derive(Accounts)]
+/// Context for updating the keeper registry.
+///
+/// This struct defines the context for the `update_keeper_registry` instruction.
+pub struct UpdateKeeperRegistry<'info> {
+    #[account(mut, seeds = [KEEPER_REGISTRY_SEED], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,  // Keeper registry to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for registering a keeper.
+///
+/// This struct defines the context for the `register_keeper` instruction.
+pub struct RegisterKeeper<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Keeper::INIT_SPACE,
+        seeds = [KEEPER_SEED, owner.key().as_ref()],
+        bump
+    )]
+    pub keeper: Account<'info, Keeper>,                   // Keeper stake account to create
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // Keeper
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for bonding, unbonding, and withdrawing keeper stake.
+///
+/// This struct defines the context for the `bond_keeper_stake`, `unbond_keeper_stake`, and
+/// `withdraw_keeper_stake` instructions.
+pub struct KeeperStake<'info> {
+    #[account(mut, seeds = [KEEPER_REGISTRY_SEED], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,  // Keeper registry
+    #[account(mut, seeds = [KEEPER_SEED, owner.key().as_ref()], bump = keeper.bump)]
+    pub keeper: Account<'info, Keeper>,                   // Keeper's stake
+    pub owner: Signer<'info>,                             // Keeper
+    #[account(mut, constraint = owner_stake_account.mint == keeper_registry.stake_mint @ ErrorCode::IncorrectStakeMint)]
+    pub owner_stake_account: Account<'info, TokenAccount>, // Keeper's token account of the stake mint
+    #[account(mut, address = keeper_registry.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,        // Token account holding keeper stake
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for slashing a keeper.
+///
+/// This struct defines the context for the `slash_keeper` instruction, which can only be
+/// called by the governance authority.
+pub struct SlashKeeper<'info> {
+    #[account(mut, seeds = [KEEPER_REGISTRY_SEED], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,  // Keeper registry
+    #[account(mut, seeds = [KEEPER_SEED, keeper.owner.as_ref()], bump = keeper.bump)]
+    pub keeper: Account<'info, Keeper>,                   // Keeper to slash
+    #[account(mut, address = keeper_registry.stake_vault)]
+    pub stake_vault: Account<'info, TokenAccount>,        // Token account holding keeper stake
+    #[account(mut, constraint = recipient_account.mint == keeper_registry.stake_mint @ ErrorCode::IncorrectStakeMint)]
+    pub recipient_account: Account<'info, TokenAccount>,  // Token account receiving the slashed stake
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for submitting a settlement price as a keeper.
+///
+/// This struct defines the context for the `submit_settlement_price` instruction.
+pub struct SubmitSettlementPrice<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to settle manually
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow
+    #[account(seeds = [KEEPER_REGISTRY_SEED], bump = keeper_registry.bump)]
+    pub keeper_registry: Account<'info, KeeperRegistry>,  // Keeper registry
+    #[account(seeds = [KEEPER_SEED, owner.key().as_ref()], bump = keeper.bump)]
+    pub keeper: Account<'info, Keeper>,                   // Submitting keeper's stake
+    pub owner: Signer<'info>,                             // Submitting keeper
+    #[account(mut, seeds = [AUDIT_LOG_SEED, escrow_account.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+}
+
+#[derive(Accounts)]
+#[instruction(expiration: i64)]
+/// Context for creating the settler list of a series.
+///
+/// This struct defines the context for the `initialize_series_settlers` instruction, which
+/// can only be called by the governance authority.
+pub struct InitializeSeriesSettlers<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + SeriesSettlers::INIT_SPACE,
+        seeds = [SERIES_SETTLERS_SEED, market.collateral_mint.as_ref(), &expiration.to_le_bytes()],
+        bump
+    )]
+    pub series_settlers: Account<'info, SeriesSettlers>,  // Settler list to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the series
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the list
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating the settler list of a series.
+///
+/// This struct defines the context for the `set_series_settlers` instruction.
+pub struct SetSeriesSettlers<'info> {
+    #[account(mut)]
+    pub series_settlers: Account<'info, SeriesSettlers>,  // Settler list to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for settling an escrow's price from a signed attestation.
+///
+/// This struct defines the context for the `submit_price_attestation` instruction.
+pub struct SubmitPriceAttestation<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow to settle manually
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow
+    /// CHECK: Instructions sysvar, read to find the ed25519 verification
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,            // Instructions sysvar
+    #[account(mut, seeds = [AUDIT_LOG_SEED, escrow_account.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+}
+
+#[derive(Accounts)]
+/// Context for exercising an escrow and swapping the proceeds.
+///
+/// This struct defines the context for the `exercise_and_swap` instruction. The Jupiter route
+/// accounts are passed as remaining accounts.
+pub struct ExerciseAndSwap<'info> {
+    pub exercise: SettleEscrow<'info>,                    // Escrow to exercise and its exercise accounts
+    /// CHECK: Jupiter aggregator program, checked by address
+    #[account(executable, address = JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,         // Program executing the swap
+    #[account(mut, constraint = output_token_account.owner == exercise.user.key() @ ErrorCode::InvalidProceedsAccount)]
+    pub output_token_account: Account<'info, TokenAccount>, // Exerciser's token account receiving the output mint
+}
+
+#[derive(Accounts)]
+/// Context for whitelisting a lending protocol for a market.
+///
+/// This struct defines the context for the `initialize_lending_config` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeLendingConfig<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + LendingConfig::INIT_SPACE,
+        seeds = [LENDING_CONFIG_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub lending_config: Account<'info, LendingConfig>,    // Lending configuration to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market whose collateral may be lent
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the configuration
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating a market's lending configuration.
+///
+/// This struct defines the context for the `update_lending_config` instruction.
+pub struct UpdateLendingConfig<'info> {
+    #[account(mut)]
+    pub lending_config: Account<'info, LendingConfig>,    // Lending configuration to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for lending an escrow's collateral.
+///
+/// This struct defines the context for the `lend_collateral` instruction. The accounts
+/// required by the lending protocol are passed as remaining accounts.
+pub struct LendCollateral<'info> {
+    #[account(mut, has_one = initializer_key @ ErrorCode::NotEscrowWriter)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose collateral is lent
+    pub initializer_key: Signer<'info>,                   // Writer of the escrow
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral
+    #[account(
+        mut,
+        constraint = receipt_account.owner == escrow_collateral_account.key() @ ErrorCode::InvalidLendingDeposit,
+        constraint = receipt_account.mint == lending_config.receipt_mint @ ErrorCode::InvalidLendingDeposit
+    )]
+    pub receipt_account: Account<'info, TokenAccount>,    // Account owned by the escrow's vault receiving the receipt tokens
+    #[account(seeds = [LENDING_CONFIG_SEED, escrow_account.collateral_mint.as_ref()], bump = lending_config.bump)]
+    pub lending_config: Account<'info, LendingConfig>,    // Market's lending configuration
+    /// CHECK: Whitelisted lending program, validated against the lending configuration
+    #[account(executable, address = lending_config.lending_program)]
+    pub lending_program: UncheckedAccount<'info>,         // Lending protocol receiving the deposit
+    #[account(mut, seeds = [AUDIT_LOG_SEED, escrow_account.collateral_mint.as_ref()], bump = audit_log.load()?.bump)]
+    pub audit_log: Option<AccountLoader<'info, AuditLog>>, // Market's audit log (if it keeps one)
+}
+
+#[derive(Accounts)]
+/// Context for recalling an escrow's lent collateral.
+///
+/// This struct defines the context for the `recall_collateral` instruction. The accounts
+/// required by the lending protocol are passed as remaining accounts.
+pub struct RecallCollateral<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose collateral is recalled
+    pub user: Signer<'info>,                              // Writer, or anyone after expiry
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault receiving the collateral
+    #[account(
+        mut,
+        constraint = initializer_collateral_account.owner == escrow_account.initializer_key @ ErrorCode::NotEscrowWriter,
+        constraint = initializer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's account receiving their share of the yield
+    #[account(seeds = [LENDING_CONFIG_SEED, escrow_account.collateral_mint.as_ref()], bump = lending_config.bump)]
+    pub lending_config: Account<'info, LendingConfig>,    // Market's lending configuration
+    #[account(seeds = [TREASURY_SEED, escrow_account.collateral_mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury receiving the protocol's share
+    #[account(mut, address = treasury.token_account)]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Treasury token account
+    /// CHECK: Whitelisted lending program, validated against the lending configuration
+    #[account(executable, address = lending_config.lending_program)]
+    pub lending_program: UncheckedAccount<'info>,         // Lending protocol returning the collateral
+    pub token_program: Program<'info, Token>,             // Token program for the yield transfers
+}
+
+#[derive(Accounts)]
+#[instruction(program: Pubkey)]
+/// Context for whitelisting a perp DEX for hedging.
+///
+/// This struct defines the context for the `add_hedge_venue` instruction, which can only be
+/// called by the governance authority.
+pub struct AddHedgeVenue<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + HedgeVenue::INIT_SPACE,
+        seeds = [HEDGE_VENUE_SEED, program.as_ref()],
+        bump
+    )]
+    pub hedge_venue: Account<'info, HedgeVenue>,          // Whitelist entry to create
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the entry
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for removing a perp DEX from the hedging whitelist.
+///
+/// This struct defines the context for the `remove_hedge_venue` instruction, which can only be
+/// called by the governance authority.
+pub struct RemoveHedgeVenue<'info> {
+    #[account(mut, close = governance_authority, seeds = [HEDGE_VENUE_SEED, hedge_venue.program.as_ref()], bump = hedge_venue.bump)]
+    pub hedge_venue: Account<'info, HedgeVenue>,          // Whitelist entry to close
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority receiving the rent
+}
+
+#[derive(Accounts)]
+/// Context for opening a hedging vault.
+///
+/// This struct defines the context for the `initialize_hedge_vault` instruction.
+pub struct InitializeHedgeVault<'info> {
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + HedgeVault::INIT_SPACE,
+        seeds = [HEDGE_VAULT_SEED, market.collateral_mint.as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub hedge_vault: Account<'info, HedgeVault>,          // Hedging vault to create
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the hedged escrows
+    #[account(seeds = [HEDGE_VENUE_SEED, hedge_venue.program.as_ref()], bump = hedge_venue.bump)]
+    pub hedge_venue: Account<'info, HedgeVenue>,          // Whitelisted perp DEX to trade on
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // Writer opening the vault
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for changing the parameters of a hedging vault.
+///
+/// This struct defines the context for the `set_hedge_params` instruction.
+pub struct SetHedgeParams<'info> {
+    #[account(mut, has_one = owner)]
+    pub hedge_vault: Account<'info, HedgeVault>,          // Hedging vault to update
+    pub owner: Signer<'info>,                             // Owner of the vault
+}
+
+#[derive(Accounts)]
+/// Context for rebalancing a hedging vault.
+///
+/// This struct defines the context for the `rebalance_hedge` instruction. The owner's escrows
+/// and the venue's accounts are passed as remaining accounts.
+pub struct RebalanceHedge<'info> {
+    #[account(
+        mut,
+        seeds = [HEDGE_VAULT_SEED, market.collateral_mint.as_ref(), hedge_vault.owner.as_ref()],
+        bump = hedge_vault.bump,
+        has_one = market
+    )]
+    pub hedge_vault: Account<'info, HedgeVault>,          // Hedging vault to rebalance
+    #[account(seeds = [HEDGE_VENUE_SEED, hedge_vault.venue.as_ref()], bump = hedge_venue.bump)]
+    pub hedge_venue: Account<'info, HedgeVenue>,          // Venue's whitelist entry, proving it is still whitelisted
+    #[account(seeds = [USER_POSITION_SEED, hedge_vault.owner.as_ref()], bump = user_position.bump)]
+    pub user_position: Account<'info, UserPosition>,      // Owner's open escrows
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the hedged escrows
+    #[account(seeds = [VOL_CONFIG_SEED, market.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Account<'info, VolConfig>,            // Market's volatility curve, for the escrows' deltas
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+    /// CHECK: Whitelisted perp DEX program, validated against the vault
+    #[account(executable, address = hedge_vault.venue)]
+    pub venue_program: UncheckedAccount<'info>,           // Perp DEX executing the order
+    pub authority: Signer<'info>,                         // Owner or rebalancer of the vault
+}
+
+#[derive(Accounts)]
+/// Context for creating a protocol-owned writer vault.
+///
+/// This struct defines the context for the `initialize_pol_vault` instruction, which can only
+/// be called by the governance authority.
+pub struct InitializePolVault<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + PolVault::INIT_SPACE,
+        seeds = [POL_VAULT_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub pol_vault: Account<'info, PolVault>,              // Vault to create
+    /// CHECK: System-owned PDA writing the vault's escrows
+    #[account(mut, seeds = [POL_WRITER_SEED, market.collateral_mint.as_ref()], bump)]
+    pub pol_writer: UncheckedAccount<'info>,              // Writer PDA of the vault
+    #[account(
+        init,
+        payer = governance_authority,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = pol_writer
+    )]
+    pub pol_token_account: Account<'info, TokenAccount>,  // Writer's token account holding the vault's funds
+    #[account(address = market.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the market
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the vault writes in
+    /// CHECK: Created by `create_user_stats`, which checks its seeds
+    #[account(mut)]
+    pub writer_stats: UncheckedAccount<'info>,            // Writer's stats account to create
+    /// CHECK: Created by `create_user_position`, which checks its seeds
+    #[account(mut)]
+    pub writer_position: UncheckedAccount<'info>,         // Writer's position account to create
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the vault
+    pub program: Program<'info, crate::program::OptionsEscrow>, // This program, called to create the writer's accounts
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the token account
+    pub associated_token_program: Program<'info, AssociatedToken>, // Associated token program for the token account
+}
+
+#[derive(Accounts)]
+/// Context for changing the parameters of a protocol-owned vault.
+///
+/// This struct defines the context for the `set_pol_vault_params` instruction.
+pub struct UpdatePolVault<'info> {
+    #[account(mut)]
+    pub pol_vault: Account<'info, PolVault>,              // Vault to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for moving funds between the treasury and a protocol-owned vault.
+///
+/// This struct defines the context for the `fund_pol_vault` and `defund_pol_vault`
+/// instructions, which can only be called by the governance authority.
+pub struct PolVaultTreasury<'info> {
+    #[account(mut, seeds = [POL_VAULT_SEED, pol_vault.collateral_mint.as_ref()], bump = pol_vault.bump)]
+    pub pol_vault: Account<'info, PolVault>,              // Vault to fund or defund
+    /// CHECK: System-owned PDA writing the vault's escrows
+    #[account(address = pol_vault.writer)]
+    pub pol_writer: UncheckedAccount<'info>,              // Writer PDA owning the vault's funds
+    #[account(mut, address = pol_vault.token_account)]
+    pub pol_token_account: Account<'info, TokenAccount>,  // Vault's token account
+    #[account(mut, seeds = [TREASURY_SEED, pol_vault.collateral_mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury of the collateral mint
+    #[account(mut, address = treasury.token_account)]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Token account holding treasury funds
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account holding the timelock
+    pub governance_authority: Signer<'info>,              // Governance authority
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for sweeping a protocol-owned vault's profits to the treasury.
+///
+/// This struct defines the context for the permissionless `sweep_pol_profits` instruction.
+pub struct SweepPolProfits<'info> {
+    #[account(mut, seeds = [POL_VAULT_SEED, pol_vault.collateral_mint.as_ref()], bump = pol_vault.bump)]
+    pub pol_vault: Account<'info, PolVault>,              // Vault to sweep
+    /// CHECK: System-owned PDA writing the vault's escrows
+    #[account(address = pol_vault.writer)]
+    pub pol_writer: UncheckedAccount<'info>,              // Writer PDA owning the vault's funds
+    #[account(mut, address = pol_vault.token_account)]
+    pub pol_token_account: Account<'info, TokenAccount>,  // Vault's token account
+    #[account(seeds = [USER_STATS_SEED, pol_vault.writer.as_ref()], bump = writer_stats.bump)]
+    pub writer_stats: Account<'info, UserStats>,          // Writer's stats, holding its open notional
+    #[account(seeds = [TREASURY_SEED, pol_vault.collateral_mint.as_ref()], bump = treasury.bump)]
+    pub treasury: Account<'info, Treasury>,               // Treasury of the collateral mint
+    #[account(mut, address = treasury.token_account)]
+    pub treasury_token_account: Account<'info, TokenAccount>, // Token account receiving the profits
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for writing an escrow from a protocol-owned vault.
+///
+/// This struct defines the context for the `pol_write` instruction. The accounts of the
+/// `initialize_escrow` and `deposit_collateral` calls are passed as remaining accounts.
+pub struct PolWrite<'info> {
+    #[account(seeds = [POL_VAULT_SEED, pol_vault.collateral_mint.as_ref()], bump = pol_vault.bump, has_one = operator)]
+    pub pol_vault: Account<'info, PolVault>,              // Vault writing the escrow
+    /// CHECK: System-owned PDA writing the vault's escrows
+    #[account(mut, address = pol_vault.writer)]
+    pub pol_writer: UncheckedAccount<'info>,              // Writer PDA of the vault
+    #[account(seeds = [USER_STATS_SEED, pol_vault.writer.as_ref()], bump = writer_stats.bump)]
+    pub writer_stats: Account<'info, UserStats>,          // Writer's stats, holding its open notional
+    #[account(seeds = [MARKET_SEED, pol_vault.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the vault writes in
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: UncheckedAccount<'info>,       // Pyth price feed for the collateral mint
+    #[account(mut)]
+    pub operator: Signer<'info>,                          // Vault operator paying the escrow's rent
+    pub program: Program<'info, crate::program::OptionsEscrow>, // This program, called to write the escrow
+    pub system_program: Program<'info, System>,           // System program for the rent transfer
+}
+
+impl<'info> PolWrite<'info> {
+    /// Writes and funds an escrow as the vault's writer, with the operator paying its rent.
+    ///
+    /// The first `init_account_count` of `remaining_accounts` are `initialize_escrow`'s and the
+    /// rest `deposit_collateral`'s for the new escrow.
+    fn write_escrow(&self, remaining_accounts: &[AccountInfo<'info>], init_account_count: u8, escrow: instruction::InitializeEscrow) -> Result<()> {
+        let init_account_count = init_account_count as usize;
+        if init_account_count == 0 || init_account_count >= remaining_accounts.len() {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+        let (init_accounts, deposit_accounts) = remaining_accounts.split_at(init_account_count);
+        if deposit_accounts[0].key() != init_accounts[0].key() {
+            return Err(ErrorCode::InvalidBatch.into());
+        }
+
+        // The operator pays the rent of the new escrow and its vault
+        let cpi_accounts = system_program::Transfer {
+            from: self.operator.to_account_info(),
+            to: self.pol_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+        let rent = Rent::get()?;
+        system_program::transfer(cpi_ctx, rent.minimum_balance(EscrowAccount::SPACE) + rent.minimum_balance(TokenAccount::LEN))?;
+
+        // Write and fund the escrow as the vault's writer
+        let pol_vault = &self.pol_vault;
+        let mint = pol_vault.collateral_mint;
+        let writer_key = pol_vault.writer;
+        let writer_seeds: &[&[u8]] = &[POL_WRITER_SEED, mint.as_ref(), &[pol_vault.writer_bump]];
+        let amount = escrow.collateral_amount;
+        let calls = [
+            (init_accounts, escrow.data()),
+            (deposit_accounts, instruction::DepositCollateral { amount }.data()),
+        ];
+        for (accounts, data) in calls {
+            let account_metas = accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer || account.key() == writer_key,
+                    is_writable: account.is_writable,
+                })
+                .collect();
+            let ix = Instruction { program_id: crate::ID, accounts: account_metas, data };
+            let mut account_infos = accounts.to_vec();
+            account_infos.push(self.program.to_account_info());
+            invoke_signed(&ix, &account_infos, &[writer_seeds])?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+/// Context for creating a backstop liquidity pool.
+///
+/// This struct defines the context for the `initialize_backstop_pool` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeBackstopPool<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + BackstopPool::INIT_SPACE,
+        seeds = [BACKSTOP_POOL_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub backstop_pool: Account<'info, BackstopPool>,      // Pool to create
+    /// CHECK: System-owned PDA holding the escrows taken over
+    #[account(mut, seeds = [BACKSTOP_WRITER_SEED, market.collateral_mint.as_ref()], bump)]
+    pub backstop_writer: UncheckedAccount<'info>,         // Writer PDA of the pool
+    #[account(
+        init,
+        payer = governance_authority,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = backstop_writer
+    )]
+    pub backstop_token_account: Account<'info, TokenAccount>, // Writer's token account holding the pool's capital
+    #[account(address = market.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the market
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the pool backstops
+    /// CHECK: Created by `create_user_stats`, which checks its seeds
+    #[account(mut)]
+    pub writer_stats: UncheckedAccount<'info>,            // Writer's stats account to create
+    /// CHECK: Created by `create_user_position`, which checks its seeds
+    #[account(mut)]
+    pub writer_position: UncheckedAccount<'info>,         // Writer's position account to create
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the pool
+    pub program: Program<'info, crate::program::OptionsEscrow>, // This program, called to create the writer's accounts
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the token account
+    pub associated_token_program: Program<'info, AssociatedToken>, // Associated token program for the token account
+}
+
+#[derive(Accounts)]
+/// Context for changing a backstop pool's discount.
+///
+/// This struct defines the context for the `set_backstop_discount` instruction.
+pub struct UpdateBackstopPool<'info> {
+    #[account(mut)]
+    pub backstop_pool: Account<'info, BackstopPool>,      // Pool to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+/// Context for designating a backstop LP.
+///
+/// This struct defines the context for the `add_backstop_lp` instruction, which can only be
+/// called by the governance authority.
+pub struct AddBackstopLp<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + BackstopLp::INIT_SPACE,
+        seeds = [BACKSTOP_LP_SEED, backstop_pool.key().as_ref(), owner.as_ref()],
+        bump
+    )]
+    pub backstop_lp: Account<'info, BackstopLp>,          // LP position to create
+    pub backstop_pool: Account<'info, BackstopPool>,      // Pool the LP commits to
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the position
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for moving an LP's funds in or out of a backstop pool.
+///
+/// This struct defines the context for the `deposit_backstop`, `withdraw_backstop`, and
+/// `claim_backstop_rewards` instructions.
+pub struct BackstopLpTransfer<'info> {
+    #[account(mut, seeds = [BACKSTOP_POOL_SEED, backstop_pool.collateral_mint.as_ref()], bump = backstop_pool.bump)]
+    pub backstop_pool: Account<'info, BackstopPool>,      // Pool the LP is in
+    #[account(
+        mut,
+        seeds = [BACKSTOP_LP_SEED, backstop_pool.key().as_ref(), owner.key().as_ref()],
+        bump = backstop_lp.bump
+    )]
+    pub backstop_lp: Account<'info, BackstopLp>,          // LP's position
+    pub owner: Signer<'info>,                             // LP owning the position
+    #[account(
+        mut,
+        constraint = owner_collateral_account.mint == backstop_pool.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub owner_collateral_account: Account<'info, TokenAccount>, // LP's collateral token account
+    /// CHECK: System-owned PDA holding the escrows taken over
+    #[account(address = backstop_pool.writer)]
+    pub backstop_writer: UncheckedAccount<'info>,         // Writer PDA owning the pool's capital
+    #[account(mut, address = backstop_pool.token_account)]
+    pub backstop_token_account: Account<'info, TokenAccount>, // Pool's token account
+    #[account(seeds = [USER_STATS_SEED, backstop_pool.writer.as_ref()], bump = backstop_stats.bump)]
+    pub backstop_stats: Account<'info, UserStats>,        // Writer's stats, holding the collateral of its escrows
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+impl<'info> BackstopLpTransfer<'info> {
+    /// Returns the pool's value: its free capital plus the collateral of the escrows it holds.
+    fn pool_value(&self) -> Result<u64> {
+        let backstop_pool = &self.backstop_pool;
+        backstop_pool.value(self.backstop_token_account.amount, self.backstop_stats.open_notional_in(&backstop_pool.collateral_mint))
+    }
+
+    /// Pays `amount` out of the pool to the LP.
+    fn transfer_out(&self, amount: u64) -> Result<()> {
+        let mint = self.backstop_pool.collateral_mint;
+        let seeds: &[&[u8]] = &[BACKSTOP_WRITER_SEED, mint.as_ref(), &[self.backstop_pool.writer_bump]];
+        let signer_seeds = &[seeds];
+        let cpi_accounts = Transfer {
+            from: self.backstop_token_account.to_account_info(),
+            to: self.owner_collateral_account.to_account_info(),
+            authority: self.backstop_writer.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+        token::transfer(cpi_ctx, amount)
+    }
+}
+
+#[derive(Accounts)]
+/// Context for taking over a defaulting writer's escrow.
+///
+/// This struct defines the context for the permissionless `backstop_default` instruction.
+pub struct BackstopDefault<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Underfunded escrow to take over
+    #[account(
+        init_if_needed,
+        payer = caller,
+        seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = escrow_collateral_account
+    )]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault receiving the missing collateral (created if nothing was deposited)
+    #[account(address = escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the escrow
+    #[account(
+        mut,
+        constraint = initializer_collateral_account.owner == escrow_account.initializer_key @ ErrorCode::NotEscrowWriter,
+        constraint = initializer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Defaulting writer's token account receiving the buyout
+    #[account(mut, seeds = [USER_STATS_SEED, escrow_account.initializer_key.as_ref()], bump = writer_stats.bump)]
+    pub writer_stats: Account<'info, UserStats>,          // Defaulting writer's stats
+    #[account(mut, seeds = [USER_POSITION_SEED, escrow_account.initializer_key.as_ref()], bump = writer_position.bump)]
+    pub writer_position: Account<'info, UserPosition>,    // Defaulting writer's open escrows
+    #[account(mut, seeds = [BACKSTOP_POOL_SEED, escrow_account.collateral_mint.as_ref()], bump = backstop_pool.bump)]
+    pub backstop_pool: Account<'info, BackstopPool>,      // Market's backstop pool
+    /// CHECK: System-owned PDA holding the escrows taken over
+    #[account(address = backstop_pool.writer)]
+    pub backstop_writer: UncheckedAccount<'info>,         // Writer PDA of the pool
+    #[account(mut, address = backstop_pool.token_account)]
+    pub backstop_token_account: Account<'info, TokenAccount>, // Pool's token account
+    #[account(mut, seeds = [USER_STATS_SEED, backstop_pool.writer.as_ref()], bump = backstop_stats.bump)]
+    pub backstop_stats: Account<'info, UserStats>,        // Pool writer's stats
+    #[account(mut, seeds = [USER_POSITION_SEED, backstop_pool.writer.as_ref()], bump = backstop_position.bump)]
+    pub backstop_position: Account<'info, UserPosition>,  // Pool writer's open escrows
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow
+    #[account(mut, seeds = [MARKET_STATS_SEED, escrow_account.collateral_mint.as_ref()], bump = market_stats.bump)]
+    pub market_stats: Account<'info, MarketStats>,        // Stats of the market
+    #[account(mut, seeds = [EMISSIONS_SEED, market.key().as_ref()], bump = emissions_schedule.bump)]
+    pub emissions_schedule: Option<Account<'info, EmissionsSchedule>>, // Market's emissions schedule (if the escrow is enrolled)
+    #[account(
+        mut,
+        seeds = [WRITER_EMISSIONS_SEED, market.key().as_ref(), escrow_account.initializer_key.as_ref()],
+        bump = writer_emissions.bump
+    )]
+    pub writer_emissions: Option<Account<'info, WriterEmissions>>, // Defaulting writer's emissions position (if the escrow is enrolled)
+    #[account(mut)]
+    pub caller: Signer<'info>,                            // Caller of the takeover (pays for the vault if needed)
+    pub system_program: Program<'info, System>,           // System program for creating the vault
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for opening a protocol-owned vault's issuance auction.
+///
+/// This struct defines the context for the `start_issuance_auction` instruction, which can
+/// only be called by the vault's operator. The escrows on sale are passed as remaining accounts.
+pub struct StartIssuanceAuction<'info> {
+    #[account(
+        init,
+        payer = operator,
+        space = 8 + IssuanceAuction::INIT_SPACE,
+        seeds = [ISSUANCE_AUCTION_SEED, pol_vault.key().as_ref(), &pol_vault.epoch.to_le_bytes()],
+        bump
+    )]
+    pub auction: Account<'info, IssuanceAuction>,         // Auction to open
+    #[account(
+        init,
+        payer = operator,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = auction
+    )]
+    pub auction_token_account: Account<'info, TokenAccount>, // Token account holding the bidders' deposits
+    #[account(mut, seeds = [POL_VAULT_SEED, pol_vault.collateral_mint.as_ref()], bump = pol_vault.bump, has_one = operator)]
+    pub pol_vault: Account<'info, PolVault>,              // Vault selling its escrows
+    #[account(address = pol_vault.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint bids are paid in
+    #[account(seeds = [MARKET_SEED, pol_vault.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrows (for the premium bounds)
+    /// CHECK: Pyth price feed, checked against the market and parsed by `load_oracle_price`
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Pyth price feed for the collateral mint (required with premium bounds)
+    #[account(seeds = [VOL_CONFIG_SEED, pol_vault.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Option<Account<'info, VolConfig>>,    // Market's volatility curve (required with premium bounds)
+    #[account(mut)]
+    pub operator: Signer<'info>,                          // Vault operator paying for the auction
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the token account
+    pub associated_token_program: Program<'info, AssociatedToken>, // Associated token program for the token account
+}
+
+#[derive(Accounts)]
+/// Context for committing a sealed bid.
+///
+/// This struct defines the context for the `commit_bid` instruction.
+pub struct CommitBid<'info> {
+    #[account(
+        init,
+        payer = bidder,
+        space = 8 + AuctionBid::INIT_SPACE,
+        seeds = [AUCTION_BID_SEED, auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, AuctionBid>,                  // Bid to create
+    pub auction: Account<'info, IssuanceAuction>,         // Auction bid in
+    #[account(mut, associated_token::mint = bidder_collateral_account.mint, associated_token::authority = auction)]
+    pub auction_token_account: Account<'info, TokenAccount>, // Auction's token account receiving the deposit
+    #[account(mut)]
+    pub bidder: Signer<'info>,                            // Bidder paying the deposit
+    #[account(mut)]
+    pub bidder_collateral_account: Account<'info, TokenAccount>, // Bidder's collateral token account
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for revealing a sealed bid.
+///
+/// This struct defines the context for the `reveal_bid` instruction.
+pub struct RevealBid<'info> {
+    #[account(mut, has_one = auction)]
+    pub bid: Account<'info, AuctionBid>,                  // Bid to reveal
+    #[account(mut, has_one = pol_vault)]
+    pub auction: Account<'info, IssuanceAuction>,         // Auction bid in
+    pub pol_vault: Account<'info, PolVault>,              // Vault selling the escrows
+    #[account(seeds = [MARKET_SEED, pol_vault.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrows (for the premium bounds)
+}
+
+#[derive(Accounts)]
+/// Context for filling or refunding a bid after its auction.
+///
+/// This struct defines the context for the permissionless `settle_auction` instruction. The
+/// escrows the bid won are passed as remaining accounts.
+pub struct SettleAuction<'info> {
+    #[account(mut, has_one = auction, close = bidder)]
+    pub bid: Account<'info, AuctionBid>,                  // Bid to settle
+    #[account(
+        mut,
+        seeds = [ISSUANCE_AUCTION_SEED, auction.pol_vault.as_ref(), &auction.epoch.to_le_bytes()],
+        bump = auction.bump
+    )]
+    pub auction: Account<'info, IssuanceAuction>,         // Auction bid in
+    #[account(mut, associated_token::mint = pol_vault.collateral_mint, associated_token::authority = auction)]
+    pub auction_token_account: Account<'info, TokenAccount>, // Auction's token account holding the deposit
+    #[account(mut, address = auction.pol_vault)]
+    pub pol_vault: Account<'info, PolVault>,              // Vault selling the escrows
+    #[account(mut, address = pol_vault.token_account)]
+    pub pol_token_account: Account<'info, TokenAccount>,  // Vault's token account receiving the proceeds
+    #[account(seeds = [MARKET_SEED, pol_vault.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrows (for the premium bounds)
+    /// CHECK: Receives the bid's rent, checked against the bid
+    #[account(mut, address = bid.bidder)]
+    pub bidder: UncheckedAccount<'info>,                  // Bidder of the bid
+    #[account(
+        mut,
+        constraint = bidder_collateral_account.owner == bid.bidder,
+        constraint = bidder_collateral_account.mint == pol_vault.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub bidder_collateral_account: Account<'info, TokenAccount>, // Bidder's token account receiving the refund
+    #[account(mut, seeds = [USER_STATS_SEED, bid.bidder.as_ref()], bump = bidder_stats.bump)]
+    pub bidder_stats: Option<Account<'info, UserStats>>,  // Bidder's points and stats (to earn premium points)
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for queueing, cancelling, or claiming a backstop withdrawal.
+///
+/// This struct defines the context for the `request_backstop_withdrawal`,
+/// `cancel_backstop_withdrawal`, and `claim_backstop_withdrawal` instructions.
+pub struct BackstopWithdrawal<'info> {
+    #[account(mut, seeds = [BACKSTOP_POOL_SEED, backstop_pool.collateral_mint.as_ref()], bump = backstop_pool.bump)]
+    pub backstop_pool: Account<'info, BackstopPool>,      // Pool withdrawn from
+    #[account(
+        mut,
+        seeds = [BACKSTOP_LP_SEED, backstop_pool.key().as_ref(), owner.key().as_ref()],
+        bump = backstop_lp.bump
+    )]
+    pub backstop_lp: Account<'info, BackstopLp>,          // LP's position
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + WithdrawalRequest::INIT_SPACE,
+        seeds = [WITHDRAWAL_REQUEST_SEED, backstop_pool.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub request: Account<'info, WithdrawalRequest>,       // LP's withdrawal request
+    #[account(mut)]
+    pub owner: Signer<'info>,                             // LP owning the position
+    #[account(
+        mut,
+        constraint = owner_collateral_account.mint == backstop_pool.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub owner_collateral_account: Account<'info, TokenAccount>, // LP's token account receiving the withdrawal
+    /// CHECK: System-owned PDA holding the escrows taken over
+    #[account(address = backstop_pool.writer)]
+    pub backstop_writer: UncheckedAccount<'info>,         // Writer PDA owning the pool's capital
+    #[account(mut, address = backstop_pool.token_account)]
+    pub backstop_token_account: Account<'info, TokenAccount>, // Pool's token account
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for processing a backstop pool's queued withdrawals.
+///
+/// This struct defines the context for the permissionless `process_backstop_withdrawals`
+/// instruction. The queued requests are passed as remaining accounts.
+pub struct ProcessBackstopWithdrawals<'info> {
+    #[account(mut, seeds = [BACKSTOP_POOL_SEED, backstop_pool.collateral_mint.as_ref()], bump = backstop_pool.bump)]
+    pub backstop_pool: Account<'info, BackstopPool>,      // Pool to process
+    #[account(address = backstop_pool.token_account)]
+    pub backstop_token_account: Account<'info, TokenAccount>, // Pool's token account
+    #[account(seeds = [USER_STATS_SEED, backstop_pool.writer.as_ref()], bump = backstop_stats.bump)]
+    pub backstop_stats: Account<'info, UserStats>,        // Writer's stats, holding the collateral of its escrows
+}
+
+#[derive(Accounts)]
+/// Context for creating a vault's strategy configuration.
+///
+/// This struct defines the context for the `initialize_strategy_config` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeStrategyConfig<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + StrategyConfig::INIT_SPACE,
+        seeds = [STRATEGY_CONFIG_SEED, pol_vault.key().as_ref()],
+        bump
+    )]
+    pub strategy_config: Account<'info, StrategyConfig>,  // Strategy configuration to create
+    pub pol_vault: Account<'info, PolVault>,              // Vault following the strategy
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the configuration
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for changing a vault's strategy parameters.
+///
+/// This struct defines the context for the `set_strategy_params` instruction.
+pub struct UpdateStrategyConfig<'info> {
+    #[account(mut)]
+    pub strategy_config: Account<'info, StrategyConfig>,  // Strategy configuration to update
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for writing a vault's escrow for the next expiry of its strategy.
+///
+/// This struct defines the context for the `process_epoch` instruction, which can only be
+/// called by the vault's operator.
+pub struct ProcessEpoch<'info> {
+    pub write: PolWrite<'info>,                           // Vault, writer, and market accounts of the write
+    #[account(
+        mut,
+        seeds = [STRATEGY_CONFIG_SEED, write.pol_vault.key().as_ref()],
+        bump = strategy_config.bump
+    )]
+    pub strategy_config: Account<'info, StrategyConfig>,  // Vault's strategy
+    #[account(address = write.pol_vault.token_account)]
+    pub pol_token_account: Account<'info, TokenAccount>,  // Vault's token account holding its free funds
+    #[account(seeds = [VOL_CONFIG_SEED, write.market.collateral_mint.as_ref()], bump = vol_config.bump)]
+    pub vol_config: Option<Account<'info, VolConfig>>,    // Market's volatility surface (for target-delta strikes)
+}
+
+#[derive(Accounts)]
+/// Context for proposing a new vault strategy.
+///
+/// This struct defines the context for the `propose_strategy` instruction.
+pub struct ProposeStrategy<'info> {
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + StrategyProposal::INIT_SPACE,
+        seeds = [STRATEGY_PROPOSAL_SEED, strategy_config.key().as_ref(), &strategy_config.proposal_count.to_le_bytes()],
+        bump
+    )]
+    pub proposal: Account<'info, StrategyProposal>,       // Proposal to create
+    #[account(
+        init,
+        payer = proposer,
+        associated_token::mint = share_mint,
+        associated_token::authority = proposal
+    )]
+    pub vote_share_account: Account<'info, TokenAccount>, // Token account holding the shares locked with votes
+    #[account(mut)]
+    pub strategy_config: Account<'info, StrategyConfig>,  // Strategy the proposal changes
+    #[account(address = strategy_config.voting.share_mint @ ErrorCode::InvalidStrategyParams)]
+    pub share_mint: Account<'info, Mint>,                 // Mint of the vault's shares
+    #[account(constraint = proposer_share_account.owner == proposer.key(), token::mint = share_mint)]
+    pub proposer_share_account: Account<'info, TokenAccount>, // Proposer's share token account
+    #[account(mut)]
+    pub proposer: Signer<'info>,                          // Share holder paying for the proposal
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the token account
+    pub associated_token_program: Program<'info, AssociatedToken>, // Associated token program for the token account
+}
+
+#[derive(Accounts)]
+/// Context for voting on a strategy proposal.
+///
+/// This struct defines the context for the `vote_strategy` instruction. The vote record can
+/// only be created once per voter and proposal.
+pub struct VoteStrategy<'info> {
+    #[account(mut)]
+    pub proposal: Account<'info, StrategyProposal>,       // Proposal voted on
+    #[account(
+        init,
+        payer = voter,
+        space = 8 + StrategyVote::INIT_SPACE,
+        seeds = [STRATEGY_VOTE_SEED, proposal.key().as_ref(), voter.key().as_ref()],
+        bump
+    )]
+    pub vote: Account<'info, StrategyVote>,               // Voter's record on the proposal
+    #[account(mut, associated_token::mint = voter_share_account.mint, associated_token::authority = proposal)]
+    pub vote_share_account: Account<'info, TokenAccount>, // Proposal's token account locking the shares
+    #[account(mut)]
+    pub voter_share_account: Account<'info, TokenAccount>, // Voter's share token account
+    #[account(mut)]
+    pub voter: Signer<'info>,                             // Share holder voting
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for taking back the shares locked with a strategy vote.
+///
+/// This struct defines the context for the `withdraw_strategy_vote` instruction.
+pub struct WithdrawStrategyVote<'info> {
+    #[account(mut, has_one = proposal, has_one = voter, close = voter)]
+    pub vote: Account<'info, StrategyVote>,               // Vote to close
+    #[account(
+        seeds = [STRATEGY_PROPOSAL_SEED, proposal.strategy_config.as_ref(), &proposal.id.to_le_bytes()],
+        bump = proposal.bump
+    )]
+    pub proposal: Account<'info, StrategyProposal>,       // Proposal voted on
+    #[account(mut, associated_token::mint = voter_share_account.mint, associated_token::authority = proposal)]
+    pub vote_share_account: Account<'info, TokenAccount>, // Proposal's token account locking the shares
+    #[account(mut, constraint = voter_share_account.owner == voter.key())]
+    pub voter_share_account: Account<'info, TokenAccount>, // Voter's share token account
+    #[account(mut)]
+    pub voter: Signer<'info>,                             // Share holder who voted
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for executing a strategy proposal.
+///
+/// This struct defines the context for the `execute_strategy_proposal` instruction.
+pub struct ExecuteStrategyProposal<'info> {
+    #[account(mut, has_one = strategy_config)]
+    pub proposal: Account<'info, StrategyProposal>,       // Proposal to execute
+    #[account(mut)]
+    pub strategy_config: Account<'info, StrategyConfig>,  // Strategy to update
+}
+
+#[derive(Accounts)]
+/// Context for offering an option on loan.
+///
+/// This struct defines the context for the `offer_option_loan` instruction.
+pub struct OfferOptionLoan<'info> {
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose option is lent
+    #[account(
+        init,
+        payer = lender,
+        space = 8 + OptionLoan::INIT_SPACE,
+        seeds = [OPTION_LOAN_SEED, escrow_account.key().as_ref()],
+        bump
+    )]
+    pub option_loan: Account<'info, OptionLoan>,          // Loan to create
+    #[account(mut)]
+    pub lender: Signer<'info>,                            // Holder lending the option
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for borrowing an option.
+///
+/// This struct defines the context for the `take_option_loan` instruction.
+pub struct TakeOptionLoan<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose option is borrowed
+    #[account(
+        mut,
+        seeds = [OPTION_LOAN_SEED, escrow_account.key().as_ref()],
+        bump = option_loan.bump,
+        constraint = option_loan.borrower == Pubkey::default() @ ErrorCode::OptionOnLoan
+    )]
+    pub option_loan: Account<'info, OptionLoan>,          // Offered loan
+    #[account(mut)]
+    pub borrower: Signer<'info>,                          // Borrower paying the fee
+    #[account(mut, constraint = borrower_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub borrower_collateral_account: Account<'info, TokenAccount>, // Borrower's collateral mint account paying the fee
+    #[account(
+        mut,
+        associated_token::mint = escrow_account.collateral_mint,
+        associated_token::authority = option_loan.lender
+    )]
+    pub lender_collateral_account: Account<'info, TokenAccount>, // Lender's associated token account receiving the fee
+    #[account(seeds = [MARKET_SEED, escrow_account.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market of the escrow (for the whitelist and exercise cutoff)
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), borrower.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Borrower's whitelist entry (required in permissioned markets)
+    pub gate_token_account: Option<Account<'info, TokenAccount>>, // Borrower's gate mint account (required in token-gated markets)
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for ending an option loan.
+///
+/// This struct defines the context for the `end_option_loan` instruction. The loan's rent
+/// returns to the lender.
+pub struct EndOptionLoan<'info> {
+    #[account(mut, address = option_loan.escrow)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose option returns to the lender
+    #[account(
+        mut,
+        seeds = [OPTION_LOAN_SEED, escrow_account.key().as_ref()],
+        bump = option_loan.bump,
+        has_one = lender,
+        close = lender
+    )]
+    pub option_loan: Account<'info, OptionLoan>,          // Loan to end
+    /// CHECK: Validated against the loan
+    #[account(mut)]
+    pub lender: UncheckedAccount<'info>,                  // Lender receiving the option and the loan's rent
+    pub caller: Signer<'info>,                            // Lender, or anyone once the term is over
+}
+
+#[derive(Accounts)]
+#[instruction(template_id: u64)]
+/// Context for creating an escrow template.
+///
+/// This struct defines the context for the `create_template` instruction.
+pub struct CreateTemplate<'info> {
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + EscrowTemplate::INIT_SPACE,
+        seeds = [ESCROW_TEMPLATE_SEED, creator.key().as_ref(), &template_id.to_le_bytes()],
+        bump
+    )]
+    pub template: Account<'info, EscrowTemplate>,         // Template to create
+    #[account(mut)]
+    pub creator: Signer<'info>,                           // Creator paying for the template
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for setting a template's fee override.
+///
+/// This struct defines the context for the `set_template_fee_override` instruction, which can
+/// only be called by the governance authority or the fee admin.
+pub struct SetTemplateFeeOverride<'info> {
+    #[account(mut)]
+    pub template: Account<'info, EscrowTemplate>,         // Template to update
+    #[account(
+        constraint = approver.key() == governance.governance_authority
+            || approver.key() == governance.fee_admin @ ErrorCode::NotFeeAdmin
+    )]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub approver: Signer<'info>,                          // Governance authority or fee admin
+}
+
+#[derive(Accounts)]
+/// Context for closing an escrow template.
+///
+/// This struct defines the context for the `close_template` instruction, which can only be
+/// called by the template's creator.
+pub struct CloseTemplate<'info> {
+    #[account(mut, has_one = creator, close = creator)]
+    pub template: Account<'info, EscrowTemplate>,         // Template to close
+    #[account(mut)]
+    pub creator: Signer<'info>,                           // Creator receiving the rent
+}
+
+#[derive(Accounts)]
+/// Context for opening an escrow from a template.
+///
+/// This struct defines the context for the `clone_from_template` instruction. The accounts of
+/// `initialize_escrow` follow as remaining accounts.
+pub struct CloneFromTemplate<'info> {
+    #[account(seeds = [ESCROW_TEMPLATE_SEED, template.creator.as_ref(), &template.template_id.to_le_bytes()], bump = template.bump)]
+    pub template: Account<'info, EscrowTemplate>,         // Template to clone
+    pub writer: Signer<'info>,                            // Writer of the new escrow
+    pub program: Program<'info, crate::program::OptionsEscrow>, // This program, called to open the escrow
+}
+
+#[derive(Accounts)]
+/// Context for creating a market's grid of standard expiries.
+///
+/// This struct defines the context for the `initialize_expiry_grid` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeExpiryGrid<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + ExpiryGrid::INIT_SPACE,
+        seeds = [EXPIRY_GRID_SEED, market.collateral_mint.as_ref()],
+        bump
+    )]
+    pub expiry_grid: Account<'info, ExpiryGrid>,          // Expiry grid to create
+    #[account(mut, seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market whose expiries are standardized
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the grid
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for updating a market's grid of standard expiries.
+///
+/// This struct defines the context for the `update_expiry_grid` instruction.
+pub struct UpdateExpiryGrid<'info> {
+    #[account(mut, has_one = market)]
+    pub expiry_grid: Account<'info, ExpiryGrid>,          // Expiry grid to update
+    #[account(mut)]
+    pub market: Account<'info, Market>,                   // Market whose expiries are standardized
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    pub governance_authority: Signer<'info>,              // Governance authority
+}
+
+#[derive(Accounts)]
+/// Context for reading a market's next standard expiry.
+///
+/// This struct defines the context for the `get_next_standard_expiry` view.
+pub struct GetNextStandardExpiry<'info> {
+    pub expiry_grid: Account<'info, ExpiryGrid>,          // Market's grid of standard expiries
+}
+
+#[derive(Accounts)]
+#[instruction(option_type: OptionType, strike_price: u64, expiration: i64)]
+/// Context for listing an option series.
+///
+/// This struct defines the context for the `create_option_series` instruction.
+pub struct CreateOptionSeries<'info> {
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + OptionSeries::INIT_SPACE,
+        seeds = [
+            OPTION_SERIES_SEED,
+            market.collateral_mint.as_ref(),
+            &strike_price.to_le_bytes(),
+            &expiration.to_le_bytes(),
+            &[OptionSeries::option_type_seed(&option_type)],
+        ],
+        bump
+    )]
+    pub series: Account<'info, OptionSeries>,             // Series to create
+    #[account(
+        init,
+        payer = creator,
+        seeds = [SERIES_OPTION_MINT_SEED, series.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = series
+    )]
+    pub option_mint: Account<'info, Mint>,                // Mint of the series' option tokens
+    #[account(
+        init,
+        payer = creator,
+        seeds = [SERIES_WRITER_MINT_SEED, series.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = series
+    )]
+    pub writer_mint: Account<'info, Mint>,                // Mint of the series' writer tokens
+    #[account(
+        init,
+        payer = creator,
+        seeds = [SERIES_VAULT_SEED, series.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = series
+    )]
+    pub vault: Account<'info, TokenAccount>,              // Token account holding the series' collateral
+    #[account(address = market.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the market
+    #[account(seeds = [MARKET_SEED, market.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the series is listed in
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (if strikes are banded)
+    #[account(seeds = [EXPIRY_GRID_SEED, market.collateral_mint.as_ref()], bump = expiry_grid.bump)]
+    pub expiry_grid: Option<Account<'info, ExpiryGrid>>, // Market's grid of standard expiries (required if the market lists on one)
+    #[account(mut)]
+    pub creator: Signer<'info>,                           // Account paying for the series
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the mint and vault
+    pub rent: Sysvar<'info, Rent>,                        // Rent sysvar for the mint
+}
+
+#[derive(Accounts)]
+/// Context for writing options of a series.
+///
+/// This struct defines the context for the `write_series_options` instruction.
+pub struct WriteSeriesOptions<'info> {
+    #[account(mut)]
+    pub series: Account<'info, OptionSeries>,             // Series written
+    #[account(
+        init_if_needed,
+        payer = writer,
+        space = 8 + SeriesWriter::INIT_SPACE,
+        seeds = [SERIES_WRITER_SEED, series.key().as_ref(), writer.key().as_ref()],
+        bump
+    )]
+    pub series_writer: Account<'info, SeriesWriter>,      // Writer's record in the series
+    #[account(mut, address = series.option_mint)]
+    pub option_mint: Account<'info, Mint>,                // Mint of the series' option tokens
+    #[account(mut, address = series.writer_mint)]
+    pub writer_mint: Account<'info, Mint>,                // Mint of the series' writer tokens
+    #[account(mut, address = series.vault)]
+    pub vault: Account<'info, TokenAccount>,              // Series' collateral vault
+    #[account(mut, token::mint = option_mint)]
+    pub writer_option_account: Account<'info, TokenAccount>, // Token account receiving the option tokens
+    #[account(mut, token::mint = writer_mint)]
+    pub writer_token_account: Account<'info, TokenAccount>, // Token account receiving the writer tokens
+    #[account(mut, constraint = writer_collateral_account.mint == series.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub writer_collateral_account: Account<'info, TokenAccount>, // Writer's token account paying the collateral
+    #[account(mut, seeds = [MARKET_SEED, series.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the series is listed in
+    #[account(seeds = [WHITELIST_SEED, market.key().as_ref(), writer.key().as_ref()], bump = whitelist_entry.bump)]
+    pub whitelist_entry: Option<Account<'info, WhitelistEntry>>, // Writer's whitelist entry (required in permissioned markets)
+    #[account(mut)]
+    pub writer: Signer<'info>,                            // Writer locking the collateral
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for settling an option series.
+///
+/// This struct defines the context for the `settle_series` instruction.
+pub struct SettleSeries<'info> {
+    #[account(mut)]
+    pub series: Account<'info, OptionSeries>,             // Series to settle
+    #[account(mut, address = series.vault)]
+    pub vault: Account<'info, TokenAccount>,              // Series' collateral vault
+    #[account(mut, seeds = [MARKET_SEED, series.collateral_mint.as_ref()], bump = market.bump)]
+    pub market: Account<'info, Market>,                   // Market the series is listed in
+    /// CHECK: Pyth price feed, validated against the market and parsed on use
+    #[account(address = market.collateral_oracle)]
+    pub collateral_oracle: Option<UncheckedAccount<'info>>, // Collateral price feed (unless the market settles on a TWAP)
+    #[account(seeds = [PRICE_HISTORY_SEED, series.collateral_mint.as_ref()], bump = price_history.load()?.bump)]
+    pub price_history: Option<AccountLoader<'info, PriceHistory>>, // Market's price history (if it settles on a TWAP)
+    #[account(seeds = [ORACLE_REGISTRY_SEED, series.collateral_mint.as_ref()], bump = oracle_registry.bump)]
+    pub oracle_registry: Option<Account<'info, OracleRegistry>>, // Market's oracle registry (if it aggregates feeds)
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub secondary_oracle: Option<UncheckedAccount<'info>>, // First extra feed of the oracle registry
+    /// CHECK: Pyth price feed, checked against the oracle registry and parsed on use
+    pub tertiary_oracle: Option<UncheckedAccount<'info>>,  // Second extra feed of the oracle registry
+    #[account(mut, seeds = [FEE_VAULT_SEED, market.fee_mint.as_ref()], bump = fee_vault.bump)]
+    pub fee_vault: Account<'info, FeeVault>,              // Fee vault for the market's fee mint
+    #[account(mut, address = fee_vault.token_account)]
+    pub fee_vault_token_account: Account<'info, TokenAccount>, // Token account receiving the fee
+    pub governance: Account<'info, Governance>,           // Governance account storing fee rates and fee collectors
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+impl<'info> SettleSeries<'info> {
+    /// Returns the market's oracle-based settlement price for the series.
+    fn settlement_price(&self) -> Result<u64> {
+        oracle_settlement_price(
+            &self.market,
+            self.series.expiration,
+            self.collateral_oracle.as_ref(),
+            self.price_history.as_ref(),
+            self.oracle_registry.as_ref(),
+            [self.secondary_oracle.as_ref(), self.tertiary_oracle.as_ref()],
+        )
+    }
+}
+
+#[derive(Accounts)]
+/// Context for claiming the payout of option tokens.
+///
+/// This struct defines the context for the `claim_payout` instruction.
+pub struct ClaimPayout<'info> {
+    pub series: Account<'info, OptionSeries>,             // Settled series
+    #[account(mut, address = series.option_mint)]
+    pub option_mint: Account<'info, Mint>,                // Mint of the series' option tokens
+    #[account(mut, address = series.vault)]
+    pub vault: Account<'info, TokenAccount>,              // Series' collateral vault
+    #[account(mut, token::mint = option_mint, token::authority = holder)]
+    pub holder_option_account: Account<'info, TokenAccount>, // Holder's option tokens to burn
+    #[account(mut, constraint = holder_collateral_account.mint == series.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub holder_collateral_account: Account<'info, TokenAccount>, // Token account receiving the payout
+    pub holder: Signer<'info>,                            // Holder of the option tokens
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for claiming collateral back from a settled series with writer tokens.
+///
+/// This struct defines the context for the `claim_series_collateral` instruction.
+pub struct ClaimSeriesCollateral<'info> {
+    pub series: Account<'info, OptionSeries>,             // Settled series
+    #[account(
+        mut,
+        seeds = [SERIES_WRITER_SEED, series.key().as_ref(), owner.key().as_ref()],
+        bump = series_writer.bump
+    )]
+    pub series_writer: Option<Account<'info, SeriesWriter>>, // Owner's record as a writer of the series (optional)
+    #[account(mut, address = series.writer_mint)]
+    pub writer_mint: Account<'info, Mint>,                // Mint of the series' writer tokens
+    #[account(mut, token::mint = writer_mint, token::authority = owner)]
+    pub writer_token_account: Account<'info, TokenAccount>, // Owner's writer tokens to burn
+    #[account(mut, address = series.vault)]
+    pub vault: Account<'info, TokenAccount>,              // Series' collateral vault
+    #[account(mut, constraint = collateral_account.mint == series.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_account: Account<'info, TokenAccount>, // Token account receiving the collateral
+    pub owner: Signer<'info>,                             // Owner of the writer tokens
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for claiming the writer's settlement proceeds.
+///
+/// This struct defines the context for the `claim_writer_settlement` instruction, which can
+/// only be called by the escrow's writer.
+pub struct ClaimWriterSettlement<'info> {
+    #[account(mut, constraint = escrow_account.initializer_key == initializer.key() @ ErrorCode::NotEscrowWriter)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Settled escrow holding the proceeds
+    pub initializer: Signer<'info>,                       // Writer of the escrow
+    #[account(
+        mut,
+        constraint = initializer_collateral_account.owner == initializer.key(),
+        constraint = initializer_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint
+    )]
+    pub initializer_collateral_account: Account<'info, TokenAccount>, // Writer's token account receiving the proceeds
+    #[account(mut, seeds = [ESCROW_VAULT_SEED, escrow_account.key().as_ref()], bump = escrow_account.vault_bump)]
+    pub escrow_collateral_account: Account<'info, TokenAccount>, // Escrow's vault holding collateral
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for exercising option tokens of a series before expiry.
+///
+/// This struct defines the context for the `exercise_series_options` instruction, pricing the
+/// series and collecting the fee through the accounts of `settle_series`.
+pub struct ExerciseSeriesOptions<'info> {
+    pub settle: SettleSeries<'info>,                      // Series, its vault, and the pricing and fee accounts
+    #[account(mut, address = settle.series.option_mint)]
+    pub option_mint: Account<'info, Mint>,                // Mint of the series' option tokens
+    #[account(mut, token::mint = option_mint, token::authority = holder)]
+    pub holder_option_account: Account<'info, TokenAccount>, // Holder's option tokens to burn
+    #[account(mut, constraint = holder_collateral_account.mint == settle.series.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub holder_collateral_account: Account<'info, TokenAccount>, // Token account receiving the collateral
+    pub holder: Signer<'info>,                            // Holder exercising the options
+}
+
+#[derive(Accounts)]
+/// Context for updating a writer's assignment record.
+///
+/// This struct defines the context for the `sync_series_writer` instruction.
+pub struct SyncSeriesWriter<'info> {
+    pub series: Account<'info, OptionSeries>,             // Series written
+    #[account(mut, has_one = series)]
+    pub series_writer: Account<'info, SeriesWriter>,      // Writer's position to update
+}
+
+#[derive(Accounts)]
+/// Context for splitting an escrow's option into fraction shares.
+///
+/// This struct defines the context for the `fractionalize_escrow` instruction. The proceeds
+/// account is the fraction's associated token account, where `claim_settlement` pays the holder.
+pub struct FractionalizeEscrow<'info> {
+    #[account(mut)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Escrow whose option is split
+    #[account(
+        init,
+        payer = holder,
+        space = 8 + EscrowFraction::INIT_SPACE,
+        seeds = [ESCROW_FRACTION_SEED, escrow_account.key().as_ref()],
+        bump
+    )]
+    pub escrow_fraction: Account<'info, EscrowFraction>,  // Fraction account becoming the holder
+    #[account(
+        init,
+        payer = holder,
+        seeds = [FRACTION_SHARE_MINT_SEED, escrow_fraction.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = escrow_fraction
+    )]
+    pub share_mint: Account<'info, Mint>,                 // Mint of the fraction shares
+    #[account(
+        init,
+        payer = holder,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = escrow_fraction
+    )]
+    pub proceeds_account: Account<'info, TokenAccount>,   // Fraction's account for the settlement proceeds
+    #[account(
+        init,
+        payer = holder,
+        associated_token::mint = share_mint,
+        associated_token::authority = holder
+    )]
+    pub holder_share_account: Account<'info, TokenAccount>, // Holder's account receiving the shares
+    #[account(address = escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub collateral_mint: Account<'info, Mint>,            // Collateral mint of the escrow
+    #[account(mut)]
+    pub holder: Signer<'info>,                            // Holder splitting the option
+    pub system_program: Program<'info, System>,           // System program for account creation
+    pub token_program: Program<'info, Token>,             // Token program for the mint and token accounts
+    pub associated_token_program: Program<'info, AssociatedToken>, // Associated token program for the token accounts
+    pub rent: Sysvar<'info, Rent>,                        // Rent sysvar for the mint
+}
+
+#[derive(Accounts)]
+/// Context for claiming the proceeds of fraction shares.
+///
+/// This struct defines the context for the `claim_fraction_proceeds` instruction.
+pub struct ClaimFractionProceeds<'info> {
+    #[account(address = escrow_fraction.escrow)]
+    pub escrow_account: Account<'info, EscrowAccount>,    // Settled escrow
+    #[account(seeds = [ESCROW_FRACTION_SEED, escrow_account.key().as_ref()], bump = escrow_fraction.bump)]
+    pub escrow_fraction: Account<'info, EscrowFraction>,  // Fraction account holding the proceeds
+    #[account(mut, address = escrow_fraction.share_mint)]
+    pub share_mint: Account<'info, Mint>,                 // Mint of the fraction shares
+    #[account(mut, token::mint = share_mint, token::authority = owner)]
+    pub owner_share_account: Account<'info, TokenAccount>, // Owner's shares to burn
+    #[account(mut, address = escrow_fraction.proceeds_account)]
+    pub proceeds_account: Account<'info, TokenAccount>,   // Fraction's account holding the proceeds
+    #[account(mut, constraint = recipient_collateral_account.mint == escrow_account.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub recipient_collateral_account: Account<'info, TokenAccount>, // Token account receiving the proceeds
+    pub owner: Signer<'info>,                             // Owner of the shares
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[derive(Accounts)]
+/// Context for creating the holder registry of a series.
+///
+/// This struct defines the context for the `initialize_holder_registry` instruction, which can
+/// only be called by the governance authority.
+pub struct InitializeHolderRegistry<'info> {
+    #[account(
+        init,
+        payer = governance_authority,
+        space = 8 + std::mem::size_of::<HolderRegistry>(),
+        seeds = [HOLDER_REGISTRY_SEED, series.key().as_ref()],
+        bump
+    )]
+    pub holder_registry: AccountLoader<'info, HolderRegistry>, // Registry to create
+    pub series: Account<'info, OptionSeries>,             // Series whose holders are registered
+    #[account(has_one = governance_authority)]
+    pub governance: Account<'info, Governance>,           // Governance account
+    #[account(mut)]
+    pub governance_authority: Signer<'info>,              // Governance authority paying for the registry
+    pub system_program: Program<'info, System>,           // System program for account creation
+}
+
+#[derive(Accounts)]
+/// Context for adding options to a holder registry.
+///
+/// This struct defines the context for the `add_registry_holder` instruction, which can only
+/// be called by the registrar.
+pub struct AddRegistryHolder<'info> {
+    pub series: Account<'info, OptionSeries>,             // Series whose holders are registered
+    #[account(
+        mut,
+        seeds = [HOLDER_REGISTRY_SEED, series.key().as_ref()],
+        bump = holder_registry.load()?.bump,
+        has_one = registrar
+    )]
+    pub holder_registry: AccountLoader<'info, HolderRegistry>, // Series' holder registry
+    #[account(mut, address = series.option_mint)]
+    pub option_mint: Account<'info, Mint>,                // Mint of the series' option tokens
+    #[account(mut, token::mint = option_mint, token::authority = registrar)]
+    pub registrar_option_account: Account<'info, TokenAccount>, // Registrar's option tokens to burn
+    pub registrar: Signer<'info>,                         // Registrar of the series' holders
+    pub token_program: Program<'info, Token>,             // Token program for the burn
+}
+
+#[derive(Accounts)]
+/// Context for removing options from a holder registry.
+///
+/// This struct defines the context for the `remove_registry_holder` instruction, which can
+/// only be called by the registrar.
+pub struct RemoveRegistryHolder<'info> {
+    pub series: Account<'info, OptionSeries>,             // Series whose holders are registered
+    #[account(
+        mut,
+        seeds = [HOLDER_REGISTRY_SEED, series.key().as_ref()],
+        bump = holder_registry.load()?.bump,
+        has_one = registrar
+    )]
+    pub holder_registry: AccountLoader<'info, HolderRegistry>, // Series' holder registry
+    #[account(mut, address = series.option_mint)]
+    pub option_mint: Account<'info, Mint>,                // Mint of the series' option tokens
+    #[account(mut, token::mint = option_mint)]
+    pub recipient_option_account: Account<'info, TokenAccount>, // Token account receiving the option tokens
+    pub registrar: Signer<'info>,                         // Registrar of the series' holders
+    pub token_program: Program<'info, Token>,             // Token program for the mint
+}
+
+#[derive(Accounts)]
+/// Context for transferring registered options between holders.
+///
+/// This struct defines the context for the `transfer_registry_holding` instruction.
+pub struct TransferRegistryHolding<'info> {
+    #[account(mut)]
+    pub holder_registry: AccountLoader<'info, HolderRegistry>, // Series' holder registry
+    pub holder: Signer<'info>,                            // Holder transferring its options
+}
+
+#[derive(Accounts)]
+/// Context for claiming the payout of registered options.
+///
+/// This struct defines the context for the `claim_registry_payout` instruction.
+pub struct ClaimRegistryPayout<'info> {
+    pub series: Account<'info, OptionSeries>,             // Settled series
+    #[account(mut, seeds = [HOLDER_REGISTRY_SEED, series.key().as_ref()], bump = holder_registry.load()?.bump)]
+    pub holder_registry: AccountLoader<'info, HolderRegistry>, // Series' holder registry
+    #[account(mut, address = series.vault)]
+    pub vault: Account<'info, TokenAccount>,              // Series' collateral vault
+    #[account(mut, constraint = holder_collateral_account.mint == series.collateral_mint @ ErrorCode::IncorrectCollateralMint)]
+    pub holder_collateral_account: Account<'info, TokenAccount>, // Token account receiving the payout
+    pub holder: Signer<'info>,                            // Registered holder
+    pub token_program: Program<'info, Token>,             // Token program for token transfers
+}
+
+#[error_code]
+/// Custom error codes for the program.
+///
+/// This enum defines custom error codes for common failure cases in the protocol,
 /// such as trying to exercise an option that has already been exercised or using
 /// the wrong collateral mint.
 pub enum ErrorCode {
@@ -366,4 +13129,522 @@ pub enum ErrorCode {
     IncorrectCollateralMint,
     #[msg("Cannot exercise the option early.")]
     CannotExerciseEarly,
+    #[msg("Fee collector count must be between 1 and the maximum allowed.")]
+    InvalidFeeCollectorCount,
+    #[msg("Fee collector weights must add up to 10000 basis points.")]
+    InvalidFeeCollectorWeights,
+    #[msg("The fee vault cannot track any more pending claims.")]
+    FeeVaultFull,
+    #[msg("There are no fees to claim for this collector.")]
+    NoFeesToClaim,
+    #[msg("Arithmetic overflow.")]
+    MathOverflow,
+    #[msg("Fee rate cannot exceed 10000 basis points.")]
+    FeeRateTooHigh,
+    #[msg("Fee account mint does not match the market's fee mint.")]
+    IncorrectFeeMint,
+    #[msg("A fee mint token account is required for this market.")]
+    MissingFeeAccount,
+    #[msg("An oracle account is required for this market.")]
+    MissingOracleAccount,
+    #[msg("The oracle account could not be parsed.")]
+    InvalidOracleAccount,
+    #[msg("The oracle price is too old.")]
+    StaleOraclePrice,
+    #[msg("The oracle price is not positive.")]
+    InvalidOraclePrice,
+    #[msg("The timelock cannot be negative.")]
+    InvalidTimelock,
+    #[msg("The withdrawal does not match the queued treasury withdrawal.")]
+    WithdrawalNotQueued,
+    #[msg("The treasury withdrawal timelock has not expired yet.")]
+    TimelockNotExpired,
+    #[msg("Staking pool fees can only be paid out to stakers.")]
+    StakingRewardsNotClaimable,
+    #[msg("Token account mint does not match the staked governance token.")]
+    IncorrectStakeMint,
+    #[msg("The stake position does not hold enough tokens.")]
+    InsufficientStake,
+    #[msg("Invalid buyback parameters.")]
+    InvalidBuybackParams,
+    #[msg("The buyback would exceed the rate limit for this period.")]
+    BuybackLimitExceeded,
+    #[msg("The swap returned less than the minimum acceptable amount.")]
+    SlippageExceeded,
+    #[msg("Fee discount tiers must be sorted by stake and discount at most 100%.")]
+    InvalidFeeDiscountTiers,
+    #[msg("Invalid lock parameters.")]
+    InvalidLockParams,
+    #[msg("Lock duration must be positive, within the maximum, and not shorten the current lock.")]
+    InvalidLockDuration,
+    #[msg("Invalid emissions parameters.")]
+    InvalidEmissionsParams,
+    #[msg("The escrow earns emissions, so the emissions accounts are required.")]
+    MissingEmissionsAccount,
+    #[msg("There are no emissions to claim.")]
+    NoEmissionsToClaim,
+    #[msg("Token account mint does not match the reward mint.")]
+    IncorrectRewardMint,
+    #[msg("Invalid proposal parameters.")]
+    InvalidProposalParams,
+    #[msg("On-chain proposals are disabled.")]
+    ProposalsDisabled,
+    #[msg("Not enough voting power.")]
+    InsufficientVotingPower,
+    #[msg("The voting period has ended.")]
+    VotingClosed,
+    #[msg("Staked tokens can't be withdrawn until voting on a proposal ends.")]
+    StakeLockedForVote,
+    #[msg("The proposal has already been executed.")]
+    ProposalAlreadyExecuted,
+    #[msg("The proposal can't be executed yet.")]
+    ProposalNotExecutable,
+    #[msg("The proposal did not pass.")]
+    ProposalNotPassed,
+    #[msg("This market is paused.")]
+    MarketPaused,
+    #[msg("The position limit of this market would be exceeded.")]
+    PositionLimitExceeded,
+    #[msg("Too many markets with open positions.")]
+    TooManyOpenMarkets,
+    #[msg("The open interest cap of this market would be exceeded.")]
+    OpenInterestCapExceeded,
+    #[msg("Too many open escrows.")]
+    TooManyOpenEscrows,
+    #[msg("The escrow is not listed in the user's position.")]
+    EscrowNotInPosition,
+    #[msg("Invalid risk scenarios.")]
+    InvalidRiskScenarios,
+    #[msg("The strike price is outside the allowed band around the oracle price.")]
+    StrikeOutOfBand,
+    #[msg("Invalid strike band.")]
+    InvalidStrikeBand,
+    #[msg("The oracle price deviates too far from its moving average; settlement needs a manual price.")]
+    PriceDeviationTooHigh,
+    #[msg("The oracle price was published too many slots ago.")]
+    StaleOracleSlot,
+    #[msg("The oracle price's confidence interval is too wide.")]
+    OracleConfidenceTooWide,
+    #[msg("Invalid oracle configuration.")]
+    InvalidOracleConfig,
+    #[msg("Invalid oracle registry.")]
+    InvalidOracleRegistry,
+    #[msg("The oracle feeds disagree beyond the allowed deviation.")]
+    OracleFeedsDisagree,
+    #[msg("Invalid TWAP parameters.")]
+    InvalidTwapParams,
+    #[msg("The TWAP can't be cranked yet.")]
+    CrankTooEarly,
+    #[msg("The price history doesn't cover the TWAP window.")]
+    TwapUnavailable,
+    #[msg("Invalid payoff parameters.")]
+    InvalidPayoff,
+    #[msg("No observation is due.")]
+    ObservationNotDue,
+    #[msg("No observations have been recorded.")]
+    NoObservations,
+    #[msg("The barrier has not been touched.")]
+    BarrierNotTouched,
+    #[msg("The option has been knocked out.")]
+    OptionKnockedOut,
+    #[msg("Invalid exercise windows.")]
+    InvalidExerciseWindows,
+    #[msg("The option can only be exercised during one of its exercise windows.")]
+    OutsideExerciseWindow,
+    #[msg("Invalid perpetual option terms.")]
+    InvalidPerpetualTerms,
+    #[msg("The perpetual option's funding has lapsed.")]
+    FundingLapsed,
+    #[msg("Only the option holder can do this.")]
+    NotOptionHolder,
+    #[msg("Invalid basket components.")]
+    InvalidBasket,
+    #[msg("Invalid vesting schedule.")]
+    InvalidVestingSchedule,
+    #[msg("Nothing has vested yet.")]
+    NothingVested,
+    #[msg("The vesting holder has already been revoked.")]
+    VestingAlreadyRevoked,
+    #[msg("Only the escrow's writer can do this.")]
+    NotEscrowWriter,
+    #[msg("The escrow's exercise rights can't be rented.")]
+    NotRentable,
+    #[msg("The rental deposit must cover at least one second of premium.")]
+    InvalidRentalDeposit,
+    #[msg("The rental has lapsed.")]
+    RentalLapsed,
+    #[msg("The rental has not lapsed.")]
+    RentalNotLapsed,
+    #[msg("Invalid auto-exercise threshold.")]
+    InvalidAutoExerciseThreshold,
+    #[msg("The exercise cutoff has passed.")]
+    ExerciseCutoffPassed,
+    #[msg("Invalid exercise cutoff.")]
+    InvalidExerciseCutoff,
+    #[msg("There is nothing to claim.")]
+    NothingToClaim,
+    #[msg("The claim grace period has not passed.")]
+    ClaimGracePeriodActive,
+    #[msg("The escrow is already fully funded or closed.")]
+    EscrowFunded,
+    #[msg("The funding deadline has not passed.")]
+    FundingDeadlineNotPassed,
+    #[msg("The escrow is still open or has unclaimed proceeds.")]
+    EscrowStillOpen,
+    #[msg("The balance is above the dust threshold.")]
+    BalanceAboveDustThreshold,
+    #[msg("The escrow's exercise rights are rented.")]
+    EscrowRented,
+    #[msg("A required token account is missing.")]
+    MissingTokenAccount,
+    #[msg("The escrow can't be rolled to this expiry.")]
+    InvalidRoll,
+    #[msg("The expiry is further out than the market's maximum tenor.")]
+    TenorTooLong,
+    #[msg("The new expiry must be later than the current one.")]
+    InvalidExpirationExtension,
+    #[msg("Interest has already been registered for this escrow.")]
+    InterestAlreadyRegistered,
+    #[msg("The escrow has a holder; terminate it by consent instead.")]
+    EscrowHasHolder,
+    #[msg("Only the governance authority or fee admin can do this.")]
+    NotFeeAdmin,
+    #[msg("Too many counterparties.")]
+    TooManyCounterparties,
+    #[msg("Only the escrow's named counterparties can do this.")]
+    NotAllowedCounterparty,
+    #[msg("The participant is not whitelisted in this market.")]
+    NotWhitelisted,
+    #[msg("Only the market's compliance authority can do this.")]
+    NotComplianceAuthority,
+    #[msg("The participant doesn't hold the market's gate token.")]
+    MissingGateToken,
+    #[msg("Proceeds must go to a token account owned by the holder.")]
+    InvalidProceedsAccount,
+    #[msg("The early-exercise surcharge exceeds the holder's proceeds.")]
+    SurchargeExceedsPayout,
+    #[msg("The session key is invalid, expired, or out of scope.")]
+    InvalidSession,
+    #[msg("The accounts don't match the deposit authorization.")]
+    InvalidDepositAccount,
+    #[msg("The deposit authorization doesn't allow pulls yet.")]
+    DepositNotYetAllowed,
+    #[msg("The collateral isn't wrapped SOL.")]
+    NotWrappedSol,
+    #[msg("Only the owner of the proceeds can unwrap them.")]
+    UnwrapNotAllowed,
+    #[msg("The batch accounts are invalid.")]
+    InvalidBatch,
+    #[msg("This escrow or market can't be settled in a batch.")]
+    BatchUnsupported,
+    #[msg("The account isn't an escrow or is already on the current layout.")]
+    InvalidMigration,
+    #[msg("Option pricing inputs are out of range.")]
+    InvalidPricingInput,
+    #[msg("Invalid volatility curve.")]
+    InvalidVolConfig,
+    #[msg("The volatility curve is stale.")]
+    StaleVolConfig,
+    #[msg("Only the curve's updater or the governance authority can do this.")]
+    NotVolUpdater,
+    #[msg("Premium bounds must be zero or at least 10000 basis points.")]
+    InvalidPremiumBounds,
+    #[msg("The premium deviates too far from the option's fair value.")]
+    PremiumOutOfBounds,
+    #[msg("Invalid keeper registry parameters.")]
+    InvalidKeeperParams,
+    #[msg("The keeper's stake is still unbonding.")]
+    KeeperStakeUnbonding,
+    #[msg("This market doesn't accept keeper price inputs.")]
+    KeeperInputsDisabled,
+    #[msg("The escrow's settlement price has already been set.")]
+    SettlementPriceAlreadySet,
+    #[msg("Invalid list of series settlers.")]
+    InvalidSeriesSettlers,
+    #[msg("Only the series' settlers can settle in this market.")]
+    NotSeriesSettler,
+    #[msg("This market doesn't accept price attestations.")]
+    AttestationsDisabled,
+    #[msg("The price attestation is missing or doesn't match.")]
+    InvalidAttestation,
+    #[msg("The swap spent more than the exercise proceeds.")]
+    SwapExceedsProceeds,
+    #[msg("The transaction landed after its deadline.")]
+    DeadlineExceeded,
+    #[msg("The escrow's collateral is lent out and must be recalled first.")]
+    CollateralLent,
+    #[msg("The escrow's collateral is not lent out.")]
+    CollateralNotLent,
+    #[msg("Invalid lending configuration.")]
+    InvalidLendingConfig,
+    #[msg("Lending is disabled for this market.")]
+    LendingDisabled,
+    #[msg("The lending deposit did not move collateral into the escrow's receipt account.")]
+    InvalidLendingDeposit,
+    #[msg("The lending protocol returned less than the lent collateral.")]
+    LendingLoss,
+    #[msg("The escrow is not fully funded.")]
+    EscrowNotFunded,
+    #[msg("The stake pool doesn't mint this market's collateral.")]
+    InvalidStakePool,
+    #[msg("The LST exchange rate hasn't been refreshed this epoch.")]
+    StaleExchangeRate,
+    #[msg("The market's collateral isn't marked as an LST.")]
+    LstCollateralDisabled,
+    #[msg("The lending reserve of the interest-bearing collateral is missing.")]
+    MissingReserveAccount,
+    #[msg("The lending reserve doesn't issue this market's collateral.")]
+    InvalidReserveAccount,
+    #[msg("Only the vault's owner or rebalancer can rebalance it.")]
+    NotHedgeRebalancer,
+    #[msg("The vault's net delta is already within its rebalance band.")]
+    HedgeWithinBand,
+    #[msg("The hedge order doesn't bring the net delta within the rebalance band.")]
+    InvalidHedgeOrder,
+    #[msg("The venue's hedge adapter isn't available in this build.")]
+    HedgeAdapterUnavailable,
+    #[msg("Invalid protocol-owned vault parameters.")]
+    InvalidPolVaultParams,
+    #[msg("The protocol-owned vault doesn't quote this series.")]
+    SeriesNotQuoted,
+    #[msg("The write would exceed the protocol-owned vault's maximum exposure.")]
+    PolExposureExceeded,
+    #[msg("Invalid protocol-owned vault amount.")]
+    InvalidPolVaultAmount,
+    #[msg("Invalid backstop discount.")]
+    InvalidBackstopDiscount,
+    #[msg("Invalid backstop amount.")]
+    InvalidBackstopAmount,
+    #[msg("The backstop pool doesn't have enough free capital.")]
+    InsufficientBackstopLiquidity,
+    #[msg("The market's backstop pool accounts are required.")]
+    MissingBackstopAccount,
+    #[msg("The backstop pool can take over this escrow.")]
+    BackstopAvailable,
+    #[msg("Invalid auction parameters.")]
+    InvalidAuctionParams,
+    #[msg("This escrow can't be sold in an issuance auction.")]
+    EscrowNotAuctionable,
+    #[msg("The auction isn't in the right phase for this.")]
+    AuctionPhaseClosed,
+    #[msg("The revealed bid doesn't match its commitment or isn't valid.")]
+    InvalidBidReveal,
+    #[msg("The auction hasn't ended yet.")]
+    AuctionNotEnded,
+    #[msg("Withdrawals are queued; request a withdrawal instead.")]
+    WithdrawalsQueued,
+    #[msg("Too many withdrawals are queued.")]
+    WithdrawalQueueFull,
+    #[msg("The withdrawal epoch hasn't ended yet.")]
+    EpochNotEnded,
+    #[msg("Invalid strategy parameters.")]
+    InvalidStrategyParams,
+    #[msg("The escrow of this epoch has already been written.")]
+    EpochAlreadyProcessed,
+    #[msg("The voting period hasn't ended yet.")]
+    VotingNotEnded,
+    #[msg("The option is on loan and must be returned first.")]
+    OptionOnLoan,
+    #[msg("Invalid option loan terms.")]
+    InvalidLoanTerms,
+    #[msg("The option loan's term isn't over yet.")]
+    LoanNotEnded,
+    #[msg("The option loan account is required for a borrowed option.")]
+    MissingOptionLoanAccount,
+    #[msg("Invalid escrow template.")]
+    InvalidTemplate,
+    #[msg("Invalid expiry grid.")]
+    InvalidExpiryGrid,
+    #[msg("The expiration isn't one of the market's standard expiries.")]
+    NonStandardExpiry,
+    #[msg("The market's expiry grid account is required.")]
+    MissingExpiryGridAccount,
+    #[msg("Option series aren't supported with these parameters in this market.")]
+    SeriesUnsupported,
+    #[msg("The series has expired.")]
+    SeriesExpired,
+    #[msg("Invalid amount of series options.")]
+    InvalidSeriesAmount,
+    #[msg("Series options can only be exercised early while in the money.")]
+    SeriesOutOfTheMoney,
+    #[msg("The escrow's settlement proceeds must first be claimed into the fraction's account.")]
+    FractionProceedsPending,
+    #[msg("Invalid number of fraction shares.")]
+    InvalidFractionShares,
+    #[msg("The holder registry is full.")]
+    HolderRegistryFull,
+    #[msg("The holder is not in the registry.")]
+    HolderNotRegistered,
+    #[msg("The holder doesn't hold enough registered options.")]
+    InsufficientHolding,
+    #[msg("The deposit would exceed the escrow's collateral amount.")]
+    DepositExceedsCollateral,
+    #[msg("The escrow predates the fixed-offset layout and can't be migrated in place.")]
+    UnsupportedEscrowVersion,
+    #[msg("The keeper-submitted settlement price is still in its dispute window.")]
+    SettlementPriceInDispute,
+    #[msg("The market has premium bounds, so its volatility curve is required.")]
+    MissingVolConfig,
+    #[msg("The treasury timelock is shorter than the minimum.")]
+    TreasuryTimelockTooShort,
+    #[msg("The oracle price wasn't published close enough to the escrow's expiry.")]
+    SettlementPriceNotAtExpiry,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAD: u128 = 1_000_000_000_000_000_000;
+
+    /// Packs an SPL token-lending `Reserve` field by field, in the program's declaration order.
+    fn pack_reserve(slot: u64, stale: bool, available: u64, borrowed_wads: u128, share_mint: &Pubkey, share_supply: u64) -> Vec<u8> {
+        let mut data = vec![1u8];                                     // version
+        data.extend_from_slice(&slot.to_le_bytes());                  // last_update.slot
+        data.push(stale as u8);                                       // last_update.stale
+        data.extend_from_slice(&[0; 32]);                             // lending_market
+        data.extend_from_slice(&[0; 32]);                             // liquidity.mint_pubkey
+        data.push(6);                                                 // liquidity.mint_decimals
+        data.extend_from_slice(&[0; 32]);                             // liquidity.supply_pubkey
+        data.extend_from_slice(&[0; 32]);                             // liquidity.pyth_oracle_pubkey
+        data.extend_from_slice(&[0; 32]);                             // liquidity.switchboard_oracle_pubkey
+        data.extend_from_slice(&available.to_le_bytes());             // liquidity.available_amount
+        data.extend_from_slice(&borrowed_wads.to_le_bytes());         // liquidity.borrowed_amount_wads
+        data.extend_from_slice(&WAD.to_le_bytes());                   // liquidity.cumulative_borrow_rate_wads
+        data.extend_from_slice(&0u128.to_le_bytes());                 // liquidity.market_price
+        data.extend_from_slice(share_mint.as_ref());                  // collateral.mint_pubkey
+        data.extend_from_slice(&share_supply.to_le_bytes());          // collateral.mint_total_supply
+        data.extend_from_slice(&[0; 32]);                             // collateral.supply_pubkey
+        data.resize(619, 0);                                          // config and padding
+        data
+    }
+
+    fn exchange_rate(data: &mut [u8], share_mint: &Pubkey, slot: u64) -> Result<u64> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let reserve = AccountInfo::new(&key, false, false, &mut lamports, data, &TOKEN_LENDING_PROGRAM_ID, false, 0);
+        reserve_exchange_rate(&reserve, share_mint, slot)
+    }
+
+    #[test]
+    fn reserve_exchange_rate_reads_the_reserve_layout() {
+        let share_mint = Pubkey::new_unique();
+        let mut data = pack_reserve(42, false, 1_000_000, 500_000 * WAD, &share_mint, 1_200_000);
+        assert_eq!(exchange_rate(&mut data, &share_mint, 42).unwrap(), EXCHANGE_RATE_SCALE / 4 * 5);
+    }
+
+    #[test]
+    fn reserve_exchange_rate_rejects_stale_or_foreign_reserves() {
+        let share_mint = Pubkey::new_unique();
+        let mut data = pack_reserve(42, false, 1_000_000, 0, &share_mint, 1_000_000);
+        assert!(exchange_rate(&mut data, &share_mint, 43).is_err());
+        assert!(exchange_rate(&mut data, &Pubkey::new_unique(), 42).is_err());
+        let mut data = pack_reserve(42, true, 1_000_000, 0, &share_mint, 1_000_000);
+        assert!(exchange_rate(&mut data, &share_mint, 42).is_err());
+    }
+
+    #[test]
+    fn fee_vault_overflow_goes_to_the_treasury() {
+        let mint = Pubkey::new_unique();
+        let (treasury, _) = Pubkey::find_program_address(&[TREASURY_SEED, mint.as_ref()], &crate::ID);
+        let mut fee_vault = FeeVault { mint, token_account: Pubkey::new_unique(), bump: 0, claims: Vec::new() };
+        let collectors: Vec<Pubkey> = (0..MAX_FEE_CLAIMS).map(|_| Pubkey::new_unique()).collect();
+        for collector in &collectors {
+            fee_vault.credit(*collector, 10).unwrap();
+        }
+        fee_vault.credit(collectors[0], 5).unwrap();
+        assert_eq!(fee_vault.claims.len(), MAX_FEE_CLAIMS);
+        assert_eq!(fee_vault.pop_claim(&collectors[0]), Some(15));
+        assert_eq!(fee_vault.pop_claim(&collectors[MAX_FEE_CLAIMS - 1]), None);
+        assert_eq!(fee_vault.pop_claim(&treasury), Some(10));
+    }
+
+    #[test]
+    fn fresh_stake_earns_no_fee_discount() {
+        let mut position = StakePosition {
+            owner: Pubkey::new_unique(),
+            amount: 0,
+            boosted_amount: 0,
+            lock_end: 0,
+            reward_debt: 0,
+            penalty_debt: 0,
+            unclaimed_rewards: 0,
+            vote_lock_end: 0,
+            discount_amount: 0,
+            last_stake_time: 0,
+            bump: 0,
+        };
+        let start = 1_700_000_000;
+        position.record_stake(1_000, start).unwrap();
+        assert_eq!(position.discount_stake(start), 0);
+        assert_eq!(position.discount_stake(start + STAKE_DISCOUNT_MIN_AGE), 1_000);
+        position.record_stake(9_000, start + STAKE_DISCOUNT_MIN_AGE).unwrap();
+        assert_eq!(position.discount_stake(start + STAKE_DISCOUNT_MIN_AGE), 1_000);
+        position.amount = 500;
+        assert_eq!(position.discount_stake(start + STAKE_DISCOUNT_MIN_AGE), 500);
+    }
+
+    const UNIT: u64 = 1_000_000;
+    const PRICE_TOLERANCE: u64 = 100;
+
+    fn call(spot: u64, strike: u64, tenor: i64, rate_bps: i64, vol_bps: u64) -> u64 {
+        math::black_scholes(OptionType::Call, spot * UNIT, strike * UNIT, tenor, rate_bps, vol_bps).unwrap()
+    }
+
+    fn put(spot: u64, strike: u64, tenor: i64, rate_bps: i64, vol_bps: u64) -> u64 {
+        math::black_scholes(OptionType::Put, spot * UNIT, strike * UNIT, tenor, rate_bps, vol_bps).unwrap()
+    }
+
+    /// Strike discounted over `tenor` at `rate_bps`, in `STRIKE_PRICE_DECIMALS`.
+    fn discounted(strike: u64, tenor: i64, rate_bps: i64) -> u64 {
+        let rate_time = -(rate_bps as f64 / 10000.0) * tenor as f64 / SECONDS_PER_YEAR as f64;
+        (strike as f64 * rate_time.exp() * UNIT as f64).round() as u64
+    }
+
+    #[test]
+    fn black_scholes_matches_known_prices() {
+        assert!(call(100, 100, SECONDS_PER_YEAR, 500, 2000).abs_diff(10_450_584) <= PRICE_TOLERANCE);
+        assert!(put(100, 100, SECONDS_PER_YEAR, 500, 2000).abs_diff(5_573_526) <= PRICE_TOLERANCE);
+        assert!(call(42, 40, SECONDS_PER_YEAR / 2, 1000, 2000).abs_diff(4_759_422) <= PRICE_TOLERANCE);
+        assert!(put(42, 40, SECONDS_PER_YEAR / 2, 1000, 2000).abs_diff(808_600) <= PRICE_TOLERANCE);
+    }
+
+    #[test]
+    fn black_scholes_satisfies_put_call_parity() {
+        let tenor = 90 * 24 * 60 * 60;
+        for strike in [60, 90, 100, 110, 150] {
+            let forward = (100 * UNIT) as i64 - discounted(strike, tenor, 300) as i64;
+            let spread = call(100, strike, tenor, 300, 6500) as i64 - put(100, strike, tenor, 300, 6500) as i64;
+            assert!(spread.abs_diff(forward) <= PRICE_TOLERANCE, "strike {strike}: {spread} != {forward}");
+        }
+    }
+
+    #[test]
+    fn black_scholes_with_zero_vol_is_discounted_intrinsic() {
+        let forward = 100 * UNIT - discounted(90, SECONDS_PER_YEAR, 500);
+        assert!(call(100, 90, SECONDS_PER_YEAR, 500, 0).abs_diff(forward) <= 1);
+        assert_eq!(put(100, 90, SECONDS_PER_YEAR, 500, 0), 0);
+        let greeks = math::greeks(OptionType::Call, 100 * UNIT, 90 * UNIT, SECONDS_PER_YEAR, 500, 0).unwrap();
+        assert_eq!((greeks.delta, greeks.gamma, greeks.vega), (GREEKS_UNIT, 0, 0));
+    }
+
+    #[test]
+    fn black_scholes_at_expiry_is_intrinsic() {
+        assert_eq!(call(110, 100, 0, 500, 2000), 10 * UNIT);
+        assert_eq!(put(110, 100, 0, 500, 2000), 0);
+        assert_eq!(call(90, 100, 0, 500, 2000), 0);
+        assert_eq!(put(90, 100, 0, 500, 2000), 10 * UNIT);
+        assert!(math::black_scholes(OptionType::Call, 100 * UNIT, 100 * UNIT, -1, 500, 2000).is_err());
+    }
+
+    #[test]
+    fn black_scholes_deep_in_and_out_of_the_money() {
+        let tenor = 30 * 24 * 60 * 60;
+        assert!(call(200, 100, tenor, 500, 2000).abs_diff(200 * UNIT - discounted(100, tenor, 500)) <= PRICE_TOLERANCE);
+        assert!(put(50, 100, tenor, 500, 2000).abs_diff(discounted(100, tenor, 500) - 50 * UNIT) <= PRICE_TOLERANCE);
+        assert!(call(50, 100, tenor, 500, 2000) <= 1);
+        assert!(put(200, 100, tenor, 500, 2000) <= 1);
+        let greeks = math::greeks(OptionType::Put, 50 * UNIT, 100 * UNIT, tenor, 500, 2000).unwrap();
+        assert!(greeks.delta.abs_diff(-GREEKS_UNIT) <= 10);
+    }
 }